@@ -0,0 +1,122 @@
+#import bevy_pbr::{
+    forward_io::{VertexOutput, FragmentOutput},
+    mesh_functions,
+    pbr_bindings,
+    pbr_fragment::pbr_input_from_standard_material,
+    pbr_functions::{alpha_discard, apply_pbr_lighting, main_pass_post_lighting_processing},
+    pbr_types,
+    view_transformations::position_world_to_clip,
+}
+
+struct ChunkMaterialSettings {
+    tile_size: vec2<f32>,
+    ambient_occlusion: f32,
+    sky_light: f32,
+}
+
+@group(2) @binding(100) var<uniform> chunk_settings: ChunkMaterialSettings;
+
+// Chunk meshes always carry positions, normals, both UV sets and colours, plus
+// the baked `(sky, block)` voxel light.
+struct ChunkVertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) uv_b: vec2<f32>,
+    @location(5) color: vec4<f32>,
+    @location(8) voxel_light: vec2<f32>,
+}
+
+struct ChunkVertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) world_position: vec4<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) uv_b: vec2<f32>,
+    @location(5) color: vec4<f32>,
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    @location(6) @interpolate(flat) instance_index: u32,
+#endif
+#ifdef VISIBILITY_RANGE_DITHER
+    @location(7) @interpolate(flat) visibility_range_dither: i32,
+#endif
+    @location(8) voxel_light: vec2<f32>,
+}
+
+@vertex
+fn vertex(vertex: ChunkVertex) -> ChunkVertexOutput {
+    var out: ChunkVertexOutput;
+
+    let world_from_local = mesh_functions::get_world_from_local(vertex.instance_index);
+    out.world_normal = mesh_functions::mesh_normal_local_to_world(vertex.normal, vertex.instance_index);
+    out.world_position = mesh_functions::mesh_position_local_to_world(world_from_local, vec4<f32>(vertex.position, 1.0));
+    out.position = position_world_to_clip(out.world_position.xyz);
+    out.uv = vertex.uv;
+    out.uv_b = vertex.uv_b;
+    out.color = vertex.color;
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    out.instance_index = vertex.instance_index;
+#endif
+#ifdef VISIBILITY_RANGE_DITHER
+    out.visibility_range_dither = mesh_functions::get_visibility_range_dither_level(
+        vertex.instance_index, world_from_local[3]);
+#endif
+    out.voxel_light = vertex.voxel_light;
+
+    return out;
+}
+
+// Minecraft-style falloff: every light level below full is ~20% darker.
+fn voxel_brightness(voxel_light: vec2<f32>) -> f32 {
+    let level = max(voxel_light.x * chunk_settings.sky_light, voxel_light.y);
+    return pow(0.8, 15.0 * (1.0 - level));
+}
+
+@fragment
+fn fragment(
+    in: ChunkVertexOutput,
+    @builtin(front_facing) is_front: bool,
+) -> FragmentOutput {
+    var mesh: VertexOutput;
+    mesh.position = in.position;
+    mesh.world_position = in.world_position;
+    mesh.world_normal = in.world_normal;
+#ifdef VERTEX_UVS_A
+    mesh.uv = in.uv;
+#endif
+#ifdef VERTEX_UVS_B
+    // UV_0 is measured in tiles across a merged quad and UV_1 is the atlas
+    // tile origin, so wrap the tile-local coordinate to repeat the block
+    // texture.
+    mesh.uv_b = in.uv_b;
+    mesh.uv = in.uv_b + fract(in.uv) * chunk_settings.tile_size;
+#endif
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    mesh.instance_index = in.instance_index;
+#endif
+#ifdef VISIBILITY_RANGE_DITHER
+    mesh.visibility_range_dither = in.visibility_range_dither;
+#endif
+
+#ifdef VERTEX_COLORS
+    let brightness = voxel_brightness(in.voxel_light);
+    mesh.color = vec4<f32>(in.color.rgb * brightness, in.color.a);
+
+    // Opaque chunk meshes carry baked corner occlusion in the vertex alpha;
+    // translucent meshes (water) keep their real alpha there.
+    let alpha_mode = pbr_bindings::material.flags & pbr_types::STANDARD_MATERIAL_FLAGS_ALPHA_MODE_RESERVED_BITS;
+    if alpha_mode == pbr_types::STANDARD_MATERIAL_FLAGS_ALPHA_MODE_OPAQUE {
+        let occlusion = mix(1.0, in.color.a, chunk_settings.ambient_occlusion);
+        mesh.color = vec4<f32>(mesh.color.rgb * occlusion, 1.0);
+    }
+#endif
+
+    var pbr_input = pbr_input_from_standard_material(mesh, is_front);
+    pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+
+    var out: FragmentOutput;
+    out.color = apply_pbr_lighting(pbr_input);
+    out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+    return out;
+}
@@ -1,4 +1,5 @@
-use forge::chunk::ChunkPos;
+use forge::chunk::mesh::{generate_chunk_meshes, ChunkMeshStats, ChunkNeighbors};
+use forge::chunk::{Chunk, ChunkPos};
 use forge::world::generator::WorldGenerator;
 use std::time::Instant;
 
@@ -13,6 +14,8 @@ fn main() {
 
     let generator = WorldGenerator::default();
     let mut durations = Vec::new();
+    let mut mesh_durations = Vec::new();
+    let mut mesh_stats = ChunkMeshStats::default();
 
     let positions: Vec<ChunkPos> = (-radius..=radius)
         .flat_map(|z| (-radius..=radius).map(move |x| ChunkPos::new(x, 2, z)))
@@ -21,9 +24,17 @@ fn main() {
     for round in 0..repeats {
         for &pos in &positions {
             let start = Instant::now();
-            let _storage = generator.bake_chunk(pos);
+            let storage = generator.bake_chunk(pos);
             let elapsed = start.elapsed().as_secs_f64() * 1000.0;
             durations.push(elapsed);
+
+            let chunk = Chunk::from_storage(pos, storage);
+            let mesh_start = Instant::now();
+            let meshes = generate_chunk_meshes(&chunk, &ChunkNeighbors::default(), None);
+            mesh_durations.push(mesh_start.elapsed().as_secs_f64() * 1000.0);
+            if round == 0 {
+                mesh_stats.accumulate(&meshes.stats);
+            }
         }
         println!("Completed round {}", round + 1);
     }
@@ -43,4 +54,24 @@ fn main() {
         avg, median, min, max
     );
     println!("Throughput: {:.2} chunks/sec", throughput);
+
+    let mesh_avg = mesh_durations.iter().sum::<f64>() / mesh_durations.len().max(1) as f64;
+    let reduction = if mesh_stats.naive_vertices() > 0 {
+        100.0 * (1.0 - mesh_stats.vertices() as f64 / mesh_stats.naive_vertices() as f64)
+    } else {
+        0.0
+    };
+
+    println!("Mesh avg: {:.2} ms", mesh_avg);
+    println!(
+        "Mesh vertices: per-face={} greedy={} ({:.1}% fewer)",
+        mesh_stats.naive_vertices(),
+        mesh_stats.vertices(),
+        reduction
+    );
+    println!(
+        "Mesh indices: per-face={} greedy={}",
+        mesh_stats.naive_indices(),
+        mesh_stats.indices()
+    );
 }
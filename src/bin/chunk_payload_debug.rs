@@ -6,7 +6,8 @@ use forge::chunk::{ChunkPos, ChunkStorage};
 use forge::world::chunk_store::{
     flush_queue_to_disk, ChunkPayloadQueue, PlanetChunkStore, QueuedChunkPayload, StoreUpdate,
 };
-use forge::world::persistence::{ChunkPersistence, DiskChunkPersistence};
+use forge::world::persistence::{persistence_dir_from_env, ChunkPersistence};
+use forge::world::region::RegionChunkPersistence;
 use forge::world::{WorldGenConfig, WorldGenerator};
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -21,12 +22,12 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let chunk_pos = ChunkPos::new(0, 0, 0);
     let storage = generator.bake_chunk(chunk_pos);
-    enqueue_if_updated(&mut store, &mut queue, chunk_pos, &storage);
+    enqueue_if_updated(&generator, &mut store, &mut queue, chunk_pos, &storage);
 
     let mut edited = storage.clone();
     edited.set(0, 0, 0, BlockType::Bedrock);
     edited.set(1, 1, 1, BlockType::Water);
-    enqueue_if_updated(&mut store, &mut queue, chunk_pos, &edited);
+    enqueue_if_updated(&generator, &mut store, &mut queue, chunk_pos, &edited);
 
     let mut queue_for_debug = ChunkPayloadQueue::default();
     let mut queue_for_persist = ChunkPayloadQueue::default();
@@ -38,15 +39,11 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let written = flush_queue_to_disk(&mut queue_for_debug, &output_dir)?;
 
-    let persist_dir = std::env::var("FORGE_PERSISTENCE_DIR")
-        .ok()
-        .filter(|dir| !dir.trim().is_empty())
-        .map(PathBuf::from)
-        .unwrap_or_else(|| PathBuf::from("target/chunk_payload_persistence"));
+    let persist_dir = persistence_dir_from_env();
 
-    let mut disk_persistence = DiskChunkPersistence::new(&persist_dir);
+    let mut region_persistence = RegionChunkPersistence::new(&persist_dir);
     for payload in queue_for_persist.take_all() {
-        disk_persistence.persist(&payload)?;
+        region_persistence.persist(&payload)?;
     }
 
     println!(
@@ -64,6 +61,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 }
 
 fn enqueue_if_updated(
+    generator: &WorldGenerator,
     store: &mut PlanetChunkStore,
     queue: &mut ChunkPayloadQueue,
     position: ChunkPos,
@@ -73,7 +71,7 @@ fn enqueue_if_updated(
         queue.enqueue(QueuedChunkPayload {
             position,
             revision,
-            bytes: storage.encode_bytes(),
+            bytes: storage.encode_bytes(generator.payload_header(position, revision)),
         });
     }
 }
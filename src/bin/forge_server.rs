@@ -0,0 +1,123 @@
+use std::error::Error;
+use std::time::Duration;
+
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::log::LogPlugin;
+use bevy::prelude::*;
+use forge::planet::{PlanetConfig, PlanetPlugin};
+use forge::server::{player_components, ServerPlugin, ServerSettings, DEFAULT_VIEW_DISTANCE};
+use forge::world::{WorldGenConfig, WorldGenerator, WorldPlugin};
+
+const USAGE: &str = "usage: forge_server [--world NAME] [--generate [--planet-size BLOCKS]]
+                    [--listen ADDR | --offline] [--tick-rate HZ] [--ticks N]
+                    [--bot X,Y,Z]... [--view-distance N] [--max-view-distance N]
+                    [--no-console]
+
+Runs the planet simulation headless: world generation, chunk streaming,
+persistence, game time and the command registry, with chunks streamed around
+connected players. Clients connect over TCP on --listen (default
+127.0.0.1:25570) by setting FORGE_SERVER_ADDR. The planet package for --world (default from PlanetConfig)
+is loaded unless --generate bakes a fresh world from the default config.
+--ticks (or FORGE_SERVER_TICKS) stops the server after N ticks, and each --bot
+attaches a stationary simulated player, which is how CI exercises streaming
+and persistence without a GPU or a client. --max-view-distance caps the view
+distance clients may ask for.";
+
+const DEFAULT_TICK_RATE: f64 = 20.0;
+/// Bot player ids start here so they never collide with connection ids.
+const BOT_ID_BASE: u64 = 1 << 48;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut planet_config = PlanetConfig::default();
+    let mut generate = false;
+    let mut planet_size: Option<u32> = None;
+    let mut tick_rate = DEFAULT_TICK_RATE;
+    let mut view_distance = DEFAULT_VIEW_DISTANCE;
+    let mut bots: Vec<Vec3> = Vec::new();
+    let mut settings = ServerSettings {
+        max_ticks: std::env::var("FORGE_SERVER_TICKS")
+            .ok()
+            .and_then(|value| value.trim().parse().ok()),
+        ..default()
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--world" => planet_config.name = args.next().ok_or(USAGE)?,
+            "--generate" => generate = true,
+            "--planet-size" => planet_size = Some(args.next().ok_or(USAGE)?.parse()?),
+            "--listen" => settings.listen = Some(args.next().ok_or(USAGE)?),
+            "--offline" => settings.listen = None,
+            "--tick-rate" => tick_rate = args.next().ok_or(USAGE)?.parse()?,
+            "--ticks" => settings.max_ticks = Some(args.next().ok_or(USAGE)?.parse()?),
+            "--view-distance" => view_distance = args.next().ok_or(USAGE)?.parse()?,
+            "--max-view-distance" => {
+                settings.max_view_distance = args.next().ok_or(USAGE)?.parse()?
+            }
+            "--no-console" => settings.console = false,
+            "--bot" => bots.push(parse_position(&args.next().ok_or(USAGE)?)?),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ => {
+                eprintln!("{}", USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+    if tick_rate <= 0.0 {
+        return Err("tick rate must be positive".into());
+    }
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+            1.0 / tick_rate,
+        ))),
+        LogPlugin::default(),
+    ))
+    .insert_resource(planet_config)
+    .add_plugins((PlanetPlugin, WorldPlugin, ServerPlugin { settings }));
+
+    if generate {
+        let mut config = WorldGenConfig::default();
+        if let Some(size) = planet_size {
+            config.planet_size = size;
+        }
+        info!(
+            "Generating a fresh world (planet size {} blocks, seed {})",
+            config.planet_size, config.seed
+        );
+        app.insert_resource(WorldGenerator::new(config));
+    }
+
+    for (index, position) in bots.into_iter().enumerate() {
+        let number = index as u64 + 1;
+        let (player, mut connected, temperature, metrics) =
+            player_components(BOT_ID_BASE + number, format!("bot-{}", number));
+        connected.view_distance = view_distance;
+        app.world_mut().spawn((
+            player,
+            connected,
+            temperature,
+            metrics,
+            TransformBundle::from_transform(Transform::from_translation(position)),
+        ));
+    }
+
+    app.run();
+    Ok(())
+}
+
+fn parse_position(value: &str) -> Result<Vec3, Box<dyn Error>> {
+    let parts = value
+        .split(',')
+        .map(|part| part.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()?;
+    match parts.as_slice() {
+        [x, y, z] => Ok(Vec3::new(*x, *y, *z)),
+        _ => Err(format!("expected X,Y,Z but got {:?}", value).into()),
+    }
+}
@@ -66,4 +66,27 @@ fn main() {
         "Cave bias {:.2}, ore bias {:.2}",
         profile.cave_bias, profile.ore_bias
     );
+
+    println!(
+        "Crust: {}",
+        if profile.is_oceanic() {
+            "oceanic"
+        } else {
+            "continental"
+        }
+    );
+    for deposit in profile.ore_deposits() {
+        println!(
+            "Ore {:?} in {:?} at depth {}-{}: {:.2} veins of {} per 16^3",
+            deposit.ore,
+            deposit.host,
+            deposit.min_depth,
+            deposit.max_depth,
+            deposit.veins_per_cell,
+            deposit.vein_size
+        );
+    }
+    for (ore, blocks) in profile.expected_ore_yield() {
+        println!("Expected {:?} per column: {:.2} blocks", ore, blocks);
+    }
 }
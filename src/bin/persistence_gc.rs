@@ -0,0 +1,197 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use forge::world::persistence::{
+    list_chunk_files, persistence_dir_from_env, unix_timestamp, ChunkPersistence,
+    DiskChunkPersistence, PersistenceConfig, PersistenceUsage, RetentionPolicy, WorldLock,
+};
+use forge::world::region::{RegionChunkPersistence, RegionPos};
+
+const USAGE: &str =
+    "usage: persistence_gc [--prune] [--keep-last N] [--keep-hours H | --keep-all] [world_dir...]
+
+Reports per-world chunk persistence disk usage and how much the retention
+policy would reclaim. With --prune, revisions outside the policy are deleted
+and region files are compacted; worlds a game or server has open are
+skipped. Defaults come from FORGE_PERSISTENCE_* and
+the world directory from FORGE_PERSISTENCE_DIR. A directory that holds
+several worlds is expanded into its subdirectories.";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut policy = PersistenceConfig::from_env().retention;
+    let mut prune = false;
+    let mut worlds = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--prune" => prune = true,
+            "--keep-all" => policy = RetentionPolicy::keep_all(),
+            "--keep-last" => {
+                policy.keep_last = args.next().ok_or(USAGE)?.parse()?;
+            }
+            "--keep-hours" => {
+                let hours: f64 = args.next().ok_or(USAGE)?.parse()?;
+                policy.keep_newer_than =
+                    (hours > 0.0).then(|| Duration::from_secs_f64(hours * 3600.0));
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ if arg.starts_with("--") => {
+                eprintln!("{}", USAGE);
+                std::process::exit(2);
+            }
+            _ => worlds.push(PathBuf::from(arg)),
+        }
+    }
+    if worlds.is_empty() {
+        worlds.push(persistence_dir_from_env());
+    }
+
+    let worlds = expand_worlds(worlds)?;
+    let now = unix_timestamp();
+    println!(
+        "Retention: keep last {} revision(s){}",
+        policy.keep_last,
+        policy
+            .keep_newer_than
+            .map(|window| format!(", and any newer than {:.1}h", window.as_secs_f64() / 3600.0))
+            .unwrap_or_default()
+    );
+
+    let mut total = PersistenceUsage::default();
+    for world in &worlds {
+        // Running games and servers cache region files that pruning rewrites.
+        let lock = if prune && world.is_dir() {
+            match WorldLock::exclusive(world) {
+                Ok(lock) => Some(lock),
+                Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => {
+                    println!(
+                        "{}: in use by a running game or server; not pruning",
+                        world.display()
+                    );
+                    None
+                }
+                Err(error) => return Err(error.into()),
+            }
+        } else {
+            None
+        };
+
+        let mut backends: Vec<(&str, Box<dyn ChunkPersistence>)> = Vec::new();
+        if has_regions(world) {
+            backends.push(("region", Box::new(RegionChunkPersistence::new(world))));
+        }
+        if !list_chunk_files(world)?.is_empty() {
+            backends.push(("legacy", Box::new(DiskChunkPersistence::new(world))));
+        }
+        if backends.is_empty() {
+            println!("{}: no persisted chunks", world.display());
+            continue;
+        }
+
+        for (kind, mut backend) in backends {
+            let usage = backend.usage(&policy, now)?;
+            print_usage(world, kind, &usage);
+            total.chunks += usage.chunks;
+            total.revisions += usage.revisions;
+            total.disk_bytes += usage.disk_bytes;
+            total.prunable_revisions += usage.prunable_revisions;
+            total.prunable_bytes += usage.prunable_bytes;
+
+            if lock.is_some() {
+                let report = backend.prune(&policy, now)?;
+                println!(
+                    "  pruned {} revision(s), freed {}",
+                    report.revisions_removed,
+                    format_bytes(report.bytes_freed)
+                );
+            }
+        }
+    }
+
+    if worlds.len() > 1 {
+        println!(
+            "Total: {} chunk(s), {} revision(s), {} on disk, {} revision(s) / {} prunable",
+            total.chunks,
+            total.revisions,
+            format_bytes(total.disk_bytes),
+            total.prunable_revisions,
+            format_bytes(total.prunable_bytes)
+        );
+    }
+    if !prune && total.prunable_revisions > 0 {
+        println!("Run again with --prune to apply the policy.");
+    }
+
+    Ok(())
+}
+
+fn has_regions(dir: &Path) -> bool {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries.flatten().any(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .and_then(RegionPos::parse_filename)
+                    .is_some()
+            })
+        })
+        .unwrap_or(false)
+}
+
+/// Directories without chunk data of their own are treated as a collection
+/// of worlds, one per subdirectory.
+fn expand_worlds(dirs: Vec<PathBuf>) -> std::io::Result<Vec<PathBuf>> {
+    let mut worlds = Vec::new();
+    for dir in dirs {
+        if !dir.is_dir() || has_regions(&dir) || !list_chunk_files(&dir)?.is_empty() {
+            worlds.push(dir);
+            continue;
+        }
+
+        let mut children: Vec<PathBuf> = std::fs::read_dir(&dir)?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect();
+        children.sort();
+        if children.is_empty() {
+            worlds.push(dir);
+        } else {
+            worlds.extend(children);
+        }
+    }
+    Ok(worlds)
+}
+
+fn print_usage(world: &Path, kind: &str, usage: &PersistenceUsage) {
+    println!(
+        "{} ({}): {} chunk(s), {} revision(s), {} payload, {} on disk",
+        world.display(),
+        kind,
+        usage.chunks,
+        usage.revisions,
+        format_bytes(usage.payload_bytes),
+        format_bytes(usage.disk_bytes)
+    );
+    println!(
+        "  prunable: {} revision(s), {}",
+        usage.prunable_revisions,
+        format_bytes(usage.prunable_bytes)
+    );
+}
+
+fn format_bytes(bytes: u64) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+    } else if bytes >= 1024 {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    } else {
+        format!("{} B", bytes)
+    }
+}
@@ -0,0 +1,85 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+use forge::chunk::ChunkStorage;
+use forge::world::chunk_store::QueuedChunkPayload;
+use forge::world::persistence::{list_chunk_files, unix_timestamp};
+use forge::world::region::RegionChunkPersistence;
+
+/// Converts a directory of per-chunk `chunk_*_rev*.bin` payloads into region
+/// files. Every revision is carried over so the region history matches the
+/// legacy directory; the legacy files are left in place.
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = std::env::args().skip(1);
+    let Some(legacy_dir) = args.next().map(PathBuf::from) else {
+        eprintln!("usage: region_migrate <legacy_chunk_dir> [region_dir]");
+        std::process::exit(2);
+    };
+    let region_dir = args
+        .next()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| legacy_dir.clone());
+
+    let mut files = list_chunk_files(&legacy_dir)?;
+    files.sort_by_key(|(position, revision, _)| (position.x, position.y, position.z, *revision));
+
+    let persistence = RegionChunkPersistence::new(&region_dir);
+    let mut migrated = 0_usize;
+    let mut skipped = 0_usize;
+    let mut corrupt = 0_usize;
+    let mut total_bytes = 0_usize;
+
+    for (position, revision, path) in files {
+        let bytes = std::fs::read(&path)?;
+        if let Err(error) = ChunkStorage::from_bytes(&bytes) {
+            eprintln!("skipping {}: {:?}", path.display(), error);
+            corrupt += 1;
+            continue;
+        }
+
+        let timestamp = std::fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_else(unix_timestamp);
+
+        let payload = QueuedChunkPayload {
+            position,
+            revision,
+            bytes,
+        };
+        if persistence.write_payload(&payload, timestamp)? {
+            migrated += 1;
+            total_bytes += payload.bytes.len();
+        } else {
+            skipped += 1;
+        }
+    }
+
+    let regions = persistence.regions_on_disk()?;
+    let region_bytes: u64 = regions
+        .iter()
+        .filter_map(|region| std::fs::metadata(region_dir.join(region.filename())).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+
+    println!(
+        "Migrated {} chunk revision(s) ({} bytes) from {} into {} region file(s) in {} ({} bytes)",
+        migrated,
+        total_bytes,
+        legacy_dir.display(),
+        regions.len(),
+        region_dir.display(),
+        region_bytes
+    );
+    if skipped > 0 {
+        println!(" - {} revision(s) already present in regions", skipped);
+    }
+    if corrupt > 0 {
+        println!(" - {} corrupt file(s) skipped", corrupt);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use std::error::Error;
+use std::path::PathBuf;
+
+use forge::replay::runner::run_replay;
+use forge::replay::Recording;
+use forge::world::generator::WorldMetadata;
+use forge::world::package::planet_package_paths;
+use forge::world::{WorldGenConfig, WorldGenerator};
+
+const USAGE: &str = "usage: replay RECORDING [--world NAME | --generate]
+
+Plays back a session recorded with FORGE_RECORD=RECORDING, headless. Chunks
+the recording doesn't hold are baked from the planet package the session used
+(or --world NAME), the recorded input is fed through player physics and block
+interaction on the recording's simulation tick, and each frame's block edits,
+the final player position and the final world hash are checked against the
+recording.
+--generate bakes from the recorded seed and planet size instead of a package.
+Exits with status 1 if the replay diverges.";
+
+/// Divergences printed before the rest are summarised.
+const MAX_REPORTED: usize = 20;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut path: Option<PathBuf> = None;
+    let mut world: Option<String> = None;
+    let mut generate = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--world" => world = Some(args.next().ok_or(USAGE)?),
+            "--generate" => generate = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(PathBuf::from(arg)),
+            _ => {
+                eprintln!("{}", USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+    let Some(path) = path else {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+
+    let recording = Recording::load(&path)?;
+    let world_gen = if generate {
+        WorldGenerator::new(WorldGenConfig {
+            seed: recording.world.seed,
+            planet_size: recording.world.planet_size,
+            ..WorldGenConfig::default()
+        })
+    } else {
+        let name = world.unwrap_or_else(|| recording.world.name.clone());
+        let (_, metadata_path) = planet_package_paths(&name);
+        let (metadata, _) = WorldMetadata::load_from_file(&metadata_path)?;
+        WorldGenerator::from_metadata(metadata)
+    };
+    if world_gen.config().seed != recording.world.seed {
+        eprintln!(
+            "warning: recorded with seed {} but the world has seed {}",
+            recording.world.seed,
+            world_gen.config().seed
+        );
+    }
+
+    println!(
+        "Replaying {} frames ({:.1}s) recorded on '{}' (seed {})",
+        recording.frames.len(),
+        recording
+            .frames
+            .iter()
+            .map(|frame| frame.delta.as_secs_f32())
+            .sum::<f32>(),
+        recording.world.name,
+        recording.world.seed
+    );
+    let report = run_replay(&recording, |position| world_gen.bake_chunk(position));
+    println!(
+        "{} edits, player at ({:.3}, {:.3}, {:.3}), world hash {:016x}",
+        report.edits, report.position.x, report.position.y, report.position.z, report.world_hash
+    );
+    if recording.final_state.is_none() {
+        println!("The recording has no final state to check against");
+    }
+
+    if report.is_faithful() {
+        println!("Replay matches the recording");
+        return Ok(());
+    }
+    for divergence in report.divergences.iter().take(MAX_REPORTED) {
+        println!("  {:?}", divergence);
+    }
+    if report.divergences.len() > MAX_REPORTED {
+        println!("  ... and {} more", report.divergences.len() - MAX_REPORTED);
+    }
+    eprintln!(
+        "Replay diverged from the recording in {} place(s)",
+        report.divergences.len()
+    );
+    std::process::exit(1);
+}
@@ -11,9 +11,10 @@ use bevy::window::{PresentMode, PrimaryWindow, WindowRef, WindowResolution};
 
 use forge::planet::PlanetSize;
 use forge::world::{
-    package::planet_package_paths, Biome, WorldGenConfig, WorldGenPhase, WorldGenerator,
+    package::planet_package_paths, structures::StructureLibrary, Biome, WorldGenConfig,
+    WorldGenPhase, WorldGenerator,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::PathBuf;
 
@@ -190,16 +191,18 @@ enum MapVisualization {
     Temperature,
     Hydrology,
     MajorRivers,
+    Structures,
 }
 
 impl MapVisualization {
-    const ALL: [Self; 6] = [
+    const ALL: [Self; 7] = [
         MapVisualization::Biomes,
         MapVisualization::Elevation,
         MapVisualization::Moisture,
         MapVisualization::Temperature,
         MapVisualization::Hydrology,
         MapVisualization::MajorRivers,
+        MapVisualization::Structures,
     ];
 
     fn label(&self) -> &'static str {
@@ -210,6 +213,7 @@ impl MapVisualization {
             MapVisualization::Temperature => "Temperature",
             MapVisualization::Hydrology => "Hydrology",
             MapVisualization::MajorRivers => "Major Rivers",
+            MapVisualization::Structures => "Structures",
         }
     }
 }
@@ -3259,6 +3263,63 @@ fn paint_map(image: &mut Image, generator: &WorldGenerator, visualization: MapVi
             data[index..index + 4].copy_from_slice(&color);
         }
     }
+
+    if visualization == MapVisualization::Structures {
+        paint_structure_sites(image, generator);
+    }
+}
+
+/// Mark every structure on the planet with a square in its template's
+/// colour. Each template cell is sampled once, or once per pixel where
+/// cells are smaller than pixels.
+fn paint_structure_sites(image: &mut Image, generator: &WorldGenerator) {
+    let width = image.texture_descriptor.size.width as i32;
+    let height = image.texture_descriptor.size.height as i32;
+    let planet_size = generator.planet_size() as f32;
+    let templates = StructureLibrary::global().templates();
+    let Some(spacing) = templates.iter().map(|template| template.spacing).min() else {
+        return;
+    };
+    let step = (spacing as f32).max(planet_size / width.min(height) as f32);
+
+    let mut sites = HashSet::new();
+    let samples = (planet_size / step).ceil() as i32;
+    for sample_z in 0..samples {
+        for sample_x in 0..samples {
+            let x = ((sample_x as f32 + 0.5) * step) as i32;
+            let z = ((sample_z as f32 + 0.5) * step) as i32;
+            for (index, template) in templates.iter().enumerate() {
+                let spacing = template.spacing;
+                let (cell_x, cell_z) = (x.div_euclid(spacing), z.div_euclid(spacing));
+                sites.extend(generator.structure_site(index, template, cell_x, cell_z, None));
+            }
+        }
+    }
+
+    let data = &mut image.data;
+    for site in sites {
+        let template = &templates[site.template];
+        let middle_x = site.origin.x as f32 + template.size.x as f32 * 0.5;
+        let middle_z = site.origin.z as f32 + template.size.z as f32 * 0.5;
+        let pixel_x = (middle_x / planet_size * width as f32) as i32;
+        let pixel_y = (middle_z / planet_size * height as f32) as i32;
+        let [r, g, b, _] = template.color;
+        let color = [(r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8, 255];
+
+        for dy in -2..=2 {
+            for dx in -2..=2 {
+                let (x, y) = (pixel_x + dx, pixel_y + dy);
+                if x < 0 || y < 0 || x >= width || y >= height {
+                    continue;
+                }
+                // A dark rim keeps markers readable on any terrain.
+                let edge = dx.abs() == 2 || dy.abs() == 2;
+                let index = ((y * width + x) * 4) as usize;
+                let pixel = if edge { [20, 20, 20, 255] } else { color };
+                data[index..index + 4].copy_from_slice(&pixel);
+            }
+        }
+    }
 }
 
 fn color_for_mode(
@@ -3290,6 +3351,15 @@ fn color_for_mode(
         }
         MapVisualization::Hydrology => hydrology_color(generator, world_x, world_z),
         MapVisualization::MajorRivers => major_river_color(generator, world_x, world_z),
+        MapVisualization::Structures => {
+            // Muted biomes, so structure markers stand out.
+            let height = generator.get_height(world_x, world_z);
+            let biome = generator.get_biome(world_x, world_z);
+            let [r, g, b, a] = generator.preview_color(world_x, world_z, biome, height);
+            let grey = ((r as u32 + g as u32 + b as u32) / 3) as u8;
+            let [r, g, b] = lerp_rgb([r, g, b], [grey; 3], 0.6);
+            [r, g, b, a]
+        }
     }
 }
 
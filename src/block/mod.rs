@@ -1,6 +1,9 @@
 use bevy::prelude::*;
 
+pub mod registry;
 pub mod types;
+#[allow(unused_imports)]
+pub use registry::BlockRegistry;
 pub use types::BlockType;
 
 pub struct BlockPlugin;
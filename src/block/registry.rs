@@ -0,0 +1,361 @@
+use super::types::BUILTIN_BLOCKS;
+use super::BlockType;
+use crate::texture::BlockFace;
+use crate::tools::Tool;
+use bevy::log::error;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Directory of block definition files. Every `*.json` file in it is read,
+/// in file name order.
+pub const BLOCKS_DIR: &str = "assets/blocks";
+/// The definitions shipped in `BLOCKS_DIR`, for binaries started somewhere
+/// the directory isn't (tests, a server run from elsewhere).
+const BUILTIN_DEFINITIONS: &str = include_str!("../../assets/blocks/core.json");
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlockFile {
+    blocks: Vec<BlockEntry>,
+}
+
+/// A block as written in a definition file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlockEntry {
+    id: u16,
+    name: String,
+    #[serde(default = "yes")]
+    solid: bool,
+    #[serde(default)]
+    transparent: bool,
+    #[serde(default)]
+    liquid: bool,
+    #[serde(default)]
+    gravity: bool,
+    #[serde(default)]
+    light: u8,
+    #[serde(default = "yes")]
+    breakable: bool,
+    #[serde(default)]
+    hardness: f32,
+    #[serde(default)]
+    textures: FaceTextures,
+    /// Name of the block extraction yields; the block itself if absent.
+    drops: Option<String>,
+    tool: Option<Tool>,
+    color: [f32; 4],
+}
+
+fn yes() -> bool {
+    true
+}
+
+/// Texture folders by face. `side` covers the four sides, `all` every face,
+/// and the most specific one given wins. Without any, the block's name.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct FaceTextures {
+    all: Option<String>,
+    side: Option<String>,
+    top: Option<String>,
+    bottom: Option<String>,
+    front: Option<String>,
+    back: Option<String>,
+    left: Option<String>,
+    right: Option<String>,
+}
+
+/// Everything the game knows about a block.
+#[derive(Clone, Debug)]
+pub struct BlockDefinition {
+    pub id: u16,
+    pub name: String,
+    pub solid: bool,
+    pub transparent: bool,
+    pub liquid: bool,
+    /// Falls when nothing solid is under it.
+    pub gravity: bool,
+    /// Block light emitted, 0-15.
+    pub light: u8,
+    pub breakable: bool,
+    /// Seconds to extract with bare hands.
+    pub hardness: f32,
+    /// Texture folder per face, in `BlockFace::all` order.
+    pub textures: [String; 6],
+    pub drops: BlockType,
+    pub tool: Option<Tool>,
+    pub color: [f32; 4],
+}
+
+impl BlockDefinition {
+    pub fn texture(&self, face: BlockFace) -> &str {
+        let index = match face {
+            BlockFace::Top => 0,
+            BlockFace::Bottom => 1,
+            BlockFace::Front => 2,
+            BlockFace::Back => 3,
+            BlockFace::Left => 4,
+            BlockFace::Right => 5,
+        };
+        &self.textures[index]
+    }
+}
+
+/// Block definitions by id. The game uses one registry for the whole
+/// process, loaded from `BLOCKS_DIR` on first use.
+#[derive(Debug)]
+pub struct BlockRegistry {
+    definitions: Vec<Option<BlockDefinition>>,
+    by_name: HashMap<String, BlockType>,
+}
+
+impl BlockRegistry {
+    pub fn global() -> &'static BlockRegistry {
+        static REGISTRY: OnceLock<BlockRegistry> = OnceLock::new();
+        REGISTRY.get_or_init(|| {
+            let dir = Path::new(BLOCKS_DIR);
+            if !dir.is_dir() {
+                return Self::builtin();
+            }
+            Self::load_dir(dir).unwrap_or_else(|err| {
+                error!("{}; using the built-in blocks", err);
+                Self::builtin()
+            })
+        })
+    }
+
+    fn builtin() -> Self {
+        Self::from_sources(&[("built-in blocks", BUILTIN_DEFINITIONS)])
+            .expect("built-in block definitions are valid")
+    }
+
+    pub fn load_dir(dir: &Path) -> Result<Self, String> {
+        let entries = fs::read_dir(dir)
+            .map_err(|err| format!("failed to read block definitions {:?}: {}", dir, err))?;
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+
+        let mut sources = Vec::with_capacity(paths.len());
+        for path in &paths {
+            let text = fs::read_to_string(path)
+                .map_err(|err| format!("failed to read block definitions {:?}: {}", path, err))?;
+            sources.push((path.display().to_string(), text));
+        }
+        let sources: Vec<(&str, &str)> = sources
+            .iter()
+            .map(|(name, text)| (name.as_str(), text.as_str()))
+            .collect();
+        Self::from_sources(&sources)
+    }
+
+    /// Build a registry from `(source name, JSON)` pairs. Ids and names must
+    /// be unique, and every built-in block must be declared under its id.
+    pub fn from_sources(sources: &[(&str, &str)]) -> Result<Self, String> {
+        let mut entries: Vec<(&str, BlockEntry)> = Vec::new();
+        for (source, text) in sources {
+            let file: BlockFile = serde_json::from_str(text)
+                .map_err(|err| format!("failed to parse block definitions {}: {}", source, err))?;
+            entries.extend(file.blocks.into_iter().map(|entry| (*source, entry)));
+        }
+
+        let mut declared_by: HashMap<u16, &str> = HashMap::new();
+        let mut by_name = HashMap::new();
+        for (source, entry) in &entries {
+            if let Some(other) = declared_by.insert(entry.id, source) {
+                return Err(format!(
+                    "block id {} is declared in both {} and {}",
+                    entry.id, other, source
+                ));
+            }
+            if by_name
+                .insert(entry.name.clone(), BlockType(entry.id))
+                .is_some()
+            {
+                return Err(format!("block {:?} is declared twice", entry.name));
+            }
+            if entry.light > 15 {
+                return Err(format!(
+                    "block {:?} emits light {}; the most is 15",
+                    entry.name, entry.light
+                ));
+            }
+        }
+        for (block, name) in BUILTIN_BLOCKS {
+            if by_name.get(name) != Some(&block) {
+                return Err(format!(
+                    "built-in block {:?} must be declared with id {}",
+                    name, block.0
+                ));
+            }
+        }
+
+        let len = entries.iter().map(|(_, entry)| entry.id as usize + 1).max();
+        let mut definitions = vec![None; len.unwrap_or(0)];
+        for (_, entry) in entries {
+            let drops = match &entry.drops {
+                Some(name) => *by_name.get(name).ok_or_else(|| {
+                    format!("block {:?} drops unknown block {:?}", entry.name, name)
+                })?,
+                None => BlockType(entry.id),
+            };
+            let textures = entry.textures.resolve(&entry.name);
+            definitions[entry.id as usize] = Some(BlockDefinition {
+                id: entry.id,
+                name: entry.name,
+                solid: entry.solid,
+                transparent: entry.transparent,
+                liquid: entry.liquid,
+                gravity: entry.gravity,
+                light: entry.light,
+                breakable: entry.breakable,
+                hardness: entry.hardness,
+                textures,
+                drops,
+                tool: entry.tool,
+                color: entry.color,
+            });
+        }
+
+        Ok(Self {
+            definitions,
+            by_name,
+        })
+    }
+
+    pub fn contains(&self, id: u16) -> bool {
+        self.definitions
+            .get(id as usize)
+            .is_some_and(|definition| definition.is_some())
+    }
+
+    pub fn try_get(&self, block: BlockType) -> Option<&BlockDefinition> {
+        self.definitions.get(block.0 as usize)?.as_ref()
+    }
+
+    /// A `BlockType` only exists for ids the registry declares.
+    pub fn get(&self, block: BlockType) -> &BlockDefinition {
+        self.try_get(block)
+            .unwrap_or_else(|| panic!("block id {} is not registered", block.0))
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<BlockType> {
+        self.by_name.get(name).copied()
+    }
+
+    /// Like `by_name`, but also accepting the names blocks were saved under
+    /// before the registry (`PackedIce` for `packed_ice`).
+    pub fn by_legacy_name(&self, name: &str) -> Option<BlockType> {
+        self.by_name(name).or_else(|| {
+            let flattened = name.to_ascii_lowercase();
+            self.iter()
+                .find(|definition| definition.name.replace('_', "") == flattened)
+                .map(|definition| BlockType(definition.id))
+        })
+    }
+
+    /// Stable FNV-1a hash of every block's id and name. Peers whose
+    /// registries hash differently would read each other's chunks as
+    /// different blocks.
+    pub fn fingerprint(&self) -> u64 {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+        const FNV_PRIME: u64 = 0x100000001b3;
+
+        self.iter()
+            .flat_map(|definition| {
+                let id = definition.id.to_le_bytes().into_iter();
+                // Terminated so "ab" then "c" differs from "a" then "bc".
+                id.chain(definition.name.bytes()).chain([0])
+            })
+            .fold(FNV_OFFSET_BASIS, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+            })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &BlockDefinition> {
+        self.definitions.iter().flatten()
+    }
+
+    #[allow(dead_code)]
+    pub fn blocks(&self) -> impl Iterator<Item = BlockType> + '_ {
+        self.iter().map(|definition| BlockType(definition.id))
+    }
+}
+
+impl FaceTextures {
+    fn resolve(&self, name: &str) -> [String; 6] {
+        let all = self.all.as_deref().unwrap_or(name);
+        let side = self.side.as_deref().unwrap_or(all);
+        [
+            self.top.as_deref().unwrap_or(all),
+            self.bottom.as_deref().unwrap_or(all),
+            self.front.as_deref().unwrap_or(side),
+            self.back.as_deref().unwrap_or(side),
+            self.left.as_deref().unwrap_or(side),
+            self.right.as_deref().unwrap_or(side),
+        ]
+        .map(str::to_string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_add_blocks_without_moving_builtin_ids() {
+        let pack = r#"{ "blocks": [
+            { "id": 200, "name": "basalt", "hardness": 4.0, "tool": "pickaxe",
+              "drops": "cobblestone", "textures": { "all": "stone", "top": "basalt" },
+              "color": [0.2, 0.2, 0.25, 1.0] }
+        ] }"#;
+        let registry =
+            BlockRegistry::from_sources(&[("core", BUILTIN_DEFINITIONS), ("pack", pack)]).unwrap();
+
+        let basalt = registry.by_name("basalt").unwrap();
+        assert_eq!(basalt.0, 200);
+        let definition = registry.get(basalt);
+        assert!(definition.solid && definition.breakable);
+        assert_eq!(definition.drops, BlockType::Cobblestone);
+        assert_eq!(definition.tool, Some(Tool::Pickaxe));
+        assert_eq!(definition.texture(BlockFace::Top), "basalt");
+        assert_eq!(definition.texture(BlockFace::Left), "stone");
+        assert!(!registry.contains(199));
+        assert_eq!(
+            registry.by_legacy_name("PackedIce"),
+            Some(BlockType::PackedIce)
+        );
+        for (block, name) in BUILTIN_BLOCKS {
+            assert_eq!(registry.by_name(name), Some(block));
+        }
+
+        // Built-in ids can't be taken over, and every built-in must be there.
+        let clash = r#"{ "blocks": [ { "id": 6, "name": "gravel", "color": [0, 0, 0, 1] } ] }"#;
+        assert!(
+            BlockRegistry::from_sources(&[("core", BUILTIN_DEFINITIONS), ("clash", clash)])
+                .is_err()
+        );
+        assert!(BlockRegistry::from_sources(&[("pack", pack)]).is_err());
+
+        // A pack changes the fingerprint peers compare when they connect.
+        let core = BlockRegistry::from_sources(&[("core", BUILTIN_DEFINITIONS)]).unwrap();
+        assert_eq!(core.fingerprint(), BlockRegistry::builtin().fingerprint());
+        assert_ne!(core.fingerprint(), registry.fingerprint());
+
+        // Binary formats keep the old enum encoding; text ones use names.
+        let bytes = bincode::serialize(&BlockType::Glowstone).unwrap();
+        assert_eq!(bytes, 14u32.to_le_bytes());
+        assert_eq!(
+            serde_json::to_string(&BlockType::PackedIce).unwrap(),
+            "\"packed_ice\""
+        );
+        let legacy: BlockType = serde_json::from_str("\"PackedIce\"").unwrap();
+        assert_eq!(legacy, BlockType::PackedIce);
+    }
+}
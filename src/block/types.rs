@@ -1,147 +1,221 @@
-use serde::{Deserialize, Serialize};
-
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
-#[repr(u8)]
-pub enum BlockType {
-    Air = 0,
-    Stone = 1,
-    Dirt = 2,
-    Grass = 3,
-    Wood = 4,
-    Leaves = 5,
-    Sand = 6,
-    Water = 7,
-    Cobblestone = 8,
-    Planks = 9,
-    Bedrock = 10, // Unbreakable planet core
-    Snow = 11,
-    Ice = 12,
-    PackedIce = 13,
+use super::registry::{BlockDefinition, BlockRegistry};
+use crate::texture::BlockFace;
+use crate::tools::Tool;
+use serde::de::{self, Deserializer, EnumAccess, VariantAccess, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+
+/// A block, by its id in the `BlockRegistry`. The built-in blocks below keep
+/// their ids for good; `assets/blocks/` declares what they are and may add
+/// more.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct BlockType(pub(super) u16);
+
+#[allow(non_upper_case_globals)]
+impl BlockType {
+    pub const Air: BlockType = BlockType(0);
+    pub const Stone: BlockType = BlockType(1);
+    pub const Dirt: BlockType = BlockType(2);
+    pub const Grass: BlockType = BlockType(3);
+    pub const Wood: BlockType = BlockType(4);
+    pub const Leaves: BlockType = BlockType(5);
+    pub const Sand: BlockType = BlockType(6);
+    pub const Water: BlockType = BlockType(7);
+    pub const Cobblestone: BlockType = BlockType(8);
+    pub const Planks: BlockType = BlockType(9);
+    pub const Bedrock: BlockType = BlockType(10); // Unbreakable planet core
+    pub const Snow: BlockType = BlockType(11);
+    pub const Ice: BlockType = BlockType(12);
+    pub const PackedIce: BlockType = BlockType(13);
+    pub const Glowstone: BlockType = BlockType(14);
+    pub const CoalOre: BlockType = BlockType(15);
+    pub const IronOre: BlockType = BlockType(16);
+    pub const CopperOre: BlockType = BlockType(17);
+    pub const GoldOre: BlockType = BlockType(18);
+    pub const TallGrass: BlockType = BlockType(19);
 }
 
+/// Blocks the code refers to by name, and the registry name each must have.
+pub(super) const BUILTIN_BLOCKS: [(BlockType, &str); 20] = [
+    (BlockType::Air, "air"),
+    (BlockType::Stone, "stone"),
+    (BlockType::Dirt, "dirt"),
+    (BlockType::Grass, "grass"),
+    (BlockType::Wood, "wood"),
+    (BlockType::Leaves, "leaves"),
+    (BlockType::Sand, "sand"),
+    (BlockType::Water, "water"),
+    (BlockType::Cobblestone, "cobblestone"),
+    (BlockType::Planks, "planks"),
+    (BlockType::Bedrock, "bedrock"),
+    (BlockType::Snow, "snow"),
+    (BlockType::Ice, "ice"),
+    (BlockType::PackedIce, "packed_ice"),
+    (BlockType::Glowstone, "glowstone"),
+    (BlockType::CoalOre, "coal_ore"),
+    (BlockType::IronOre, "iron_ore"),
+    (BlockType::CopperOre, "copper_ore"),
+    (BlockType::GoldOre, "gold_ore"),
+    (BlockType::TallGrass, "tall_grass"),
+];
+
+const BUILTIN_NAMES: [&str; BUILTIN_BLOCKS.len()] = {
+    let mut names = [""; BUILTIN_BLOCKS.len()];
+    let mut index = 0;
+    while index < names.len() {
+        names[index] = BUILTIN_BLOCKS[index].1;
+        index += 1;
+    }
+    names
+};
+
 impl BlockType {
+    /// Registry id; stable across saves.
+    pub fn id(self) -> u16 {
+        self.0
+    }
+
+    /// The block with registry id `id`, if the registry declares one.
+    pub fn from_id(id: u16) -> Option<Self> {
+        BlockRegistry::global()
+            .contains(id)
+            .then_some(BlockType(id))
+    }
+
+    pub fn definition(&self) -> &'static BlockDefinition {
+        BlockRegistry::global().get(*self)
+    }
+
+    pub fn name(&self) -> &'static str {
+        &self.definition().name
+    }
+
     pub fn is_solid(&self) -> bool {
-        !matches!(self, BlockType::Air | BlockType::Water)
+        self.definition().solid
     }
 
     pub fn is_breakable(&self) -> bool {
-        !matches!(self, BlockType::Bedrock)
+        self.definition().breakable
     }
 
     pub fn is_transparent(&self) -> bool {
-        matches!(
-            self,
-            BlockType::Air | BlockType::Water | BlockType::Leaves | BlockType::Ice
-        )
+        self.definition().transparent
+    }
+
+    /// Blocks that fall when nothing solid is under them.
+    pub fn is_affected_by_gravity(&self) -> bool {
+        self.definition().gravity
+    }
+
+    /// Block light level (0-15) this block emits into the voxel light engine.
+    pub fn light_emission(&self) -> u8 {
+        self.definition().light
     }
 
     /// Base time in seconds to extract this block with bare hands
     pub fn extraction_time(&self) -> f32 {
-        match self {
-            BlockType::Air => 0.0,
-            BlockType::Bedrock => f32::INFINITY, // Cannot be extracted
-            BlockType::Dirt | BlockType::Sand | BlockType::Snow => 0.5,
-            BlockType::Grass => 0.6,
-            BlockType::Wood | BlockType::Planks => 2.0,
-            BlockType::Leaves => 0.2,
-            BlockType::Stone | BlockType::Cobblestone => 3.0,
-            BlockType::Ice | BlockType::PackedIce => 1.5,
-            BlockType::Water => 0.0, // Cannot be extracted as a block
+        let definition = self.definition();
+        if definition.breakable {
+            definition.hardness
+        } else {
+            f32::INFINITY // Cannot be extracted
         }
     }
 
+    /// Tool that extracts this block fastest, if any.
+    pub fn preferred_tool(&self) -> Option<Tool> {
+        self.definition().tool
+    }
+
+    /// Block picked up when this one is extracted.
+    pub fn drops(&self) -> BlockType {
+        self.definition().drops
+    }
+
     pub fn is_visible(&self) -> bool {
-        !matches!(self, BlockType::Air)
+        *self != BlockType::Air
     }
 
     pub fn is_liquid(&self) -> bool {
-        matches!(self, BlockType::Water)
-    }
-
-    pub fn get_texture_name(&self) -> &str {
-        match self {
-            BlockType::Air => "air",
-            BlockType::Stone => "stone",
-            BlockType::Dirt => "dirt",
-            BlockType::Grass => "grass",
-            BlockType::Wood => "wood",
-            BlockType::Leaves => "leaves",
-            BlockType::Sand => "sand",
-            BlockType::Water => "water",
-            BlockType::Cobblestone => "cobblestone",
-            BlockType::Planks => "planks",
-            BlockType::Bedrock => "bedrock",
-            BlockType::Snow => "snow",
-            BlockType::Ice => "ice",
-            BlockType::PackedIce => "packed_ice",
-        }
+        self.definition().liquid
     }
 
-    pub fn get_texture_indices(&self) -> [u32; 6] {
-        match self {
-            BlockType::Air => [0; 6],
-            BlockType::Stone => [1, 1, 1, 1, 1, 1],
-            BlockType::Dirt => [2, 2, 2, 2, 2, 2],
-            BlockType::Grass => [0, 2, 3, 3, 3, 3],
-            BlockType::Wood => [5, 5, 4, 4, 4, 4],
-            BlockType::Leaves => [6, 6, 6, 6, 6, 6],
-            BlockType::Sand => [7, 7, 7, 7, 7, 7],
-            BlockType::Water => [8, 8, 8, 8, 8, 8],
-            BlockType::Cobblestone => [9, 9, 9, 9, 9, 9],
-            BlockType::Planks => [10, 10, 10, 10, 10, 10],
-            BlockType::Bedrock => [11, 11, 11, 11, 11, 11],
-            BlockType::Snow => [12, 12, 12, 12, 12, 12],
-            BlockType::Ice => [13, 13, 13, 13, 13, 13],
-            BlockType::PackedIce => [14, 14, 14, 14, 14, 14],
-        }
+    /// Visible blocks that are neither solid nor liquid, such as tall
+    /// grass. They are drawn as two crossed quads rather than a cube.
+    pub fn is_plant(&self) -> bool {
+        self.is_visible() && !self.is_solid() && !self.is_liquid()
+    }
+
+    /// Texture folder under `assets/textures/blocks/` for one face.
+    pub fn texture_name(&self, face: BlockFace) -> &'static str {
+        self.definition().texture(face)
     }
 
     pub fn get_color(&self) -> [f32; 4] {
-        match self {
-            BlockType::Grass => [0.5, 0.8, 0.3, 1.0],
-            BlockType::Dirt => [0.5, 0.35, 0.2, 1.0],
-            BlockType::Stone => [0.5, 0.5, 0.5, 1.0],
-            BlockType::Wood => [0.6, 0.4, 0.2, 1.0],
-            BlockType::Leaves => [0.2, 0.6, 0.2, 1.0],
-            BlockType::Sand => [0.9, 0.8, 0.6, 1.0],
-            BlockType::Water => [0.2, 0.4, 0.8, 0.8],
-            BlockType::Cobblestone => [0.4, 0.4, 0.4, 1.0],
-            BlockType::Planks => [0.7, 0.5, 0.3, 1.0],
-            BlockType::Bedrock => [0.1, 0.1, 0.1, 1.0],
-            BlockType::Snow => [0.95, 0.95, 1.0, 1.0],
-            BlockType::Ice => [0.7, 0.85, 1.0, 0.9],
-            BlockType::PackedIce => [0.6, 0.75, 0.95, 1.0],
-            _ => [1.0, 1.0, 1.0, 1.0],
-        }
+        self.definition().color
     }
+}
 
-    pub fn to_u8(self) -> u8 {
-        self as u8
-    }
-
-    pub fn from_u8(value: u8) -> Option<Self> {
-        match value {
-            0 => Some(BlockType::Air),
-            1 => Some(BlockType::Stone),
-            2 => Some(BlockType::Dirt),
-            3 => Some(BlockType::Grass),
-            4 => Some(BlockType::Wood),
-            5 => Some(BlockType::Leaves),
-            6 => Some(BlockType::Sand),
-            7 => Some(BlockType::Water),
-            8 => Some(BlockType::Cobblestone),
-            9 => Some(BlockType::Planks),
-            10 => Some(BlockType::Bedrock),
-            11 => Some(BlockType::Snow),
-            12 => Some(BlockType::Ice),
-            13 => Some(BlockType::PackedIce),
-            _ => None,
+impl fmt::Debug for BlockType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match BlockRegistry::global().try_get(*self) {
+            Some(definition) => f.write_str(&definition.name),
+            None => write!(f, "BlockType({})", self.0),
         }
     }
 }
 
-impl Default for BlockType {
-    fn default() -> Self {
-        BlockType::Air
+/// Serialised like the enum `BlockType` used to be: binary formats store the
+/// id where the variant index was, text formats the name. Names from before
+/// the registry (`PackedIce`) are still read.
+impl Serialize for BlockType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit_variant("BlockType", self.0 as u32, self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for BlockType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_enum("BlockType", &BUILTIN_NAMES, BlockTypeVisitor)
+    }
+}
+
+struct BlockTypeVisitor;
+
+impl<'de> Visitor<'de> for BlockTypeVisitor {
+    type Value = BlockType;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a block id or name")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<BlockType, E> {
+        u16::try_from(value)
+            .ok()
+            .and_then(BlockType::from_id)
+            .ok_or_else(|| E::custom(format!("unknown block id {}", value)))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<BlockType, E> {
+        BlockRegistry::global()
+            .by_legacy_name(value)
+            .ok_or_else(|| E::custom(format!("unknown block {:?}", value)))
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<BlockType, A::Error> {
+        let (block, variant) = data.variant_seed(BlockTypeSeed)?;
+        variant.unit_variant()?;
+        Ok(block)
+    }
+}
+
+/// Reads the variant tag of a serialised block, index or name.
+struct BlockTypeSeed;
+
+impl<'de> de::DeserializeSeed<'de> for BlockTypeSeed {
+    type Value = BlockType;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<BlockType, D::Error> {
+        deserializer.deserialize_identifier(BlockTypeVisitor)
     }
 }
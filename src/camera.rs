@@ -1,5 +1,6 @@
 use crate::loading::GameState;
 use crate::planet::config::PlanetConfig;
+use crate::player::{local_player_name, Player};
 use crate::world::WorldGenerator;
 use bevy::input::mouse::MouseMotion;
 use bevy::pbr::{FogFalloff, FogSettings};
@@ -36,12 +37,21 @@ pub const GRAVITY: f32 = -20.0;
 pub const JUMP_VELOCITY: f32 = 8.0;
 pub const DOUBLE_TAP_TIME: f64 = 0.3;
 
+/// Mouse look. Block interaction aims along the view after it.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CameraLookSet;
+
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(OnEnter(GameState::Playing), setup_camera)
-            .add_systems(Update, camera_look.run_if(in_state(GameState::Playing)));
+            .add_systems(
+                Update,
+                camera_look
+                    .in_set(CameraLookSet)
+                    .run_if(in_state(GameState::Playing)),
+            );
     }
 }
 
@@ -86,6 +96,7 @@ fn setup_camera(
             ..default()
         },
         PlayerCamera,
+        Player::new(0, local_player_name()),
         CameraController::default(),
         FogSettings {
             color: Color::srgba(0.7, 0.8, 0.9, 1.0),
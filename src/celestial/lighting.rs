@@ -1,5 +1,6 @@
 use crate::celestial::sun::calculate_local_sun_angle;
 use crate::celestial::time::GameTime;
+use crate::chunk::material::ChunkRenderSettings;
 use crate::chunk::ChunkPos;
 use bevy::prelude::*;
 use std::f32::consts::PI;
@@ -129,7 +130,12 @@ impl Plugin for LightingPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<LightingUpdateTimer>().add_systems(
             Update,
-            (update_chunk_lighting, apply_player_lighting).chain(),
+            (
+                update_chunk_lighting,
+                apply_player_lighting,
+                update_sky_light,
+            )
+                .chain(),
         );
     }
 }
@@ -160,3 +166,28 @@ fn apply_player_lighting(
     // TODO: Re-enable fog integration once we figure out the correct FogSettings API
     // For now, the sky color changes and sun movement will demonstrate the day/night cycle
 }
+
+/// Scale the voxel sky light channel by the light level at the player's chunk.
+fn update_sky_light(
+    game_time: Res<GameTime>,
+    timer: Res<LightingUpdateTimer>,
+    player_query: Query<&Transform, With<crate::camera::CameraController>>,
+    render_settings: Option<ResMut<ChunkRenderSettings>>,
+) {
+    let Some(mut render_settings) = render_settings else {
+        return;
+    };
+    if !timer.timer.just_finished() {
+        return;
+    }
+    let Ok(transform) = player_query.get_single() else {
+        return;
+    };
+
+    let mut lighting = ChunkLighting::new(ChunkPos::from_world_pos(transform.translation));
+    lighting.update(&game_time);
+
+    if (render_settings.sky_light - lighting.light_level).abs() > 0.01 {
+        render_settings.sky_light = lighting.light_level;
+    }
+}
@@ -1,14 +1,21 @@
 use crate::block::BlockType;
 use bevy::prelude::*;
-use rand::{rngs::StdRng, Rng, SeedableRng};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 
 pub const CHUNK_SIZE: usize = 32;
 pub const CHUNK_VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
 pub const CHUNK_SIZE_F32: f32 = CHUNK_SIZE as f32;
 
 const CHUNK_PAYLOAD_MAGIC: [u8; 4] = *b"FBCH";
-pub const CHUNK_PAYLOAD_VERSION: u8 = 1;
+pub const CHUNK_PAYLOAD_VERSION: u8 = 3;
+/// The v2 layout with one-byte block ids, from before the block registry.
+/// Still decoded.
+pub const BYTE_ID_CHUNK_PAYLOAD_VERSION: u8 = 2;
+/// Original header-less format: palette and runs only. Still decoded.
+pub const LEGACY_CHUNK_PAYLOAD_VERSION: u8 = 1;
+/// Magic, version, position, revision, seed, config hash and body CRC32.
+const CHUNK_PAYLOAD_HEADER_LEN: usize = 4 + 1 + 12 + 4 + 8 + 8 + 4;
 
 #[derive(Debug)]
 pub enum ChunkPayloadError {
@@ -16,10 +23,15 @@ pub enum ChunkPayloadError {
     UnsupportedVersion(u8),
     UnexpectedEof,
     PaletteTooLarge(usize),
-    UnknownBlock(u8),
+    UnknownBlock(u16),
     PaletteIndexOutOfBounds(u16),
     RunOverflow,
     LengthMismatch { expected: usize, actual: usize },
+    ChecksumMismatch { expected: u32, actual: u32 },
+    MissingSection(u16),
+    DuplicateSection(u16),
+    SectionOutOfBounds(u16),
+    MalformedSection(u16),
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -28,17 +40,173 @@ pub struct VoxelRun {
     pub length: u16,
 }
 
+/// Per-chunk metadata carried by v2 and v3 payloads. Legacy payloads decode with a
+/// zeroed header.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChunkPayloadHeader {
+    pub position: ChunkPos,
+    pub revision: u32,
+    pub generator_seed: u64,
+    /// Fingerprint of the world-generation config that baked the chunk.
+    pub config_hash: u64,
+}
+
+/// A tagged blob in the v2/v3 section table. Sections this build does not
+/// understand are kept verbatim so re-encoding does not drop them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PayloadSection {
+    pub kind: u16,
+    pub data: Vec<u8>,
+}
+
+impl PayloadSection {
+    /// Palette and runs; required in every v2/v3 payload.
+    pub const BLOCKS: u16 = 1;
+    /// Reserved for baked voxel light.
+    #[allow(dead_code)]
+    pub const LIGHT: u16 = 2;
+    /// Reserved for block-entity data.
+    #[allow(dead_code)]
+    pub const BLOCK_ENTITIES: u16 = 3;
+    /// Levels of flowing water, as `(voxel index, level)` pairs.
+    pub const FLUID_LEVELS: u16 = 4;
+}
+
 #[derive(Clone, Debug)]
 pub struct ChunkPayload {
     pub version: u8,
+    pub header: ChunkPayloadHeader,
     pub palette: Vec<BlockType>,
     pub runs: Vec<VoxelRun>,
+    /// Non-block sections, in the order they appear in the section table.
+    pub sections: Vec<PayloadSection>,
 }
 
-/// Compact storage for chunk voxel data in X-major linear order.
+/// Compact storage for chunk voxel data in X-major linear order. Chunks made
+/// of a single block type store just that block; anything else keeps a
+/// palette of the block types present plus bit-packed palette indices. `set`
+/// upgrades the representation transparently.
+///
+/// Water is a source unless it has a level in `fluid_levels`, in which case
+/// it is flowing water: `1..=7` spreading sideways, or `FALLING_WATER_LEVEL`.
 #[derive(Clone, Debug)]
 pub struct ChunkStorage {
-    voxels: Box<[BlockType; CHUNK_VOLUME]>,
+    voxels: Voxels,
+    fluid_levels: BTreeMap<u16, u8>,
+}
+
+/// Level of flowing water fed from above: full height, but not a source.
+pub const FALLING_WATER_LEVEL: u8 = 8;
+
+#[derive(Clone, Debug)]
+enum Voxels {
+    Uniform(BlockType),
+    Paletted(PalettedVoxels),
+}
+
+/// Palette indices packed `bits` at a time into `u64` words. `bits` is always
+/// a power of two so an index never straddles two words.
+#[derive(Clone, Debug)]
+struct PalettedVoxels {
+    palette: Vec<BlockType>,
+    bits: usize,
+    words: Box<[u64]>,
+}
+
+impl PalettedVoxels {
+    fn with_palette(palette: Vec<BlockType>) -> Self {
+        let bits = Self::bits_for(palette.len());
+        Self {
+            palette,
+            bits,
+            words: vec![0; CHUNK_VOLUME * bits / 64].into_boxed_slice(),
+        }
+    }
+
+    fn bits_for(palette_len: usize) -> usize {
+        match palette_len {
+            0..=2 => 1,
+            3..=4 => 2,
+            5..=16 => 4,
+            17..=256 => 8,
+            _ => 16,
+        }
+    }
+
+    #[inline]
+    fn index_at(&self, idx: usize) -> usize {
+        let per_word = 64 / self.bits;
+        let shift = (idx % per_word) * self.bits;
+        let mask = (1u64 << self.bits) - 1;
+        ((self.words[idx / per_word] >> shift) & mask) as usize
+    }
+
+    #[inline]
+    fn set_index(&mut self, idx: usize, palette_index: usize) {
+        let per_word = 64 / self.bits;
+        let shift = (idx % per_word) * self.bits;
+        let mask = (1u64 << self.bits) - 1;
+        let word = &mut self.words[idx / per_word];
+        *word = (*word & !(mask << shift)) | ((palette_index as u64 & mask) << shift);
+    }
+
+    #[inline]
+    fn get(&self, idx: usize) -> BlockType {
+        self.palette[self.index_at(idx)]
+    }
+
+    /// Palette index for `block`, appending it (and widening the packed
+    /// indices when needed) if it is not in the palette yet.
+    fn palette_index(&mut self, block: BlockType) -> usize {
+        if let Some(index) = self.palette.iter().position(|entry| *entry == block) {
+            return index;
+        }
+
+        // Blocks edited away keep their entries; reuse those before widening.
+        if Self::bits_for(self.palette.len() + 1) != self.bits {
+            self.compact();
+        }
+        self.palette.push(block);
+        let bits = Self::bits_for(self.palette.len());
+        if bits != self.bits {
+            let mut widened = Self {
+                palette: Vec::new(),
+                bits,
+                words: vec![0; CHUNK_VOLUME * bits / 64].into_boxed_slice(),
+            };
+            for idx in 0..CHUNK_VOLUME {
+                widened.set_index(idx, self.index_at(idx));
+            }
+            self.bits = bits;
+            self.words = widened.words;
+        }
+        self.palette.len() - 1
+    }
+
+    /// Drop the palette entries no voxel uses, renumbering the rest.
+    fn compact(&mut self) {
+        let mut used = vec![false; self.palette.len()];
+        for idx in 0..CHUNK_VOLUME {
+            used[self.index_at(idx)] = true;
+        }
+        if used.iter().all(|used| *used) {
+            return;
+        }
+
+        let mut remap = vec![0; self.palette.len()];
+        let mut palette = Vec::new();
+        for (index, block) in self.palette.iter().enumerate() {
+            if used[index] {
+                remap[index] = palette.len();
+                palette.push(*block);
+            }
+        }
+        for idx in 0..CHUNK_VOLUME {
+            let index = self.index_at(idx);
+            self.set_index(idx, remap[index]);
+        }
+        self.palette = palette;
+    }
 }
 
 impl ChunkStorage {
@@ -48,7 +216,8 @@ impl ChunkStorage {
 
     pub fn filled(block_type: BlockType) -> Self {
         Self {
-            voxels: Box::new([block_type; CHUNK_VOLUME]),
+            voxels: Voxels::Uniform(block_type),
+            fluid_levels: BTreeMap::new(),
         }
     }
 
@@ -56,38 +225,123 @@ impl ChunkStorage {
     where
         F: FnMut(usize, usize, usize) -> BlockType,
     {
-        let mut voxels = Box::new([BlockType::Air; CHUNK_VOLUME]);
+        let mut palette = Vec::new();
+        let mut indices = vec![0u16; CHUNK_VOLUME];
         for z in 0..CHUNK_SIZE {
             for y in 0..CHUNK_SIZE {
                 for x in 0..CHUNK_SIZE {
-                    let idx = Self::linear_index(x, y, z);
-                    voxels[idx] = f(x, y, z);
+                    let block = f(x, y, z);
+                    let index = match palette.iter().position(|entry| *entry == block) {
+                        Some(index) => index,
+                        None => {
+                            palette.push(block);
+                            palette.len() - 1
+                        }
+                    };
+                    indices[Self::linear_index(x, y, z)] = index as u16;
                 }
             }
         }
-        Self { voxels }
+
+        if palette.len() == 1 {
+            return Self::filled(palette[0]);
+        }
+
+        let mut paletted = PalettedVoxels::with_palette(palette);
+        for (idx, index) in indices.into_iter().enumerate() {
+            paletted.set_index(idx, index as usize);
+        }
+        Self {
+            voxels: Voxels::Paletted(paletted),
+            fluid_levels: BTreeMap::new(),
+        }
     }
 
     #[inline]
     pub fn get(&self, x: usize, y: usize, z: usize) -> BlockType {
-        let idx = Self::linear_index(x, y, z);
-        self.voxels[idx]
+        match &self.voxels {
+            Voxels::Uniform(block) => *block,
+            Voxels::Paletted(paletted) => paletted.get(Self::linear_index(x, y, z)),
+        }
+    }
+
+    #[inline]
+    fn get_linear(&self, idx: usize) -> BlockType {
+        match &self.voxels {
+            Voxels::Uniform(block) => *block,
+            Voxels::Paletted(paletted) => paletted.get(idx),
+        }
     }
 
     #[inline]
     pub fn set(&mut self, x: usize, y: usize, z: usize, block_type: BlockType) {
         let idx = Self::linear_index(x, y, z);
-        self.voxels[idx] = block_type;
+        if !self.fluid_levels.is_empty() {
+            self.fluid_levels.remove(&(idx as u16));
+        }
+        if let Voxels::Uniform(block) = self.voxels {
+            if block == block_type {
+                return;
+            }
+            self.voxels = Voxels::Paletted(PalettedVoxels::with_palette(vec![block]));
+        }
+
+        if let Voxels::Paletted(paletted) = &mut self.voxels {
+            let palette_index = paletted.palette_index(block_type);
+            paletted.set_index(idx, palette_index);
+        }
+    }
+
+    /// Level of the flowing water at a voxel; `None` for source water and
+    /// anything that isn't water.
+    pub fn fluid_level(&self, x: usize, y: usize, z: usize) -> Option<u8> {
+        if self.fluid_levels.is_empty() {
+            return None;
+        }
+        self.fluid_levels
+            .get(&(Self::linear_index(x, y, z) as u16))
+            .copied()
+    }
+
+    /// Make a voxel flowing water at `level`.
+    pub fn set_flowing_water(&mut self, x: usize, y: usize, z: usize, level: u8) {
+        debug_assert!((1..=FALLING_WATER_LEVEL).contains(&level));
+        self.set(x, y, z, BlockType::Water);
+        self.fluid_levels
+            .insert(Self::linear_index(x, y, z) as u16, level);
+    }
+
+    /// Flowing water levels by linear voxel index, in index order.
+    pub fn fluid_levels(&self) -> impl Iterator<Item = (usize, u8)> + '_ {
+        self.fluid_levels
+            .iter()
+            .map(|(index, level)| (*index as usize, *level))
     }
 
     #[inline]
     pub fn iter(&self) -> impl Iterator<Item = BlockType> + '_ {
-        self.voxels.iter().copied()
+        (0..CHUNK_VOLUME).map(move |idx| self.get_linear(idx))
     }
 
-    #[inline]
-    pub fn as_slice(&self) -> &[BlockType; CHUNK_VOLUME] {
-        &self.voxels
+    /// The single block type filling this chunk, if it is uniform.
+    pub fn uniform_block(&self) -> Option<BlockType> {
+        match self.voxels {
+            Voxels::Uniform(block) => Some(block),
+            Voxels::Paletted(_) => None,
+        }
+    }
+
+    /// Approximate bytes held by this storage, including its heap buffers.
+    pub fn memory_usage(&self) -> usize {
+        let heap = match &self.voxels {
+            Voxels::Uniform(_) => 0,
+            Voxels::Paletted(paletted) => {
+                paletted.palette.capacity() * std::mem::size_of::<BlockType>()
+                    + std::mem::size_of_val(&*paletted.words)
+            }
+        };
+        let levels = self.fluid_levels.len() * std::mem::size_of::<(u16, u8)>();
+        std::mem::size_of::<Self>() + heap + levels
     }
 
     #[inline]
@@ -96,7 +350,7 @@ impl ChunkStorage {
         x + CHUNK_SIZE * (y + CHUNK_SIZE * z)
     }
 
-    pub fn encode_payload(&self) -> ChunkPayload {
+    pub fn encode_payload(&self, header: ChunkPayloadHeader) -> ChunkPayload {
         use std::convert::TryFrom;
 
         let mut palette = Vec::new();
@@ -142,34 +396,56 @@ impl ChunkStorage {
             });
         }
 
+        let mut sections = Vec::new();
+        if !self.fluid_levels.is_empty() {
+            let mut data = Vec::with_capacity(4 + self.fluid_levels.len() * 3);
+            data.extend_from_slice(&(self.fluid_levels.len() as u32).to_le_bytes());
+            for (index, level) in &self.fluid_levels {
+                data.extend_from_slice(&index.to_le_bytes());
+                data.push(*level);
+            }
+            sections.push(PayloadSection {
+                kind: PayloadSection::FLUID_LEVELS,
+                data,
+            });
+        }
+
         ChunkPayload {
             version: CHUNK_PAYLOAD_VERSION,
+            header,
             palette,
             runs,
+            sections,
         }
     }
 
-    pub fn encode_bytes(&self) -> Vec<u8> {
-        self.encode_payload().to_bytes()
+    pub fn encode_bytes(&self, header: ChunkPayloadHeader) -> Vec<u8> {
+        self.encode_payload(header).to_bytes()
     }
 
     pub fn from_payload(payload: &ChunkPayload) -> Result<Self, ChunkPayloadError> {
-        if payload.version != CHUNK_PAYLOAD_VERSION {
+        if !matches!(
+            payload.version,
+            CHUNK_PAYLOAD_VERSION | BYTE_ID_CHUNK_PAYLOAD_VERSION | LEGACY_CHUNK_PAYLOAD_VERSION
+        ) {
             return Err(ChunkPayloadError::UnsupportedVersion(payload.version));
         }
 
-        if payload.palette.len() > u16::MAX as usize {
+        // Palette indices are packed into at most two bytes per voxel.
+        if payload.palette.len() > 1 << 16 {
             return Err(ChunkPayloadError::PaletteTooLarge(payload.palette.len()));
         }
 
-        let mut voxels = Box::new([BlockType::Air; CHUNK_VOLUME]);
+        let mut paletted = PalettedVoxels::with_palette(payload.palette.clone());
         let mut offset = 0usize;
 
         for run in &payload.runs {
             let palette_index = run.palette_index as usize;
-            let block = payload.palette.get(palette_index).ok_or(
-                ChunkPayloadError::PaletteIndexOutOfBounds(run.palette_index),
-            )?;
+            if palette_index >= payload.palette.len() {
+                return Err(ChunkPayloadError::PaletteIndexOutOfBounds(
+                    run.palette_index,
+                ));
+            }
             let length = run.length as usize;
 
             if length == 0 {
@@ -181,7 +457,7 @@ impl ChunkStorage {
             }
 
             for idx in offset..offset + length {
-                voxels[idx] = *block;
+                paletted.set_index(idx, palette_index);
             }
             offset += length;
         }
@@ -193,7 +469,44 @@ impl ChunkStorage {
             });
         }
 
-        Ok(Self { voxels })
+        let mut storage = match payload.palette[..] {
+            [block] => Self::filled(block),
+            _ => Self {
+                voxels: Voxels::Paletted(paletted),
+                fluid_levels: BTreeMap::new(),
+            },
+        };
+        if let Some(section) = payload
+            .sections
+            .iter()
+            .find(|section| section.kind == PayloadSection::FLUID_LEVELS)
+        {
+            storage.fluid_levels = Self::read_fluid_levels(&storage, &section.data)?;
+        }
+        Ok(storage)
+    }
+
+    fn read_fluid_levels(
+        storage: &ChunkStorage,
+        data: &[u8],
+    ) -> Result<BTreeMap<u16, u8>, ChunkPayloadError> {
+        let malformed = |_| ChunkPayloadError::MalformedSection(PayloadSection::FLUID_LEVELS);
+        let mut reader = ByteReader::new(data);
+        let count = reader.u32().map_err(malformed)?;
+        let mut levels = BTreeMap::new();
+        for _ in 0..count {
+            let index = reader.u16().map_err(malformed)?;
+            let level = reader.u8().map_err(malformed)?;
+            let is_water = (index as usize) < CHUNK_VOLUME
+                && storage.get_linear(index as usize) == BlockType::Water;
+            if !is_water || !(1..=FALLING_WATER_LEVEL).contains(&level) {
+                return Err(ChunkPayloadError::MalformedSection(
+                    PayloadSection::FLUID_LEVELS,
+                ));
+            }
+            levels.insert(index, level);
+        }
+        Ok(levels)
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, ChunkPayloadError> {
@@ -203,95 +516,189 @@ impl ChunkStorage {
 }
 
 impl ChunkPayload {
+    /// Serialise in the layout of `self.version`: the legacy layout for v1,
+    /// otherwise the v2/v3 header followed by a checksummed section table.
     pub fn to_bytes(&self) -> Vec<u8> {
-        use std::convert::TryFrom;
-
-        let mut bytes = Vec::with_capacity(
-            4 + 1 + 2 + self.palette.len() + 4 + self.runs.len() * std::mem::size_of::<VoxelRun>(),
+        let mut blocks = Vec::with_capacity(
+            2 + self.palette.len() + 4 + self.runs.len() * std::mem::size_of::<VoxelRun>(),
         );
-        bytes.extend_from_slice(&CHUNK_PAYLOAD_MAGIC);
-        bytes.push(self.version);
-
-        let palette_len = u16::try_from(self.palette.len()).expect("palette exceeds u16 range");
-        bytes.extend_from_slice(&palette_len.to_le_bytes());
-        for block in &self.palette {
-            bytes.push(block.to_u8());
+        self.write_blocks(&mut blocks);
+
+        if self.version == LEGACY_CHUNK_PAYLOAD_VERSION {
+            let mut bytes = Vec::with_capacity(5 + blocks.len());
+            bytes.extend_from_slice(&CHUNK_PAYLOAD_MAGIC);
+            bytes.push(self.version);
+            bytes.extend_from_slice(&blocks);
+            return bytes;
         }
 
-        let run_len = u32::try_from(self.runs.len()).expect("run count exceeds u32 range");
-        bytes.extend_from_slice(&run_len.to_le_bytes());
-        for run in &self.runs {
-            bytes.extend_from_slice(&run.palette_index.to_le_bytes());
-            bytes.extend_from_slice(&run.length.to_le_bytes());
+        let sections: Vec<(u16, &[u8])> = std::iter::once((PayloadSection::BLOCKS, &blocks[..]))
+            .chain(
+                self.sections
+                    .iter()
+                    .map(|section| (section.kind, &section.data[..])),
+            )
+            .collect();
+
+        let section_count = u16::try_from(sections.len()).expect("too many payload sections");
+        let mut body = Vec::new();
+        body.extend_from_slice(&section_count.to_le_bytes());
+        for (kind, data) in &sections {
+            let length = u32::try_from(data.len()).expect("payload section exceeds u32 range");
+            body.extend_from_slice(&kind.to_le_bytes());
+            body.extend_from_slice(&length.to_le_bytes());
+        }
+        for (_, data) in &sections {
+            body.extend_from_slice(data);
         }
 
+        let header = &self.header;
+        let mut bytes = Vec::with_capacity(CHUNK_PAYLOAD_HEADER_LEN + body.len());
+        bytes.extend_from_slice(&CHUNK_PAYLOAD_MAGIC);
+        bytes.push(self.version);
+        bytes.extend_from_slice(&header.position.x.to_le_bytes());
+        bytes.extend_from_slice(&header.position.y.to_le_bytes());
+        bytes.extend_from_slice(&header.position.z.to_le_bytes());
+        bytes.extend_from_slice(&header.revision.to_le_bytes());
+        bytes.extend_from_slice(&header.generator_seed.to_le_bytes());
+        bytes.extend_from_slice(&header.config_hash.to_le_bytes());
+        bytes.extend_from_slice(&crc32(&body).to_le_bytes());
+        bytes.extend_from_slice(&body);
         bytes
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, ChunkPayloadError> {
-        let mut cursor = 0usize;
+        let mut reader = ByteReader::new(bytes);
 
-        if bytes.len() < 7 {
-            return Err(ChunkPayloadError::UnexpectedEof);
+        if reader.take(4)? != CHUNK_PAYLOAD_MAGIC {
+            return Err(ChunkPayloadError::InvalidMagic);
         }
 
-        if bytes[..4] != CHUNK_PAYLOAD_MAGIC {
-            return Err(ChunkPayloadError::InvalidMagic);
+        let version = reader.u8()?;
+        if version == LEGACY_CHUNK_PAYLOAD_VERSION {
+            let (palette, runs) = Self::read_blocks(&mut reader, version)?;
+            return Ok(ChunkPayload {
+                version,
+                header: ChunkPayloadHeader::default(),
+                palette,
+                runs,
+                sections: Vec::new(),
+            });
+        }
+        if !matches!(
+            version,
+            CHUNK_PAYLOAD_VERSION | BYTE_ID_CHUNK_PAYLOAD_VERSION
+        ) {
+            return Err(ChunkPayloadError::UnsupportedVersion(version));
+        }
+
+        let header = ChunkPayloadHeader {
+            position: ChunkPos::new(reader.i32()?, reader.i32()?, reader.i32()?),
+            revision: reader.u32()?,
+            generator_seed: reader.u64()?,
+            config_hash: reader.u64()?,
+        };
+        let expected = reader.u32()?;
+        let body = reader.rest();
+        let actual = crc32(body);
+        if actual != expected {
+            return Err(ChunkPayloadError::ChecksumMismatch { expected, actual });
+        }
+
+        let mut table = ByteReader::new(body);
+        let section_count = table.u16()? as usize;
+        let mut entries = Vec::with_capacity(section_count);
+        for _ in 0..section_count {
+            entries.push((table.u16()?, table.u32()? as usize));
+        }
+
+        let mut blocks = None;
+        let mut sections: Vec<PayloadSection> = Vec::new();
+        let mut data = ByteReader::new(table.rest());
+        for (kind, length) in entries {
+            let section = data
+                .take(length)
+                .map_err(|_| ChunkPayloadError::SectionOutOfBounds(kind))?;
+
+            let duplicate = if kind == PayloadSection::BLOCKS {
+                blocks.is_some()
+            } else {
+                sections.iter().any(|existing| existing.kind == kind)
+            };
+            if duplicate {
+                return Err(ChunkPayloadError::DuplicateSection(kind));
+            }
+
+            if kind == PayloadSection::BLOCKS {
+                blocks = Some(Self::read_blocks(&mut ByteReader::new(section), version)?);
+            } else {
+                sections.push(PayloadSection {
+                    kind,
+                    data: section.to_vec(),
+                });
+            }
         }
-        cursor += 4;
 
-        let version = bytes[cursor];
-        cursor += 1;
+        let (palette, runs) =
+            blocks.ok_or(ChunkPayloadError::MissingSection(PayloadSection::BLOCKS))?;
+
+        Ok(ChunkPayload {
+            version,
+            header,
+            palette,
+            runs,
+            sections,
+        })
+    }
 
-        if cursor + 2 > bytes.len() {
-            return Err(ChunkPayloadError::UnexpectedEof);
+    fn write_blocks(&self, bytes: &mut Vec<u8>) {
+        let palette_len = u16::try_from(self.palette.len()).expect("palette exceeds u16 range");
+        bytes.extend_from_slice(&palette_len.to_le_bytes());
+        for block in &self.palette {
+            // Registry ids; older versions only had room for a byte.
+            if self.version == CHUNK_PAYLOAD_VERSION {
+                bytes.extend_from_slice(&block.id().to_le_bytes());
+            } else {
+                bytes.push(u8::try_from(block.id()).expect("block id exceeds u8 range"));
+            }
         }
-        let palette_len = u16::from_le_bytes([bytes[cursor], bytes[cursor + 1]]) as usize;
-        cursor += 2;
 
-        if cursor + palette_len > bytes.len() {
-            return Err(ChunkPayloadError::UnexpectedEof);
+        let run_len = u32::try_from(self.runs.len()).expect("run count exceeds u32 range");
+        bytes.extend_from_slice(&run_len.to_le_bytes());
+        for run in &self.runs {
+            bytes.extend_from_slice(&run.palette_index.to_le_bytes());
+            bytes.extend_from_slice(&run.length.to_le_bytes());
         }
+    }
 
+    fn read_blocks(
+        reader: &mut ByteReader<'_>,
+        version: u8,
+    ) -> Result<(Vec<BlockType>, Vec<VoxelRun>), ChunkPayloadError> {
+        let palette_len = reader.u16()? as usize;
         let mut palette = Vec::with_capacity(palette_len);
         for _ in 0..palette_len {
-            let block_byte = bytes[cursor];
-            cursor += 1;
-            let block = BlockType::from_u8(block_byte)
-                .ok_or(ChunkPayloadError::UnknownBlock(block_byte))?;
+            let id = if version == CHUNK_PAYLOAD_VERSION {
+                reader.u16()?
+            } else {
+                reader.u8()? as u16
+            };
+            let block = BlockType::from_id(id).ok_or(ChunkPayloadError::UnknownBlock(id))?;
             palette.push(block);
         }
 
-        if cursor + 4 > bytes.len() {
-            return Err(ChunkPayloadError::UnexpectedEof);
-        }
-        let run_len = u32::from_le_bytes([
-            bytes[cursor],
-            bytes[cursor + 1],
-            bytes[cursor + 2],
-            bytes[cursor + 3],
-        ]) as usize;
-        cursor += 4;
-
-        let mut runs = Vec::with_capacity(run_len);
+        let run_len = reader.u32()? as usize;
+        let mut runs = Vec::with_capacity(run_len.min(CHUNK_VOLUME));
         for _ in 0..run_len {
-            if cursor + 4 > bytes.len() {
-                return Err(ChunkPayloadError::UnexpectedEof);
-            }
-            let palette_index = u16::from_le_bytes([bytes[cursor], bytes[cursor + 1]]);
-            let length = u16::from_le_bytes([bytes[cursor + 2], bytes[cursor + 3]]);
-            cursor += 4;
+            let palette_index = reader.u16()?;
+            let length = reader.u16()?;
             runs.push(VoxelRun {
                 palette_index,
                 length,
             });
         }
 
-        Ok(ChunkPayload {
-            version,
-            palette,
-            runs,
-        })
+        Ok((palette, runs))
     }
 
     pub fn total_voxels(&self) -> usize {
@@ -302,9 +709,95 @@ impl ChunkPayload {
     }
 }
 
+/// Little-endian cursor over payload bytes that reports truncation as
+/// `UnexpectedEof`.
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    cursor: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, cursor: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ChunkPayloadError> {
+        let end = self
+            .cursor
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(ChunkPayloadError::UnexpectedEof)?;
+        let slice = &self.bytes[self.cursor..end];
+        self.cursor = end;
+        Ok(slice)
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let slice = &self.bytes[self.cursor..];
+        self.cursor = self.bytes.len();
+        slice
+    }
+
+    fn u8(&mut self) -> Result<u8, ChunkPayloadError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ChunkPayloadError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, ChunkPayloadError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn i32(&mut self) -> Result<i32, ChunkPayloadError> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, ChunkPayloadError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ChunkPayloadError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Standard (IEEE, reflected) CRC-32, as used by zip and PNG.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in bytes {
+        crc = CRC32_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::block::BlockRegistry;
 
     #[test]
     fn payload_roundtrip_preserves_voxels() {
@@ -314,7 +807,7 @@ mod tests {
         storage.set(0, 1, 0, BlockType::Grass);
         storage.set(10, 10, 10, BlockType::Water);
 
-        let payload = storage.encode_payload();
+        let payload = storage.encode_payload(ChunkPayloadHeader::default());
         assert_eq!(payload.version, CHUNK_PAYLOAD_VERSION);
         assert_eq!(payload.total_voxels(), CHUNK_VOLUME);
 
@@ -332,12 +825,173 @@ mod tests {
         storage.set(0, 0, 0, BlockType::Bedrock);
         storage.set(31, 31, 31, BlockType::Grass);
 
-        let bytes = storage.encode_bytes();
+        let bytes = storage.encode_bytes(ChunkPayloadHeader::default());
         let decoded = ChunkStorage::from_bytes(&bytes).expect("decode bytes");
         assert_eq!(decoded.get(0, 0, 0), BlockType::Bedrock);
         assert_eq!(decoded.get(31, 31, 31), BlockType::Grass);
         assert_eq!(decoded.get(2, 2, 2), BlockType::Dirt);
     }
+
+    #[test]
+    fn v2_header_and_unknown_sections_roundtrip() {
+        let mut storage = ChunkStorage::new();
+        storage.set(5, 6, 7, BlockType::Sand);
+        let header = ChunkPayloadHeader {
+            position: ChunkPos::new(-3, 2, 9),
+            revision: 41,
+            generator_seed: 0xDEAD_BEEF,
+            config_hash: 0x0123_4567_89AB_CDEF,
+        };
+
+        let mut payload = storage.encode_payload(header);
+        payload.sections.push(PayloadSection {
+            kind: 0x7F00,
+            data: vec![1, 2, 3],
+        });
+
+        let decoded = ChunkPayload::from_bytes(&payload.to_bytes()).expect("decode v2");
+        assert_eq!(decoded.version, CHUNK_PAYLOAD_VERSION);
+        assert_eq!(decoded.header, header);
+        assert_eq!(decoded.sections, payload.sections);
+        let restored = ChunkStorage::from_payload(&decoded).expect("restore storage");
+        assert_eq!(restored.get(5, 6, 7), BlockType::Sand);
+    }
+
+    #[test]
+    fn fluid_levels_roundtrip_and_clear_on_set() {
+        let mut storage = ChunkStorage::new();
+        storage.set(3, 4, 5, BlockType::Water);
+        storage.set_flowing_water(4, 4, 5, 6);
+        storage.set_flowing_water(4, 3, 5, FALLING_WATER_LEVEL);
+
+        let bytes = storage.encode_bytes(ChunkPayloadHeader::default());
+        let decoded = ChunkStorage::from_bytes(&bytes).expect("decode fluid levels");
+        assert_eq!(decoded.fluid_level(3, 4, 5), None);
+        assert_eq!(decoded.fluid_level(4, 4, 5), Some(6));
+        assert_eq!(decoded.fluid_level(4, 3, 5), Some(FALLING_WATER_LEVEL));
+        assert_eq!(decoded.get(4, 4, 5), BlockType::Water);
+
+        storage.set(4, 4, 5, BlockType::Air);
+        assert_eq!(storage.fluid_level(4, 4, 5), None);
+        assert_eq!(storage.fluid_levels().count(), 1);
+    }
+
+    #[test]
+    fn corrupted_v2_payload_fails_checksum() {
+        let storage = ChunkStorage::filled(BlockType::Stone);
+        let mut bytes = storage.encode_bytes(ChunkPayloadHeader::default());
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0x01;
+
+        assert!(matches!(
+            ChunkStorage::from_bytes(&bytes),
+            Err(ChunkPayloadError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn legacy_v1_payload_still_decodes() {
+        let mut storage = ChunkStorage::filled(BlockType::Dirt);
+        storage.set(1, 2, 3, BlockType::Grass);
+
+        let mut payload = storage.encode_payload(ChunkPayloadHeader::default());
+        payload.version = LEGACY_CHUNK_PAYLOAD_VERSION;
+        let bytes = payload.to_bytes();
+        assert_eq!(bytes[4], LEGACY_CHUNK_PAYLOAD_VERSION);
+
+        let decoded = ChunkStorage::from_bytes(&bytes).expect("decode v1");
+        assert_eq!(decoded.get(1, 2, 3), BlockType::Grass);
+        assert_eq!(decoded.get(0, 0, 0), BlockType::Dirt);
+
+        // v2 had the current layout with one-byte block ids.
+        payload.version = BYTE_ID_CHUNK_PAYLOAD_VERSION;
+        let decoded = ChunkStorage::from_bytes(&payload.to_bytes()).expect("decode v2");
+        assert_eq!(decoded.get(1, 2, 3), BlockType::Grass);
+        assert_eq!(decoded.get(0, 0, 0), BlockType::Dirt);
+    }
+
+    #[test]
+    fn uniform_storage_upgrades_on_set() {
+        let mut storage = ChunkStorage::filled(BlockType::Stone);
+        assert_eq!(storage.uniform_block(), Some(BlockType::Stone));
+        let uniform_bytes = storage.memory_usage();
+
+        storage.set(3, 4, 5, BlockType::Stone);
+        assert_eq!(storage.uniform_block(), Some(BlockType::Stone));
+
+        storage.set(3, 4, 5, BlockType::Air);
+        assert_eq!(storage.uniform_block(), None);
+        assert_eq!(storage.get(3, 4, 5), BlockType::Air);
+        assert_eq!(storage.get(4, 4, 5), BlockType::Stone);
+        assert!(storage.memory_usage() > uniform_bytes);
+        assert!(storage.memory_usage() < CHUNK_VOLUME);
+    }
+
+    #[test]
+    fn palette_widens_without_losing_voxels() {
+        let blocks: Vec<BlockType> = BlockRegistry::global().blocks().collect();
+        let pick = |x: usize, y: usize, z: usize| blocks[(x + y * 7 + z * 13) % blocks.len()];
+
+        let mut storage = ChunkStorage::new();
+        for z in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    storage.set(x, y, z, pick(x, y, z));
+                }
+            }
+        }
+
+        let rebuilt = ChunkStorage::from_fn(pick);
+        for z in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    assert_eq!(storage.get(x, y, z), pick(x, y, z));
+                }
+            }
+        }
+        assert!(storage.iter().eq(rebuilt.iter()));
+        assert_eq!(
+            ChunkStorage::from_fn(|_, _, _| BlockType::Water).uniform_block(),
+            Some(BlockType::Water)
+        );
+    }
+
+    #[test]
+    fn palette_compacts_before_widening_and_packs_two_byte_indices() {
+        // Four blocks fit two-bit indices; once two are edited away the next
+        // two reuse their entries.
+        let mut storage = ChunkStorage::new();
+        storage.set(0, 0, 0, BlockType::Stone);
+        storage.set(1, 0, 0, BlockType::Dirt);
+        storage.set(2, 0, 0, BlockType::Grass);
+        storage.set(1, 0, 0, BlockType::Air);
+        storage.set(2, 0, 0, BlockType::Air);
+        storage.set(3, 0, 0, BlockType::Sand);
+        storage.set(4, 0, 0, BlockType::Water);
+        let Voxels::Paletted(paletted) = &storage.voxels else {
+            panic!("expected paletted voxels");
+        };
+        assert_eq!(paletted.bits, 2);
+        let blocks: Vec<BlockType> = (0..5).map(|x| storage.get(x, 0, 0)).collect();
+        assert_eq!(
+            blocks,
+            [
+                BlockType::Stone,
+                BlockType::Air,
+                BlockType::Air,
+                BlockType::Sand,
+                BlockType::Water,
+            ]
+        );
+
+        // Palettes past 256 entries get a u16 per voxel.
+        let mut wide = PalettedVoxels::with_palette(vec![BlockType::Stone; 300]);
+        assert_eq!(wide.bits, 16);
+        for idx in 0..CHUNK_VOLUME {
+            wide.set_index(idx, idx % 300);
+        }
+        assert!((0..CHUNK_VOLUME).all(|idx| wide.index_at(idx) == idx % 300));
+    }
 }
 
 impl Default for ChunkStorage {
@@ -346,7 +1000,7 @@ impl Default for ChunkStorage {
     }
 }
 
-#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ChunkPos {
     pub x: i32,
     pub y: i32,
@@ -375,6 +1029,13 @@ impl ChunkPos {
     }
 }
 
+/// A single voxel edit made through gameplay, in world block coordinates.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct BlockChanged {
+    pub position: IVec3,
+    pub block: BlockType,
+}
+
 #[derive(Component)]
 pub struct Chunk {
     pub storage: ChunkStorage,
@@ -472,10 +1133,7 @@ impl Chunk {
         position: ChunkPos,
         world_gen: &crate::world::WorldGenerator,
     ) -> Self {
-        let mut storage = world_gen.bake_chunk(position);
-        apply_lithology_layers(position, &mut storage, world_gen);
-        carve_caves_and_ores(position, &mut storage, world_gen);
-        Self::from_storage(position, storage)
+        Self::from_storage(position, world_gen.bake_chunk(position))
     }
 
     pub fn get_block(&self, x: usize, y: usize, z: usize) -> BlockType {
@@ -520,92 +1178,3 @@ impl Chunk {
         false
     }
 }
-
-fn apply_lithology_layers(
-    chunk_pos: ChunkPos,
-    storage: &mut ChunkStorage,
-    world_gen: &crate::world::WorldGenerator,
-) {
-    let world_origin = chunk_pos.to_world_pos();
-
-    for x in 0..CHUNK_SIZE {
-        for z in 0..CHUNK_SIZE {
-            let profile = world_gen
-                .lithology_profile_at(world_origin.x + x as f32, world_origin.z + z as f32);
-            let mut remaining = profile.surface_depth.max(1) as i32;
-
-            for y in (0..CHUNK_SIZE).rev() {
-                let mut block = storage.get(x, y, z);
-
-                if block == crate::block::BlockType::Air {
-                    continue;
-                }
-
-                if remaining > 0 {
-                    block = profile.surface_block;
-                    remaining -= 1;
-                } else {
-                    let mut depth = profile.surface_depth as i32;
-                    let mut replaced = false;
-                    for layer in &profile.strata {
-                        depth += layer.thickness as i32;
-                        if world_origin.y as i32 + CHUNK_SIZE as i32 - (y as i32 + 1) < depth {
-                            block = layer.block;
-                            replaced = true;
-                            break;
-                        }
-                    }
-
-                    if !replaced {
-                        block = profile.basement_block;
-                    }
-                }
-
-                storage.set(x, y, z, block);
-            }
-        }
-    }
-}
-
-fn carve_caves_and_ores(
-    chunk_pos: ChunkPos,
-    storage: &mut ChunkStorage,
-    world_gen: &crate::world::WorldGenerator,
-) {
-    let world_origin = chunk_pos.to_world_pos();
-    let seed = world_gen.config().seed;
-
-    for x in 0..CHUNK_SIZE {
-        for z in 0..CHUNK_SIZE {
-            let profile = world_gen
-                .lithology_profile_at(world_origin.x + x as f32, world_origin.z + z as f32);
-
-            let column_seed = seed
-                ^ ((world_origin.x as i64 + x as i64) as u64).wrapping_mul(0x9E3779B97F4A7C15)
-                ^ ((world_origin.z as i64 + z as i64) as u64).wrapping_mul(0xC2B2AE3D27D4EB4F)
-                ^ ((chunk_pos.y as i64) as u64).wrapping_mul(0x165667B19E3779F9);
-            let mut rng = StdRng::seed_from_u64(column_seed);
-
-            let cave_threshold = (profile.cave_bias * 0.015).clamp(0.0, 0.35);
-            let ore_threshold = (profile.ore_bias * 0.01).clamp(0.0, 0.25);
-
-            for y in (0..CHUNK_SIZE).rev() {
-                let block = storage.get(x, y, z);
-                if !block.is_solid() || matches!(block, BlockType::Bedrock) {
-                    continue;
-                }
-
-                if y > 2 && rng.gen::<f32>() < cave_threshold {
-                    storage.set(x, y, z, BlockType::Air);
-                    continue;
-                }
-
-                if matches!(block, BlockType::Stone | BlockType::Cobblestone)
-                    && rng.gen::<f32>() < ore_threshold
-                {
-                    storage.set(x, y, z, BlockType::Cobblestone);
-                }
-            }
-        }
-    }
-}
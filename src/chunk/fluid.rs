@@ -0,0 +1,264 @@
+use super::light::split_world;
+use super::{BlockChanged, Chunk, ChunkPos, FALLING_WATER_LEVEL};
+use crate::block::BlockType;
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::DerefMut;
+
+/// Cells the simulation updates per tick at most; the rest wait their turn.
+pub const FLUID_UPDATES_PER_TICK: usize = 256;
+/// Seconds water takes to advance one block.
+const FLOW_INTERVAL: f32 = 0.2;
+/// Level of flowing water next to a source. Each block further drops a level.
+const MAX_FLOW_LEVEL: u8 = 7;
+
+const HORIZONTAL: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+const NEIGHBORS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+/// A voxel as the simulation sees it. Unloaded chunks count as blocked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Cell {
+    Blocked,
+    Air,
+    Source,
+    /// Flowing water at a level, up to `FALLING_WATER_LEVEL`.
+    Flowing(u8),
+}
+
+impl Cell {
+    fn is_water(self) -> bool {
+        matches!(self, Cell::Source | Cell::Flowing(_))
+    }
+}
+
+/// Cellular water flow. Source water never changes on its own; air and
+/// flowing water next to a block edit are looked at again once water has had
+/// time to move, and become flowing water if fed from above (falling) or from
+/// the side (one level below the feeding cell), or air if nothing feeds them
+/// any more. Cells changed in a tick queue their neighbours, so flows advance
+/// one block per `FLOW_INTERVAL` and drain the same way.
+#[derive(Resource, Default)]
+pub struct FluidSimulation {
+    tick: u64,
+    queue: VecDeque<(u64, IVec3)>,
+    queued: HashSet<IVec3>,
+}
+
+impl FluidSimulation {
+    fn schedule(&mut self, position: IVec3, delay: u64) {
+        if self.queued.insert(position) {
+            self.queue.push_back((self.tick + delay, position));
+        }
+    }
+
+    fn schedule_around(&mut self, position: IVec3, delay: u64) {
+        self.schedule(position, delay);
+        for offset in NEIGHBORS {
+            self.schedule(position + offset, delay);
+        }
+    }
+
+    fn has_due_cells(&self) -> bool {
+        self.queue.front().is_some_and(|(due, _)| *due <= self.tick)
+    }
+
+    /// Update up to `budget` due cells. Every cell's new state is worked out
+    /// before any is written, so the order they were queued in doesn't skew
+    /// the flow.
+    fn step<C: DerefMut<Target = Chunk>>(
+        &mut self,
+        chunks: &mut HashMap<ChunkPos, C>,
+        delay: u64,
+        budget: usize,
+    ) {
+        let mut batch = Vec::new();
+        while batch.len() < budget && self.has_due_cells() {
+            let (_, position) = self.queue.pop_front().unwrap();
+            self.queued.remove(&position);
+            batch.push(position);
+        }
+
+        let changes: Vec<(IVec3, Cell)> = batch
+            .into_iter()
+            .filter_map(|position| {
+                let next = next_cell(chunks, position)?;
+                (next != cell_at(chunks, position)).then_some((position, next))
+            })
+            .collect();
+
+        for (position, cell) in &changes {
+            let (chunk_pos, [x, y, z]) = split_world(*position);
+            let Some(chunk) = chunks.get_mut(&chunk_pos) else {
+                continue;
+            };
+            match cell {
+                Cell::Flowing(level) => {
+                    chunk.storage.set_flowing_water(x, y, z, *level);
+                    chunk.dirty = true;
+                }
+                _ => chunk.set_block(x, y, z, BlockType::Air),
+            }
+            for offset in NEIGHBORS {
+                self.schedule(*position + offset, delay);
+            }
+        }
+    }
+}
+
+fn cell_at<C: DerefMut<Target = Chunk>>(chunks: &HashMap<ChunkPos, C>, position: IVec3) -> Cell {
+    let (chunk_pos, [x, y, z]) = split_world(position);
+    let Some(chunk) = chunks.get(&chunk_pos) else {
+        return Cell::Blocked;
+    };
+    match chunk.storage.get(x, y, z) {
+        BlockType::Air => Cell::Air,
+        BlockType::Water => chunk
+            .storage
+            .fluid_level(x, y, z)
+            .map_or(Cell::Source, Cell::Flowing),
+        _ => Cell::Blocked,
+    }
+}
+
+/// What the cell at `position` should become, or `None` if the simulation
+/// leaves it alone.
+fn next_cell<C: DerefMut<Target = Chunk>>(
+    chunks: &HashMap<ChunkPos, C>,
+    position: IVec3,
+) -> Option<Cell> {
+    if !matches!(cell_at(chunks, position), Cell::Air | Cell::Flowing(_)) {
+        return None;
+    }
+    if cell_at(chunks, position + IVec3::Y).is_water() {
+        return Some(Cell::Flowing(FALLING_WATER_LEVEL));
+    }
+
+    let level = HORIZONTAL
+        .iter()
+        .map(|offset| {
+            let feeder = position + *offset;
+            match cell_at(chunks, feeder) {
+                Cell::Source => MAX_FLOW_LEVEL,
+                // Flowing water only spreads sideways once it has landed.
+                Cell::Flowing(level) if rests_on_something(chunks, feeder) => {
+                    level.min(MAX_FLOW_LEVEL + 1) - 1
+                }
+                _ => 0,
+            }
+        })
+        .max()
+        .unwrap_or(0);
+
+    Some(if level == 0 {
+        Cell::Air
+    } else {
+        Cell::Flowing(level)
+    })
+}
+
+fn rests_on_something<C: DerefMut<Target = Chunk>>(
+    chunks: &HashMap<ChunkPos, C>,
+    position: IVec3,
+) -> bool {
+    matches!(
+        cell_at(chunks, position - IVec3::Y),
+        Cell::Blocked | Cell::Source
+    )
+}
+
+/// Queue the cells around this tick's block edits and advance the flow by at
+/// most `FLUID_UPDATES_PER_TICK` cells. Runs in `FixedUpdate`.
+pub fn update_fluids(
+    time: Res<Time>,
+    mut simulation: ResMut<FluidSimulation>,
+    mut block_events: EventReader<BlockChanged>,
+    mut chunk_query: Query<(&mut Chunk, &ChunkPos)>,
+) {
+    let delay = (FLOW_INTERVAL / time.delta_seconds()).round().max(1.0) as u64;
+    simulation.tick += 1;
+    for event in block_events.read() {
+        simulation.schedule_around(event.position, delay);
+    }
+    if !simulation.has_due_cells() {
+        return;
+    }
+
+    let mut chunks: HashMap<ChunkPos, Mut<Chunk>> = chunk_query
+        .iter_mut()
+        .map(|(chunk, position)| (*position, chunk))
+        .collect();
+    simulation.step(&mut chunks, delay, FLUID_UPDATES_PER_TICK);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::CHUNK_SIZE;
+
+    fn run(
+        simulation: &mut FluidSimulation,
+        chunks: &mut HashMap<ChunkPos, &mut Chunk>,
+        ticks: usize,
+    ) {
+        for _ in 0..ticks {
+            simulation.tick += 1;
+            simulation.step(chunks, 1, FLUID_UPDATES_PER_TICK);
+        }
+    }
+
+    #[test]
+    fn water_spreads_into_carved_space_and_drains_when_cut_off() {
+        // A stone floor at y = 0 with a pit at (8, 0, 4).
+        let position = ChunkPos::new(0, 0, 0);
+        let mut chunk = Chunk::new(position);
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk.set_block(x, 0, z, BlockType::Stone);
+            }
+        }
+        chunk.set_block(8, 0, 4, BlockType::Air);
+
+        let mut simulation = FluidSimulation::default();
+        let mut chunks = HashMap::from([(position, &mut chunk)]);
+        run(&mut simulation, &mut chunks, 4);
+        assert_eq!(simulation.queue.len(), 0, "nothing flows without an edit");
+
+        // A source on the floor thins out one level per block, pours into
+        // the pit and stops seven blocks out.
+        let source = IVec3::new(4, 1, 4);
+        chunks
+            .get_mut(&position)
+            .unwrap()
+            .set_block(4, 1, 4, BlockType::Water);
+        simulation.schedule_around(source, 1);
+        run(&mut simulation, &mut chunks, 40);
+        let storage = &chunks[&position].storage;
+        assert_eq!(storage.fluid_level(4, 1, 4), None);
+        assert_eq!(storage.fluid_level(5, 1, 4), Some(7));
+        assert_eq!(storage.fluid_level(7, 1, 4), Some(5));
+        assert_eq!(storage.fluid_level(8, 0, 4), Some(FALLING_WATER_LEVEL));
+        assert_eq!(storage.fluid_level(4, 1, 11), Some(1));
+        assert_eq!(storage.get(4, 1, 12), BlockType::Air);
+        assert_eq!(storage.get(4, 2, 4), BlockType::Air);
+        assert_eq!(simulation.queue.len(), 0);
+
+        // Taking the source away drains everything it fed.
+        chunks
+            .get_mut(&position)
+            .unwrap()
+            .set_block(4, 1, 4, BlockType::Air);
+        simulation.schedule_around(source, 1);
+        run(&mut simulation, &mut chunks, 200);
+        let storage = &chunks[&position].storage;
+        assert_eq!(storage.fluid_levels().count(), 0);
+        assert!(storage.iter().all(|block| block != BlockType::Water));
+        assert_eq!(simulation.queue.len(), 0);
+    }
+}
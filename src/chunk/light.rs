@@ -0,0 +1,702 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+use bevy::prelude::*;
+
+use super::data::CHUNK_VOLUME;
+use super::mesh::ChunkMeshJobs;
+use super::{BlockChanged, ChunkPos, ChunkStorage, CHUNK_SIZE};
+use crate::block::BlockType;
+use crate::world::{ChunkPayloadReady, PlanetChunkStore, WorldGenerator};
+
+pub const MAX_LIGHT_LEVEL: u8 = 15;
+/// A new revision changing more voxels than this is re-lit as a whole chunk
+/// rather than voxel by voxel.
+const MAX_INCREMENTAL_CHANGES: usize = 64;
+
+const NEIGHBOR_OFFSETS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightChannel {
+    /// Light from the open sky, scaled by the day/night cycle when rendered.
+    Sky,
+    /// Light from emissive blocks.
+    Block,
+}
+
+impl LightChannel {
+    const ALL: [LightChannel; 2] = [LightChannel::Sky, LightChannel::Block];
+}
+
+/// Per-voxel light levels for one chunk, laid out like `ChunkStorage`. Sky
+/// light lives in the high nibble and block light in the low nibble.
+#[derive(Clone, Debug)]
+pub struct ChunkLight {
+    levels: Box<[u8; CHUNK_VOLUME]>,
+}
+
+impl ChunkLight {
+    pub fn dark() -> Self {
+        Self {
+            levels: Box::new([0; CHUNK_VOLUME]),
+        }
+    }
+
+    pub fn get(&self, channel: LightChannel, x: usize, y: usize, z: usize) -> u8 {
+        let packed = self.levels[ChunkStorage::linear_index(x, y, z)];
+        match channel {
+            LightChannel::Sky => packed >> 4,
+            LightChannel::Block => packed & 0x0F,
+        }
+    }
+
+    pub fn sky(&self, x: usize, y: usize, z: usize) -> u8 {
+        self.get(LightChannel::Sky, x, y, z)
+    }
+
+    pub fn block(&self, x: usize, y: usize, z: usize) -> u8 {
+        self.get(LightChannel::Block, x, y, z)
+    }
+
+    pub fn set(&mut self, channel: LightChannel, x: usize, y: usize, z: usize, level: u8) {
+        let level = level.min(MAX_LIGHT_LEVEL);
+        let packed = &mut self.levels[ChunkStorage::linear_index(x, y, z)];
+        *packed = match channel {
+            LightChannel::Sky => (*packed & 0x0F) | (level << 4),
+            LightChannel::Block => (*packed & 0xF0) | level,
+        };
+    }
+}
+
+/// Light volumes for every chunk that has been lit, keyed like
+/// `PlanetChunkStore`. Entries are shared with mesh tasks as snapshots.
+#[derive(Resource, Default)]
+pub struct VoxelLightMap {
+    chunks: HashMap<ChunkPos, Arc<ChunkLight>>,
+    /// The store revision and storage each chunk's light was computed from.
+    sources: HashMap<ChunkPos, (u32, Arc<ChunkStorage>)>,
+}
+
+impl VoxelLightMap {
+    pub fn get(&self, position: &ChunkPos) -> Option<Arc<ChunkLight>> {
+        self.chunks.get(position).cloned()
+    }
+
+    /// Whether a chunk's light already accounts for `revision`.
+    pub fn is_current(&self, position: &ChunkPos, revision: u32) -> bool {
+        self.sources
+            .get(position)
+            .is_some_and(|(lit, _)| *lit >= revision)
+    }
+
+    /// Forget a chunk's light, as when it leaves the loaded area.
+    pub fn remove(&mut self, position: &ChunkPos) {
+        self.chunks.remove(position);
+        self.sources.remove(position);
+    }
+
+    /// Bring a chunk's light up to date with the store: light it if it has
+    /// none, otherwise re-light the voxels that changed since its light was
+    /// computed, or the whole chunk when too many did. Returns every chunk
+    /// whose light changed.
+    pub fn sync_chunk(
+        &mut self,
+        store: &PlanetChunkStore,
+        position: ChunkPos,
+        surface_height: impl Fn(i32, i32) -> i32,
+    ) -> HashSet<ChunkPos> {
+        let Some((storage, revision)) = store.get_with_revision(&position) else {
+            return HashSet::new();
+        };
+        let Some((lit_revision, lit_storage)) = self.sources.get(&position).cloned() else {
+            return self.light_chunk(store, position, surface_height);
+        };
+        if lit_revision >= revision || Arc::ptr_eq(&lit_storage, &storage) {
+            return HashSet::new();
+        }
+
+        let origin = chunk_origin(position);
+        let mut changed = Vec::new();
+        for z in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    if lit_storage.get(x, y, z) != storage.get(x, y, z) {
+                        changed.push(local_to_world(origin, x, y, z));
+                    }
+                }
+            }
+        }
+        if changed.len() > MAX_INCREMENTAL_CHANGES {
+            return self.light_chunk(store, position, surface_height);
+        }
+
+        let mut touched = HashSet::new();
+        for voxel in changed {
+            touched.extend(self.update_block(store, voxel));
+        }
+        self.sources.insert(position, (revision, storage));
+        touched
+    }
+
+    /// Light a newly available chunk and spill its light into (or pull light
+    /// from) lit neighbours. `surface_height` estimates the terrain height of a
+    /// world column and decides sky exposure while the chunk above is unlit.
+    /// A chunk that was already lit is darkened first, taking the light it
+    /// spilled into its neighbours with it. Returns every chunk whose light
+    /// changed.
+    pub fn light_chunk(
+        &mut self,
+        store: &PlanetChunkStore,
+        position: ChunkPos,
+        surface_height: impl Fn(i32, i32) -> i32,
+    ) -> HashSet<ChunkPos> {
+        let Some((storage_arc, revision)) = store.get_with_revision(&position) else {
+            return HashSet::new();
+        };
+        let storage = storage_arc.as_ref();
+        let (darkened, [sky_reseeds, block_reseeds]) = self.darken(store, position);
+
+        let origin = chunk_origin(position);
+        let above = ChunkPos::new(position.x, position.y + 1, position.z);
+        let below = ChunkPos::new(position.x, position.y - 1, position.z);
+        let above_light = self.get(&above);
+
+        let mut light = ChunkLight::dark();
+        let mut sky_seeds = VecDeque::new();
+        let mut block_seeds = VecDeque::new();
+
+        // Pour sky light straight down each column until it hits something
+        // that is not open air.
+        let mut column_floor = [CHUNK_SIZE; CHUNK_SIZE * CHUNK_SIZE];
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let open = match above_light.as_ref() {
+                    Some(above_light) => above_light.sky(x, 0, z) == MAX_LIGHT_LEVEL,
+                    None => {
+                        let top = origin.y + CHUNK_SIZE as i32;
+                        top >= surface_height(origin.x + x as i32, origin.z + z as i32)
+                    }
+                };
+                if !open {
+                    continue;
+                }
+
+                for y in (0..CHUNK_SIZE).rev() {
+                    if storage.get(x, y, z) != BlockType::Air {
+                        break;
+                    }
+                    light.set(LightChannel::Sky, x, y, z, MAX_LIGHT_LEVEL);
+                    column_floor[z * CHUNK_SIZE + x] = y;
+                }
+            }
+        }
+
+        // Only column cells next to something darker need to spread sideways.
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let column = z * CHUNK_SIZE + x;
+                let floor = column_floor[column];
+                let on_border = x == 0 || z == 0 || x == CHUNK_SIZE - 1 || z == CHUNK_SIZE - 1;
+                for y in floor..CHUNK_SIZE {
+                    let exposed = on_border
+                        || y == floor
+                        || column_floor[column - 1] > y
+                        || column_floor[column + 1] > y
+                        || column_floor[column - CHUNK_SIZE] > y
+                        || column_floor[column + CHUNK_SIZE] > y;
+                    if exposed {
+                        sky_seeds.push_back(local_to_world(origin, x, y, z));
+                    }
+                }
+            }
+        }
+
+        for z in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    let emission = storage.get(x, y, z).light_emission();
+                    if emission > 0 {
+                        light.set(LightChannel::Block, x, y, z, emission);
+                        block_seeds.push_back(local_to_world(origin, x, y, z));
+                    }
+                }
+            }
+        }
+
+        self.chunks.insert(position, Arc::new(light));
+        self.sources.insert(position, (revision, storage_arc));
+        sky_seeds.extend(sky_reseeds);
+        block_seeds.extend(block_reseeds);
+
+        let mut propagator = Propagator::new(store, &mut self.chunks);
+        propagator.touched.insert(position);
+        propagator.touched.extend(darkened);
+
+        // Pull light in across every lit face neighbour.
+        for offset in NEIGHBOR_OFFSETS {
+            let neighbor = ChunkPos::new(
+                position.x + offset.x,
+                position.y + offset.y,
+                position.z + offset.z,
+            );
+            if !propagator.chunks.contains_key(&neighbor) {
+                continue;
+            }
+            for cell in boundary_cells(origin, offset) {
+                let outside = cell + offset;
+                for channel in LightChannel::ALL {
+                    let level = propagator.level(channel, outside).unwrap_or(0);
+                    if level > 1 {
+                        let seeds = match channel {
+                            LightChannel::Sky => &mut sky_seeds,
+                            LightChannel::Block => &mut block_seeds,
+                        };
+                        seeds.push_back(outside);
+                    }
+                }
+            }
+        }
+
+        // The chunk below may have assumed open sky before this one arrived.
+        if propagator.chunks.contains_key(&below) {
+            let mut removals = VecDeque::new();
+            for cell in boundary_cells(origin, IVec3::NEG_Y) {
+                let under = cell + IVec3::NEG_Y;
+                let ours = propagator.level(LightChannel::Sky, cell).unwrap_or(0);
+                let theirs = propagator.level(LightChannel::Sky, under).unwrap_or(0);
+                if theirs == MAX_LIGHT_LEVEL && ours != MAX_LIGHT_LEVEL {
+                    propagator.set_level(LightChannel::Sky, under, 0);
+                    removals.push_back((under, theirs));
+                }
+            }
+            let reseeds = propagator.remove(LightChannel::Sky, removals);
+            sky_seeds.extend(reseeds);
+        }
+
+        propagator.propagate(LightChannel::Sky, sky_seeds);
+        propagator.propagate(LightChannel::Block, block_seeds);
+        propagator.touched
+    }
+
+    /// Take away a lit chunk's light along with whatever it lit in its
+    /// neighbours. Returns the chunks darkened and, for sky then block
+    /// light, the brighter cells left bordering the darkness.
+    fn darken(
+        &mut self,
+        store: &PlanetChunkStore,
+        position: ChunkPos,
+    ) -> (HashSet<ChunkPos>, [VecDeque<IVec3>; 2]) {
+        let Some(previous) = self.chunks.get(&position).cloned() else {
+            return (HashSet::new(), [VecDeque::new(), VecDeque::new()]);
+        };
+        let origin = chunk_origin(position);
+        let mut propagator = Propagator::new(store, &mut self.chunks);
+        let reseeds = LightChannel::ALL.map(|channel| {
+            let mut removals = VecDeque::new();
+            for z in 0..CHUNK_SIZE {
+                for y in 0..CHUNK_SIZE {
+                    for x in 0..CHUNK_SIZE {
+                        let level = previous.get(channel, x, y, z);
+                        if level > 0 {
+                            let cell = local_to_world(origin, x, y, z);
+                            propagator.set_level(channel, cell, 0);
+                            removals.push_back((cell, level));
+                        }
+                    }
+                }
+            }
+            propagator.remove(channel, removals)
+        });
+        (propagator.touched, reseeds)
+    }
+
+    /// Re-light around a single changed voxel. `store` must already hold the
+    /// new block. Returns every chunk whose light changed.
+    pub fn update_block(&mut self, store: &PlanetChunkStore, position: IVec3) -> HashSet<ChunkPos> {
+        let chunk = chunk_of(position);
+        if !self.chunks.contains_key(&chunk) {
+            return HashSet::new();
+        }
+
+        let mut propagator = Propagator::new(store, &mut self.chunks);
+        let block = propagator.block(position).unwrap_or(BlockType::Air);
+
+        for channel in LightChannel::ALL {
+            let previous = propagator.level(channel, position).unwrap_or(0);
+            let mut seeds = if previous > 0 {
+                propagator.set_level(channel, position, 0);
+                propagator.remove(channel, VecDeque::from([(position, previous)]))
+            } else {
+                VecDeque::new()
+            };
+
+            if channel == LightChannel::Block && block.light_emission() > 0 {
+                propagator.set_level(channel, position, block.light_emission());
+                seeds.push_back(position);
+            }
+
+            // Let the surroundings flow back into the changed voxel.
+            for offset in NEIGHBOR_OFFSETS {
+                let neighbor = position + offset;
+                if propagator
+                    .level(channel, neighbor)
+                    .is_some_and(|level| level > 1)
+                {
+                    seeds.push_back(neighbor);
+                }
+            }
+
+            propagator.propagate(channel, seeds);
+        }
+
+        let touched = propagator.touched;
+        if let Some((storage, revision)) = store.get_with_revision(&chunk) {
+            self.sources.insert(chunk, (revision, storage));
+        }
+        touched
+    }
+}
+
+/// Breadth-first light propagation over world coordinates, spanning every
+/// lit chunk. Voxels in chunks without light are treated as walls.
+struct Propagator<'a> {
+    store: &'a PlanetChunkStore,
+    chunks: &'a mut HashMap<ChunkPos, Arc<ChunkLight>>,
+    touched: HashSet<ChunkPos>,
+}
+
+impl<'a> Propagator<'a> {
+    fn new(
+        store: &'a PlanetChunkStore,
+        chunks: &'a mut HashMap<ChunkPos, Arc<ChunkLight>>,
+    ) -> Self {
+        Self {
+            store,
+            chunks,
+            touched: HashSet::new(),
+        }
+    }
+
+    fn block(&self, position: IVec3) -> Option<BlockType> {
+        let (chunk, [x, y, z]) = split_world(position);
+        self.store
+            .storage(&chunk)
+            .map(|storage| storage.get(x, y, z))
+    }
+
+    fn level(&self, channel: LightChannel, position: IVec3) -> Option<u8> {
+        let (chunk, [x, y, z]) = split_world(position);
+        self.chunks
+            .get(&chunk)
+            .map(|light| light.get(channel, x, y, z))
+    }
+
+    fn set_level(&mut self, channel: LightChannel, position: IVec3, level: u8) {
+        let (chunk, [x, y, z]) = split_world(position);
+        if let Some(light) = self.chunks.get_mut(&chunk) {
+            Arc::make_mut(light).set(channel, x, y, z, level);
+            self.touched.insert(chunk);
+        }
+    }
+
+    /// Level `level` light at `from` carries into a neighbour in direction
+    /// `offset`. Full sky light travels straight down through air unchanged.
+    fn carried_level(channel: LightChannel, level: u8, offset: IVec3, into: BlockType) -> u8 {
+        if channel == LightChannel::Sky
+            && level == MAX_LIGHT_LEVEL
+            && offset == IVec3::NEG_Y
+            && into == BlockType::Air
+        {
+            MAX_LIGHT_LEVEL
+        } else {
+            level.saturating_sub(1)
+        }
+    }
+
+    /// Spread light outwards from `queue`. Levels are read back from the map
+    /// so seeds darkened after being queued do not spread stale light.
+    fn propagate(&mut self, channel: LightChannel, mut queue: VecDeque<IVec3>) {
+        while let Some(position) = queue.pop_front() {
+            let level = self.level(channel, position).unwrap_or(0);
+            if level <= 1 {
+                continue;
+            }
+
+            for offset in NEIGHBOR_OFFSETS {
+                let neighbor = position + offset;
+                let Some(block) = self.block(neighbor) else {
+                    continue;
+                };
+                if !block.is_transparent() {
+                    continue;
+                }
+                let Some(current) = self.level(channel, neighbor) else {
+                    continue;
+                };
+
+                let carried = Self::carried_level(channel, level, offset, block);
+                if carried > current {
+                    self.set_level(channel, neighbor, carried);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+    }
+
+    /// Darken everything that was lit by the removed `(position, level)`
+    /// sources. Returns the brighter cells bordering the darkened region so
+    /// the caller can propagate them back in.
+    fn remove(
+        &mut self,
+        channel: LightChannel,
+        mut queue: VecDeque<(IVec3, u8)>,
+    ) -> VecDeque<IVec3> {
+        let mut reseeds = VecDeque::new();
+
+        while let Some((position, level)) = queue.pop_front() {
+            for offset in NEIGHBOR_OFFSETS {
+                let neighbor = position + offset;
+                let Some(current) = self.level(channel, neighbor) else {
+                    continue;
+                };
+                if current == 0 {
+                    continue;
+                }
+
+                let fed_by_column = channel == LightChannel::Sky
+                    && offset == IVec3::NEG_Y
+                    && level == MAX_LIGHT_LEVEL
+                    && current == MAX_LIGHT_LEVEL;
+                let emitted = channel == LightChannel::Block
+                    && self
+                        .block(neighbor)
+                        .is_some_and(|block| block.light_emission() == current);
+
+                if (current < level || fed_by_column) && !emitted {
+                    self.set_level(channel, neighbor, 0);
+                    queue.push_back((neighbor, current));
+                } else {
+                    reseeds.push_back(neighbor);
+                }
+            }
+        }
+
+        reseeds
+    }
+}
+
+fn chunk_origin(position: ChunkPos) -> IVec3 {
+    IVec3::new(position.x, position.y, position.z) * CHUNK_SIZE as i32
+}
+
+fn chunk_of(position: IVec3) -> ChunkPos {
+    let size = CHUNK_SIZE as i32;
+    ChunkPos::new(
+        position.x.div_euclid(size),
+        position.y.div_euclid(size),
+        position.z.div_euclid(size),
+    )
+}
+
+pub(crate) fn split_world(position: IVec3) -> (ChunkPos, [usize; 3]) {
+    let size = CHUNK_SIZE as i32;
+    (
+        chunk_of(position),
+        [
+            position.x.rem_euclid(size) as usize,
+            position.y.rem_euclid(size) as usize,
+            position.z.rem_euclid(size) as usize,
+        ],
+    )
+}
+
+fn local_to_world(origin: IVec3, x: usize, y: usize, z: usize) -> IVec3 {
+    origin + IVec3::new(x as i32, y as i32, z as i32)
+}
+
+/// World positions of the chunk cells on the face pointing along `offset`.
+fn boundary_cells(origin: IVec3, offset: IVec3) -> impl Iterator<Item = IVec3> {
+    let last = CHUNK_SIZE as i32 - 1;
+    let edge = move |component: i32| if component > 0 { last } else { 0 };
+    (0..CHUNK_SIZE as i32).flat_map(move |a| {
+        (0..CHUNK_SIZE as i32).map(move |b| {
+            let local = if offset.x != 0 {
+                IVec3::new(edge(offset.x), a, b)
+            } else if offset.y != 0 {
+                IVec3::new(a, edge(offset.y), b)
+            } else {
+                IVec3::new(a, b, edge(offset.z))
+            };
+            origin + local
+        })
+    })
+}
+
+/// Light chunks as they enter the store, re-light chunks replaced by newer
+/// revisions and re-light around block edits, queueing remeshes for every
+/// chunk whose light changed. Edits that have not reached the store yet are
+/// held until `sync_dirty_chunks_to_store` catches up; the payload that
+/// brings them re-lights whatever they changed anyway.
+pub fn update_voxel_light(
+    mut payload_events: EventReader<ChunkPayloadReady>,
+    mut block_events: EventReader<BlockChanged>,
+    mut pending_edits: Local<Vec<BlockChanged>>,
+    chunk_store: Res<PlanetChunkStore>,
+    world_gen: Option<Res<WorldGenerator>>,
+    mut light_map: ResMut<VoxelLightMap>,
+    mut mesh_jobs: ResMut<ChunkMeshJobs>,
+) {
+    let surface_height = |world_x: i32, world_z: i32| match world_gen.as_ref() {
+        Some(world_gen) => world_gen.get_height(world_x as f32, world_z as f32).ceil() as i32,
+        None => 0,
+    };
+
+    let mut touched = HashSet::new();
+    let mut synced = HashSet::new();
+
+    for event in payload_events.read() {
+        synced.insert(event.position);
+        if light_map.is_current(&event.position, event.revision) {
+            continue;
+        }
+        touched.extend(light_map.sync_chunk(&chunk_store, event.position, surface_height));
+    }
+
+    // Only the latest edit of a voxel matters.
+    for edit in block_events.read() {
+        pending_edits.retain(|pending| pending.position != edit.position);
+        pending_edits.push(*edit);
+    }
+    pending_edits.retain(|edit| {
+        let (chunk, [x, y, z]) = split_world(edit.position);
+        match chunk_store.storage(&chunk) {
+            Some(storage) if storage.get(x, y, z) == edit.block => {
+                touched.extend(light_map.update_block(&chunk_store, edit.position));
+                false
+            }
+            // A payload for the chunk has been lit from the store, and the
+            // one carrying this edit will be too.
+            Some(_) => !synced.contains(&chunk),
+            None => false,
+        }
+    });
+
+    for position in touched {
+        mesh_jobs.request_remesh(position);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_sky(_: i32, _: i32) -> i32 {
+        i32::MIN
+    }
+
+    /// A stone chunk with a 1x1 shaft from the top down to a sealed room.
+    fn shaft_store() -> PlanetChunkStore {
+        let storage = ChunkStorage::from_fn(|x, y, z| {
+            let in_room = (8..16).contains(&x) && (4..8).contains(&y) && (8..16).contains(&z);
+            let in_shaft = x == 10 && z == 10 && y >= 8;
+            if in_room || in_shaft {
+                BlockType::Air
+            } else {
+                BlockType::Stone
+            }
+        });
+
+        let mut store = PlanetChunkStore::default();
+        store.insert_with_revision(ChunkPos::new(0, 0, 0), storage, 1);
+        store
+    }
+
+    #[test]
+    fn sky_light_pours_down_shafts_and_fades_sideways() {
+        let store = shaft_store();
+        let mut lights = VoxelLightMap::default();
+        lights.light_chunk(&store, ChunkPos::new(0, 0, 0), open_sky);
+        let light = lights.get(&ChunkPos::new(0, 0, 0)).unwrap();
+
+        // Straight down the shaft and onto the room floor stays at full sky.
+        assert_eq!(light.sky(10, 20, 10), MAX_LIGHT_LEVEL);
+        assert_eq!(light.sky(10, 4, 10), MAX_LIGHT_LEVEL);
+        // Away from the shaft the room only receives attenuated light.
+        assert_eq!(light.sky(14, 4, 10), MAX_LIGHT_LEVEL - 4);
+        // Solid rock stays dark.
+        assert_eq!(light.sky(0, 4, 0), 0);
+    }
+
+    #[test]
+    fn edits_update_light_incrementally() {
+        let mut store = shaft_store();
+        let position = ChunkPos::new(0, 0, 0);
+        let mut lights = VoxelLightMap::default();
+        lights.light_chunk(&store, position, open_sky);
+
+        // Cap the shaft: the room goes dark.
+        let mut storage = store.get(&position).unwrap().as_ref().clone();
+        storage.set(10, 31, 10, BlockType::Stone);
+        store.upsert_storage(position, &storage);
+        lights.update_block(&store, IVec3::new(10, 31, 10));
+        let light = lights.get(&position).unwrap();
+        assert_eq!(light.sky(10, 4, 10), 0);
+        assert_eq!(light.sky(14, 4, 10), 0);
+
+        // Drop a glowstone into the room: block light fills it.
+        storage.set(12, 4, 12, BlockType::Glowstone);
+        store.upsert_storage(position, &storage);
+        lights.update_block(&store, IVec3::new(12, 4, 12));
+        let light = lights.get(&position).unwrap();
+        assert_eq!(light.block(12, 4, 12), 15);
+        assert_eq!(light.block(12, 5, 12), 14);
+        assert_eq!(light.block(8, 4, 12), 11);
+
+        // Removing it darkens the room again.
+        storage.set(12, 4, 12, BlockType::Air);
+        store.upsert_storage(position, &storage);
+        lights.update_block(&store, IVec3::new(12, 4, 12));
+        let light = lights.get(&position).unwrap();
+        assert_eq!(light.block(12, 5, 12), 0);
+        assert_eq!(light.block(8, 4, 12), 0);
+    }
+
+    #[test]
+    fn newer_revisions_relight_the_chunk() {
+        let mut store = shaft_store();
+        let position = ChunkPos::new(0, 0, 0);
+        let mut lights = VoxelLightMap::default();
+        lights.sync_chunk(&store, position, open_sky);
+        assert!(lights.is_current(&position, 1));
+
+        // A revision that caps the shaft and lights the room, with no edit
+        // events, as a full payload from the server arrives.
+        let mut storage = store.get(&position).unwrap().as_ref().clone();
+        storage.set(10, 31, 10, BlockType::Stone);
+        storage.set(12, 4, 12, BlockType::Glowstone);
+        store.insert_with_revision(position, storage, 2);
+        assert!(!lights.is_current(&position, 2));
+        lights.sync_chunk(&store, position, open_sky);
+        let light = lights.get(&position).unwrap();
+        assert_eq!(light.sky(10, 4, 10), 0);
+        assert_eq!(light.block(8, 4, 12), 11);
+
+        // One replacing most of the chunk is lit from scratch.
+        store.insert_with_revision(position, ChunkStorage::new(), 3);
+        lights.sync_chunk(&store, position, open_sky);
+        let light = lights.get(&position).unwrap();
+        assert_eq!(light.sky(0, 4, 0), MAX_LIGHT_LEVEL);
+        assert_eq!(light.block(12, 4, 12), 0);
+        assert!(lights.is_current(&position, 3));
+
+        lights.remove(&position);
+        assert!(lights.get(&position).is_none());
+    }
+}
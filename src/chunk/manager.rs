@@ -1,11 +1,14 @@
+use super::light::VoxelLightMap;
 use super::mesh::ChunkMeshJobs;
 use crate::camera::PlayerCamera;
-use crate::chunk::{Chunk, ChunkPos, ChunkStorage, CHUNK_SIZE};
+use crate::chunk::{Chunk, ChunkPayloadHeader, ChunkPos, ChunkStorage, CHUNK_SIZE};
 use crate::loading::{GameState, LoadingProgress};
+use crate::net::client::ChunkClient;
 use crate::planet::altitude_system::{should_render_chunks, AltitudeRenderSystem};
 use crate::planet::config::PLANET_SIZE_BLOCKS;
 use crate::world::chunk_store::StoreUpdate;
-use crate::world::persistence::{ChunkPersistence, DiskChunkPersistence, PersistenceHandler};
+use crate::world::persistence::{load_latest_valid, PersistenceHandler};
+use crate::world::region::RegionChunkPersistence;
 use crate::world::{
     ChunkPayloadQueue, ChunkPayloadReady, PlanetChunkStore, QueuedChunkPayload, WorldGenerator,
 };
@@ -91,8 +94,12 @@ pub struct ChunkGenerationQueue {
     recent_durations_ms: VecDeque<f32>,
 }
 
+/// Chunks one viewer wants, best first: a disc around the viewer at the
+/// surface and its own height, scored by distance and how far off its
+/// heading they are, with chunks well behind it culled. Rebuilt only when the
+/// viewer changes chunk, view distance or heading noticeably.
 #[derive(Default)]
-struct CandidateState {
+pub struct CandidateState {
     last_player_chunk: Option<ChunkPos>,
     last_render_distance: i32,
     last_forward: Vec3,
@@ -141,29 +148,37 @@ impl CandidateState {
         false
     }
 
-    fn ensure_candidates(
+    /// Returns whether the candidates were rebuilt.
+    pub fn ensure_candidates(
         &mut self,
         player_chunk: ChunkPos,
         view_distance: i32,
         forward: Vec3,
         player_pos: Vec3,
         world_gen: &WorldGenerator,
-    ) {
+    ) -> bool {
         let normalized_forward = if forward.length_squared() > f32::EPSILON {
             forward.normalize()
         } else {
             Vec3::Z
         };
 
-        if self.should_rebuild(player_chunk, view_distance, normalized_forward) {
-            self.rebuild(
-                player_chunk,
-                view_distance,
-                normalized_forward,
-                player_pos,
-                world_gen,
-            );
+        if !self.should_rebuild(player_chunk, view_distance, normalized_forward) {
+            return false;
         }
+        self.rebuild(
+            player_chunk,
+            view_distance,
+            normalized_forward,
+            player_pos,
+            world_gen,
+        );
+        true
+    }
+
+    #[allow(dead_code)]
+    pub fn candidates(&self) -> &[(ChunkPos, i32)] {
+        &self.cached_candidates
     }
 
     fn rebuild(
@@ -259,7 +274,7 @@ impl ChunkGenerationQueue {
         true
     }
 
-    fn requeue(&mut self, position: ChunkPos, priority: i32) {
+    pub(super) fn requeue(&mut self, position: ChunkPos, priority: i32) {
         self.pending_chunks.push(QueuedChunk { priority, position });
     }
 
@@ -281,7 +296,7 @@ impl ChunkGenerationQueue {
         self.pending_chunks.len()
     }
 
-    fn mark_completed(&mut self, position: &ChunkPos) {
+    pub(super) fn mark_completed(&mut self, position: &ChunkPos) {
         self.scheduled.remove(position);
     }
 
@@ -319,7 +334,7 @@ pub fn load_persisted_pending_chunks(
     mut chunk_manager: ResMut<ChunkManager>,
     mut loading_progress: ResMut<LoadingProgress>,
     mut chunk_store: ResMut<PlanetChunkStore>,
-    persistence: Option<Res<PersistenceHandler<DiskChunkPersistence>>>,
+    persistence: Option<Res<PersistenceHandler<RegionChunkPersistence>>>,
     mut chunk_events: EventWriter<ChunkPayloadReady>,
 ) {
     let handler = match persistence {
@@ -343,9 +358,9 @@ pub fn load_persisted_pending_chunks(
                 spawn_storage = Some(arc.as_ref().clone());
                 revision = rev;
             }
-        } else if let Ok(Some((persist_revision, bytes))) = handler.handler().load(chunk_pos) {
-            match ChunkStorage::from_bytes(&bytes) {
-                Ok(storage) => {
+        } else {
+            match load_latest_valid(handler.handler(), chunk_pos) {
+                Ok(Some((persist_revision, storage))) => {
                     let arc = chunk_store.insert_with_revision(
                         chunk_pos,
                         storage.clone(),
@@ -359,9 +374,10 @@ pub fn load_persisted_pending_chunks(
                     spawn_storage = Some(storage);
                     revision = persist_revision;
                 }
+                Ok(None) => {}
                 Err(error) => {
                     warn!(
-                        "Failed to decode persisted chunk {:?}: {:?}. Falling back to regeneration.",
+                        "Failed to load persisted chunk {:?}: {}. Falling back to regeneration.",
                         chunk_pos, error
                     );
                 }
@@ -450,7 +466,29 @@ pub fn spawn_chunk_tasks(
     mut chunk_queue: ResMut<ChunkGenerationQueue>,
     world_gen: Res<WorldGenerator>,
     time: Res<Time>,
+    remote: Option<ResMut<ChunkClient>>,
 ) {
+    // With a server connection the queue feeds requests instead of bakes.
+    if let Some(mut client) = remote {
+        let mut batch = Vec::new();
+        while batch.len() < client.request_capacity() {
+            let Some(entry) = chunk_queue.pop_pending() else {
+                break;
+            };
+            batch.push(entry);
+        }
+        if !batch.is_empty() {
+            debug!(
+                "chunk-request: requested={} in_flight={} pending={}",
+                batch.len(),
+                client.requests_in_flight() + batch.len(),
+                chunk_queue.pending_len()
+            );
+            client.request_chunks(batch);
+        }
+        return;
+    }
+
     let task_pool = AsyncComputeTaskPool::get();
 
     let initial_tasks = chunk_queue.tasks.len();
@@ -509,6 +547,7 @@ pub fn poll_chunk_tasks(
     mut chunk_store: ResMut<PlanetChunkStore>,
     mut chunk_events: EventWriter<ChunkPayloadReady>,
     time: Res<Time>,
+    remote: Option<Res<ChunkClient>>,
 ) {
     let mut completed_indices = Vec::new();
     let mut completed_positions = Vec::new();
@@ -582,6 +621,7 @@ pub fn poll_chunk_tasks(
     // Check if initial generation is complete
     if chunk_queue.pending_len() == 0
         && chunk_queue.tasks.is_empty()
+        && remote.is_none_or(|client| client.requests_in_flight() == 0)
         && !chunk_queue.initial_generation_complete
     {
         chunk_queue.initial_generation_complete = true;
@@ -600,7 +640,7 @@ pub fn spawn_chunks_around_player(
     world_gen: Res<WorldGenerator>,
     mut chunk_store: ResMut<PlanetChunkStore>,
     mut chunk_events: EventWriter<ChunkPayloadReady>,
-    persistence: Option<Res<PersistenceHandler<DiskChunkPersistence>>>,
+    persistence: Option<Res<PersistenceHandler<RegionChunkPersistence>>>,
 ) {
     // Wait a bit after initial spawn before generating more chunks
     if !chunk_manager.initial_spawn_complete {
@@ -670,68 +710,16 @@ pub fn spawn_chunks_around_player(
             continue;
         }
 
-        if let Some((storage_arc, _revision)) = chunk_store.get_with_revision(&chunk_pos) {
-            let storage = storage_arc.as_ref().clone();
-            let world_pos = chunk_pos.to_world_pos();
-            commands.spawn((
-                Chunk::from_storage(chunk_pos, storage),
-                chunk_pos,
-                TransformBundle::from_transform(Transform::from_translation(world_pos)),
-                VisibilityBundle::default(),
-            ));
-            chunk_manager.loaded_chunks.insert(chunk_pos);
-            chunk_queue.mark_completed(&chunk_pos);
+        if spawn_known_chunk(
+            &mut commands,
+            &mut chunk_manager,
+            &mut chunk_queue,
+            &mut chunk_store,
+            &mut chunk_events,
+            persistence.as_deref(),
+            chunk_pos,
+        ) {
             immediate_spawned += 1;
-
-            if immediate_spawned >= max_immediate && queued >= max_queued {
-                break;
-            }
-            continue;
-        }
-
-        let mut spawned_from_persistence = false;
-        if let Some(handler) = persistence.as_ref() {
-            match handler.handler().load(chunk_pos) {
-                Ok(Some((revision, bytes))) => match ChunkStorage::from_bytes(&bytes) {
-                    Ok(storage) => {
-                        let arc =
-                            chunk_store.insert_with_revision(chunk_pos, storage.clone(), revision);
-                        chunk_events.send(ChunkPayloadReady {
-                            position: chunk_pos,
-                            revision,
-                            storage: arc,
-                        });
-
-                        let world_pos = chunk_pos.to_world_pos();
-                        commands.spawn((
-                            Chunk::from_storage(chunk_pos, storage),
-                            chunk_pos,
-                            TransformBundle::from_transform(Transform::from_translation(world_pos)),
-                            VisibilityBundle::default(),
-                        ));
-                        chunk_manager.loaded_chunks.insert(chunk_pos);
-                        chunk_queue.mark_completed(&chunk_pos);
-                        immediate_spawned += 1;
-                        spawned_from_persistence = true;
-                    }
-                    Err(error) => {
-                        warn!(
-                            "Failed to decode persisted chunk {:?}: {:?}. Queuing regeneration.",
-                            chunk_pos, error
-                        );
-                    }
-                },
-                Ok(None) => {}
-                Err(error) => {
-                    warn!(
-                        "Failed to load persisted chunk {:?}: {}. Queuing regeneration.",
-                        chunk_pos, error
-                    );
-                }
-            }
-        }
-
-        if spawned_from_persistence {
             if immediate_spawned >= max_immediate && queued >= max_queued {
                 break;
             }
@@ -773,6 +761,56 @@ pub fn spawn_chunks_around_player(
     }
 }
 
+/// Spawn a chunk from the in-memory store or, failing that, from persistence.
+/// Returns `false` when neither holds the chunk and it has to be baked.
+pub fn spawn_known_chunk(
+    commands: &mut Commands,
+    chunk_manager: &mut ChunkManager,
+    chunk_queue: &mut ChunkGenerationQueue,
+    chunk_store: &mut PlanetChunkStore,
+    chunk_events: &mut EventWriter<ChunkPayloadReady>,
+    persistence: Option<&PersistenceHandler<RegionChunkPersistence>>,
+    chunk_pos: ChunkPos,
+) -> bool {
+    let storage = if let Some(storage_arc) = chunk_store.get(&chunk_pos) {
+        storage_arc.as_ref().clone()
+    } else {
+        let Some(handler) = persistence else {
+            return false;
+        };
+        match load_latest_valid(handler.handler(), chunk_pos) {
+            Ok(Some((revision, storage))) => {
+                let arc = chunk_store.insert_with_revision(chunk_pos, storage.clone(), revision);
+                chunk_events.send(ChunkPayloadReady {
+                    position: chunk_pos,
+                    revision,
+                    storage: arc,
+                });
+                storage
+            }
+            Ok(None) => return false,
+            Err(error) => {
+                warn!(
+                    "Failed to load persisted chunk {:?}: {}. Queuing regeneration.",
+                    chunk_pos, error
+                );
+                return false;
+            }
+        }
+    };
+
+    let world_pos = chunk_pos.to_world_pos();
+    commands.spawn((
+        Chunk::from_storage(chunk_pos, storage),
+        chunk_pos,
+        TransformBundle::from_transform(Transform::from_translation(world_pos)),
+        VisibilityBundle::default(),
+    ));
+    chunk_manager.loaded_chunks.insert(chunk_pos);
+    chunk_queue.mark_completed(&chunk_pos);
+    true
+}
+
 pub fn sync_dirty_chunks_to_store(
     chunk_query: Query<(&Chunk, &ChunkPos)>,
     mut chunk_store: ResMut<PlanetChunkStore>,
@@ -801,6 +839,8 @@ pub fn despawn_far_chunks(
     player_query: Query<&Transform, With<PlayerCamera>>,
     chunk_query: Query<(Entity, &ChunkPos)>,
     altitude_system: Res<AltitudeRenderSystem>,
+    mut light_map: ResMut<VoxelLightMap>,
+    remote: Option<ResMut<ChunkClient>>,
 ) {
     let Ok(player_transform) = player_query.get_single() else {
         return;
@@ -808,6 +848,7 @@ pub fn despawn_far_chunks(
 
     let player_chunk = ChunkPos::from_world_pos(player_transform.translation);
     let despawn_distance = (altitude_system.render_distance as i32) + 2;
+    let mut despawned = Vec::new();
 
     for (entity, chunk_pos) in chunk_query.iter() {
         let distance = (chunk_pos.x - player_chunk.x).abs().max(
@@ -819,13 +860,20 @@ pub fn despawn_far_chunks(
         if distance > despawn_distance || !should_render_chunks(player_transform.translation.y) {
             commands.entity(entity).despawn_recursive();
             chunk_manager.loaded_chunks.remove(chunk_pos);
+            light_map.remove(chunk_pos);
+            despawned.push(*chunk_pos);
         }
     }
+
+    if let Some(mut client) = remote {
+        client.unload_chunks(despawned);
+    }
 }
 
 pub fn collect_chunk_payloads(
     mut events: EventReader<ChunkPayloadReady>,
     mut queue: ResMut<ChunkPayloadQueue>,
+    world_gen: Option<Res<WorldGenerator>>,
 ) {
     let mut total_duration_ms = 0.0_f32;
     let mut total_bytes = 0_usize;
@@ -833,7 +881,15 @@ pub fn collect_chunk_payloads(
 
     for event in events.read() {
         let start = Instant::now();
-        let bytes = event.storage.encode_bytes();
+        let header = match world_gen.as_ref() {
+            Some(world_gen) => world_gen.payload_header(event.position, event.revision),
+            None => ChunkPayloadHeader {
+                position: event.position,
+                revision: event.revision,
+                ..default()
+            },
+        };
+        let bytes = event.storage.encode_bytes(header);
         let duration_ms = start.elapsed().as_secs_f32() * 1000.0;
 
         total_duration_ms += duration_ms;
@@ -874,13 +930,14 @@ pub fn log_chunk_streaming_metrics(
     let scheduled = chunk_queue.scheduled.len();
     let mesh_tasks = mesh_jobs.task_count();
     let mesh_scheduled = mesh_jobs.scheduled_count();
+    let mesh_remesh = mesh_jobs.pending_remesh_count();
     let avg_mesh_ms = mesh_jobs.average_duration_ms().unwrap_or(0.0);
     let avg_gen_ms = chunk_queue.average_duration_ms().unwrap_or(0.0);
     let inflight = chunk_queue.inflight_count();
 
     if pending > 0 || active_tasks > 0 || mesh_tasks > 0 {
         info!(
-            "chunk-stream: pending={} active_tasks={} inflight={} scheduled={} gen_avg_ms={:.2} mesh_tasks={} mesh_scheduled={} mesh_avg_ms={:.2}",
+            "chunk-stream: pending={} active_tasks={} inflight={} scheduled={} gen_avg_ms={:.2} mesh_tasks={} mesh_scheduled={} mesh_remesh={} mesh_avg_ms={:.2}",
             pending,
             active_tasks,
             inflight,
@@ -888,6 +945,7 @@ pub fn log_chunk_streaming_metrics(
             avg_gen_ms,
             mesh_tasks,
             mesh_scheduled,
+            mesh_remesh,
             avg_mesh_ms,
         );
     }
@@ -0,0 +1,300 @@
+use crate::texture::BlockTextureAtlas;
+use bevy::pbr::{
+    ExtendedMaterial, MaterialExtension, MaterialExtensionKey, MaterialExtensionPipeline,
+};
+use bevy::prelude::*;
+use bevy::render::mesh::{MeshVertexAttribute, MeshVertexBufferLayoutRef};
+use bevy::render::render_resource::{
+    AsBindGroup, RenderPipelineDescriptor, ShaderRef, ShaderType, SpecializedMeshPipelineError,
+    VertexFormat,
+};
+
+const CHUNK_SHADER_PATH: &str = "shaders/chunk.wgsl";
+
+/// Per-vertex `(sky, block)` voxel light in `0.0..=1.0`, read by the chunk
+/// vertex shader at location 8.
+pub const ATTRIBUTE_VOXEL_LIGHT: MeshVertexAttribute =
+    MeshVertexAttribute::new("Vertex_VoxelLight", 988_540_917, VertexFormat::Float32x2);
+
+/// Material used for chunk meshes. Greedy quads span several voxels, so their
+/// `UV_0` is expressed in tile units and `UV_1` carries the atlas tile origin;
+/// the extension shader wraps the former back into the tile.
+pub type ChunkMaterial = ExtendedMaterial<StandardMaterial, ChunkMaterialExtension>;
+
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
+pub struct ChunkMaterialExtension {
+    #[uniform(100)]
+    pub settings: ChunkMaterialSettings,
+}
+
+#[derive(ShaderType, Reflect, Debug, Clone, Copy)]
+pub struct ChunkMaterialSettings {
+    /// Size of a single atlas tile in UV space.
+    pub tile_size: Vec2,
+    /// Blend factor for the corner occlusion baked into opaque vertices;
+    /// `0.0` disables it.
+    pub ambient_occlusion: f32,
+    /// Day/night multiplier applied to the sky light channel.
+    pub sky_light: f32,
+}
+
+/// Runtime switches for chunk rendering, mirrored onto the shared chunk
+/// materials.
+#[derive(Resource, Clone, Debug)]
+pub struct ChunkRenderSettings {
+    pub ambient_occlusion: bool,
+    /// Current brightness of sky light, driven by the day/night cycle.
+    pub sky_light: f32,
+}
+
+impl Default for ChunkRenderSettings {
+    fn default() -> Self {
+        Self {
+            ambient_occlusion: true,
+            sky_light: 1.0,
+        }
+    }
+}
+
+impl ChunkRenderSettings {
+    fn ambient_occlusion_factor(&self) -> f32 {
+        if self.ambient_occlusion {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+impl MaterialExtension for ChunkMaterialExtension {
+    fn vertex_shader() -> ShaderRef {
+        CHUNK_SHADER_PATH.into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        CHUNK_SHADER_PATH.into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialExtensionPipeline,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayoutRef,
+        _key: MaterialExtensionKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        // Prepass and shadow pipelines keep Bevy's own vertex shader and layout.
+        if descriptor
+            .vertex
+            .shader_defs
+            .contains(&"PREPASS_PIPELINE".into())
+        {
+            return Ok(());
+        }
+
+        let vertex_layout = layout.0.get_layout(&[
+            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+            Mesh::ATTRIBUTE_NORMAL.at_shader_location(1),
+            Mesh::ATTRIBUTE_UV_0.at_shader_location(2),
+            Mesh::ATTRIBUTE_UV_1.at_shader_location(3),
+            Mesh::ATTRIBUTE_COLOR.at_shader_location(5),
+            ATTRIBUTE_VOXEL_LIGHT.at_shader_location(8),
+        ])?;
+        descriptor.vertex.buffers = vec![vertex_layout];
+        Ok(())
+    }
+}
+
+impl ChunkMaterialExtension {
+    fn new(
+        texture_atlas: Option<&BlockTextureAtlas>,
+        render_settings: &ChunkRenderSettings,
+    ) -> Self {
+        let tile_size = texture_atlas
+            .map(|atlas| Vec2::splat(atlas.texture_size) / atlas.atlas_size)
+            .unwrap_or(Vec2::ONE);
+
+        Self {
+            settings: ChunkMaterialSettings {
+                tile_size,
+                ambient_occlusion: render_settings.ambient_occlusion_factor(),
+                sky_light: render_settings.sky_light,
+            },
+        }
+    }
+}
+
+/// Handles to the materials every chunk mesh is drawn with.
+#[derive(Clone, Debug)]
+pub struct ChunkMaterialHandles {
+    pub opaque: Handle<ChunkMaterial>,
+    pub water: Handle<ChunkMaterial>,
+    pub plants: Handle<ChunkMaterial>,
+}
+
+/// The chunk materials, shared by all chunks so a settings change such as
+/// the day/night sky light touches three assets however many chunks are
+/// loaded. Built on first use, and again if the atlas texture changes.
+#[derive(Resource, Default)]
+pub struct ChunkMaterials {
+    atlas: Option<AssetId<Image>>,
+    handles: Option<ChunkMaterialHandles>,
+}
+
+impl ChunkMaterials {
+    pub fn handles(
+        &mut self,
+        materials: &mut Assets<ChunkMaterial>,
+        texture_atlas: Option<&BlockTextureAtlas>,
+        render_settings: &ChunkRenderSettings,
+    ) -> &ChunkMaterialHandles {
+        let atlas = texture_atlas.map(|atlas| atlas.texture.id());
+        if self.atlas != atlas {
+            self.atlas = atlas;
+            self.handles = None;
+        }
+        self.handles.get_or_insert_with(|| ChunkMaterialHandles {
+            opaque: materials.add(opaque_chunk_material(texture_atlas, render_settings)),
+            water: materials.add(water_chunk_material(texture_atlas, render_settings)),
+            plants: materials.add(plant_chunk_material(texture_atlas, render_settings)),
+        })
+    }
+}
+
+/// Push `ChunkRenderSettings` changes into the shared chunk materials.
+pub fn apply_chunk_render_settings(
+    render_settings: Res<ChunkRenderSettings>,
+    chunk_materials: Res<ChunkMaterials>,
+    mut materials: ResMut<Assets<ChunkMaterial>>,
+) {
+    if !render_settings.is_changed() {
+        return;
+    }
+    let Some(handles) = &chunk_materials.handles else {
+        return;
+    };
+
+    let ambient_occlusion = render_settings.ambient_occlusion_factor();
+    for handle in [&handles.opaque, &handles.water, &handles.plants] {
+        if let Some(material) = materials.get_mut(handle) {
+            material.extension.settings.ambient_occlusion = ambient_occlusion;
+            material.extension.settings.sky_light = render_settings.sky_light;
+        }
+    }
+}
+
+pub fn opaque_chunk_material(
+    texture_atlas: Option<&BlockTextureAtlas>,
+    render_settings: &ChunkRenderSettings,
+) -> ChunkMaterial {
+    let base = if let Some(atlas) = texture_atlas {
+        StandardMaterial {
+            base_color_texture: Some(atlas.texture.clone()),
+            base_color: Color::WHITE,
+            perceptual_roughness: 0.9,
+            metallic: 0.0,
+            reflectance: 0.1,
+            double_sided: true,
+            cull_mode: None,
+            alpha_mode: AlphaMode::Opaque,
+            ..default()
+        }
+    } else {
+        StandardMaterial {
+            base_color: Color::WHITE,
+            perceptual_roughness: 0.9,
+            metallic: 0.0,
+            reflectance: 0.1,
+            double_sided: true,
+            cull_mode: None,
+            alpha_mode: AlphaMode::Opaque,
+            ..default()
+        }
+    };
+
+    ExtendedMaterial {
+        base,
+        extension: ChunkMaterialExtension::new(texture_atlas, render_settings),
+    }
+}
+
+/// Material for plant quads: the atlas cut out by its alpha, so only the
+/// blades show.
+pub fn plant_chunk_material(
+    texture_atlas: Option<&BlockTextureAtlas>,
+    render_settings: &ChunkRenderSettings,
+) -> ChunkMaterial {
+    let mut material = opaque_chunk_material(texture_atlas, render_settings);
+    material.base.alpha_mode = AlphaMode::Mask(0.5);
+    material
+}
+
+pub fn water_chunk_material(
+    texture_atlas: Option<&BlockTextureAtlas>,
+    render_settings: &ChunkRenderSettings,
+) -> ChunkMaterial {
+    let base = if let Some(atlas) = texture_atlas {
+        StandardMaterial {
+            base_color_texture: Some(atlas.texture.clone()),
+            base_color: Color::srgba(0.2, 0.6, 1.2, 0.85),
+            perceptual_roughness: 0.05,
+            metallic: 0.1,
+            reflectance: 0.6,
+            double_sided: true,
+            cull_mode: None,
+            alpha_mode: AlphaMode::Premultiplied,
+            emissive: Color::srgba(0.0, 0.1, 0.3, 1.0).into(),
+            ..default()
+        }
+    } else {
+        StandardMaterial {
+            base_color: Color::srgba(0.1, 0.4, 0.9, 0.8),
+            perceptual_roughness: 0.1,
+            metallic: 0.0,
+            reflectance: 0.4,
+            double_sided: true,
+            cull_mode: None,
+            alpha_mode: AlphaMode::Premultiplied,
+            ..default()
+        }
+    };
+
+    ExtendedMaterial {
+        base,
+        extension: ChunkMaterialExtension::new(texture_atlas, render_settings),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_share_materials_that_follow_the_settings() {
+        let mut app = App::new();
+        app.init_resource::<Assets<ChunkMaterial>>()
+            .init_resource::<ChunkMaterials>()
+            .init_resource::<ChunkRenderSettings>()
+            .add_systems(Update, apply_chunk_render_settings);
+
+        let settings = ChunkRenderSettings::default();
+        let world = app.world_mut();
+        let handles = world.resource_scope(|world, mut materials| {
+            let mut shared = world.resource_mut::<ChunkMaterials>();
+            let first = shared.handles(&mut materials, None, &settings).clone();
+            let again = shared.handles(&mut materials, None, &settings).clone();
+            assert_eq!(first.opaque, again.opaque);
+            first
+        });
+        assert_eq!(app.world().resource::<Assets<ChunkMaterial>>().len(), 3);
+
+        app.world_mut()
+            .resource_mut::<ChunkRenderSettings>()
+            .sky_light = 0.25;
+        app.update();
+
+        let materials = app.world().resource::<Assets<ChunkMaterial>>();
+        for handle in [handles.opaque, handles.water, handles.plants] {
+            let material = materials.get(&handle).unwrap();
+            assert_eq!(material.extension.settings.sky_light, 0.25);
+        }
+    }
+}
@@ -1,5 +1,10 @@
-use crate::chunk::{Chunk, CHUNK_SIZE};
+use super::light::{ChunkLight, VoxelLightMap, MAX_LIGHT_LEVEL};
+use super::material::{ChunkMaterial, ChunkMaterials, ChunkRenderSettings, ATTRIBUTE_VOXEL_LIGHT};
+use crate::block::BlockType;
+use crate::chunk::{Chunk, ChunkPos, ChunkStorage, CHUNK_SIZE};
 use crate::texture::{BlockFace, BlockState, BlockTextureAtlas};
+use crate::world::{ChunkPayloadReady, PlanetChunkStore};
+use bevy::pbr::NotShadowCaster;
 use bevy::prelude::*;
 use bevy::render::mesh::{Indices, PrimitiveTopology};
 use bevy::render::render_asset::RenderAssetUsages;
@@ -15,7 +20,10 @@ struct Vertex {
     position: [f32; 3],
     normal: [f32; 3],
     uv: [f32; 2],
+    tile_origin: [f32; 2],
     color: [f32; 4],
+    /// Smoothed `(sky, block)` light, normalised to `0.0..=1.0`.
+    light: [f32; 2],
 }
 
 /// Component to mark water mesh entities
@@ -26,6 +34,8 @@ pub struct WaterMesh;
 pub struct ChunkMeshJobs {
     tasks: Vec<(Entity, Task<MeshBuildResult>)>,
     scheduled: HashSet<Entity>,
+    /// Chunks whose neighbours changed since their last mesh was queued.
+    pending_remesh: HashSet<ChunkPos>,
     recent_durations: VecDeque<f32>,
 }
 
@@ -38,6 +48,16 @@ impl ChunkMeshJobs {
         self.scheduled.len()
     }
 
+    pub fn pending_remesh_count(&self) -> usize {
+        self.pending_remesh.len()
+    }
+
+    /// Rebuild the mesh of the chunk at `position` on the next pass, e.g.
+    /// after its light changed.
+    pub fn request_remesh(&mut self, position: ChunkPos) {
+        self.pending_remesh.insert(position);
+    }
+
     pub fn average_duration_ms(&self) -> Option<f32> {
         if self.recent_durations.is_empty() {
             None
@@ -51,6 +71,8 @@ impl ChunkMeshJobs {
 struct MeshBuildResult {
     opaque_mesh: Mesh,
     water_mesh: Mesh,
+    plant_mesh: Mesh,
+    stats: ChunkMeshStats,
     duration: f32,
 }
 
@@ -59,20 +81,41 @@ const MESH_HISTORY_SAMPLES: usize = 120;
 pub fn queue_chunk_mesh_builds(
     mut chunk_query: Query<(Entity, &mut Chunk)>,
     texture_atlas: Option<Res<BlockTextureAtlas>>,
+    chunk_store: Res<PlanetChunkStore>,
+    light_map: Res<VoxelLightMap>,
+    mut payload_events: EventReader<ChunkPayloadReady>,
     mut mesh_jobs: ResMut<ChunkMeshJobs>,
 ) {
+    // A chunk arriving or changing can expose or hide faces on the
+    // boundaries of the chunks around it, so those need a fresh mesh too.
+    for event in payload_events.read() {
+        for face in Face::ALL {
+            mesh_jobs
+                .pending_remesh
+                .insert(face.neighbor_position(event.position));
+        }
+    }
+
     let atlas_snapshot = texture_atlas
         .as_ref()
         .map(|atlas| Arc::new((**atlas).clone()));
     let task_pool = IoTaskPool::get();
+    let mut deferred = HashSet::new();
 
     for (entity, mut chunk) in chunk_query.iter_mut() {
-        if !chunk.dirty || mesh_jobs.scheduled.contains(&entity) {
+        let chunk_pos = chunk.position;
+        if !chunk.dirty && !mesh_jobs.pending_remesh.contains(&chunk_pos) {
+            continue;
+        }
+
+        if mesh_jobs.scheduled.contains(&entity) {
+            // The in-flight mesh may predate the change; retry once it lands.
+            deferred.insert(chunk_pos);
             continue;
         }
 
         let storage = chunk.storage.clone();
-        let chunk_pos = chunk.position;
+        let neighbors = ChunkNeighbors::from_store(&chunk_store, &light_map, chunk_pos);
         chunk.dirty = false;
         mesh_jobs.scheduled.insert(entity);
 
@@ -82,31 +125,40 @@ pub fn queue_chunk_mesh_builds(
             let mut chunk_copy = Chunk::from_storage(chunk_pos, storage);
             chunk_copy.dirty = false;
             let atlas_ref = atlas_for_task.as_ref().map(|atlas| atlas.as_ref());
-            let (opaque_mesh, water_mesh) = generate_chunk_meshes(&chunk_copy, atlas_ref);
+            let meshes = generate_chunk_meshes(&chunk_copy, &neighbors, atlas_ref);
             MeshBuildResult {
-                opaque_mesh,
-                water_mesh,
+                opaque_mesh: meshes.opaque,
+                water_mesh: meshes.water,
+                plant_mesh: meshes.plants,
+                stats: meshes.stats,
                 duration: start.elapsed().as_secs_f32(),
             }
         });
 
         mesh_jobs.tasks.push((entity, task));
     }
+
+    // Positions without a loaded chunk get meshed from scratch when they
+    // spawn, so only keep requests that are waiting on an in-flight build.
+    mesh_jobs
+        .pending_remesh
+        .retain(|position| deferred.contains(position));
 }
 
 pub fn apply_chunk_mesh_results(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut materials: ResMut<Assets<ChunkMaterial>>,
+    mut chunk_materials: ResMut<ChunkMaterials>,
     mut mesh_jobs: ResMut<ChunkMeshJobs>,
     texture_atlas: Option<Res<BlockTextureAtlas>>,
+    render_settings: Res<ChunkRenderSettings>,
 ) {
-    let atlas_option = texture_atlas.as_deref();
-
     let mut finished_indices = Vec::new();
     let mut finished_payloads = Vec::new();
     let mut total_duration_ms = 0.0_f32;
     let mut total_vertices = 0_usize;
+    let mut total_faces = 0_usize;
     let mut processed = 0_usize;
 
     for (index, (entity, task)) in mesh_jobs.tasks.iter_mut().enumerate() {
@@ -116,6 +168,9 @@ pub fn apply_chunk_mesh_results(
         }
     }
 
+    let chunk_materials =
+        chunk_materials.handles(&mut materials, texture_atlas.as_deref(), &render_settings);
+
     for (entity, result) in finished_payloads.into_iter() {
         mesh_jobs.scheduled.remove(&entity);
         mesh_jobs
@@ -133,81 +188,32 @@ pub fn apply_chunk_mesh_results(
 
         let opaque_vertices = result.opaque_mesh.count_vertices();
         let water_vertices = result.water_mesh.count_vertices();
+        let plant_vertices = result.plant_mesh.count_vertices();
 
         processed += 1;
         total_duration_ms += result.duration * 1000.0;
-        total_vertices += (opaque_vertices + water_vertices) as usize;
+        total_vertices += opaque_vertices + water_vertices + plant_vertices;
+        total_faces += result.stats.visible_faces;
 
         if opaque_vertices == 0 && water_vertices == 0 {
             entity_commands.remove::<Handle<Mesh>>();
-            entity_commands.remove::<Handle<StandardMaterial>>();
+            entity_commands.remove::<Handle<ChunkMaterial>>();
         } else if opaque_vertices > 0 {
             let mesh_handle = meshes.add(result.opaque_mesh);
-            let material = if let Some(atlas) = atlas_option {
-                materials.add(StandardMaterial {
-                    base_color_texture: Some(atlas.texture.clone()),
-                    base_color: Color::WHITE,
-                    perceptual_roughness: 0.9,
-                    metallic: 0.0,
-                    reflectance: 0.1,
-                    double_sided: true,
-                    cull_mode: None,
-                    alpha_mode: AlphaMode::Opaque,
-                    ..default()
-                })
-            } else {
-                materials.add(StandardMaterial {
-                    base_color: Color::WHITE,
-                    perceptual_roughness: 0.9,
-                    metallic: 0.0,
-                    reflectance: 0.1,
-                    double_sided: true,
-                    cull_mode: None,
-                    alpha_mode: AlphaMode::Opaque,
-                    ..default()
-                })
-            };
-
-            entity_commands.insert((mesh_handle, material));
+            entity_commands.insert((mesh_handle, chunk_materials.opaque.clone()));
         } else {
             entity_commands.remove::<Handle<Mesh>>();
-            entity_commands.remove::<Handle<StandardMaterial>>();
+            entity_commands.remove::<Handle<ChunkMaterial>>();
         }
 
         drop(entity_commands);
 
         if water_vertices > 0 {
             let water_mesh_handle = meshes.add(result.water_mesh);
-            let water_material = if let Some(atlas) = atlas_option {
-                materials.add(StandardMaterial {
-                    base_color_texture: Some(atlas.texture.clone()),
-                    base_color: Color::srgba(0.2, 0.6, 1.2, 0.85),
-                    perceptual_roughness: 0.05,
-                    metallic: 0.1,
-                    reflectance: 0.6,
-                    double_sided: true,
-                    cull_mode: None,
-                    alpha_mode: AlphaMode::Premultiplied,
-                    emissive: Color::srgba(0.0, 0.1, 0.3, 1.0).into(),
-                    ..default()
-                })
-            } else {
-                materials.add(StandardMaterial {
-                    base_color: Color::srgba(0.1, 0.4, 0.9, 0.8),
-                    perceptual_roughness: 0.1,
-                    metallic: 0.0,
-                    reflectance: 0.4,
-                    double_sided: true,
-                    cull_mode: None,
-                    alpha_mode: AlphaMode::Premultiplied,
-                    ..default()
-                })
-            };
-
             let water_entity = commands
                 .spawn((
                     water_mesh_handle,
-                    water_material,
+                    chunk_materials.water.clone(),
                     WaterMesh,
                     TransformBundle::default(),
                     VisibilityBundle::default(),
@@ -216,13 +222,34 @@ pub fn apply_chunk_mesh_results(
 
             commands.entity(entity).add_child(water_entity);
         }
+
+        if plant_vertices > 0 {
+            let plant_mesh_handle = meshes.add(result.plant_mesh);
+            // Shadow passes don't wrap the atlas tile, so they would cut
+            // plants out by the wrong texels.
+            let plant_entity = commands
+                .spawn((
+                    plant_mesh_handle,
+                    chunk_materials.plants.clone(),
+                    NotShadowCaster,
+                    TransformBundle::default(),
+                    VisibilityBundle::default(),
+                ))
+                .id();
+
+            commands.entity(entity).add_child(plant_entity);
+        }
     }
 
     if processed > 0 {
         let average_ms = total_duration_ms / processed as f32;
         info!(
-            "chunk-mesh apply: count={} total_ms={:.2} avg_ms={:.2} total_vertices={}",
-            processed, total_duration_ms, average_ms, total_vertices
+            "chunk-mesh apply: count={} total_ms={:.2} avg_ms={:.2} total_vertices={} unmerged_vertices={}",
+            processed,
+            total_duration_ms,
+            average_ms,
+            total_vertices,
+            total_faces * 4
         );
     }
 
@@ -235,232 +262,438 @@ pub fn apply_chunk_mesh_results(
     }
 }
 
+/// Face and quad counts for a meshed chunk. `visible_faces` is what a
+/// one-quad-per-face mesher would emit; `quads` is what greedy merging kept.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChunkMeshStats {
+    pub visible_faces: usize,
+    pub quads: usize,
+}
+
+#[allow(dead_code)]
+impl ChunkMeshStats {
+    pub fn naive_vertices(&self) -> usize {
+        self.visible_faces * 4
+    }
+
+    pub fn naive_indices(&self) -> usize {
+        self.visible_faces * 6
+    }
+
+    pub fn vertices(&self) -> usize {
+        self.quads * 4
+    }
+
+    pub fn indices(&self) -> usize {
+        self.quads * 6
+    }
+
+    pub fn accumulate(&mut self, other: &ChunkMeshStats) {
+        self.visible_faces += other.visible_faces;
+        self.quads += other.quads;
+    }
+}
+
+/// Storage snapshots of the 26 chunks surrounding the one being meshed. Face
+/// neighbours cull boundary faces; edge and corner neighbours feed ambient
+/// occlusion. Missing neighbours fall back to conservative rules. Light
+/// snapshots cover the centre chunk too; unlit voxels render at full sky.
+#[derive(Clone, Default)]
+pub struct ChunkNeighbors {
+    storages: [Option<Arc<ChunkStorage>>; 27],
+    lights: [Option<Arc<ChunkLight>>; 27],
+}
+
+impl ChunkNeighbors {
+    pub fn from_store(
+        store: &PlanetChunkStore,
+        light_map: &VoxelLightMap,
+        position: ChunkPos,
+    ) -> Self {
+        let neighbor = |index: usize| {
+            let [dx, dy, dz] = Self::offset(index);
+            ChunkPos::new(position.x + dx, position.y + dy, position.z + dz)
+        };
+        let storages = std::array::from_fn(|index| {
+            if index == Self::index([0, 0, 0]) {
+                return None;
+            }
+            store.get(&neighbor(index))
+        });
+        let lights = std::array::from_fn(|index| light_map.get(&neighbor(index)));
+
+        Self { storages, lights }
+    }
+
+    fn offset(index: usize) -> [i32; 3] {
+        let index = index as i32;
+        [index % 3 - 1, (index / 3) % 3 - 1, index / 9 - 1]
+    }
+
+    fn index(offset: [i32; 3]) -> usize {
+        ((offset[0] + 1) + 3 * ((offset[1] + 1) + 3 * (offset[2] + 1))) as usize
+    }
+}
+
+/// Block at chunk-local `position`, which may lie up to one chunk outside
+/// `chunk`. Returns `None` when the chunk holding it is not loaded.
+fn sample_block(
+    chunk: &Chunk,
+    neighbors: &ChunkNeighbors,
+    position: [isize; 3],
+) -> Option<BlockType> {
+    let size = CHUNK_SIZE as isize;
+    let mut offset = [0i32; 3];
+    let mut local = [0usize; 3];
+    for axis in 0..3 {
+        let value = position[axis];
+        offset[axis] = value.div_euclid(size) as i32;
+        local[axis] = value.rem_euclid(size) as usize;
+    }
+
+    if offset == [0, 0, 0] {
+        return Some(chunk.get_block(local[0], local[1], local[2]));
+    }
+
+    neighbors.storages[ChunkNeighbors::index(offset)]
+        .as_deref()
+        .map(|storage| storage.get(local[0], local[1], local[2]))
+}
+
+/// Top of the water at chunk-local `position`, in `FaceKey::height` units:
+/// full for source water and for anything that isn't water. Returns `None`
+/// when the chunk holding it is not loaded.
+fn sample_water_height(
+    chunk: &Chunk,
+    neighbors: &ChunkNeighbors,
+    position: [isize; 3],
+) -> Option<u8> {
+    let size = CHUNK_SIZE as isize;
+    let offset = position.map(|value| value.div_euclid(size) as i32);
+    let [x, y, z] = position.map(|value| value.rem_euclid(size) as usize);
+
+    let storage = if offset == [0, 0, 0] {
+        &chunk.storage
+    } else {
+        neighbors.storages[ChunkNeighbors::index(offset)].as_deref()?
+    };
+    Some(
+        storage
+            .fluid_level(x, y, z)
+            .map_or(FULL_HEIGHT, |level| level.min(FULL_HEIGHT)),
+    )
+}
+
+/// `(sky, block)` light at chunk-local `position`, which may lie up to one
+/// chunk outside the meshed chunk. Returns `None` when that chunk is unlit.
+fn sample_light(neighbors: &ChunkNeighbors, position: [isize; 3]) -> Option<[u8; 2]> {
+    let size = CHUNK_SIZE as isize;
+    let offset = position.map(|value| value.div_euclid(size) as i32);
+    let [x, y, z] = position.map(|value| value.rem_euclid(size) as usize);
+
+    neighbors.lights[ChunkNeighbors::index(offset)]
+        .as_deref()
+        .map(|light| [light.sky(x, y, z), light.block(x, y, z)])
+}
+
+pub struct GeneratedChunkMeshes {
+    pub opaque: Mesh,
+    pub water: Mesh,
+    /// Crossed quads of the chunk's plants, drawn cut out by texture alpha.
+    pub plants: Mesh,
+    pub stats: ChunkMeshStats,
+}
+
+/// Per-face data that must match for two neighbouring faces to be merged
+/// into a single quad.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct FaceKey {
+    block: BlockType,
+    /// Corner occlusion level (0 = fully occluded, 3 = open) per quad vertex,
+    /// in `Face::corners` order.
+    ao: [u8; 4],
+    /// Smoothed `(sky, block)` light per quad vertex, in quarter light levels.
+    light: [[u8; 2]; 4],
+    /// Top of the voxel in eighths of a block. Only flowing water sits lower.
+    height: u8,
+}
+
+/// `FaceKey::height` of a full block.
+const FULL_HEIGHT: u8 = 8;
+
+/// Vertex brightness for each corner occlusion level.
+const AO_CURVE: [f32; 4] = [0.45, 0.65, 0.82, 1.0];
+
 pub fn generate_chunk_meshes(
     chunk: &Chunk,
+    neighbors: &ChunkNeighbors,
     texture_atlas: Option<&BlockTextureAtlas>,
-) -> (Mesh, Mesh) {
+) -> GeneratedChunkMeshes {
     // Separate vertices for opaque and water meshes
     let mut opaque_vertices: Vec<Vertex> = Vec::with_capacity(1024);
     let mut opaque_indices: Vec<u32> = Vec::with_capacity(1536);
     let mut water_vertices: Vec<Vertex> = Vec::with_capacity(256);
     let mut water_indices: Vec<u32> = Vec::with_capacity(384);
+    let mut plant_vertices: Vec<Vertex> = Vec::new();
+    let mut plant_indices: Vec<u32> = Vec::new();
+    let mut stats = ChunkMeshStats::default();
 
-    for x in 0..CHUNK_SIZE {
-        for y in 0..CHUNK_SIZE {
-            for z in 0..CHUNK_SIZE {
-                let block = chunk.get_block(x, y, z);
-                if !block.is_visible() {
-                    continue;
-                }
+    let mut mask: Vec<Option<FaceKey>> = vec![None; CHUNK_SIZE * CHUNK_SIZE];
 
-                let pos = Vec3::new(x as f32, y as f32, z as f32);
-                let is_water = block.is_liquid();
-
-                // Choose which mesh to add to
-                let (vertices, indices) = if is_water {
-                    (&mut water_vertices, &mut water_indices)
-                } else {
-                    (&mut opaque_vertices, &mut opaque_indices)
-                };
-
-                let color = if texture_atlas.is_some() {
-                    [1.0, 1.0, 1.0, 1.0] // White when using textures
-                } else {
-                    block.get_color() // Use block colors as fallback
-                };
-
-                // Check each face for visibility
-                // Left face
-                if x == 0 {
-                    // At chunk boundary - render solid blocks always, skip water (likely continues in next chunk)
-                    if !is_water {
-                        add_face(
-                            vertices,
-                            indices,
-                            pos,
-                            Face::Left,
-                            color,
-                            block,
-                            texture_atlas,
-                        );
-                    }
-                } else {
-                    let adjacent = chunk.get_block(x - 1, y, z);
-                    if should_render_face(block, adjacent) {
-                        add_face(
-                            vertices,
-                            indices,
-                            pos,
-                            Face::Left,
-                            color,
-                            block,
-                            texture_atlas,
-                        );
+    for face in Face::ALL {
+        let (normal_axis, u_axis, v_axis) = face.axes();
+
+        for slice in 0..CHUNK_SIZE {
+            // Collect the visible faces of this slice into a 2D mask.
+            for v in 0..CHUNK_SIZE {
+                for u in 0..CHUNK_SIZE {
+                    let mut voxel = [0usize; 3];
+                    voxel[normal_axis] = slice;
+                    voxel[u_axis] = u;
+                    voxel[v_axis] = v;
+
+                    let key = visible_face(chunk, neighbors, voxel, face);
+                    if key.is_some() {
+                        stats.visible_faces += 1;
                     }
+                    mask[v * CHUNK_SIZE + u] = key;
                 }
+            }
 
-                // Right face
-                if x == CHUNK_SIZE - 1 {
-                    // At chunk boundary - render solid blocks always, skip water
-                    if !is_water {
-                        add_face(
-                            vertices,
-                            indices,
-                            pos,
-                            Face::Right,
-                            color,
-                            block,
-                            texture_atlas,
-                        );
-                    }
-                } else {
-                    let adjacent = chunk.get_block(x + 1, y, z);
-                    if should_render_face(block, adjacent) {
-                        add_face(
-                            vertices,
-                            indices,
-                            pos,
-                            Face::Right,
-                            color,
-                            block,
-                            texture_atlas,
-                        );
+            // Greedily grow rectangles of identical faces, widest first.
+            for v in 0..CHUNK_SIZE {
+                let mut u = 0;
+                while u < CHUNK_SIZE {
+                    let Some(key) = mask[v * CHUNK_SIZE + u] else {
+                        u += 1;
+                        continue;
+                    };
+
+                    let mut width = 1;
+                    while u + width < CHUNK_SIZE && mask[v * CHUNK_SIZE + u + width] == Some(key) {
+                        width += 1;
                     }
-                }
 
-                // Bottom face
-                if y == 0 {
-                    // At chunk boundary - render solid blocks always, skip water
-                    if !is_water {
-                        add_face(
-                            vertices,
-                            indices,
-                            pos,
-                            Face::Bottom,
-                            color,
-                            block,
-                            texture_atlas,
-                        );
+                    let mut height = 1;
+                    'grow: while v + height < CHUNK_SIZE {
+                        let row = (v + height) * CHUNK_SIZE;
+                        for offset in 0..width {
+                            if mask[row + u + offset] != Some(key) {
+                                break 'grow;
+                            }
+                        }
+                        height += 1;
                     }
-                } else {
-                    let adjacent = chunk.get_block(x, y - 1, z);
-                    if should_render_face(block, adjacent) {
-                        add_face(
-                            vertices,
-                            indices,
-                            pos,
-                            Face::Bottom,
-                            color,
-                            block,
-                            texture_atlas,
-                        );
+
+                    for row in v..v + height {
+                        for column in u..u + width {
+                            mask[row * CHUNK_SIZE + column] = None;
+                        }
                     }
-                }
 
-                // Top face
-                if y == CHUNK_SIZE - 1 {
-                    // At chunk boundary - always render (water surface needs to be visible)
-                    add_face(
+                    let mut origin = [0.0_f32; 3];
+                    origin[normal_axis] = slice as f32;
+                    origin[u_axis] = u as f32;
+                    origin[v_axis] = v as f32;
+
+                    let (vertices, indices) = if key.block.is_liquid() {
+                        (&mut water_vertices, &mut water_indices)
+                    } else {
+                        (&mut opaque_vertices, &mut opaque_indices)
+                    };
+
+                    let color = if texture_atlas.is_some() {
+                        [1.0, 1.0, 1.0, 1.0] // White when using textures
+                    } else {
+                        key.block.get_color() // Use block colors as fallback
+                    };
+
+                    add_quad(
                         vertices,
                         indices,
-                        pos,
-                        Face::Top,
+                        Vec3::from_array(origin),
+                        width as f32,
+                        height as f32,
+                        face,
                         color,
-                        block,
+                        key,
                         texture_atlas,
                     );
-                } else {
-                    let adjacent = chunk.get_block(x, y + 1, z);
-                    if should_render_face(block, adjacent) {
-                        add_face(
-                            vertices,
-                            indices,
-                            pos,
-                            Face::Top,
-                            color,
-                            block,
-                            texture_atlas,
-                        );
-                    }
-                }
+                    stats.quads += 1;
 
-                // Front face
-                if z == 0 {
-                    // At chunk boundary - render solid blocks always, skip water
-                    if !is_water {
-                        add_face(
-                            vertices,
-                            indices,
-                            pos,
-                            Face::Front,
-                            color,
-                            block,
-                            texture_atlas,
-                        );
-                    }
-                } else {
-                    let adjacent = chunk.get_block(x, y, z - 1);
-                    if should_render_face(block, adjacent) {
-                        add_face(
-                            vertices,
-                            indices,
-                            pos,
-                            Face::Front,
-                            color,
-                            block,
-                            texture_atlas,
-                        );
-                    }
+                    u += width;
                 }
+            }
+        }
+    }
 
-                // Back face
-                if z == CHUNK_SIZE - 1 {
-                    // At chunk boundary - render solid blocks always, skip water
-                    if !is_water {
-                        add_face(
-                            vertices,
-                            indices,
-                            pos,
-                            Face::Back,
-                            color,
-                            block,
-                            texture_atlas,
-                        );
-                    }
-                } else {
-                    let adjacent = chunk.get_block(x, y, z + 1);
-                    if should_render_face(block, adjacent) {
-                        add_face(
-                            vertices,
-                            indices,
-                            pos,
-                            Face::Back,
-                            color,
-                            block,
-                            texture_atlas,
-                        );
-                    }
+    for x in 0..CHUNK_SIZE {
+        for y in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let block = chunk.get_block(x, y, z);
+                if block.is_plant() {
+                    let light = sample_light(neighbors, [x as isize, y as isize, z as isize]);
+                    add_plant(
+                        &mut plant_vertices,
+                        &mut plant_indices,
+                        Vec3::new(x as f32, y as f32, z as f32),
+                        block,
+                        light,
+                        texture_atlas,
+                    );
                 }
             }
         }
     }
 
-    (
-        build_mesh(opaque_vertices, opaque_indices),
-        build_mesh(water_vertices, water_indices),
-    )
+    GeneratedChunkMeshes {
+        opaque: build_mesh(opaque_vertices, opaque_indices),
+        water: build_mesh(water_vertices, water_indices),
+        plants: build_mesh(plant_vertices, plant_indices),
+        stats,
+    }
 }
 
-/// Determine if a face should be rendered based on block adjacency
-fn should_render_face(block: crate::block::BlockType, adjacent: crate::block::BlockType) -> bool {
-    use crate::block::BlockType;
+/// Returns the merge key for `face` of the voxel at `voxel` if that face is visible.
+fn visible_face(
+    chunk: &Chunk,
+    neighbors: &ChunkNeighbors,
+    voxel: [usize; 3],
+    face: Face,
+) -> Option<FaceKey> {
+    let [x, y, z] = voxel;
+    let block = chunk.get_block(x, y, z);
+    // Plants are meshed on their own by `add_plant`.
+    if !block.is_visible() || block.is_plant() {
+        return None;
+    }
+
+    let (normal_axis, _, _) = face.axes();
+    let mut front = voxel.map(|value| value as isize);
+    front[normal_axis] += face.step();
+
+    let height = if block.is_liquid() {
+        chunk
+            .storage
+            .fluid_level(x, y, z)
+            .map_or(FULL_HEIGHT, |level| level.min(FULL_HEIGHT))
+    } else {
+        FULL_HEIGHT
+    };
+
+    let visible = match sample_block(chunk, neighbors, front) {
+        // Water beside lower water shows the step between their surfaces.
+        Some(adjacent) if block.is_liquid() && adjacent.is_liquid() => {
+            normal_axis != 1
+                && sample_water_height(chunk, neighbors, front)
+                    .is_some_and(|adjacent_height| adjacent_height < height)
+        }
+        Some(adjacent) => should_render_face(block, adjacent),
+        // Neighbour chunk not loaded yet - render solid blocks always, skip
+        // water (likely continues in next chunk). Top faces always render so
+        // the water surface stays visible.
+        None => matches!(face, Face::Top) || !block.is_liquid(),
+    };
+
+    if !visible {
+        return None;
+    }
+
+    let ao = if block.is_liquid() {
+        [3; 4]
+    } else {
+        corner_occlusion(chunk, neighbors, front, face)
+    };
+    let light = corner_light(chunk, neighbors, front, face);
+
+    Some(FaceKey {
+        block,
+        ao,
+        light,
+        height,
+    })
+}
+
+/// Classic voxel corner occlusion: each vertex looks at the two edge
+/// neighbours and the diagonal neighbour in the layer in front of the face.
+fn corner_occlusion(
+    chunk: &Chunk,
+    neighbors: &ChunkNeighbors,
+    front: [isize; 3],
+    face: Face,
+) -> [u8; 4] {
+    let (_, u_axis, v_axis) = face.axes();
+    let occludes = |du: isize, dv: isize| {
+        let mut position = front;
+        position[u_axis] += du;
+        position[v_axis] += dv;
+        sample_block(chunk, neighbors, position).is_some_and(|block| block.is_solid())
+    };
+
+    face.corners().map(|[du, dv]| {
+        let side_u = occludes(du, 0);
+        let side_v = occludes(0, dv);
+        if side_u && side_v {
+            0
+        } else {
+            3 - side_u as u8 - side_v as u8 - occludes(du, dv) as u8
+        }
+    })
+}
+
+/// Smooth lighting: each vertex averages the light of the open voxels among
+/// the four that touch it in the layer in front of the face.
+fn corner_light(
+    chunk: &Chunk,
+    neighbors: &ChunkNeighbors,
+    front: [isize; 3],
+    face: Face,
+) -> [[u8; 2]; 4] {
+    let full_sky = [MAX_LIGHT_LEVEL * 4, 0];
+    let Some(front_light) = sample_light(neighbors, front) else {
+        return [full_sky; 4];
+    };
 
+    let (_, u_axis, v_axis) = face.axes();
+    let open_light = |du: isize, dv: isize| {
+        let mut position = front;
+        position[u_axis] += du;
+        position[v_axis] += dv;
+        let block = sample_block(chunk, neighbors, position)?;
+        if !block.is_transparent() {
+            return None;
+        }
+        sample_light(neighbors, position)
+    };
+
+    face.corners().map(|[du, dv]| {
+        let side_u = open_light(du, 0);
+        let side_v = open_light(0, dv);
+        // A corner voxel hidden behind both sides cannot leak light in.
+        let corner = if side_u.is_some() || side_v.is_some() {
+            open_light(du, dv)
+        } else {
+            None
+        };
+
+        let samples = [Some(front_light), side_u, side_v, corner];
+        let count = samples.iter().flatten().count() as u32;
+        [0, 1].map(|channel| {
+            let sum: u32 = samples.iter().flatten().map(|s| s[channel] as u32).sum();
+            // Quarter levels keep the average exact while staying mergeable.
+            (sum * 4 / count) as u8
+        })
+    })
+}
+
+/// Determine if a face should be rendered based on block adjacency
+fn should_render_face(block: BlockType, adjacent: BlockType) -> bool {
     // Never render faces between identical solid blocks
     if block == adjacent && block.is_solid() {
         return false;
     }
 
-    // Don't render faces between water blocks
+    // Water against water is decided by `visible_face`, from their levels
     if block.is_liquid() && adjacent.is_liquid() {
         return false;
     }
@@ -480,6 +713,7 @@ fn should_render_face(block: crate::block::BlockType, adjacent: crate::block::Bl
     true
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum Face {
     Top,
     Bottom,
@@ -489,130 +723,256 @@ enum Face {
     Back,
 }
 
-fn add_face(
+impl Face {
+    const ALL: [Face; 6] = [
+        Face::Top,
+        Face::Bottom,
+        Face::Left,
+        Face::Right,
+        Face::Front,
+        Face::Back,
+    ];
+
+    /// Axis indices as `(normal, u, v)`, where `u`/`v` span the face plane.
+    fn axes(self) -> (usize, usize, usize) {
+        match self {
+            Face::Top | Face::Bottom => (1, 0, 2),
+            Face::Left | Face::Right => (0, 2, 1),
+            Face::Front | Face::Back => (2, 0, 1),
+        }
+    }
+
+    /// Direction of the face normal along its axis.
+    fn step(self) -> isize {
+        match self {
+            Face::Top | Face::Right | Face::Back => 1,
+            Face::Bottom | Face::Left | Face::Front => -1,
+        }
+    }
+
+    /// Direction of each quad vertex along `(u, v)`, in the order `add_quad`
+    /// emits them.
+    fn corners(self) -> [[isize; 2]; 4] {
+        match self {
+            Face::Top | Face::Right | Face::Front => [[-1, -1], [1, -1], [1, 1], [-1, 1]],
+            Face::Bottom => [[-1, 1], [1, 1], [1, -1], [-1, -1]],
+            Face::Left | Face::Back => [[1, -1], [-1, -1], [-1, 1], [1, 1]],
+        }
+    }
+
+    fn neighbor_position(self, position: ChunkPos) -> ChunkPos {
+        let (normal_axis, _, _) = self.axes();
+        let mut offset = [0; 3];
+        offset[normal_axis] = self.step() as i32;
+        ChunkPos::new(
+            position.x + offset[0],
+            position.y + offset[1],
+            position.z + offset[2],
+        )
+    }
+
+    fn block_face(self) -> BlockFace {
+        match self {
+            Face::Top => BlockFace::Top,
+            Face::Bottom => BlockFace::Bottom,
+            Face::Front => BlockFace::Front,
+            Face::Back => BlockFace::Back,
+            Face::Left => BlockFace::Left,
+            Face::Right => BlockFace::Right,
+        }
+    }
+}
+
+/// Emit a `width` x `height` quad for `face`, where `pos` is the minimum voxel
+/// corner of the merged region and the extents follow `Face::axes`.
+#[allow(clippy::too_many_arguments)]
+fn add_quad(
     vertices: &mut Vec<Vertex>,
     indices: &mut Vec<u32>,
     pos: Vec3,
+    width: f32,
+    height: f32,
     face: Face,
     color: [f32; 4],
-    block: crate::block::BlockType,
+    key: FaceKey,
     texture_atlas: Option<&BlockTextureAtlas>,
 ) {
+    let block = key.block;
     let start_index = vertices.len() as u32;
+    let (w, h) = (width, height);
 
-    let (positions, normal) = match face {
+    let (mut positions, normal) = match face {
         Face::Top => (
             [
                 [pos.x, pos.y + 1.0, pos.z],
-                [pos.x + 1.0, pos.y + 1.0, pos.z],
-                [pos.x + 1.0, pos.y + 1.0, pos.z + 1.0],
-                [pos.x, pos.y + 1.0, pos.z + 1.0],
+                [pos.x + w, pos.y + 1.0, pos.z],
+                [pos.x + w, pos.y + 1.0, pos.z + h],
+                [pos.x, pos.y + 1.0, pos.z + h],
             ],
             [0.0, 1.0, 0.0],
         ),
         Face::Bottom => (
             [
-                [pos.x, pos.y, pos.z + 1.0],
-                [pos.x + 1.0, pos.y, pos.z + 1.0],
-                [pos.x + 1.0, pos.y, pos.z],
+                [pos.x, pos.y, pos.z + h],
+                [pos.x + w, pos.y, pos.z + h],
+                [pos.x + w, pos.y, pos.z],
                 [pos.x, pos.y, pos.z],
             ],
             [0.0, -1.0, 0.0],
         ),
         Face::Left => (
             [
-                [pos.x, pos.y, pos.z + 1.0],
+                [pos.x, pos.y, pos.z + w],
                 [pos.x, pos.y, pos.z],
-                [pos.x, pos.y + 1.0, pos.z],
-                [pos.x, pos.y + 1.0, pos.z + 1.0],
+                [pos.x, pos.y + h, pos.z],
+                [pos.x, pos.y + h, pos.z + w],
             ],
             [-1.0, 0.0, 0.0],
         ),
         Face::Right => (
             [
                 [pos.x + 1.0, pos.y, pos.z],
-                [pos.x + 1.0, pos.y, pos.z + 1.0],
-                [pos.x + 1.0, pos.y + 1.0, pos.z + 1.0],
-                [pos.x + 1.0, pos.y + 1.0, pos.z],
+                [pos.x + 1.0, pos.y, pos.z + w],
+                [pos.x + 1.0, pos.y + h, pos.z + w],
+                [pos.x + 1.0, pos.y + h, pos.z],
             ],
             [1.0, 0.0, 0.0],
         ),
         Face::Front => (
             [
                 [pos.x, pos.y, pos.z],
-                [pos.x + 1.0, pos.y, pos.z],
-                [pos.x + 1.0, pos.y + 1.0, pos.z],
-                [pos.x, pos.y + 1.0, pos.z],
+                [pos.x + w, pos.y, pos.z],
+                [pos.x + w, pos.y + h, pos.z],
+                [pos.x, pos.y + h, pos.z],
             ],
             [0.0, 0.0, -1.0],
         ),
         Face::Back => (
             [
-                [pos.x + 1.0, pos.y, pos.z + 1.0],
+                [pos.x + w, pos.y, pos.z + 1.0],
                 [pos.x, pos.y, pos.z + 1.0],
-                [pos.x, pos.y + 1.0, pos.z + 1.0],
-                [pos.x + 1.0, pos.y + 1.0, pos.z + 1.0],
+                [pos.x, pos.y + h, pos.z + 1.0],
+                [pos.x + w, pos.y + h, pos.z + 1.0],
             ],
             [0.0, 0.0, 1.0],
         ),
     };
 
-    // Get UV coordinates from texture atlas
-    let uvs = if let Some(atlas) = texture_atlas {
-        let block_face = match face {
-            Face::Top => BlockFace::Top,
-            Face::Bottom => BlockFace::Bottom,
-            Face::Front => BlockFace::Front,
-            Face::Back => BlockFace::Back,
-            Face::Left => BlockFace::Left,
-            Face::Right => BlockFace::Right,
+    // Shallow flowing water: lower the top face, and the top edge of the
+    // side faces, to the water's surface.
+    if key.height < FULL_HEIGHT {
+        let drop = 1.0 - key.height as f32 / FULL_HEIGHT as f32;
+        let top = match face {
+            Face::Top => pos.y + 1.0,
+            Face::Bottom => f32::INFINITY,
+            _ => pos.y + h,
         };
+        for position in positions.iter_mut() {
+            if position[1] == top {
+                position[1] -= drop;
+            }
+        }
+    }
 
-        let (uv_min, uv_max) =
-            atlas.get_uv(block.get_texture_name(), block_face, BlockState::Normal);
-
-        // Flip V coordinates for side faces to correct orientation
-        match face {
-            Face::Left | Face::Right | Face::Front | Face::Back => [
-                [uv_min.x, uv_max.y], // Bottom-left (was top-left)
-                [uv_max.x, uv_max.y], // Bottom-right (was top-right)
-                [uv_max.x, uv_min.y], // Top-right (was bottom-right)
-                [uv_min.x, uv_min.y], // Top-left (was bottom-left)
-            ],
-            Face::Top | Face::Bottom => [
-                [uv_min.x, uv_min.y],
-                [uv_max.x, uv_min.y],
-                [uv_max.x, uv_max.y],
-                [uv_min.x, uv_max.y],
-            ],
+    // UVs are in tile units so the chunk shader can repeat the atlas tile
+    // across merged quads. Side faces flip V so textures stay upright.
+    let uvs = match face {
+        Face::Left | Face::Right | Face::Front | Face::Back => {
+            [[0.0, h], [w, h], [w, 0.0], [0.0, 0.0]]
         }
+        Face::Top | Face::Bottom => [[0.0, 0.0], [w, 0.0], [w, h], [0.0, h]],
+    };
+
+    let tile_origin = if let Some(atlas) = texture_atlas {
+        let (uv_min, _uv_max) = atlas.get_uv(
+            block.texture_name(face.block_face()),
+            face.block_face(),
+            BlockState::Normal,
+        );
+        [uv_min.x, uv_min.y]
     } else {
-        // Default UVs when no texture atlas
-        match face {
-            Face::Left | Face::Right | Face::Front | Face::Back => {
-                [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]]
-            }
-            Face::Top | Face::Bottom => [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
-        }
+        [0.0, 0.0]
     };
 
     for i in 0..4 {
+        // Opaque meshes carry corner occlusion in the vertex alpha; the chunk
+        // shader applies it when enabled on the material.
+        let mut color = color;
+        if !block.is_liquid() {
+            color[3] = AO_CURVE[key.ao[i] as usize];
+        }
+
+        let max_light = (MAX_LIGHT_LEVEL * 4) as f32;
+        let light = key.light[i].map(|level| level as f32 / max_light);
+
         vertices.push(Vertex {
             position: positions[i],
             normal,
             uv: uvs[i],
+            tile_origin,
             color,
+            light,
         });
     }
 
-    indices.extend_from_slice(&[
-        start_index,
-        start_index + 1,
-        start_index + 2,
-        start_index,
-        start_index + 2,
-        start_index + 3,
-    ]);
+    // Split along the brighter diagonal so occlusion interpolates without
+    // the anisotropy artifact.
+    let [a0, a1, a2, a3] = key.ao.map(u32::from);
+    let quad = if a0 + a2 < a1 + a3 {
+        [1, 2, 3, 1, 3, 0]
+    } else {
+        [0, 1, 2, 0, 2, 3]
+    };
+    indices.extend(quad.map(|corner| start_index + corner));
+}
+
+/// Emit the two quads crossing diagonally through the voxel at `pos`, lit
+/// by the voxel's own `(sky, block)` light.
+fn add_plant(
+    vertices: &mut Vec<Vertex>,
+    indices: &mut Vec<u32>,
+    pos: Vec3,
+    block: BlockType,
+    light: Option<[u8; 2]>,
+    texture_atlas: Option<&BlockTextureAtlas>,
+) {
+    let (color, tile_origin) = match texture_atlas {
+        Some(atlas) => {
+            let (uv_min, _uv_max) = atlas.get_uv(
+                block.texture_name(BlockFace::Front),
+                BlockFace::Front,
+                BlockState::Normal,
+            );
+            ([1.0; 4], [uv_min.x, uv_min.y])
+        }
+        None => (block.get_color(), [0.0, 0.0]),
+    };
+    let light = light.map_or([1.0, 0.0], |light| {
+        light.map(|level| level as f32 / MAX_LIGHT_LEVEL as f32)
+    });
+
+    for [(x0, z0), (x1, z1)] in [[(0.0, 0.0), (1.0, 1.0)], [(1.0, 0.0), (0.0, 1.0)]] {
+        let start_index = vertices.len() as u32;
+        let positions = [
+            [pos.x + x0, pos.y, pos.z + z0],
+            [pos.x + x1, pos.y, pos.z + z1],
+            [pos.x + x1, pos.y + 1.0, pos.z + z1],
+            [pos.x + x0, pos.y + 1.0, pos.z + z0],
+        ];
+        let uvs = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+        for i in 0..4 {
+            vertices.push(Vertex {
+                position: positions[i],
+                // Lit like the ground they stand on, from either side.
+                normal: [0.0, 1.0, 0.0],
+                uv: uvs[i],
+                tile_origin,
+                color,
+                light,
+            });
+        }
+        indices.extend([0, 1, 2, 0, 2, 3].map(|corner| start_index + corner));
+    }
 }
 
 fn build_mesh(vertices: Vec<Vertex>, indices: Vec<u32>) -> Mesh {
@@ -624,13 +984,130 @@ fn build_mesh(vertices: Vec<Vertex>, indices: Vec<u32>) -> Mesh {
     let positions: Vec<[f32; 3]> = vertices.iter().map(|v| v.position).collect();
     let normals: Vec<[f32; 3]> = vertices.iter().map(|v| v.normal).collect();
     let uvs: Vec<[f32; 2]> = vertices.iter().map(|v| v.uv).collect();
+    let tile_origins: Vec<[f32; 2]> = vertices.iter().map(|v| v.tile_origin).collect();
     let colors: Vec<[f32; 4]> = vertices.iter().map(|v| v.color).collect();
+    let lights: Vec<[f32; 2]> = vertices.iter().map(|v| v.light).collect();
 
     mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
     mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
     mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_1, tile_origins);
     mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.insert_attribute(ATTRIBUTE_VOXEL_LIGHT, lights);
     mesh.insert_indices(Indices::U32(indices));
 
     mesh
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::render::mesh::VertexAttributeValues;
+
+    #[test]
+    fn flat_slab_merges_into_single_top_quad() {
+        let mut chunk = Chunk::new(ChunkPos::new(0, 0, 0));
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk.set_block(x, 0, z, BlockType::Stone);
+            }
+        }
+
+        let meshes = generate_chunk_meshes(&chunk, &ChunkNeighbors::default(), None);
+
+        // Top and bottom are one quad each, plus one strip per chunk side.
+        assert_eq!(meshes.stats.quads, 6);
+        assert_eq!(
+            meshes.stats.visible_faces,
+            CHUNK_SIZE * CHUNK_SIZE * 2 + CHUNK_SIZE * 4
+        );
+        assert_eq!(meshes.opaque.count_vertices(), meshes.stats.vertices());
+    }
+
+    #[test]
+    fn differing_blocks_are_not_merged() {
+        let mut chunk = Chunk::new(ChunkPos::new(0, 0, 0));
+        chunk.set_block(4, 4, 4, BlockType::Stone);
+        chunk.set_block(5, 4, 4, BlockType::Dirt);
+
+        let meshes = generate_chunk_meshes(&chunk, &ChunkNeighbors::default(), None);
+
+        // The shared face is hidden; every other face stays a separate quad
+        // because the block types differ.
+        assert_eq!(meshes.stats.visible_faces, 10);
+        assert_eq!(meshes.stats.quads, 10);
+    }
+
+    #[test]
+    fn loaded_neighbors_cull_boundary_faces() {
+        let chunk = Chunk::new_filled(ChunkPos::new(0, 0, 0), BlockType::Stone);
+        let lone = generate_chunk_meshes(&chunk, &ChunkNeighbors::default(), None);
+        assert_eq!(lone.stats.visible_faces, CHUNK_SIZE * CHUNK_SIZE * 6);
+
+        let solid = Arc::new(ChunkStorage::filled(BlockType::Stone));
+        let neighbors = ChunkNeighbors {
+            storages: std::array::from_fn(|_| Some(solid.clone())),
+            ..Default::default()
+        };
+        let enclosed = generate_chunk_meshes(&chunk, &neighbors, None);
+        assert_eq!(enclosed.stats.visible_faces, 0);
+    }
+
+    #[test]
+    fn occlusion_splits_merged_faces() {
+        let mut chunk = Chunk::new(ChunkPos::new(0, 0, 0));
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk.set_block(x, 0, z, BlockType::Stone);
+            }
+        }
+        chunk.set_block(10, 1, 10, BlockType::Stone);
+
+        let meshes = generate_chunk_meshes(&chunk, &ChunkNeighbors::default(), None);
+
+        // The pillar darkens the slab around its base, so the slab top can no
+        // longer collapse into a single quad.
+        assert!(meshes.stats.quads > 6 + 5);
+
+        let Some(VertexAttributeValues::Float32x4(colors)) =
+            meshes.opaque.attribute(Mesh::ATTRIBUTE_COLOR)
+        else {
+            panic!("chunk mesh is missing vertex colors");
+        };
+        assert!(colors.iter().any(|color| color[3] < 1.0));
+    }
+
+    #[test]
+    fn water_shows_a_side_face_above_lower_water() {
+        let mut chunk = Chunk::new(ChunkPos::new(0, 0, 0));
+        chunk.set_block(4, 4, 4, BlockType::Water);
+        chunk.storage.set_flowing_water(5, 4, 4, 4);
+
+        let meshes = generate_chunk_meshes(&chunk, &ChunkNeighbors::default(), None);
+
+        // Five open faces each, plus the source's side above the flow.
+        assert_eq!(meshes.stats.visible_faces, 11);
+
+        chunk.storage.set_flowing_water(4, 4, 4, 4);
+        let level = generate_chunk_meshes(&chunk, &ChunkNeighbors::default(), None);
+        assert_eq!(level.stats.visible_faces, 10);
+    }
+
+    #[test]
+    fn plants_are_crossed_quads_that_hide_nothing() {
+        let mut chunk = Chunk::new(ChunkPos::new(0, 0, 0));
+        chunk.set_block(4, 4, 4, BlockType::Grass);
+        chunk.set_block(4, 5, 4, BlockType::TallGrass);
+
+        let meshes = generate_chunk_meshes(&chunk, &ChunkNeighbors::default(), None);
+
+        // The grass block keeps its top face, and the plant adds no cube.
+        assert_eq!(meshes.stats.visible_faces, 6);
+        assert_eq!(meshes.opaque.count_vertices(), 6 * 4);
+        assert_eq!(meshes.plants.count_vertices(), 2 * 4);
+        assert_eq!(
+            meshes.plants.indices().map(|indices| indices.len()),
+            Some(12)
+        );
+    }
+}
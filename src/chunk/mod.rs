@@ -1,15 +1,22 @@
 use crate::loading::GameState;
+use crate::net::client::ChunkClient;
 use bevy::prelude::*;
+use material::{ChunkMaterial, ChunkRenderSettings};
 
 pub mod data;
 pub mod far;
+pub mod fluid;
+pub mod light;
 pub mod manager;
+pub mod material;
 pub mod mesh;
+pub mod remote;
 
 #[allow(unused_imports)]
 pub use data::{
-    Chunk, ChunkPayload, ChunkPayloadError, ChunkPos, ChunkStorage, VoxelRun,
-    CHUNK_PAYLOAD_VERSION, CHUNK_SIZE, CHUNK_SIZE_F32,
+    BlockChanged, Chunk, ChunkPayload, ChunkPayloadError, ChunkPayloadHeader, ChunkPos,
+    ChunkStorage, PayloadSection, VoxelRun, BYTE_ID_CHUNK_PAYLOAD_VERSION, CHUNK_PAYLOAD_VERSION,
+    CHUNK_SIZE, CHUNK_SIZE_F32, FALLING_WATER_LEVEL, LEGACY_CHUNK_PAYLOAD_VERSION,
 };
 pub use manager::{ChunkGenerationQueue, ChunkManager};
 
@@ -17,10 +24,24 @@ pub struct ChunkPlugin;
 
 impl Plugin for ChunkPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<ChunkManager>()
+        app.add_plugins(MaterialPlugin::<ChunkMaterial>::default())
+            .init_resource::<ChunkRenderSettings>()
+            .init_resource::<material::ChunkMaterials>()
+            .init_resource::<ChunkManager>()
             .init_resource::<ChunkGenerationQueue>()
             .init_resource::<mesh::ChunkMeshJobs>()
             .init_resource::<far::FarTileTracker>()
+            .init_resource::<light::VoxelLightMap>()
+            .init_resource::<fluid::FluidSimulation>()
+            .add_event::<BlockChanged>()
+            .add_systems(Update, material::apply_chunk_render_settings)
+            // Connected to a server, water flows there and arrives with the chunks.
+            .add_systems(
+                FixedUpdate,
+                fluid::update_fluids.run_if(
+                    in_state(GameState::Playing).and_then(not(resource_exists::<ChunkClient>)),
+                ),
+            )
             // World generation systems during loading
             .add_systems(
                 Update,
@@ -31,6 +52,7 @@ impl Plugin for ChunkPlugin {
                     manager::poll_chunk_tasks,
                     manager::sync_dirty_chunks_to_store,
                     manager::collect_chunk_payloads,
+                    light::update_voxel_light,
                     mesh::queue_chunk_mesh_builds,
                     mesh::apply_chunk_mesh_results,
                     manager::log_chunk_streaming_metrics,
@@ -49,6 +71,7 @@ impl Plugin for ChunkPlugin {
                     manager::despawn_far_chunks,
                     manager::sync_dirty_chunks_to_store,
                     manager::collect_chunk_payloads,
+                    light::update_voxel_light,
                     mesh::queue_chunk_mesh_builds,
                     mesh::apply_chunk_mesh_results,
                     manager::log_chunk_streaming_metrics,
@@ -57,5 +80,9 @@ impl Plugin for ChunkPlugin {
                     .chain()
                     .run_if(in_state(GameState::Playing)),
             );
+
+        if let Some(addr) = remote::server_addr_from_env() {
+            app.add_plugins(remote::RemoteChunkPlugin { addr });
+        }
     }
 }
@@ -0,0 +1,416 @@
+use super::light::VoxelLightMap;
+use super::manager::{ChunkGenerationQueue, ChunkManager};
+use super::{BlockChanged, Chunk, ChunkPos, ChunkStorage, CHUNK_SIZE};
+use crate::block::BlockType;
+use crate::camera::PlayerCamera;
+use crate::inventory::Hotbar;
+use crate::loading::LoadingProgress;
+use crate::net::client::ChunkClient;
+use crate::net::protocol::{BlockDelta, ClientMessage, EditRejection, ServerMessage};
+use crate::planet::altitude_system::AltitudeRenderSystem;
+use crate::player::{self, local_player_name, ServerPlayerMessage};
+use crate::world::persistence::{PersistenceConfig, PersistenceHandler};
+use crate::world::region::RegionChunkPersistence;
+use crate::world::{ChunkPayloadReady, PlanetChunkStore};
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+pub const SERVER_ADDR_ENV: &str = "FORGE_SERVER_ADDR";
+
+const POSITION_UPDATE_INTERVAL_SECONDS: f32 = 0.1;
+const POSITION_UPDATE_MIN_DISTANCE: f32 = 0.25;
+const POSITION_UPDATE_MIN_TURN: f32 = 0.05;
+const ROLLBACK_FLASH_SECONDS: f32 = 0.6;
+
+/// Blocks whose predicted edit the server turned down, flashed so the
+/// player can see what was undone.
+#[derive(Resource, Default)]
+pub struct EditRollbacks {
+    flashes: Vec<(IVec3, f32)>,
+}
+
+pub fn server_addr_from_env() -> Option<String> {
+    std::env::var(SERVER_ADDR_ENV)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Streams chunks from a `forge_server` instead of baking them locally. The
+/// generation queue keeps deciding what is needed and in which order;
+/// `spawn_chunk_tasks` turns queued chunks into requests while a
+/// `ChunkClient` is present. If the server can't be reached, or the
+/// connection drops, outstanding chunks go back to local baking.
+pub struct RemoteChunkPlugin {
+    pub addr: String,
+}
+
+impl Plugin for RemoteChunkPlugin {
+    fn build(&self, app: &mut App) {
+        let client = match ChunkClient::connect(self.addr.as_str(), &local_player_name()) {
+            Ok(client) => client,
+            Err(error) => {
+                warn!(
+                    "Could not reach chunk server at {}: {}. Baking chunks locally.",
+                    self.addr, error
+                );
+                return;
+            }
+        };
+
+        info!("Streaming chunks from server at {}", self.addr);
+        app.insert_resource(client)
+            .init_resource::<EditRollbacks>()
+            .add_event::<ServerPlayerMessage>()
+            .add_systems(Startup, disable_local_persistence)
+            .add_systems(
+                Update,
+                (
+                    (send_viewer_position, receive_server_chunks)
+                        .run_if(resource_exists::<ChunkClient>),
+                    player::apply_remote_player_messages,
+                    player::interpolate_remote_players,
+                )
+                    .chain(),
+            )
+            .add_systems(Update, draw_edit_rollbacks);
+    }
+}
+
+/// The server owns persistence; a local copy would only go stale.
+fn disable_local_persistence(mut commands: Commands, config: Option<ResMut<PersistenceConfig>>) {
+    commands.remove_resource::<PersistenceHandler<RegionChunkPersistence>>();
+    if let Some(mut config) = config {
+        config.enabled = false;
+    }
+}
+
+fn send_viewer_position(
+    client: Res<ChunkClient>,
+    camera_query: Query<&Transform, With<PlayerCamera>>,
+    altitude_system: Res<AltitudeRenderSystem>,
+    time: Res<Time>,
+    mut last_sent: Local<Option<(Vec3, f32, i32)>>,
+    mut accumulator: Local<f32>,
+) {
+    *accumulator += time.delta_seconds();
+    if *accumulator < POSITION_UPDATE_INTERVAL_SECONDS {
+        return;
+    }
+    *accumulator = 0.0;
+
+    let Ok(transform) = camera_query.get_single() else {
+        return;
+    };
+
+    let position = transform.translation;
+    let (yaw, _, _) = transform.rotation.to_euler(EulerRot::YXZ);
+    let view_distance = altitude_system.render_distance as i32;
+    let unchanged = last_sent.is_some_and(|(last_position, last_yaw, last_distance)| {
+        last_distance == view_distance
+            && last_position.distance(position) < POSITION_UPDATE_MIN_DISTANCE
+            && (last_yaw - yaw).abs() < POSITION_UPDATE_MIN_TURN
+    });
+    if unchanged {
+        return;
+    }
+
+    client.send(ClientMessage::PlayerMoved {
+        position,
+        yaw,
+        view_distance,
+    });
+    *last_sent = Some((position, yaw, view_distance));
+}
+
+#[allow(clippy::too_many_arguments)]
+fn receive_server_chunks(
+    mut commands: Commands,
+    mut client: ResMut<ChunkClient>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut chunk_queue: ResMut<ChunkGenerationQueue>,
+    mut chunk_store: ResMut<PlanetChunkStore>,
+    mut loading_progress: ResMut<LoadingProgress>,
+    mut payload_events: EventWriter<ChunkPayloadReady>,
+    mut block_events: EventWriter<BlockChanged>,
+    mut player_events: EventWriter<ServerPlayerMessage>,
+    mut rollbacks: ResMut<EditRollbacks>,
+    mut hotbar: Option<ResMut<Hotbar>>,
+    mut light_map: ResMut<VoxelLightMap>,
+    mut chunks: Query<(Entity, &mut Chunk)>,
+) {
+    let messages = client.poll();
+    let mut entities: HashMap<ChunkPos, Entity> = if messages.is_empty() {
+        HashMap::new()
+    } else {
+        chunks
+            .iter()
+            .map(|(entity, chunk)| (chunk.position, entity))
+            .collect()
+    };
+    let mut received = Vec::new();
+
+    for message in messages {
+        match message {
+            ServerMessage::Welcome { player_id, .. } => {
+                info!("Joined chunk server as player {}", player_id);
+                player_events.send(ServerPlayerMessage(message));
+            }
+            ServerMessage::PlayerJoined { .. }
+            | ServerMessage::PlayerLeft { .. }
+            | ServerMessage::PlayerStates(_) => {
+                player_events.send(ServerPlayerMessage(message));
+            }
+            ServerMessage::Rejected { reason } => {
+                error!("Chunk server refused the connection: {}", reason);
+            }
+            ServerMessage::ChunkPayload {
+                position,
+                revision,
+                bytes,
+            } => {
+                let mut storage = match ChunkStorage::from_bytes(&bytes) {
+                    Ok(storage) => storage,
+                    Err(error) => {
+                        warn!(
+                            "Discarding undecodable payload for chunk {:?}: {:?}. Requesting it again.",
+                            position, error
+                        );
+                        if let Some(priority) = client.complete(&position) {
+                            chunk_queue.requeue(position, priority);
+                        }
+                        continue;
+                    }
+                };
+
+                client.complete(&position);
+                received.push(position);
+                if chunk_queue.contains(&position) {
+                    chunk_queue.mark_completed(&position);
+                    loading_progress.chunks_generated += 1;
+                }
+
+                let stale = client
+                    .server_revision(&position)
+                    .is_some_and(|current| current >= revision)
+                    && entities.contains_key(&position);
+                if stale {
+                    continue;
+                }
+
+                // Keep showing edits the server hasn't answered yet.
+                for edit in client.pending_edits() {
+                    let (chunk, [x, y, z]) = split_block_position(edit.position);
+                    if chunk == position {
+                        storage.set(x, y, z, edit.block);
+                    }
+                }
+                client.set_server_revision(position, revision);
+
+                let storage_arc =
+                    chunk_store.insert_with_revision(position, storage.clone(), revision);
+                payload_events.send(ChunkPayloadReady {
+                    position,
+                    revision,
+                    storage: storage_arc,
+                });
+
+                match entities
+                    .get(&position)
+                    .and_then(|entity| chunks.get_mut(*entity).ok())
+                {
+                    Some((_, mut chunk)) => {
+                        chunk.storage = storage;
+                        chunk.dirty = true;
+                    }
+                    None => {
+                        let entity = commands
+                            .spawn((
+                                Chunk::from_storage(position, storage),
+                                position,
+                                TransformBundle::from_transform(Transform::from_translation(
+                                    position.to_world_pos(),
+                                )),
+                                VisibilityBundle::default(),
+                            ))
+                            .id();
+                        entities.insert(position, entity);
+                        chunk_manager.loaded_chunks.insert(position);
+                    }
+                }
+            }
+            ServerMessage::BlockDelta(delta) => {
+                let chunk = entities
+                    .get(&delta.position)
+                    .and_then(|entity| chunks.get_mut(*entity).ok())
+                    .map(|(_, chunk)| chunk);
+                apply_block_delta(
+                    &delta,
+                    chunk,
+                    &mut client,
+                    &mut chunk_store,
+                    &mut block_events,
+                );
+            }
+            ServerMessage::UnloadChunks { positions } => {
+                for position in positions {
+                    if client.complete(&position).is_some() {
+                        chunk_queue.mark_completed(&position);
+                    }
+                    client.forget_chunk(&position);
+                    light_map.remove(&position);
+                    if let Some(entity) = entities.remove(&position) {
+                        commands.entity(entity).despawn_recursive();
+                        chunk_manager.loaded_chunks.remove(&position);
+                    }
+                }
+            }
+            ServerMessage::EditAccepted { sequence } => {
+                client.resolve_edit(sequence);
+            }
+            ServerMessage::EditRejected {
+                sequence,
+                position,
+                block,
+                reason,
+            } => {
+                let Some(edit) = client.resolve_edit(sequence) else {
+                    continue;
+                };
+                info!(
+                    "Server rejected edit at {:?} ({:?}); rolling back",
+                    position, reason
+                );
+
+                let (chunk_pos, [x, y, z]) = split_block_position(position);
+                let chunk = entities
+                    .get(&chunk_pos)
+                    .and_then(|entity| chunks.get_mut(*entity).ok())
+                    .map(|(_, chunk)| chunk);
+                if let Some(mut chunk) = chunk {
+                    // A chunk the server doesn't have can't tell us what the
+                    // block should be, so put back what was there.
+                    let restored = if reason == EditRejection::NotLoaded {
+                        edit.previous
+                    } else {
+                        block
+                    };
+                    if chunk.get_block(x, y, z) != restored {
+                        chunk.set_block(x, y, z, restored);
+                        block_events.send(BlockChanged {
+                            position,
+                            block: restored,
+                        });
+                    }
+                }
+
+                // A refused placement gives the block back.
+                if edit.block != BlockType::Air {
+                    if let Some(hotbar) = hotbar.as_mut() {
+                        hotbar.add_item(edit.block, 1);
+                    }
+                }
+                rollbacks.flashes.push((position, ROLLBACK_FLASH_SECONDS));
+            }
+        }
+    }
+
+    if !received.is_empty() {
+        client.send(ClientMessage::AckChunks {
+            positions: received,
+        });
+    }
+
+    if !client.is_connected() {
+        warn!("Lost connection to the chunk server. Baking chunks locally.");
+        for (position, priority) in client.take_requests() {
+            chunk_queue.requeue(position, priority);
+        }
+        commands.remove_resource::<ChunkClient>();
+    }
+}
+
+/// Apply a delta on top of the revision it was built from. A delta for a
+/// revision we already have is ignored; one that skips ahead means we missed
+/// an update, so the whole chunk is fetched again.
+fn apply_block_delta(
+    delta: &BlockDelta,
+    chunk: Option<Mut<Chunk>>,
+    client: &mut ChunkClient,
+    chunk_store: &mut PlanetChunkStore,
+    block_events: &mut EventWriter<BlockChanged>,
+) {
+    let Some(current) = client.server_revision(&delta.position) else {
+        return;
+    };
+    if current >= delta.revision {
+        return;
+    }
+    if current.wrapping_add(1) != delta.revision {
+        client.request_chunks(vec![(delta.position, 0)]);
+        return;
+    }
+    let Some(stored) = chunk_store.get(&delta.position) else {
+        return;
+    };
+
+    let origin =
+        IVec3::new(delta.position.x, delta.position.y, delta.position.z) * CHUNK_SIZE as i32;
+    let storage = match chunk {
+        Some(mut chunk) => {
+            for edit in &delta.changes {
+                let [x, y, z] = edit.local.map(usize::from);
+                chunk.set_block(x, y, z, edit.block);
+            }
+            chunk.storage.clone()
+        }
+        None => {
+            let mut storage = stored.as_ref().clone();
+            for edit in &delta.changes {
+                let [x, y, z] = edit.local.map(usize::from);
+                storage.set(x, y, z, edit.block);
+            }
+            storage
+        }
+    };
+
+    for edit in &delta.changes {
+        let [x, y, z] = edit.local.map(i32::from);
+        block_events.send(BlockChanged {
+            position: origin + IVec3::new(x, y, z),
+            block: edit.block,
+        });
+    }
+    chunk_store.insert_with_revision(delta.position, storage, delta.revision);
+    client.set_server_revision(delta.position, delta.revision);
+}
+
+fn split_block_position(position: IVec3) -> (ChunkPos, [usize; 3]) {
+    let chunk = ChunkPos::from_world_pos(position.as_vec3());
+    let local = position.rem_euclid(IVec3::splat(CHUNK_SIZE as i32));
+    (
+        chunk,
+        [local.x as usize, local.y as usize, local.z as usize],
+    )
+}
+
+fn draw_edit_rollbacks(mut gizmos: Gizmos, mut rollbacks: ResMut<EditRollbacks>, time: Res<Time>) {
+    if rollbacks.flashes.is_empty() {
+        return;
+    }
+
+    let delta = time.delta_seconds();
+    rollbacks.flashes.retain_mut(|(position, remaining)| {
+        *remaining -= delta;
+        if *remaining <= 0.0 {
+            return false;
+        }
+        let alpha = *remaining / ROLLBACK_FLASH_SECONDS;
+        gizmos.cuboid(
+            Transform::from_translation(position.as_vec3() + Vec3::splat(0.5))
+                .with_scale(Vec3::splat(1.02)),
+            Color::srgba(1.0, 0.2, 0.2, alpha),
+        );
+        true
+    });
+}
@@ -1,12 +1,13 @@
 use crate::block::BlockType;
 use crate::camera::PlayerCamera;
-use crate::chunk::{Chunk, ChunkPos, CHUNK_SIZE, CHUNK_SIZE_F32};
+use crate::chunk::{BlockChanged, Chunk, ChunkPos, CHUNK_SIZE, CHUNK_SIZE_F32};
 use crate::inventory::Hotbar;
 use crate::items;
+use crate::net::client::ChunkClient;
 use crate::tools::Tool;
 use bevy::prelude::*;
 
-const REACH_DISTANCE: f32 = 8.0;
+pub const REACH_DISTANCE: f32 = 8.0;
 
 #[derive(Resource)]
 pub struct SelectedBlock {
@@ -51,6 +52,8 @@ pub fn block_interaction_system(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     texture_atlas: Option<Res<crate::texture::BlockTextureAtlas>>,
+    mut block_events: EventWriter<BlockChanged>,
+    mut remote: Option<ResMut<ChunkClient>>,
 ) {
     let Ok(camera_transform) = camera_query.get_single() else {
         return;
@@ -89,20 +92,27 @@ pub fn block_interaction_system(
 
                     // Check if extraction complete
                     if extraction_state.progress >= extraction_state.total_time {
-                        // Spawn dropped item
+                        // Spawn what the block drops, if anything
                         let world_pos = hit_pos.as_vec3() + Vec3::splat(0.5);
-                        items::spawn_dropped_item(
-                            &mut commands,
-                            block_type,
-                            world_pos,
-                            &mut meshes,
-                            &mut materials,
-                            texture_atlas.as_deref(),
-                            &time,
-                        );
-
-                        // Remove the block
-                        remove_block(hit_pos, &mut chunk_query);
+                        let drop = block_type.drops();
+                        if drop != BlockType::Air {
+                            items::spawn_dropped_item(
+                                &mut commands,
+                                drop,
+                                world_pos,
+                                &mut meshes,
+                                &mut materials,
+                                texture_atlas.as_deref(),
+                                &time,
+                            );
+                        }
+
+                        // Remove the block, and let the server know when playing on one
+                        if remove_block(hit_pos, &mut chunk_query, &mut block_events) {
+                            if let Some(remote) = remote.as_mut() {
+                                remote.submit_edit(hit_pos, block_type, BlockType::Air);
+                            }
+                        }
 
                         // Reset extraction state
                         extraction_state.extracting_pos = None;
@@ -126,8 +136,11 @@ pub fn block_interaction_system(
                 if block_type != BlockType::Air {
                     let place_pos = hit_pos + hit_normal.as_ivec3();
                     // Try to place block and use item from inventory
-                    if place_block(place_pos, block_type, &mut chunk_query) {
+                    if place_block(place_pos, block_type, &mut chunk_query, &mut block_events) {
                         hotbar.use_selected_item();
+                        if let Some(remote) = remote.as_mut() {
+                            remote.submit_edit(place_pos, BlockType::Air, block_type);
+                        }
                     }
                 }
             }
@@ -630,9 +643,9 @@ fn raycast_voxel(
     let mut steps = 0;
 
     while distance_traveled < max_distance && steps < max_steps {
-        // Check current voxel for solid block
+        // Check current voxel for a block that can be targeted (solid blocks and plants)
         if let Some(block) = get_block_at_world_pos(current_voxel, chunk_query) {
-            if block.is_solid() {
+            if block.is_visible() && !block.is_liquid() {
                 let normal = calculate_hit_normal(previous_voxel, current_voxel);
                 return Some((current_voxel, normal));
             }
@@ -741,7 +754,11 @@ fn get_block_at_world_pos_readonly(
     None
 }
 
-fn remove_block(world_pos: IVec3, chunk_query: &mut Query<(&mut Chunk, &ChunkPos)>) {
+fn remove_block(
+    world_pos: IVec3,
+    chunk_query: &mut Query<(&mut Chunk, &ChunkPos)>,
+    block_events: &mut EventWriter<BlockChanged>,
+) -> bool {
     let chunk_pos = ChunkPos::new(
         (world_pos.x as f32 / CHUNK_SIZE_F32).floor() as i32,
         (world_pos.y as f32 / CHUNK_SIZE_F32).floor() as i32,
@@ -759,16 +776,23 @@ fn remove_block(world_pos: IVec3, chunk_query: &mut Query<(&mut Chunk, &ChunkPos
             if block.is_breakable() {
                 chunk.set_block(local_x, local_y, local_z, BlockType::Air);
                 chunk.dirty = true; // Mark chunk for mesh regeneration
+                block_events.send(BlockChanged {
+                    position: world_pos,
+                    block: BlockType::Air,
+                });
+                return true;
             }
-            return;
+            return false;
         }
     }
+    false
 }
 
 fn place_block(
     world_pos: IVec3,
     block_type: BlockType,
     chunk_query: &mut Query<(&mut Chunk, &ChunkPos)>,
+    block_events: &mut EventWriter<BlockChanged>,
 ) -> bool {
     let chunk_pos = ChunkPos::new(
         (world_pos.x as f32 / CHUNK_SIZE_F32).floor() as i32,
@@ -786,6 +810,10 @@ fn place_block(
             if chunk.get_block(local_x, local_y, local_z) == BlockType::Air {
                 chunk.set_block(local_x, local_y, local_z, block_type);
                 chunk.dirty = true; // Mark chunk for mesh regeneration
+                block_events.send(BlockChanged {
+                    position: world_pos,
+                    block: block_type,
+                });
                 return true;
             }
             return false;
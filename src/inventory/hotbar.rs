@@ -1,4 +1,5 @@
 use crate::block::BlockType;
+use crate::texture::BlockFace;
 use bevy::prelude::*;
 
 const HOTBAR_SLOTS: usize = 9;
@@ -274,6 +275,20 @@ pub fn hotbar_selection_system(
     // This is a placeholder - actual mouse wheel implementation would use events
 }
 
+/// Icon for a block: the texture its registry definition puts on the top
+/// face, preferring that folder's `top.png` over `all.png`. Blocks whose
+/// texture folder is missing show the stone placeholder.
+fn icon_texture_path(block_type: BlockType) -> String {
+    let folder = block_type.texture_name(BlockFace::Top);
+    for file in ["top", "all"] {
+        let path = format!("textures/blocks/{}/{}.png", folder, file);
+        if std::path::Path::new("assets").join(&path).exists() {
+            return path;
+        }
+    }
+    "textures/blocks/stone/all.png".to_string()
+}
+
 pub fn update_hotbar_ui(
     hotbar: Res<Hotbar>,
     mut slot_query: Query<(&HotbarSlot, &mut BackgroundColor, &Children), Without<HotbarSelector>>,
@@ -307,32 +322,13 @@ pub fn update_hotbar_ui(
                     // Show the block icon
                     *visibility = Visibility::Visible;
 
-                    // Load the appropriate texture for this block type
-                    let block_name = format!("{:?}", block_type).to_lowercase();
-
                     // Skip air blocks
-                    if block_name == "air" {
+                    if block_type == BlockType::Air {
                         *visibility = Visibility::Hidden;
                         continue;
                     }
 
-                    // Use "all.png" for uniform blocks or specific face textures
-                    let texture_path = match block_name.as_str() {
-                        "grass" => "textures/blocks/grass/top.png".to_string(),
-                        "dirt" | "stone" | "sand" | "cobblestone" | "bedrock" | "planks"
-                        | "wood" | "leaves" | "water" => {
-                            format!("textures/blocks/{}/all.png", block_name)
-                        }
-                        "snow" | "ice" | "packedice" => {
-                            // Use a white/blue tinted stone texture as fallback for ice blocks
-                            "textures/blocks/stone/all.png".to_string()
-                        }
-                        _ => {
-                            // Fallback to a default texture
-                            "textures/blocks/stone/all.png".to_string()
-                        }
-                    };
-                    ui_image.texture = asset_server.load(&texture_path);
+                    ui_image.texture = asset_server.load(icon_texture_path(block_type));
                 } else {
                     // Hide icon for empty slots
                     *visibility = Visibility::Hidden;
@@ -2,6 +2,8 @@ use crate::block::BlockType;
 use crate::camera::PlayerCamera;
 use crate::chunk::{Chunk, ChunkPos, CHUNK_SIZE, CHUNK_SIZE_F32};
 use crate::inventory::Hotbar;
+use crate::player::Player;
+use crate::simulation::InterpolatedTransform;
 use bevy::prelude::*;
 
 const ITEM_SIZE: f32 = 0.25; // Smaller size, about 1/4 of a full block
@@ -93,11 +95,12 @@ pub fn spawn_dropped_item(
             ..default()
         },
         item,
+        InterpolatedTransform::new(transform.translation),
     ));
 }
 
-// Helper function to create a textured cube mesh for dropped items
-fn create_textured_cube_mesh(
+// Helper function to create a textured unit cube mesh for dropped items and falling blocks
+pub fn create_textured_cube_mesh(
     block_type: BlockType,
     texture_atlas: Option<&crate::texture::BlockTextureAtlas>,
 ) -> Mesh {
@@ -109,9 +112,6 @@ fn create_textured_cube_mesh(
     let mut uvs = Vec::new();
     let mut indices = Vec::new();
 
-    // Get block type name for texture lookup
-    let block_name = format!("{:?}", block_type).to_lowercase();
-
     // Define the 6 faces of a cube
     let faces = [
         // Top face (Y+)
@@ -194,7 +194,11 @@ fn create_textured_cube_mesh(
 
         // Get UVs for this face from the texture atlas
         if let Some(atlas) = texture_atlas {
-            let (uv_min, uv_max) = atlas.get_uv(&block_name, *face_type, BlockState::Normal);
+            let (uv_min, uv_max) = atlas.get_uv(
+                block_type.texture_name(*face_type),
+                *face_type,
+                BlockState::Normal,
+            );
 
             // Add UVs based on face orientation
             match face_type {
@@ -283,17 +287,11 @@ fn is_solid_block_at(pos: Vec3, chunk_query: &Query<(&Chunk, &ChunkPos)>) -> boo
 }
 
 pub fn update_dropped_items(
-    mut items: Query<(
-        Entity,
-        &mut DroppedItem,
-        &mut Transform,
-        &Handle<StandardMaterial>,
-    )>,
+    mut items: Query<(Entity, &mut DroppedItem, &mut Transform)>,
     chunk_query: Query<(&Chunk, &ChunkPos)>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
     time: Res<Time>,
 ) {
-    for (_entity, mut item, mut transform, material_handle) in items.iter_mut() {
+    for (_entity, mut item, mut transform) in items.iter_mut() {
         let dt = time.delta_seconds();
         let start_pos = transform.translation;
 
@@ -339,8 +337,16 @@ pub fn update_dropped_items(
                 item.velocity = Vec3::ZERO;
             }
         }
+    }
+}
 
-        // Update material with pulsing glow effect
+/// Pulse the glow of dropped items. Runs every frame, unlike their movement.
+pub fn pulse_dropped_items(
+    items: Query<(&DroppedItem, &Handle<StandardMaterial>)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    time: Res<Time>,
+) {
+    for (item, material_handle) in items.iter() {
         if let Some(material) = materials.get_mut(material_handle) {
             let elapsed = time.elapsed_seconds() - item.spawn_time;
             let pulse = (elapsed * 3.0).sin() * 0.25 + 0.75; // Faster, more noticeable pulse between 0.5 and 1.0
@@ -416,33 +422,87 @@ pub fn apply_item_collisions(
     }
 }
 
+/// Items go to the local player when it is the nearest player in reach.
+/// Dropped items only exist on the client that spawned them, so an item a
+/// remote avatar is closer to is left where it lies for now.
 pub fn collect_items(
     mut commands: Commands,
     items: Query<(Entity, &DroppedItem, &Transform)>,
-    player: Query<&Transform, With<PlayerCamera>>,
+    players: Query<(&Transform, Has<PlayerCamera>), With<Player>>,
     mut hotbar: ResMut<Hotbar>,
 ) {
-    let Ok(player_transform) = player.get_single() else {
+    if players.is_empty() {
         return;
-    };
+    }
 
     for (entity, item, item_transform) in items.iter() {
-        let distance = player_transform
-            .translation
-            .distance(item_transform.translation);
-
-        if distance < COLLECTION_RADIUS {
-            // Try to add to hotbar using the new stacking system
-            let remaining = hotbar.add_item(item.block_type, 1);
-
-            if remaining == 0 {
-                // Successfully added to inventory
-                commands.entity(entity).despawn();
-                info!("Collected {:?}", item.block_type);
-            } else {
-                // Inventory full
-                info!("Inventory full! Cannot collect {:?}", item.block_type);
+        let nearest = players
+            .iter()
+            .map(|(transform, is_local)| {
+                (
+                    transform.translation.distance(item_transform.translation),
+                    is_local,
+                )
+            })
+            .filter(|(distance, _)| *distance < COLLECTION_RADIUS)
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+
+        match nearest {
+            None | Some((_, false)) => {}
+            Some((_, true)) => {
+                // Try to add to hotbar using the new stacking system
+                let remaining = hotbar.add_item(item.block_type, 1);
+
+                if remaining == 0 {
+                    // Successfully added to inventory
+                    commands.entity(entity).despawn();
+                    info!("Collected {:?}", item.block_type);
+                } else {
+                    // Inventory full
+                    info!("Inventory full! Cannot collect {:?}", item.block_type);
+                }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn items_near_remote_avatars_are_left_alone() {
+        let mut app = App::new();
+        app.init_resource::<Hotbar>()
+            .add_systems(Update, collect_items);
+        app.world_mut().spawn((
+            Player::new(0, "local"),
+            PlayerCamera,
+            Transform::from_xyz(1.5, 0.0, 0.0),
+        ));
+        app.world_mut()
+            .spawn((Player::new(7, "remote"), Transform::default()));
+        let item = || DroppedItem {
+            block_type: BlockType::Dirt,
+            velocity: Vec3::ZERO,
+            spawn_time: 0.0,
+        };
+        let beside_remote = app
+            .world_mut()
+            .spawn((item(), Transform::from_xyz(-0.5, 0.0, 0.0)))
+            .id();
+        let beside_local = app
+            .world_mut()
+            .spawn((item(), Transform::from_xyz(1.5, 0.0, 0.5)))
+            .id();
+
+        app.update();
+
+        // The remote player can't collect it here, so it stays for now.
+        assert!(app.world().get_entity(beside_remote).is_some());
+        assert!(app.world().get_entity(beside_local).is_none());
+        let hotbar = app.world().resource::<Hotbar>();
+        assert_eq!(hotbar.slots[0].block_type, Some(BlockType::Dirt));
+        assert_eq!(hotbar.slots[0].quantity, 1);
+    }
+}
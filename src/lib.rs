@@ -8,10 +8,15 @@ pub mod interaction;
 pub mod inventory;
 pub mod items;
 pub mod loading;
+pub mod net;
 pub mod particles;
 pub mod physics;
 pub mod planet;
+pub mod player;
 pub mod render;
+pub mod replay;
+pub mod server;
+pub mod simulation;
 pub mod texture;
 pub mod tools;
 pub mod ui;
@@ -11,17 +11,21 @@ mod interaction;
 mod inventory;
 mod items;
 mod loading;
+mod net;
 mod particles;
 mod physics;
 mod planet;
+mod player;
 mod render;
+mod replay;
+mod simulation;
 mod texture;
 mod tools;
 mod ui;
 mod world;
 
 use block::BlockPlugin;
-use camera::CameraPlugin;
+use camera::{CameraLookSet, CameraPlugin};
 use celestial::CelestialPlugin;
 use chunk::ChunkPlugin;
 use fog::FogPlugin;
@@ -31,6 +35,8 @@ use loading::{GameState, LoadingPlugin};
 use physics::PhysicsPlugin;
 use planet::PlanetPlugin;
 use render::RenderPlugin;
+use replay::ReplayPlugin;
+use simulation::SimulationPlugin;
 use texture::TexturePlugin;
 use ui::UIPlugin;
 use world::WorldPlugin;
@@ -61,23 +67,33 @@ fn main() {
             FogPlugin,
             UIPlugin,
             TexturePlugin,
+            ReplayPlugin, // Records the session when FORGE_RECORD is set
         ))
+        .add_plugins(SimulationPlugin) // Fixed-timestep physics, FORGE_TICK_RATE per second
         .init_resource::<interaction::SelectedBlock>()
         .init_resource::<interaction::BlockExtractionState>()
         .add_systems(OnEnter(GameState::Playing), setup)
         .add_systems(
             Update,
             (
-                interaction::block_interaction_system,
+                interaction::block_interaction_system.after(CameraLookSet),
                 interaction::update_extraction_visual,
                 interaction::draw_selection_box,
-                items::update_dropped_items,
-                items::apply_item_collisions, // Add collision system after physics update
+                items::pulse_dropped_items,
                 items::collect_items,
                 particles::update_particles,
             )
                 .run_if(in_state(GameState::Playing)),
         )
+        .add_systems(
+            FixedUpdate,
+            (
+                items::update_dropped_items,
+                items::apply_item_collisions, // Add collision system after physics update
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        )
         .run();
 }
 
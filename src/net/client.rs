@@ -0,0 +1,204 @@
+use super::protocol::{BlockEditRequest, ClientMessage, ServerMessage, PROTOCOL_VERSION};
+use super::transport::{spawn_reader, spawn_writer};
+use crate::block::{BlockRegistry, BlockType};
+use crate::chunk::ChunkPos;
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Mutex;
+
+/// Requests allowed on the wire at once, so a fast-moving player doesn't
+/// queue up chunks the server will have to unload again.
+pub const MAX_CHUNK_REQUESTS_IN_FLIGHT: usize = 192;
+/// Messages waiting for the socket before `send` waits for it to drain.
+const OUTGOING_QUEUE_CAPACITY: usize = 256;
+
+/// A block edit applied locally and not yet answered by the server.
+#[derive(Clone, Copy, Debug)]
+pub struct PendingEdit {
+    pub sequence: u32,
+    pub position: IVec3,
+    /// What the block was before the edit, to roll back to.
+    pub previous: BlockType,
+    pub block: BlockType,
+}
+
+/// Client end of a server connection. Chunks requested through it are
+/// remembered with their queue priority until the server answers, so they can
+/// be handed back to local baking if the connection drops.
+///
+/// The client keeps the server's revision of every chunk it holds separately
+/// from `PlanetChunkStore`, whose revisions also move with edits the client
+/// predicted before the server confirmed them.
+#[derive(Resource)]
+pub struct ChunkClient {
+    outgoing: SyncSender<ClientMessage>,
+    incoming: Mutex<Receiver<Option<ServerMessage>>>,
+    player_id: Option<u64>,
+    requested: HashMap<ChunkPos, i32>,
+    revisions: HashMap<ChunkPos, u32>,
+    pending_edits: Vec<PendingEdit>,
+    next_sequence: u32,
+    connected: bool,
+}
+
+impl ChunkClient {
+    /// Connect and send the handshake. The server's answer arrives through
+    /// `poll` like any other message.
+    pub fn connect(addr: impl ToSocketAddrs, name: &str) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+
+        let (incoming_sender, incoming) = mpsc::channel();
+        spawn_reader::<ServerMessage>(
+            stream.try_clone()?,
+            "chunk-client-read".into(),
+            move |message| incoming_sender.send(message).is_ok(),
+        )?;
+        let outgoing = spawn_writer(stream, "chunk-client-write".into(), OUTGOING_QUEUE_CAPACITY)?;
+
+        let client = Self {
+            outgoing,
+            incoming: Mutex::new(incoming),
+            player_id: None,
+            requested: HashMap::new(),
+            revisions: HashMap::new(),
+            pending_edits: Vec::new(),
+            next_sequence: 1,
+            connected: true,
+        };
+        client.send(ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+            name: name.to_string(),
+            blocks: BlockRegistry::global().fingerprint(),
+        });
+        Ok(client)
+    }
+
+    #[allow(dead_code)]
+    pub fn player_id(&self) -> Option<u64> {
+        self.player_id
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    pub fn send(&self, message: ClientMessage) -> bool {
+        self.outgoing.send(message).is_ok()
+    }
+
+    /// Messages received since the last call. Once the connection is gone
+    /// this returns nothing and `is_connected` turns false.
+    pub fn poll(&mut self) -> Vec<ServerMessage> {
+        let mut messages = Vec::new();
+        let Ok(incoming) = self.incoming.lock() else {
+            return messages;
+        };
+        for message in incoming.try_iter() {
+            let Some(message) = message else {
+                self.connected = false;
+                continue;
+            };
+            if let ServerMessage::Welcome {
+                player_id, blocks, ..
+            } = message
+            {
+                // Chunks from it would decode to the wrong blocks, or not at all.
+                if blocks != BlockRegistry::global().fingerprint() {
+                    error!("Chunk server has different block definitions; disconnecting");
+                    self.connected = false;
+                    return Vec::new();
+                }
+                self.player_id = Some(player_id);
+            }
+            messages.push(message);
+        }
+        messages
+    }
+
+    pub fn request_capacity(&self) -> usize {
+        MAX_CHUNK_REQUESTS_IN_FLIGHT.saturating_sub(self.requested.len())
+    }
+
+    pub fn requests_in_flight(&self) -> usize {
+        self.requested.len()
+    }
+
+    pub fn request_chunks(&mut self, chunks: Vec<(ChunkPos, i32)>) {
+        let positions = chunks.iter().map(|(position, _)| *position).collect();
+        self.requested.extend(chunks);
+        self.send(ClientMessage::RequestChunks { positions });
+    }
+
+    /// Forget a request the server has answered, returning its priority.
+    pub fn complete(&mut self, position: &ChunkPos) -> Option<i32> {
+        self.requested.remove(position)
+    }
+
+    /// Every request still waiting on the server.
+    pub fn take_requests(&mut self) -> Vec<(ChunkPos, i32)> {
+        self.requested.drain().collect()
+    }
+
+    pub fn unload_chunks(&mut self, positions: Vec<ChunkPos>) {
+        if positions.is_empty() {
+            return;
+        }
+        for position in &positions {
+            self.requested.remove(position);
+            self.revisions.remove(position);
+        }
+        self.send(ClientMessage::UnloadChunks { positions });
+    }
+
+    /// Server revision of a chunk the client holds.
+    pub fn server_revision(&self, position: &ChunkPos) -> Option<u32> {
+        self.revisions.get(position).copied()
+    }
+
+    pub fn set_server_revision(&mut self, position: ChunkPos, revision: u32) {
+        self.revisions.insert(position, revision);
+    }
+
+    pub fn forget_chunk(&mut self, position: &ChunkPos) {
+        self.revisions.remove(position);
+    }
+
+    /// Send an edit the client has already applied, replacing `previous`
+    /// with `block` at a world block position.
+    pub fn submit_edit(&mut self, position: IVec3, previous: BlockType, block: BlockType) {
+        let sequence = self.next_sequence;
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+        let chunk = ChunkPos::from_world_pos(position.as_vec3());
+        self.send(ClientMessage::EditBlock(BlockEditRequest {
+            sequence,
+            position,
+            expected: previous,
+            block,
+            base_revision: self.server_revision(&chunk).unwrap_or(0),
+        }));
+        self.pending_edits.push(PendingEdit {
+            sequence,
+            position,
+            previous,
+            block,
+        });
+    }
+
+    /// Forget an edit the server has answered, returning it.
+    pub fn resolve_edit(&mut self, sequence: u32) -> Option<PendingEdit> {
+        let index = self
+            .pending_edits
+            .iter()
+            .position(|edit| edit.sequence == sequence)?;
+        Some(self.pending_edits.remove(index))
+    }
+
+    /// Unanswered edits, oldest first.
+    pub fn pending_edits(&self) -> &[PendingEdit] {
+        &self.pending_edits
+    }
+}
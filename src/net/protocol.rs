@@ -0,0 +1,136 @@
+use crate::block::BlockType;
+use crate::chunk::ChunkPos;
+use bevy::math::{IVec3, Vec3};
+use serde::{Deserialize, Serialize};
+
+/// Bumped whenever a message changes shape. `ClientMessage::Hello` and the
+/// first two `ServerMessage` variants must keep their position and leading
+/// fields, only gaining new ones at the end, so an older peer can still read
+/// a newer one's version and tell it why they can't talk.
+pub const PROTOCOL_VERSION: u16 = 6;
+
+#[allow(dead_code)]
+pub const DEFAULT_SERVER_ADDR: &str = "127.0.0.1:25570";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ClientMessage {
+    /// First message on every connection. `blocks` is the client's
+    /// `BlockRegistry::fingerprint`; the server turns away clients whose
+    /// block ids don't match its own.
+    Hello {
+        version: u16,
+        name: String,
+        blocks: u64,
+    },
+    /// Chunks the client wants, most important first. The server answers
+    /// each with a `ChunkPayload`, or with `UnloadChunks` if it won't send it.
+    RequestChunks { positions: Vec<ChunkPos> },
+    /// Chunks the client dropped; the server stops sending updates for them.
+    UnloadChunks { positions: Vec<ChunkPos> },
+    /// Eye position and heading of the player, plus how far it can see.
+    PlayerMoved {
+        position: Vec3,
+        yaw: f32,
+        view_distance: i32,
+    },
+    /// A place or remove the client has already applied locally. The server
+    /// answers with `EditAccepted` or `EditRejected`.
+    EditBlock(BlockEditRequest),
+    /// Chunk payloads the client has received and applied.
+    AckChunks { positions: Vec<ChunkPos> },
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ServerMessage {
+    Welcome {
+        version: u16,
+        player_id: u64,
+        /// The server's `BlockRegistry::fingerprint`.
+        blocks: u64,
+    },
+    Rejected {
+        reason: String,
+    },
+    /// A full chunk encoded with `ChunkStorage::encode_bytes`.
+    ChunkPayload {
+        position: ChunkPos,
+        revision: u32,
+        bytes: Vec<u8>,
+    },
+    BlockDelta(BlockDelta),
+    /// Chunks the client should drop, or requests the server declined.
+    UnloadChunks {
+        positions: Vec<ChunkPos>,
+    },
+    /// The edit is in; its block change follows in a `BlockDelta` like
+    /// everyone else's.
+    EditAccepted {
+        sequence: u32,
+    },
+    /// The edit was refused; `block` is what the server has at `position`.
+    EditRejected {
+        sequence: u32,
+        position: IVec3,
+        block: BlockType,
+        reason: EditRejection,
+    },
+    /// Another player is in the world. Its position follows in `PlayerStates`.
+    PlayerJoined {
+        player_id: u64,
+        name: String,
+    },
+    PlayerLeft {
+        player_id: u64,
+    },
+    /// Latest position of every other player that moved since the last one.
+    PlayerStates(Vec<PlayerState>),
+}
+
+/// The edits that took a chunk from `revision - 1` to `revision`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BlockDelta {
+    pub position: ChunkPos,
+    pub revision: u32,
+    pub changes: Vec<BlockEdit>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockEdit {
+    /// Chunk-local x, y, z.
+    pub local: [u8; 3],
+    pub block: BlockType,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerState {
+    pub player_id: u64,
+    pub position: Vec3,
+    pub yaw: f32,
+}
+
+/// Setting `block` to `Air` removes; anything else places.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockEditRequest {
+    /// Client-chosen id echoed back in the answer.
+    pub sequence: u32,
+    /// World block position.
+    pub position: IVec3,
+    /// The block the client saw there before editing.
+    pub expected: BlockType,
+    pub block: BlockType,
+    /// Server revision of the chunk the client made the edit against.
+    pub base_revision: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EditRejection {
+    /// The block is further from the player than they can reach.
+    OutOfReach,
+    /// The server doesn't have the chunk loaded.
+    NotLoaded,
+    /// Removing a block that can't be broken, or placing into a solid block.
+    NotAllowed,
+    /// The block changed since the revision the client edited against.
+    Stale,
+}
@@ -0,0 +1,89 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::mpsc::{self, SyncSender};
+use std::thread;
+
+/// Largest frame either side will accept; a full chunk payload is well under
+/// this even uncompressed.
+pub const MAX_FRAME_LEN: usize = 8 * 1024 * 1024;
+
+/// Frames are a little-endian `u32` length followed by the bincode body.
+pub fn write_frame<T: Serialize>(writer: &mut impl Write, message: &T) -> io::Result<()> {
+    let body = bincode::serialize(message)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    if body.len() > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("frame of {} bytes exceeds the limit", body.len()),
+        ));
+    }
+    writer.write_all(&(body.len() as u32).to_le_bytes())?;
+    writer.write_all(&body)
+}
+
+pub fn read_frame<T: DeserializeOwned>(reader: &mut impl Read) -> io::Result<T> {
+    let mut length = [0; 4];
+    reader.read_exact(&mut length)?;
+    let length = u32::from_le_bytes(length) as usize;
+    if length > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {} bytes exceeds the limit", length),
+        ));
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    bincode::deserialize(&body).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// Start a thread that writes every message sent on the returned channel,
+/// which holds at most `capacity` messages the socket hasn't taken yet.
+/// Dropping the sender closes the socket, which also ends the reader.
+pub fn spawn_writer<T: Serialize + Send + 'static>(
+    stream: TcpStream,
+    name: String,
+    capacity: usize,
+) -> io::Result<SyncSender<T>> {
+    let (sender, receiver) = mpsc::sync_channel::<T>(capacity.max(1));
+    thread::Builder::new().name(name).spawn(move || {
+        let mut writer = BufWriter::new(&stream);
+        'connection: while let Ok(message) = receiver.recv() {
+            // Batch whatever else is queued into the same flush.
+            for message in std::iter::once(message).chain(receiver.try_iter()) {
+                if write_frame(&mut writer, &message).is_err() {
+                    break 'connection;
+                }
+            }
+            if writer.flush().is_err() {
+                break;
+            }
+        }
+        drop(writer);
+        let _ = stream.shutdown(Shutdown::Both);
+    })?;
+    Ok(sender)
+}
+
+/// Start a thread that decodes frames and hands each to `on_message`, then
+/// calls it once with `None` when the peer disconnects or sends garbage.
+/// Returning `false` from the callback stops reading.
+pub fn spawn_reader<T: DeserializeOwned + Send + 'static>(
+    stream: TcpStream,
+    name: String,
+    mut on_message: impl FnMut(Option<T>) -> bool + Send + 'static,
+) -> io::Result<()> {
+    thread::Builder::new().name(name).spawn(move || {
+        let mut reader = BufReader::new(&stream);
+        while let Ok(message) = read_frame(&mut reader) {
+            if !on_message(Some(message)) {
+                let _ = stream.shutdown(Shutdown::Both);
+                return;
+            }
+        }
+        on_message(None);
+    })?;
+    Ok(())
+}
@@ -0,0 +1,407 @@
+use super::collision::{check_collision_with_world, AABB};
+use crate::block::BlockType;
+use crate::chunk::light::split_world;
+use crate::chunk::{BlockChanged, Chunk, ChunkPos};
+use crate::items::{create_textured_cube_mesh, spawn_dropped_item};
+use crate::simulation::InterpolatedTransform;
+use crate::texture::BlockTextureAtlas;
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::DerefMut;
+
+/// Support checks made per tick at most; the rest of a collapse waits its turn.
+pub const SUPPORT_CHECKS_PER_TICK: usize = 64;
+const TERMINAL_VELOCITY: f32 = -40.0;
+/// Falling blocks move at most this far between collision checks.
+const MAX_FALL_STEP: f32 = 0.5;
+/// Falling blocks that haven't landed after this many blocks are dropped.
+const MAX_FALL_DISTANCE: f32 = 256.0;
+/// Slightly smaller than a block, so walls beside the shaft don't catch it.
+const FALLING_BLOCK_SIZE: f32 = 0.98;
+
+/// A gravity-affected block on its way down, until it lands and turns back
+/// into a voxel.
+#[derive(Component)]
+pub struct FallingBlock {
+    pub block: BlockType,
+    pub velocity: f32,
+    start_y: f32,
+}
+
+/// Positions whose gravity-affected block may have lost its support. Block
+/// edits queue the edited position and the one above it; a block that falls
+/// sends its own edit, so the block above it is looked at the tick after, and
+/// a column collapses from the bottom up.
+#[derive(Resource, Default)]
+pub struct SupportChecks {
+    queue: VecDeque<IVec3>,
+    queued: HashSet<IVec3>,
+}
+
+impl SupportChecks {
+    fn queue(&mut self, position: IVec3) {
+        if self.queued.insert(position) {
+            self.queue.push_back(position);
+        }
+    }
+
+    /// Take up to `budget` queued positions and turn those whose block rests
+    /// on nothing solid into air, returning the blocks that fell. Below the
+    /// world and unloaded chunks count as support.
+    fn collapse<C: DerefMut<Target = Chunk>>(
+        &mut self,
+        chunks: &mut HashMap<ChunkPos, C>,
+        budget: usize,
+    ) -> Vec<(IVec3, BlockType)> {
+        let mut fallen = Vec::new();
+        for _ in 0..budget {
+            let Some(position) = self.queue.pop_front() else {
+                break;
+            };
+            self.queued.remove(&position);
+
+            let block = block_at(chunks, position);
+            if !block.is_some_and(|block| block.is_affected_by_gravity()) {
+                continue;
+            }
+            let below = position - IVec3::Y;
+            let unsupported =
+                below.y >= 0 && matches!(block_at(chunks, below), Some(block) if !block.is_solid());
+            if !unsupported {
+                continue;
+            }
+            let (chunk_pos, [x, y, z]) = split_world(position);
+            if let Some(chunk) = chunks.get_mut(&chunk_pos) {
+                chunk.set_block(x, y, z, BlockType::Air);
+                fallen.push((position, block.unwrap()));
+            }
+        }
+        fallen
+    }
+}
+
+fn block_at<C: DerefMut<Target = Chunk>>(
+    chunks: &HashMap<ChunkPos, C>,
+    position: IVec3,
+) -> Option<BlockType> {
+    let (chunk_pos, [x, y, z]) = split_world(position);
+    chunks
+        .get(&chunk_pos)
+        .map(|chunk| chunk.storage.get(x, y, z))
+}
+
+/// A falling block that landed where a solid block has since appeared, to
+/// be dropped as an item where the game has items.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct FallingBlockDropped {
+    pub block: BlockType,
+    pub translation: Vec3,
+}
+
+/// Falling blocks in single player, and their meshes wherever they fall. A
+/// server runs the simulation systems itself and has no meshes to attach.
+pub struct FallingBlockPlugin;
+
+impl Plugin for FallingBlockPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SupportChecks>()
+            .add_event::<FallingBlockDropped>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    queue_support_checks,
+                    collapse_unsupported_blocks,
+                    update_falling_blocks,
+                )
+                    .chain()
+                    // After the player moves, so a replay sees the same world.
+                    .after(super::player::PlayerPhysicsSet)
+                    .after(crate::chunk::fluid::update_fluids)
+                    // Connected to a server, blocks fall there.
+                    .run_if(
+                        in_state(crate::loading::GameState::Playing)
+                            .and_then(not(resource_exists::<crate::net::client::ChunkClient>)),
+                    ),
+            )
+            .add_systems(
+                Update,
+                (attach_falling_block_meshes, drop_falling_block_items),
+            );
+    }
+}
+
+/// Queue the support checks for this tick's block edits: a block removed
+/// may have held up the one above, and a gravity-affected block placed may
+/// have nothing under it.
+pub fn queue_support_checks(
+    mut checks: ResMut<SupportChecks>,
+    mut block_events: EventReader<BlockChanged>,
+) {
+    for event in block_events.read() {
+        if event.block.is_affected_by_gravity() {
+            checks.queue(event.position);
+        }
+        if !event.block.is_solid() {
+            checks.queue(event.position + IVec3::Y);
+        }
+    }
+}
+
+/// Make up to `SUPPORT_CHECKS_PER_TICK` support checks, turning unsupported
+/// blocks into falling ones.
+pub fn collapse_unsupported_blocks(
+    mut commands: Commands,
+    mut checks: ResMut<SupportChecks>,
+    mut chunk_query: Query<(&mut Chunk, &ChunkPos)>,
+    mut block_events: EventWriter<BlockChanged>,
+) {
+    if checks.queue.is_empty() {
+        return;
+    }
+    let mut chunks: HashMap<ChunkPos, Mut<Chunk>> = chunk_query
+        .iter_mut()
+        .map(|(chunk, position)| (*position, chunk))
+        .collect();
+
+    for (position, block) in checks.collapse(&mut chunks, SUPPORT_CHECKS_PER_TICK) {
+        block_events.send(BlockChanged {
+            position,
+            block: BlockType::Air,
+        });
+
+        let translation = position.as_vec3() + Vec3::splat(0.5);
+        commands.spawn((
+            TransformBundle::from_transform(Transform::from_translation(translation)),
+            FallingBlock {
+                block,
+                velocity: 0.0,
+                start_y: translation.y,
+            },
+        ));
+    }
+}
+
+/// Move falling blocks down under gravity and put them back into the world
+/// where they land. One that lands where a solid block has since appeared
+/// sends a `FallingBlockDropped` instead.
+pub fn update_falling_blocks(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut falling_query: Query<(Entity, &mut FallingBlock, &mut Transform)>,
+    mut chunk_query: Query<(&mut Chunk, &ChunkPos)>,
+    mut block_events: EventWriter<BlockChanged>,
+    mut dropped_events: EventWriter<FallingBlockDropped>,
+) {
+    let dt = time.delta_seconds();
+    let mut landed = Vec::new();
+    for (entity, mut falling, mut transform) in falling_query.iter_mut() {
+        falling.velocity = (falling.velocity + crate::camera::GRAVITY * dt).max(TERMINAL_VELOCITY);
+
+        let mut remaining = -falling.velocity * dt;
+        let mut hit = false;
+        while remaining > 0.0 {
+            let step = remaining.min(MAX_FALL_STEP);
+            let next = transform.translation - Vec3::Y * step;
+            let aabb = AABB::new(next, Vec3::splat(FALLING_BLOCK_SIZE));
+            if check_collision_with_world(&aabb, &chunk_query.to_readonly()) {
+                hit = true;
+                break;
+            }
+            transform.translation = next;
+            remaining -= step;
+        }
+
+        if hit {
+            landed.push((entity, falling.block, transform.translation));
+        } else if falling.start_y - transform.translation.y > MAX_FALL_DISTANCE {
+            commands.entity(entity).despawn();
+        }
+    }
+    if landed.is_empty() {
+        return;
+    }
+
+    let mut chunks: HashMap<ChunkPos, Mut<Chunk>> = chunk_query
+        .iter_mut()
+        .map(|(chunk, position)| (*position, chunk))
+        .collect();
+    for (entity, block, translation) in landed {
+        commands.entity(entity).despawn();
+        let position = (translation - Vec3::splat(0.5)).round().as_ivec3();
+        let (chunk_pos, [x, y, z]) = split_world(position);
+        match chunks.get_mut(&chunk_pos) {
+            Some(chunk) if !chunk.get_block(x, y, z).is_solid() => {
+                chunk.set_block(x, y, z, block);
+                block_events.send(BlockChanged { position, block });
+            }
+            _ => {
+                dropped_events.send(FallingBlockDropped { block, translation });
+            }
+        }
+    }
+}
+
+/// Give falling blocks a cube mesh and draw them between ticks.
+pub fn attach_falling_block_meshes(
+    mut commands: Commands,
+    falling_query: Query<(Entity, &FallingBlock, &Transform), Added<FallingBlock>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    texture_atlas: Option<Res<BlockTextureAtlas>>,
+) {
+    for (entity, falling, transform) in falling_query.iter() {
+        let block = falling.block;
+        let material = match texture_atlas.as_deref() {
+            Some(atlas) => materials.add(StandardMaterial {
+                base_color_texture: Some(atlas.texture.clone()),
+                perceptual_roughness: 0.9,
+                reflectance: 0.1,
+                ..default()
+            }),
+            None => {
+                let [r, g, b, _] = block.get_color();
+                materials.add(StandardMaterial::from(Color::srgb(r, g, b)))
+            }
+        };
+        commands.entity(entity).insert((
+            meshes.add(create_textured_cube_mesh(block, texture_atlas.as_deref())),
+            material,
+            VisibilityBundle::default(),
+            InterpolatedTransform::new(transform.translation),
+        ));
+    }
+}
+
+/// Drop the falling blocks that landed on something solid as items.
+pub fn drop_falling_block_items(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut dropped_events: EventReader<FallingBlockDropped>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    texture_atlas: Option<Res<BlockTextureAtlas>>,
+) {
+    for event in dropped_events.read() {
+        spawn_dropped_item(
+            &mut commands,
+            event.block,
+            event.translation,
+            &mut meshes,
+            &mut materials,
+            texture_atlas.as_deref(),
+            &time,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::CHUNK_SIZE;
+
+    /// One tick's collapse. A block that falls queues the one above it, as
+    /// its edit would.
+    fn tick(
+        checks: &mut SupportChecks,
+        chunks: &mut HashMap<ChunkPos, &mut Chunk>,
+    ) -> Vec<(IVec3, BlockType)> {
+        let fallen = checks.collapse(chunks, 2);
+        for (position, _) in &fallen {
+            checks.queue(*position + IVec3::Y);
+        }
+        fallen
+    }
+
+    #[test]
+    fn unsupported_columns_collapse_bottom_up_within_budget() {
+        // Three sand columns on a stone floor at y = 0, each with the stone
+        // under it taken away.
+        let position = ChunkPos::new(0, 0, 0);
+        let mut chunk = Chunk::new(position);
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk.set_block(x, 0, z, BlockType::Stone);
+            }
+        }
+        for x in [2, 4, 6] {
+            chunk.set_block(x, 1, 4, BlockType::Stone);
+            for y in 2..5 {
+                chunk.set_block(x, y, 4, BlockType::Sand);
+            }
+            chunk.set_block(x, 1, 4, BlockType::Air);
+        }
+        // Snow on stone stays put.
+        chunk.set_block(8, 1, 4, BlockType::Snow);
+
+        let mut checks = SupportChecks::default();
+        for x in [2, 4, 6, 8] {
+            checks.queue(IVec3::new(x, 1, 4));
+            checks.queue(IVec3::new(x, 2, 4));
+        }
+        let mut chunks = HashMap::from([(position, &mut chunk)]);
+
+        // Two checks per tick: the first tick only gets to one column.
+        let first = tick(&mut checks, &mut chunks);
+        assert_eq!(first, vec![(IVec3::new(2, 2, 4), BlockType::Sand)]);
+        let mut fallen = first.len();
+        let mut ticks = 1;
+        while !checks.queue.is_empty() {
+            fallen += tick(&mut checks, &mut chunks).len();
+            ticks += 1;
+        }
+        assert_eq!(fallen, 9);
+        assert_eq!(ticks, 9);
+
+        let storage = &chunks[&position].storage;
+        for x in [2, 4, 6] {
+            assert!((1..5).all(|y| storage.get(x, y, 4) == BlockType::Air));
+        }
+        assert_eq!(storage.get(8, 1, 4), BlockType::Snow);
+    }
+
+    #[test]
+    fn blocks_fall_and_land_without_render_assets() {
+        // What a server runs: no meshes, materials or texture atlas.
+        let mut app = App::new();
+        app.add_plugins(bevy::time::TimePlugin)
+            .insert_resource(bevy::time::TimeUpdateStrategy::ManualDuration(
+                std::time::Duration::from_millis(20),
+            ))
+            .init_resource::<SupportChecks>()
+            .add_event::<BlockChanged>()
+            .add_event::<FallingBlockDropped>()
+            .add_systems(
+                Update,
+                (
+                    queue_support_checks,
+                    collapse_unsupported_blocks,
+                    update_falling_blocks,
+                )
+                    .chain(),
+            );
+
+        // Sand eight blocks above a stone floor, the stone under it just
+        // taken away.
+        let position = ChunkPos::new(0, 0, 0);
+        let mut chunk = Chunk::new(position);
+        chunk.set_block(4, 0, 4, BlockType::Stone);
+        chunk.set_block(4, 8, 4, BlockType::Sand);
+        app.world_mut().spawn((chunk, position));
+        app.world_mut().send_event(BlockChanged {
+            position: IVec3::new(4, 7, 4),
+            block: BlockType::Air,
+        });
+
+        let mut falling = app.world_mut().query::<&FallingBlock>();
+        let mut chunks = app.world_mut().query::<&Chunk>();
+        app.update();
+        assert_eq!(falling.iter(app.world()).count(), 1);
+        for _ in 0..100 {
+            app.update();
+        }
+        assert_eq!(falling.iter(app.world()).count(), 0);
+        let storage = &chunks.single(app.world()).storage;
+        assert_eq!(storage.get(4, 8, 4), BlockType::Air);
+        assert_eq!(storage.get(4, 1, 4), BlockType::Sand);
+    }
+}
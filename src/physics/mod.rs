@@ -1,15 +1,17 @@
 pub mod collision;
+pub mod falling;
 pub mod player;
 
 pub use player::PlayerPhysics;
 
 use bevy::prelude::*;
+use falling::FallingBlockPlugin;
 use player::PlayerPhysicsPlugin;
 
 pub struct PhysicsPlugin;
 
 impl Plugin for PhysicsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(PlayerPhysicsPlugin);
+        app.add_plugins((PlayerPhysicsPlugin, FallingBlockPlugin));
     }
 }
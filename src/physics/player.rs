@@ -2,6 +2,7 @@ use super::collision::{check_collision_with_world, AABB};
 use crate::camera::{CameraController, PlayerCamera};
 use crate::chunk::{Chunk, ChunkPos, CHUNK_SIZE, CHUNK_SIZE_F32};
 use crate::loading::GameState;
+use crate::simulation::{InterpolatedTransform, TickInput};
 use bevy::prelude::*;
 
 const PLAYER_WIDTH: f32 = 0.6;
@@ -32,6 +33,11 @@ impl Default for PlayerPhysics {
     }
 }
 
+/// Player physics for a fixed tick: input to velocity, then collision-checked
+/// movement.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PlayerPhysicsSet;
+
 pub struct PlayerPhysicsPlugin;
 
 impl Plugin for PlayerPhysicsPlugin {
@@ -39,13 +45,17 @@ impl Plugin for PlayerPhysicsPlugin {
         info!("PlayerPhysicsPlugin initializing");
         app.add_systems(
             Update,
+            ensure_player_has_physics.run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            FixedUpdate,
             (
-                ensure_player_has_physics,
                 // simple_test_movement,  // Disabled - using full physics now
                 update_player_physics,
                 apply_player_movement,
             )
                 .chain()
+                .in_set(PlayerPhysicsSet)
                 .run_if(in_state(GameState::Playing)),
         );
     }
@@ -62,7 +72,9 @@ fn ensure_player_has_physics(
             transform.translation - Vec3::new(0.0, PLAYER_EYE_HEIGHT - PLAYER_HEIGHT / 2.0, 0.0);
         let aabb_center = physics.aabb.center;
 
-        commands.entity(entity).insert(physics);
+        commands
+            .entity(entity)
+            .insert((physics, InterpolatedTransform::new(transform.translation)));
         info!(
             "Added PlayerPhysics to camera at position: {:?} (AABB center: {:?})",
             transform.translation, aabb_center
@@ -144,7 +156,7 @@ fn simple_test_movement(
 
 pub fn update_player_physics(
     time: Res<Time>,
-    keyboard: Res<ButtonInput<KeyCode>>,
+    keyboard: Res<TickInput>,
     mut query: Query<
         (&mut Transform, &mut CameraController, &mut PlayerPhysics),
         With<PlayerCamera>,
@@ -288,7 +300,7 @@ pub fn update_player_physics(
     }
 }
 
-fn apply_player_movement(
+pub fn apply_player_movement(
     time: Res<Time>,
     mut query: Query<(&mut Transform, &mut PlayerPhysics, &CameraController), With<PlayerCamera>>,
     chunk_query: Query<(&Chunk, &ChunkPos)>,
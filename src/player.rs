@@ -0,0 +1,260 @@
+use crate::camera::PlayerCamera;
+use crate::net::protocol::ServerMessage;
+use bevy::prelude::*;
+use std::collections::{HashMap, VecDeque};
+
+pub const PLAYER_NAME_ENV: &str = "FORGE_PLAYER_NAME";
+
+/// How far behind the newest state remote players are drawn, so there is
+/// usually a later state to interpolate towards.
+const INTERPOLATION_DELAY_SECONDS: f64 = 0.1;
+const MAX_SNAPSHOTS: usize = 16;
+/// Eye height above the feet; positions are replicated at the eye.
+const AVATAR_EYE_HEIGHT: f32 = 1.6;
+
+/// Identity of a player entity: the local player (alongside `PlayerCamera`),
+/// a remote player's avatar, or a player attached to the server. The local
+/// player's id is 0 until the server assigns one.
+#[derive(Component, Clone, Debug)]
+pub struct Player {
+    pub id: u64,
+    pub name: String,
+}
+
+impl Player {
+    pub fn new(id: u64, name: impl Into<String>) -> Self {
+        Self {
+            id,
+            name: name.into(),
+        }
+    }
+}
+
+pub fn local_player_name() -> String {
+    std::env::var(PLAYER_NAME_ENV).unwrap_or_else(|_| "player".to_string())
+}
+
+/// Player-related messages from the server, forwarded by the chunk client.
+#[derive(Event, Clone, Debug)]
+pub struct ServerPlayerMessage(pub ServerMessage);
+
+/// Receive time, eye position and yaw of one replicated state.
+type Snapshot = (f64, Vec3, f32);
+
+/// Someone else's player, moved along the states the server sends.
+#[derive(Component, Default)]
+pub struct RemotePlayer {
+    /// Oldest first.
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl RemotePlayer {
+    pub fn push(&mut self, time: f64, position: Vec3, yaw: f32) {
+        self.snapshots.push_back((time, position, yaw));
+        while self.snapshots.len() > MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// Position and yaw at `time`, held at the ends of the buffer.
+    pub fn sample(&self, time: f64) -> Option<(Vec3, f32)> {
+        let (first_time, first_position, first_yaw) = *self.snapshots.front()?;
+        if time <= first_time {
+            return Some((first_position, first_yaw));
+        }
+
+        for ((from_time, from_position, from_yaw), (to_time, to_position, to_yaw)) in
+            self.snapshots.iter().zip(self.snapshots.iter().skip(1))
+        {
+            if time <= *to_time {
+                let span = (to_time - from_time).max(f64::EPSILON);
+                let t = ((time - from_time) / span) as f32;
+                let turn = (to_yaw - from_yaw + std::f32::consts::PI)
+                    .rem_euclid(std::f32::consts::TAU)
+                    - std::f32::consts::PI;
+                return Some((from_position.lerp(*to_position, t), from_yaw + turn * t));
+            }
+        }
+
+        self.snapshots
+            .back()
+            .map(|(_, position, yaw)| (*position, *yaw))
+    }
+}
+
+fn spawn_avatar(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    player: Player,
+    snapshot: Option<Snapshot>,
+) {
+    let color = Color::hsl((player.id.wrapping_mul(67) % 360) as f32, 0.55, 0.5);
+    let body = materials.add(StandardMaterial {
+        base_color: color,
+        perceptual_roughness: 0.8,
+        ..default()
+    });
+    let head = materials.add(StandardMaterial {
+        base_color: color.lighter(0.2),
+        perceptual_roughness: 0.8,
+        ..default()
+    });
+
+    // Hidden until the first state says where the player is.
+    let mut remote = RemotePlayer::default();
+    let (transform, visibility) = match snapshot {
+        Some((time, position, yaw)) => {
+            remote.push(time, position, yaw);
+            (
+                Transform::from_translation(position).with_rotation(Quat::from_rotation_y(yaw)),
+                Visibility::Inherited,
+            )
+        }
+        None => (Transform::default(), Visibility::Hidden),
+    };
+
+    info!("Player '{}' (#{}) is in the world", player.name, player.id);
+    commands
+        .spawn((
+            player,
+            remote,
+            SpatialBundle {
+                transform,
+                visibility,
+                ..default()
+            },
+        ))
+        .with_children(|avatar| {
+            avatar.spawn(PbrBundle {
+                mesh: meshes.add(Capsule3d::new(0.3, 0.9)),
+                material: body,
+                transform: Transform::from_xyz(0.0, 0.75 - AVATAR_EYE_HEIGHT, 0.0),
+                ..default()
+            });
+            avatar.spawn(PbrBundle {
+                mesh: meshes.add(Cuboid::new(0.5, 0.5, 0.5)),
+                material: head,
+                ..default()
+            });
+        });
+}
+
+/// Spawn, move and remove remote player avatars as the server reports them.
+pub fn apply_remote_player_messages(
+    mut commands: Commands,
+    mut messages: EventReader<ServerPlayerMessage>,
+    time: Res<Time>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut local_player: Query<&mut Player, With<PlayerCamera>>,
+    mut remote_players: Query<
+        (Entity, &Player, &mut RemotePlayer, &mut Visibility),
+        Without<PlayerCamera>,
+    >,
+) {
+    if messages.is_empty() {
+        return;
+    }
+
+    let now = time.elapsed_seconds_f64();
+    let mut avatars: HashMap<u64, Entity> = remote_players
+        .iter()
+        .map(|(entity, player, _, _)| (player.id, entity))
+        .collect();
+    // Players first seen this frame, spawned once every message is in.
+    let mut arrivals: HashMap<u64, (String, Option<Snapshot>)> = HashMap::new();
+
+    for ServerPlayerMessage(message) in messages.read() {
+        match message {
+            ServerMessage::Welcome { player_id, .. } => {
+                if let Ok(mut player) = local_player.get_single_mut() {
+                    player.id = *player_id;
+                }
+            }
+            ServerMessage::PlayerJoined { player_id, name } if !avatars.contains_key(player_id) => {
+                arrivals
+                    .entry(*player_id)
+                    .or_insert_with(|| (name.clone(), None))
+                    .0 = name.clone();
+            }
+            ServerMessage::PlayerLeft { player_id } => {
+                arrivals.remove(player_id);
+                if let Some(entity) = avatars.remove(player_id) {
+                    info!("Player #{} left", player_id);
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+            ServerMessage::PlayerStates(states) => {
+                for state in states {
+                    let snapshot = (now, state.position, state.yaw);
+                    let avatar = avatars
+                        .get(&state.player_id)
+                        .and_then(|entity| remote_players.get_mut(*entity).ok());
+                    match avatar {
+                        Some((_, _, mut remote, mut visibility)) => {
+                            remote.push(now, state.position, state.yaw);
+                            *visibility = Visibility::Inherited;
+                        }
+                        // Joined before we did if the announcement was missed.
+                        None => {
+                            arrivals
+                                .entry(state.player_id)
+                                .or_insert_with(|| (format!("player-{}", state.player_id), None))
+                                .1 = Some(snapshot);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for (player_id, (name, snapshot)) in arrivals {
+        spawn_avatar(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            Player::new(player_id, name),
+            snapshot,
+        );
+    }
+}
+
+pub fn interpolate_remote_players(
+    time: Res<Time>,
+    mut remote_players: Query<(&RemotePlayer, &mut Transform)>,
+) {
+    let render_time = time.elapsed_seconds_f64() - INTERPOLATION_DELAY_SECONDS;
+    for (remote, mut transform) in remote_players.iter_mut() {
+        if let Some((position, yaw)) = remote.sample(render_time) {
+            transform.translation = position;
+            transform.rotation = Quat::from_rotation_y(yaw);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remote_players_interpolate_between_states() {
+        let mut remote = RemotePlayer::default();
+        assert_eq!(remote.sample(1.0), None);
+
+        remote.push(1.0, Vec3::ZERO, 3.0);
+        remote.push(2.0, Vec3::new(10.0, 0.0, 0.0), -3.0);
+
+        assert_eq!(remote.sample(0.5), Some((Vec3::ZERO, 3.0)));
+        let (position, yaw) = remote.sample(1.5).unwrap();
+        assert_eq!(position, Vec3::new(5.0, 0.0, 0.0));
+        // Turns the short way round through pi rather than back through 0.
+        assert!(
+            (yaw.abs() - std::f32::consts::PI).abs() < 1e-3,
+            "yaw {}",
+            yaw
+        );
+        assert_eq!(remote.sample(3.0), Some((Vec3::new(10.0, 0.0, 0.0), -3.0)));
+    }
+}
@@ -0,0 +1,188 @@
+pub mod recorder;
+// Only the replay binary plays recordings back.
+#[allow(dead_code)]
+pub mod runner;
+
+use crate::block::BlockType;
+use crate::chunk::ChunkPos;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+/// Path to record the session to. Unset, nothing is recorded.
+pub const RECORD_ENV: &str = "FORGE_RECORD";
+pub const REPLAY_FORMAT_VERSION: u16 = 4;
+const REPLAY_MAGIC: &[u8; 8] = b"FORGEREC";
+
+/// Records the session to the file named by `FORGE_RECORD`, if set.
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(path) = recorder::recording_path_from_env() {
+            app.add_plugins(recorder::ReplayRecorderPlugin { path });
+        }
+    }
+}
+
+/// Keys the physics and interaction systems read, in mask bit order.
+pub const RECORDED_KEYS: [KeyCode; 7] = [
+    KeyCode::KeyW,
+    KeyCode::KeyA,
+    KeyCode::KeyS,
+    KeyCode::KeyD,
+    KeyCode::Space,
+    KeyCode::ShiftLeft,
+    KeyCode::KeyF,
+];
+pub const RECORDED_BUTTONS: [MouseButton; 2] = [MouseButton::Left, MouseButton::Right];
+
+/// The world a recording was made in.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WorldSource {
+    /// Planet package name.
+    pub name: String,
+    pub seed: u64,
+    pub planet_size: u32,
+}
+
+/// Player state when recording started.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerStart {
+    pub position: Vec3,
+    pub rotation: Quat,
+    pub velocity: Vec3,
+    pub is_grounded: bool,
+    pub fly_mode: bool,
+    pub move_speed: f32,
+    /// Seconds between the last Space press and the start, for double taps.
+    pub since_space_press: f64,
+    pub held: Option<BlockType>,
+}
+
+/// `RECORDED_KEYS` as one simulation tick saw them through `TickInput`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TickKeys {
+    pub keys: u16,
+    pub keys_just_pressed: u16,
+}
+
+/// One frame of input and what it did to the world.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputFrame {
+    /// Virtual time the frame advanced by.
+    pub delta: Duration,
+    /// The keyboard of every simulation tick the frame ran, in order.
+    pub ticks: Vec<TickKeys>,
+    /// `RECORDED_KEYS` held down, and those pressed this frame, as the
+    /// frame's own systems saw them.
+    pub keys: u16,
+    pub keys_just_pressed: u16,
+    /// `RECORDED_BUTTONS` likewise.
+    pub buttons: u8,
+    pub buttons_just_pressed: u8,
+    pub rotation: Quat,
+    pub prompt_open: bool,
+    /// Block selected in the hotbar at the end of the frame.
+    pub held: Option<BlockType>,
+    /// Block edits made during the frame.
+    pub edits: Vec<(IVec3, BlockType)>,
+    /// Chunks that appeared during the frame, with their storage hash, and
+    /// chunks that went away. They take part from the next frame on.
+    pub loaded: Vec<(ChunkPos, u64)>,
+    pub unloaded: Vec<ChunkPos>,
+}
+
+/// State at the end of the recording, for a replay to be checked against.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FinalState {
+    pub position: Vec3,
+    pub game_seconds: f64,
+    /// `compute_world_hash` over every loaded chunk.
+    pub world_hash: u64,
+    pub chunks: usize,
+}
+
+/// A recorded play session: where it happened, the starting state, and every
+/// frame's input, block edits and chunk loads.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    pub world: WorldSource,
+    /// The simulation timestep.
+    pub timestep: Duration,
+    /// Time the simulation was into its next tick when recording started,
+    /// so the replay's ticks fall in the same frames.
+    pub overstep: Duration,
+    pub game_seconds: f64,
+    pub time_speed: f32,
+    pub start: PlayerStart,
+    /// Chunks loaded when recording started, with their storage hash.
+    pub initial_chunks: Vec<(ChunkPos, u64)>,
+    /// Chunks the generator doesn't bake the way the session first loaded
+    /// them, such as ones edited in earlier sessions, as
+    /// `ChunkStorage::encode_bytes` payloads.
+    pub chunks: Vec<(ChunkPos, Vec<u8>)>,
+    pub frames: Vec<InputFrame>,
+    pub final_state: Option<FinalState>,
+}
+
+impl Recording {
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let file = File::create(path)
+            .map_err(|err| format!("failed to create recording {:?}: {}", path, err))?;
+        let mut writer = BufWriter::new(file);
+        writer
+            .write_all(REPLAY_MAGIC)
+            .and_then(|_| writer.write_all(&REPLAY_FORMAT_VERSION.to_le_bytes()))
+            .map_err(|err| format!("failed to write recording {:?}: {}", path, err))?;
+        bincode::serialize_into(&mut writer, self)
+            .map_err(|err| format!("failed to serialize recording {:?}: {}", path, err))?;
+        writer
+            .flush()
+            .map_err(|err| format!("failed to flush recording {:?}: {}", path, err))
+    }
+
+    #[allow(dead_code)]
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let file = File::open(path)
+            .map_err(|err| format!("failed to open recording {:?}: {}", path, err))?;
+        let mut reader = BufReader::new(file);
+        let mut header = [0u8; 10];
+        reader
+            .read_exact(&mut header)
+            .map_err(|err| format!("failed to read recording {:?}: {}", path, err))?;
+        if &header[..8] != REPLAY_MAGIC {
+            return Err(format!("{:?} is not a replay recording", path));
+        }
+        let version = u16::from_le_bytes([header[8], header[9]]);
+        if version != REPLAY_FORMAT_VERSION {
+            return Err(format!(
+                "recording {:?} has format version {}, expected {}",
+                path, version, REPLAY_FORMAT_VERSION
+            ));
+        }
+        bincode::deserialize_from(reader)
+            .map_err(|err| format!("failed to parse recording {:?}: {}", path, err))
+    }
+}
+
+/// Bit mask of the `buttons` held in `input`, and of those pressed this frame.
+pub fn button_masks<T>(input: &ButtonInput<T>, buttons: &[T]) -> (u16, u16)
+where
+    T: Copy + Eq + std::hash::Hash + Send + Sync + 'static,
+{
+    let mut pressed = 0;
+    let mut just_pressed = 0;
+    for (bit, button) in buttons.iter().enumerate() {
+        if input.pressed(*button) {
+            pressed |= 1 << bit;
+        }
+        if input.just_pressed(*button) {
+            just_pressed |= 1 << bit;
+        }
+    }
+    (pressed, just_pressed)
+}
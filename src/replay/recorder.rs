@@ -0,0 +1,271 @@
+use super::{
+    button_masks, FinalState, InputFrame, PlayerStart, Recording, TickKeys, WorldSource,
+    RECORDED_BUTTONS, RECORDED_KEYS, RECORD_ENV,
+};
+use crate::camera::{CameraController, PlayerCamera};
+use crate::celestial::time::GameTime;
+use crate::chunk::{BlockChanged, Chunk, ChunkPayloadHeader, ChunkPos};
+use crate::interaction::BlockExtractionState;
+use crate::inventory::Hotbar;
+use crate::loading::GameState;
+use crate::net::client::ChunkClient;
+use crate::physics::PlayerPhysics;
+use crate::planet::config::PlanetConfig;
+use crate::simulation::{InterpolatedTransform, TickInput};
+use crate::ui::command_prompt::CommandPromptState;
+use crate::world::chunk_store::{compute_storage_hash, compute_world_hash};
+use crate::world::WorldGenerator;
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+pub fn recording_path_from_env() -> Option<PathBuf> {
+    std::env::var(RECORD_ENV)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Records the session to `path`, written out when the game exits. Recording
+/// starts once the player is in the world.
+///
+/// The game runs in real time while recording. Every frame records how far it
+/// advanced time and the keyboard of each simulation tick it ran, so a replay
+/// runs the same ticks in the same frames.
+pub struct ReplayRecorderPlugin {
+    pub path: PathBuf,
+}
+
+impl Plugin for ReplayRecorderPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ReplayRecorder {
+            path: self.path.clone(),
+            recording: None,
+            chunks: HashMap::new(),
+            first_loads: HashMap::new(),
+            ticks: Vec::new(),
+        })
+        .add_systems(
+            FixedPreUpdate,
+            record_tick.run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            Last,
+            (
+                record_frame.run_if(in_state(GameState::Playing)),
+                save_recording_on_exit,
+            )
+                .chain(),
+        );
+    }
+}
+
+#[derive(Resource)]
+pub struct ReplayRecorder {
+    path: PathBuf,
+    recording: Option<Recording>,
+    /// Loaded chunk entities, to name the chunks that get despawned.
+    chunks: HashMap<Entity, ChunkPos>,
+    /// Every chunk's storage hash and payload the first time it loaded,
+    /// checked against the generator when the recording is saved.
+    first_loads: HashMap<ChunkPos, (u64, Vec<u8>)>,
+    /// Ticks run so far in the current frame.
+    ticks: Vec<TickKeys>,
+}
+
+fn record_tick(mut recorder: ResMut<ReplayRecorder>, tick_input: Res<TickInput>) {
+    if recorder.recording.is_none() {
+        return;
+    }
+    let (keys, keys_just_pressed) = button_masks(&**tick_input, &RECORDED_KEYS);
+    recorder.ticks.push(TickKeys {
+        keys,
+        keys_just_pressed,
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn record_frame(
+    mut recorder: ResMut<ReplayRecorder>,
+    (fixed_time, virtual_time): (Res<Time<Fixed>>, Res<Time<Virtual>>),
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    hotbar: Res<Hotbar>,
+    prompt: Option<Res<CommandPromptState>>,
+    world_gen: Res<WorldGenerator>,
+    planet_config: Res<PlanetConfig>,
+    game_time: Res<GameTime>,
+    remote: Option<Res<ChunkClient>>,
+    mut extraction: ResMut<BlockExtractionState>,
+    player: Query<
+        (
+            &Transform,
+            &InterpolatedTransform,
+            &CameraController,
+            &PlayerPhysics,
+        ),
+        With<PlayerCamera>,
+    >,
+    chunks: Query<(Entity, &Chunk)>,
+    added: Query<(Entity, &Chunk), Added<Chunk>>,
+    mut removed: RemovedComponents<Chunk>,
+    mut block_events: EventReader<BlockChanged>,
+) {
+    let Ok((transform, interpolated, controller, physics)) = player.get_single() else {
+        return;
+    };
+    let recorder = &mut *recorder;
+
+    let Some(recording) = recorder.recording.as_mut() else {
+        if remote.is_some() {
+            warn!("Recording while connected to a server; edits by other players won't replay");
+        }
+        // Start from a clean slate rather than halfway through breaking a block.
+        *extraction = BlockExtractionState::default();
+        block_events.clear();
+        removed.clear();
+        recorder.ticks.clear();
+
+        let initial_chunks = chunks
+            .iter()
+            .map(|(entity, chunk)| {
+                recorder.chunks.insert(entity, chunk.position);
+                let hash = compute_storage_hash(&chunk.storage);
+                note_first_load(&mut recorder.first_loads, chunk, hash);
+                (chunk.position, hash)
+            })
+            .collect::<Vec<_>>();
+        let config = world_gen.config();
+        info!(
+            "Recording replay to {:?} ({} chunks loaded)",
+            recorder.path,
+            initial_chunks.len()
+        );
+        recorder.recording = Some(Recording {
+            world: WorldSource {
+                name: planet_config.name.clone(),
+                seed: config.seed,
+                planet_size: config.planet_size,
+            },
+            timestep: fixed_time.timestep(),
+            overstep: fixed_time.overstep(),
+            game_seconds: game_time.total_seconds,
+            time_speed: game_time.time_speed,
+            start: PlayerStart {
+                // Where the simulation has the player, not where it was drawn.
+                position: interpolated.current(),
+                rotation: transform.rotation,
+                velocity: physics.velocity,
+                is_grounded: physics.is_grounded,
+                fly_mode: controller.fly_mode,
+                move_speed: controller.move_speed,
+                since_space_press: fixed_time.elapsed_seconds_f64() - controller.last_space_press,
+                held: hotbar.get_selected_block(),
+            },
+            initial_chunks,
+            chunks: Vec::new(),
+            frames: Vec::new(),
+            final_state: None,
+        });
+        return;
+    };
+
+    let (keys, keys_just_pressed) = button_masks(&keyboard, &RECORDED_KEYS);
+    let (buttons, buttons_just_pressed) = button_masks(&mouse, &RECORDED_BUTTONS);
+    let mut frame = InputFrame {
+        delta: virtual_time.delta(),
+        ticks: std::mem::take(&mut recorder.ticks),
+        keys,
+        keys_just_pressed,
+        buttons: buttons as u8,
+        buttons_just_pressed: buttons_just_pressed as u8,
+        rotation: transform.rotation,
+        prompt_open: prompt.is_some_and(|prompt| prompt.is_open),
+        held: hotbar.get_selected_block(),
+        ..default()
+    };
+
+    frame.edits = block_events
+        .read()
+        .map(|event| (event.position, event.block))
+        .collect();
+    for entity in removed.read() {
+        if let Some(position) = recorder.chunks.remove(&entity) {
+            frame.unloaded.push(position);
+        }
+    }
+    for (entity, chunk) in added.iter() {
+        recorder.chunks.insert(entity, chunk.position);
+        let hash = compute_storage_hash(&chunk.storage);
+        note_first_load(&mut recorder.first_loads, chunk, hash);
+        frame.loaded.push((chunk.position, hash));
+    }
+
+    recording.frames.push(frame);
+}
+
+/// Keep `chunk` as it first loaded. Later loads replay from what the
+/// recording's edits make of it.
+fn note_first_load(first_loads: &mut HashMap<ChunkPos, (u64, Vec<u8>)>, chunk: &Chunk, hash: u64) {
+    first_loads.entry(chunk.position).or_insert_with(|| {
+        let header = ChunkPayloadHeader {
+            position: chunk.position,
+            ..default()
+        };
+        (hash, chunk.storage.encode_bytes(header))
+    });
+}
+
+fn save_recording_on_exit(
+    mut exit_events: EventReader<AppExit>,
+    mut recorder: ResMut<ReplayRecorder>,
+    game_time: Res<GameTime>,
+    world_gen: Res<WorldGenerator>,
+    player: Query<&InterpolatedTransform, With<PlayerCamera>>,
+    chunks: Query<&Chunk>,
+) {
+    if exit_events.read().next().is_none() {
+        return;
+    }
+    let Some(mut recording) = recorder.recording.take() else {
+        warn!("Exiting before the replay recording started; nothing was recorded");
+        return;
+    };
+
+    let position = player
+        .get_single()
+        .map_or(recording.start.position, InterpolatedTransform::current);
+    recording.final_state = Some(FinalState {
+        position,
+        game_seconds: game_time.total_seconds,
+        world_hash: compute_world_hash(chunks.iter().map(|chunk| (chunk.position, &chunk.storage))),
+        chunks: chunks.iter().count(),
+    });
+
+    // Only chunks the generator wouldn't bake the same need their blocks
+    // in the recording.
+    info!(
+        "Checking {} recorded chunks against the generator",
+        recorder.first_loads.len()
+    );
+    let mut payloads: Vec<(ChunkPos, Vec<u8>)> = recorder
+        .first_loads
+        .drain()
+        .filter(|(position, (hash, _))| {
+            compute_storage_hash(&world_gen.bake_chunk(*position)) != *hash
+        })
+        .map(|(position, (_, bytes))| (position, bytes))
+        .collect();
+    payloads.sort_unstable_by_key(|(position, _)| (position.x, position.y, position.z));
+    recording.chunks = payloads;
+
+    match recording.save(&recorder.path) {
+        Ok(()) => info!(
+            "Saved replay of {} frames to {:?}",
+            recording.frames.len(),
+            recorder.path
+        ),
+        Err(error) => error!("Failed to save replay: {}", error),
+    }
+}
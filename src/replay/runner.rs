@@ -0,0 +1,618 @@
+use super::{InputFrame, Recording, TickKeys, RECORDED_BUTTONS, RECORDED_KEYS};
+use crate::block::BlockType;
+use crate::camera::{CameraController, PlayerCamera};
+use crate::celestial::time::{GameTime, TimePlugin as GameTimePlugin};
+use crate::chunk::fluid::{update_fluids, FluidSimulation};
+use crate::chunk::{BlockChanged, Chunk, ChunkPos, ChunkStorage};
+use crate::interaction::{block_interaction_system, BlockExtractionState, SelectedBlock};
+use crate::inventory::hotbar::InventorySlot;
+use crate::inventory::Hotbar;
+use crate::physics::falling::{
+    collapse_unsupported_blocks, queue_support_checks, update_falling_blocks, FallingBlockDropped,
+    SupportChecks,
+};
+use crate::physics::player::{apply_player_movement, update_player_physics};
+use crate::physics::PlayerPhysics;
+use crate::simulation::{TickInput, TickInputPlugin};
+use crate::ui::command_prompt::CommandPromptState;
+use crate::world::chunk_store::{compute_storage_hash, compute_world_hash};
+use bevy::ecs::event::ManualEventReader;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use std::collections::{HashMap, VecDeque};
+
+/// Somewhere a replay went differently from the recorded session.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Divergence {
+    /// A chunk baked for the replay isn't the one the session loaded.
+    Chunk {
+        frame: usize,
+        position: ChunkPos,
+        expected: u64,
+        actual: u64,
+    },
+    /// A frame ran a different number of simulation ticks.
+    Ticks {
+        frame: usize,
+        expected: usize,
+        actual: usize,
+    },
+    /// A frame made different block edits.
+    Edits {
+        frame: usize,
+        expected: Vec<(IVec3, BlockType)>,
+        actual: Vec<(IVec3, BlockType)>,
+    },
+    Position {
+        expected: Vec3,
+        actual: Vec3,
+    },
+    GameTime {
+        expected: f64,
+        actual: f64,
+    },
+    WorldHash {
+        expected: u64,
+        actual: u64,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct ReplayReport {
+    pub frames: usize,
+    pub edits: usize,
+    pub position: Vec3,
+    pub game_seconds: f64,
+    pub world_hash: u64,
+    pub divergences: Vec<Divergence>,
+}
+
+impl ReplayReport {
+    pub fn is_faithful(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+/// Replay `recording` headless, advancing time by each recorded frame's delta
+/// so the simulation ticks in the same frames, with chunks loaded and unloaded
+/// when the session had them. A chunk comes back as the replay left it when
+/// it was unloaded, or else from the recording's payloads, and only chunks
+/// the session had straight from the generator are baked by `bake`. Each tick's recorded keyboard
+/// drives `update_player_physics` and `apply_player_movement`, the frame's
+/// mouse and view drive `block_interaction_system` after it, water flows as it
+/// did in the game, and every frame's ticks and edits and the final state are
+/// checked against the recording.
+pub fn run_replay(
+    recording: &Recording,
+    mut bake: impl FnMut(ChunkPos) -> ChunkStorage,
+) -> ReplayReport {
+    let mut app = replay_app(recording);
+    // The first update only starts the clock; frames advance it from here.
+    app.update();
+    // Start as far into the next tick as the session was. No tick runs, but
+    // the frame's game time has to be wound back.
+    *app.world_mut().resource_mut::<TimeUpdateStrategy>() =
+        TimeUpdateStrategy::ManualDuration(recording.overstep);
+    app.update();
+    app.world_mut().insert_resource(game_time(recording));
+
+    let mut divergences = Vec::new();
+    let mut chunks: HashMap<ChunkPos, Entity> = HashMap::new();
+    // A payload that fails to decode is baked instead, and shows up as a
+    // chunk divergence unless the generator happens to agree.
+    let mut unloaded: HashMap<ChunkPos, ChunkStorage> = recording
+        .chunks
+        .iter()
+        .filter_map(|(position, bytes)| {
+            ChunkStorage::from_bytes(bytes)
+                .ok()
+                .map(|storage| (*position, storage))
+        })
+        .collect();
+    load_chunks(
+        app.world_mut(),
+        &mut chunks,
+        &mut unloaded,
+        &mut bake,
+        &recording.initial_chunks,
+        &[],
+        0,
+        &mut divergences,
+    );
+    spawn_player(app.world_mut(), recording);
+
+    let mut edit_reader = ManualEventReader::<BlockChanged>::default();
+    let mut edits = 0;
+    let mut previous = None;
+    for (index, frame) in recording.frames.iter().enumerate() {
+        apply_frame_input(app.world_mut(), recording, frame, previous);
+        app.update();
+        previous = Some(frame);
+
+        let ticks = app.world_mut().resource_mut::<RecordedTicks>().finish();
+        if ticks != frame.ticks.len() {
+            divergences.push(Divergence::Ticks {
+                frame: index,
+                expected: frame.ticks.len(),
+                actual: ticks,
+            });
+        }
+
+        let actual: Vec<(IVec3, BlockType)> = edit_reader
+            .read(app.world().resource::<Events<BlockChanged>>())
+            .map(|event| (event.position, event.block))
+            .collect();
+        edits += actual.len();
+        if actual != frame.edits {
+            divergences.push(Divergence::Edits {
+                frame: index,
+                expected: frame.edits.clone(),
+                actual,
+            });
+        }
+
+        load_chunks(
+            app.world_mut(),
+            &mut chunks,
+            &mut unloaded,
+            &mut bake,
+            &frame.loaded,
+            &frame.unloaded,
+            index + 1,
+            &mut divergences,
+        );
+    }
+
+    let world = app.world_mut();
+    let position = world
+        .query_filtered::<&Transform, With<PlayerCamera>>()
+        .single(world)
+        .translation;
+    let game_seconds = world.resource::<GameTime>().total_seconds;
+    let world_hash = compute_world_hash(
+        world
+            .query::<&Chunk>()
+            .iter(world)
+            .map(|chunk| (chunk.position, &chunk.storage)),
+    );
+
+    if let Some(expected) = &recording.final_state {
+        if expected.position != position {
+            divergences.push(Divergence::Position {
+                expected: expected.position,
+                actual: position,
+            });
+        }
+        if expected.game_seconds != game_seconds {
+            divergences.push(Divergence::GameTime {
+                expected: expected.game_seconds,
+                actual: game_seconds,
+            });
+        }
+        if expected.world_hash != world_hash {
+            divergences.push(Divergence::WorldHash {
+                expected: expected.world_hash,
+                actual: world_hash,
+            });
+        }
+    }
+
+    ReplayReport {
+        frames: recording.frames.len(),
+        edits,
+        position,
+        game_seconds,
+        world_hash,
+        divergences,
+    }
+}
+
+fn replay_app(recording: &Recording) -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, GameTimePlugin, TickInputPlugin))
+        .insert_resource(Time::<Fixed>::from_duration(recording.timestep))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(recording.timestep))
+        .insert_resource(game_time(recording))
+        .init_resource::<ButtonInput<KeyCode>>()
+        .init_resource::<ButtonInput<MouseButton>>()
+        .init_resource::<CommandPromptState>()
+        .init_resource::<Hotbar>()
+        .init_resource::<SelectedBlock>()
+        .init_resource::<BlockExtractionState>()
+        .init_resource::<FluidSimulation>()
+        .init_resource::<SupportChecks>()
+        .init_resource::<RecordedView>()
+        .init_resource::<RecordedTicks>()
+        .init_resource::<Assets<Mesh>>()
+        .init_resource::<Assets<StandardMaterial>>()
+        .add_event::<BlockChanged>()
+        .add_event::<FallingBlockDropped>()
+        .add_systems(FixedPreUpdate, tick_input_as_recorded)
+        .add_systems(
+            FixedUpdate,
+            (
+                (
+                    (update_player_physics, apply_player_movement).chain(),
+                    update_fluids,
+                ),
+                (
+                    queue_support_checks,
+                    collapse_unsupported_blocks,
+                    update_falling_blocks,
+                )
+                    .chain(),
+            )
+                .chain(),
+        )
+        .add_systems(Update, (look_as_recorded, block_interaction_system).chain());
+    app
+}
+
+/// Where the player looked at the end of the frame being replayed. In the
+/// game, mouse look comes after the frame's tick and before block
+/// interaction, so the tick still sees the previous frame's view.
+#[derive(Resource, Default)]
+struct RecordedView(Quat);
+
+/// The keyboard of the ticks the frame being replayed has left to run.
+#[derive(Resource, Default)]
+struct RecordedTicks {
+    pending: VecDeque<TickKeys>,
+    ran: usize,
+}
+
+impl RecordedTicks {
+    /// Ticks run since the frame started; ends the frame.
+    fn finish(&mut self) -> usize {
+        self.pending.clear();
+        std::mem::take(&mut self.ran)
+    }
+}
+
+/// Give the tick the keyboard the session's tick saw. A tick the session
+/// didn't run sees no keys held.
+fn tick_input_as_recorded(mut ticks: ResMut<RecordedTicks>, mut tick_input: ResMut<TickInput>) {
+    let keys = ticks.pending.pop_front().unwrap_or_default();
+    ticks.ran += 1;
+    apply_button_masks(
+        tick_input.keys_mut(),
+        &RECORDED_KEYS,
+        keys.keys,
+        keys.keys_just_pressed,
+    );
+}
+
+fn look_as_recorded(
+    view: Res<RecordedView>,
+    mut player: Query<&mut Transform, With<PlayerCamera>>,
+) {
+    for mut transform in player.iter_mut() {
+        transform.rotation = view.0;
+    }
+}
+
+fn game_time(recording: &Recording) -> GameTime {
+    let mut game_time = GameTime {
+        total_seconds: recording.game_seconds,
+        time_speed: recording.time_speed,
+        ..default()
+    };
+    game_time.update(0.0);
+    game_time
+}
+
+fn spawn_player(world: &mut World, recording: &Recording) {
+    let start = &recording.start;
+    let elapsed = world.resource::<Time<Fixed>>().elapsed_seconds_f64();
+    let physics = PlayerPhysics {
+        velocity: start.velocity,
+        is_grounded: start.is_grounded,
+        ..default()
+    };
+    world.spawn((
+        TransformBundle::from_transform(
+            Transform::from_translation(start.position).with_rotation(start.rotation),
+        ),
+        PlayerCamera,
+        CameraController {
+            move_speed: start.move_speed,
+            fly_mode: start.fly_mode,
+            last_space_press: elapsed - start.since_space_press,
+            ..default()
+        },
+        physics,
+    ));
+    world.resource_mut::<RecordedView>().0 = start.rotation;
+}
+
+/// Set up the input for `frame`. The prompt and hotbar are as the previous
+/// frame left them, which is what the frame's tick and block interaction saw.
+fn apply_frame_input(
+    world: &mut World,
+    recording: &Recording,
+    frame: &InputFrame,
+    previous: Option<&InputFrame>,
+) {
+    apply_button_masks(
+        &mut world.resource_mut::<ButtonInput<KeyCode>>(),
+        &RECORDED_KEYS,
+        frame.keys,
+        frame.keys_just_pressed,
+    );
+    apply_button_masks(
+        &mut world.resource_mut::<ButtonInput<MouseButton>>(),
+        &RECORDED_BUTTONS,
+        frame.buttons as u16,
+        frame.buttons_just_pressed as u16,
+    );
+    *world.resource_mut::<TimeUpdateStrategy>() = TimeUpdateStrategy::ManualDuration(frame.delta);
+    world.resource_mut::<RecordedTicks>().pending = frame.ticks.iter().copied().collect();
+    world.resource_mut::<CommandPromptState>().is_open =
+        previous.is_some_and(|previous| previous.prompt_open);
+    world.resource_mut::<RecordedView>().0 = frame.rotation;
+
+    // Only the selected block matters; what else the hotbar held doesn't
+    // change what the frame does.
+    let held = previous.map_or(recording.start.held, |previous| previous.held);
+    let mut hotbar = world.resource_mut::<Hotbar>();
+    hotbar.selected_slot = 0;
+    hotbar.slots[0] = InventorySlot {
+        block_type: held,
+        quantity: held.map_or(0, |_| 1),
+    };
+}
+
+/// Put `input` into the state `button_masks` recorded. Call once per frame;
+/// it starts by clearing last frame's presses and releases.
+fn apply_button_masks<T>(input: &mut ButtonInput<T>, buttons: &[T], pressed: u16, just_pressed: u16)
+where
+    T: Copy + Eq + std::hash::Hash + Send + Sync + 'static,
+{
+    input.clear();
+    for (bit, button) in buttons.iter().enumerate() {
+        let held = pressed & (1 << bit) != 0;
+        if just_pressed & (1 << bit) != 0 {
+            // Pressed this frame, and possibly released again already.
+            input.release(*button);
+            input.clear_just_released(*button);
+            input.press(*button);
+            if !held {
+                input.release(*button);
+            }
+        } else if held {
+            input.press(*button);
+        } else {
+            input.release(*button);
+        }
+    }
+}
+
+/// Despawn the `unloaded` chunks, keeping their storage in `stored`, then
+/// spawn the `loaded` ones from `stored` or, failing that, `bake`.
+#[allow(clippy::too_many_arguments)]
+fn load_chunks(
+    world: &mut World,
+    chunks: &mut HashMap<ChunkPos, Entity>,
+    stored: &mut HashMap<ChunkPos, ChunkStorage>,
+    bake: &mut impl FnMut(ChunkPos) -> ChunkStorage,
+    loaded: &[(ChunkPos, u64)],
+    unloaded: &[ChunkPos],
+    frame: usize,
+    divergences: &mut Vec<Divergence>,
+) {
+    for position in unloaded {
+        if let Some(entity) = chunks.remove(position) {
+            if let Some(chunk) = world.entity_mut(entity).take::<Chunk>() {
+                stored.insert(*position, chunk.storage);
+            }
+            world.despawn(entity);
+        }
+    }
+    for (position, expected) in loaded {
+        let storage = stored.remove(position).unwrap_or_else(|| bake(*position));
+        let actual = compute_storage_hash(&storage);
+        if actual != *expected {
+            divergences.push(Divergence::Chunk {
+                frame,
+                position: *position,
+                expected: *expected,
+                actual,
+            });
+        }
+        let entity = world
+            .spawn((
+                Chunk::from_storage(*position, storage),
+                *position,
+                TransformBundle::from_transform(Transform::from_translation(
+                    position.to_world_pos(),
+                )),
+            ))
+            .id();
+        if let Some(previous) = chunks.insert(*position, entity) {
+            world.despawn(previous);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::CHUNK_SIZE;
+    use crate::replay::{FinalState, PlayerStart, WorldSource};
+    use std::time::Duration;
+
+    /// Grass up to y = 31, air above.
+    fn flat(position: ChunkPos) -> ChunkStorage {
+        if position.y == 0 {
+            ChunkStorage::filled(BlockType::Grass)
+        } else {
+            ChunkStorage::new()
+        }
+    }
+
+    fn recording(frames: Vec<InputFrame>) -> Recording {
+        let chunks = (-1..=1)
+            .flat_map(|x| (0..=1).flat_map(move |y| (-1..=1).map(move |z| ChunkPos::new(x, y, z))))
+            .map(|position| (position, compute_storage_hash(&flat(position))))
+            .collect();
+        let top = CHUNK_SIZE as f32;
+        Recording {
+            world: WorldSource {
+                name: "flat".to_string(),
+                seed: 0,
+                planet_size: 0,
+            },
+            timestep: Duration::from_secs_f64(1.0 / 60.0),
+            overstep: Duration::ZERO,
+            game_seconds: 6.0 * 3600.0,
+            time_speed: 60.0,
+            start: PlayerStart {
+                position: Vec3::new(4.5, top + 1.62, 4.5),
+                // Looking down at the ground in front of the player.
+                rotation: Quat::from_rotation_x(-1.2),
+                velocity: Vec3::ZERO,
+                is_grounded: true,
+                fly_mode: false,
+                move_speed: 15.0,
+                since_space_press: 10.0,
+                held: None,
+            },
+            initial_chunks: chunks,
+            chunks: Vec::new(),
+            frames,
+            final_state: None,
+        }
+    }
+
+    #[test]
+    fn replays_are_deterministic_and_checked_against_the_recording() {
+        let look_down = Quat::from_rotation_x(-1.2);
+        let tick = Duration::from_secs_f64(1.0 / 60.0);
+        let mut frames = Vec::new();
+        // Walk forward for a second at two ticks a frame, then dig for a
+        // second at two frames a tick.
+        for index in 0..150 {
+            let walking = index < 30;
+            let keys = if walking { 1 } else { 0 };
+            let ticks = if walking { 2 } else { (index - 30) % 2 };
+            frames.push(InputFrame {
+                delta: match (walking, ticks) {
+                    (true, _) => tick * 2,
+                    (false, 0) => tick / 2,
+                    (false, _) => tick - tick / 2,
+                },
+                ticks: (0..ticks)
+                    .map(|tick| TickKeys {
+                        keys,
+                        keys_just_pressed: if index == 0 && tick == 0 { 1 } else { 0 },
+                    })
+                    .collect(),
+                keys,
+                keys_just_pressed: if index == 0 { 1 } else { 0 },
+                buttons: if walking { 0 } else { 1 },
+                buttons_just_pressed: if index == 30 { 1 } else { 0 },
+                rotation: look_down,
+                ..default()
+            });
+        }
+
+        let mut recording = recording(frames);
+        let first = run_replay(&recording, flat);
+        assert_eq!(first.edits, 1, "{:?}", first.divergences);
+        assert!(
+            first
+                .divergences
+                .iter()
+                .all(|divergence| matches!(divergence, Divergence::Edits { .. })),
+            "{:?}",
+            first.divergences
+        );
+        assert!(first.position.z < 4.5, "walked to {:?}", first.position);
+
+        // Write down what that run did, as a recording session would have,
+        // and play it again.
+        for divergence in &first.divergences {
+            if let Divergence::Edits { frame, actual, .. } = divergence {
+                recording.frames[*frame].edits = actual.clone();
+            }
+        }
+        recording.final_state = Some(FinalState {
+            position: first.position,
+            game_seconds: first.game_seconds,
+            world_hash: first.world_hash,
+            chunks: recording.initial_chunks.len(),
+        });
+        let second = run_replay(&recording, flat);
+        assert!(second.is_faithful(), "{:?}", second.divergences);
+
+        let path = std::env::temp_dir().join(format!("forge_replay_{}.rec", std::process::id()));
+        recording.save(&path).unwrap();
+        assert_eq!(Recording::load(&path).unwrap(), recording);
+        let _ = std::fs::remove_file(&path);
+
+        // A different world underneath shows up as a divergence.
+        let third = run_replay(&recording, |position| {
+            let mut storage = flat(position);
+            if position == ChunkPos::new(0, 0, 0) {
+                storage.set(0, 0, 0, BlockType::Stone);
+            }
+            storage
+        });
+        assert!(third
+            .divergences
+            .iter()
+            .any(|divergence| matches!(divergence, Divergence::Chunk { .. })));
+        assert!(third
+            .divergences
+            .iter()
+            .any(|divergence| matches!(divergence, Divergence::WorldHash { .. })));
+    }
+
+    #[test]
+    fn chunks_the_generator_did_not_bake_are_kept() {
+        // A chunk edited before the session, unloaded and loaded again.
+        let edited = ChunkPos::new(1, 0, 1);
+        let mut storage = flat(edited);
+        storage.set(3, 31, 3, BlockType::Stone);
+        let hash = compute_storage_hash(&storage);
+        let tick = Duration::from_secs_f64(1.0 / 60.0);
+        let frame = |unloaded: Vec<ChunkPos>, loaded: Vec<(ChunkPos, u64)>| InputFrame {
+            delta: tick,
+            ticks: vec![TickKeys::default()],
+            unloaded,
+            loaded,
+            ..default()
+        };
+
+        let mut recording = recording(vec![
+            frame(vec![edited], Vec::new()),
+            frame(Vec::new(), vec![(edited, hash)]),
+            frame(Vec::new(), Vec::new()),
+        ]);
+        for (position, expected) in &mut recording.initial_chunks {
+            if *position == edited {
+                *expected = hash;
+            }
+        }
+        recording.chunks = vec![(edited, storage.encode_bytes(default()))];
+
+        let report = run_replay(&recording, flat);
+        assert!(report.is_faithful(), "{:?}", report.divergences);
+        let world: Vec<(ChunkPos, ChunkStorage)> = recording
+            .initial_chunks
+            .iter()
+            .map(|(position, _)| {
+                let storage = if *position == edited {
+                    storage.clone()
+                } else {
+                    flat(*position)
+                };
+                (*position, storage)
+            })
+            .collect();
+        assert_eq!(
+            report.world_hash,
+            compute_world_hash(world.iter().map(|(position, storage)| (*position, storage)))
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use super::{ChunkInterestMetrics, ServerSettings};
+use crate::player::Player;
+use crate::ui::command_prompt::{CommandRegistry, PermissionLevel, PlayerPermissions};
+use crate::world::persistence::PersistenceConfig;
+use crate::world::persistence_worker::PersistenceWorker;
+use crate::world::{ChunkPayloadQueue, CurrentTemperature};
+use bevy::prelude::*;
+use std::io::BufRead;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Mutex;
+
+/// Lines read from stdin by the console thread.
+#[derive(Resource)]
+pub struct ConsoleInput(Mutex<Receiver<String>>);
+
+/// Operator console for the headless server. Lines typed on stdin run
+/// through the same `CommandRegistry` as the in-game prompt, with a leading
+/// `/` optional, and the result is printed back.
+pub struct ServerConsolePlugin;
+
+impl Plugin for ServerConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CommandRegistry>()
+            .init_resource::<PlayerPermissions>()
+            .add_systems(Startup, spawn_console_reader)
+            .add_systems(Update, run_console_commands);
+
+        register_server_commands(&mut app.world_mut().resource_mut::<CommandRegistry>());
+    }
+}
+
+fn spawn_console_reader(mut commands: Commands, settings: Res<ServerSettings>) {
+    if !settings.console {
+        return;
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let spawned = std::thread::Builder::new()
+        .name("server-console".into())
+        .spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+    match spawned {
+        Ok(_) => commands.insert_resource(ConsoleInput(Mutex::new(receiver))),
+        Err(error) => warn!("Failed to start server console: {}", error),
+    }
+}
+
+fn run_console_commands(world: &mut World) {
+    let lines: Vec<String> = match world.get_resource::<ConsoleInput>() {
+        Some(input) => input
+            .0
+            .lock()
+            .map(|receiver| receiver.try_iter().collect())
+            .unwrap_or_default(),
+        None => return,
+    };
+
+    for line in lines {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let command = if line.starts_with('/') {
+            line.to_string()
+        } else {
+            format!("/{}", line)
+        };
+
+        let registry = world.resource::<CommandRegistry>().clone();
+        match registry.execute_command(&command, world) {
+            Ok(output) => println!("{}", output.trim_end()),
+            Err(error) => println!("Error: {}", error),
+        }
+    }
+}
+
+fn register_server_commands(registry: &mut CommandRegistry) {
+    registry.register_command(
+        "players",
+        "List connected players",
+        "/players",
+        PermissionLevel::Player,
+        |_, world| {
+            let mut query = world.query::<(
+                &Player,
+                &Transform,
+                Option<&CurrentTemperature>,
+                Option<&ChunkInterestMetrics>,
+            )>();
+            let mut players: Vec<_> = query
+                .iter(world)
+                .map(|(player, transform, temperature, metrics)| {
+                    (
+                        player.id,
+                        player.name.clone(),
+                        transform.translation,
+                        temperature.map(|temperature| temperature.fahrenheit),
+                        metrics.copied(),
+                    )
+                })
+                .collect();
+            players.sort_by_key(|(id, ..)| *id);
+
+            let mut output = format!("{} player(s) connected", players.len());
+            for (id, name, position, fahrenheit, metrics) in players {
+                output.push_str(&format!(
+                    "\n  #{} {} at ({:.1}, {:.1}, {:.1})",
+                    id, name, position.x, position.y, position.z
+                ));
+                if let Some(fahrenheit) = fahrenheit {
+                    output.push_str(&format!(", {:.0}°F", fahrenheit));
+                }
+                if let Some(metrics) = metrics {
+                    output.push_str(&format!(
+                        ", chunks: {} wanted, {} queued, {} sent, {} acked",
+                        metrics.interested, metrics.queued, metrics.sent, metrics.acknowledged
+                    ));
+                }
+            }
+            Ok(output)
+        },
+    );
+
+    registry.register_command(
+        "save",
+        "Write every pending chunk revision to disk",
+        "/save",
+        PermissionLevel::Admin,
+        |_, world| {
+            if world
+                .get_resource::<PersistenceConfig>()
+                .is_some_and(|config| !config.enabled)
+            {
+                return Err("Chunk persistence is disabled".to_string());
+            }
+
+            let payloads = world.resource_mut::<ChunkPayloadQueue>().take_all();
+            let Some(mut worker) = world.get_resource_mut::<PersistenceWorker>() else {
+                return Err("Chunk persistence is not running".to_string());
+            };
+            worker.submit(payloads);
+            worker.flush();
+            let metrics = worker.metrics();
+            Ok(format!(
+                "Saved ({} revision(s) persisted, {} failure(s))",
+                metrics.persisted, metrics.failures
+            ))
+        },
+    );
+
+    registry.register_command(
+        "stop",
+        "Save and shut down the server",
+        "/stop",
+        PermissionLevel::Admin,
+        |_, world| {
+            world.send_event(AppExit::Success);
+            Ok("Shutting down".to_string())
+        },
+    );
+}
@@ -0,0 +1,209 @@
+use super::network::{NetworkServer, RemoteClient};
+use crate::block::BlockType;
+use crate::chunk::{BlockChanged, Chunk, ChunkPos, ChunkStorage, CHUNK_SIZE};
+use crate::interaction::REACH_DISTANCE;
+use crate::net::protocol::{BlockEditRequest, EditRejection, ServerMessage};
+use crate::world::{ChunkPayloadReady, PlanetChunkStore, StoreUpdate};
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+/// Extra reach allowed on top of the client's, for the block's own extent
+/// and for position updates that trail the player slightly.
+const REACH_TOLERANCE: f32 = 1.5;
+
+/// Decide whether a client edit may go ahead. `eye` is the player's reported
+/// position, `current` and `revision` what the server holds right now.
+///
+/// An edit made against an older revision still applies as long as the
+/// block it targets is the one the client saw; if that block changed in the
+/// meantime the edit is stale.
+pub fn validate_edit(
+    request: &BlockEditRequest,
+    eye: Vec3,
+    current: BlockType,
+    revision: u32,
+) -> Result<(), EditRejection> {
+    let center = request.position.as_vec3() + Vec3::splat(0.5);
+    if eye.distance(center) > REACH_DISTANCE + REACH_TOLERANCE {
+        return Err(EditRejection::OutOfReach);
+    }
+    if request.base_revision > revision || current != request.expected {
+        return Err(EditRejection::Stale);
+    }
+
+    let allowed = if request.block == BlockType::Air {
+        current != BlockType::Air && current.is_breakable()
+    } else {
+        current == BlockType::Air
+    };
+    if !allowed {
+        return Err(EditRejection::NotAllowed);
+    }
+    Ok(())
+}
+
+fn split_block_position(position: IVec3) -> (ChunkPos, [usize; 3]) {
+    let size = CHUNK_SIZE as i32;
+    let chunk = ChunkPos::new(
+        position.x.div_euclid(size),
+        position.y.div_euclid(size),
+        position.z.div_euclid(size),
+    );
+    let local = position.rem_euclid(IVec3::splat(size));
+    (
+        chunk,
+        [local.x as usize, local.y as usize, local.z as usize],
+    )
+}
+
+/// Validate and apply the edits clients sent this tick, answering each one.
+/// Accepted edits land on the chunk entity, so `sync_dirty_chunks_to_store`
+/// folds a tick's worth of them into a single revision and
+/// `broadcast_chunk_updates` ships them as one delta. Chunks that are only in
+/// the store are updated there directly.
+pub fn apply_block_edits(
+    server: Res<NetworkServer>,
+    mut clients: Query<(&mut RemoteClient, Option<&Transform>)>,
+    mut chunks: Query<(Entity, &mut Chunk, &ChunkPos)>,
+    mut chunk_store: ResMut<PlanetChunkStore>,
+    mut block_events: EventWriter<BlockChanged>,
+    mut payload_events: EventWriter<ChunkPayloadReady>,
+) {
+    let mut entities: Option<HashMap<ChunkPos, Entity>> = None;
+    let mut detached: HashMap<ChunkPos, ChunkStorage> = HashMap::new();
+
+    for (mut client, transform) in clients.iter_mut() {
+        let edits = client.take_edits();
+        if edits.is_empty() {
+            continue;
+        }
+        let entities = entities.get_or_insert_with(|| {
+            chunks
+                .iter()
+                .map(|(entity, _, position)| (*position, entity))
+                .collect()
+        });
+
+        for request in edits {
+            let (chunk_pos, [x, y, z]) = split_block_position(request.position);
+            let mut chunk = entities
+                .get(&chunk_pos)
+                .and_then(|entity| chunks.get_mut(*entity).ok())
+                .map(|(_, chunk, _)| chunk);
+
+            let revision = chunk_store.get_with_revision(&chunk_pos);
+            let current = match (&chunk, revision.as_ref()) {
+                (Some(chunk), _) => Some(chunk.get_block(x, y, z)),
+                (None, Some((stored, _))) => Some(
+                    detached
+                        .get(&chunk_pos)
+                        .map_or_else(|| stored.get(x, y, z), |storage| storage.get(x, y, z)),
+                ),
+                (None, None) => None,
+            };
+
+            let result = match (current, transform) {
+                (None, _) => Err(EditRejection::NotLoaded),
+                (Some(_), None) => Err(EditRejection::OutOfReach),
+                (Some(current), Some(transform)) => validate_edit(
+                    &request,
+                    transform.translation,
+                    current,
+                    revision.as_ref().map_or(0, |(_, revision)| *revision),
+                ),
+            };
+
+            if let Err(reason) = result {
+                server.send(
+                    client.connection,
+                    ServerMessage::EditRejected {
+                        sequence: request.sequence,
+                        position: request.position,
+                        block: current.unwrap_or(BlockType::Air),
+                        reason,
+                    },
+                );
+                continue;
+            }
+
+            match chunk.as_mut() {
+                Some(chunk) => chunk.set_block(x, y, z, request.block),
+                None => {
+                    if let Some((stored, _)) = revision {
+                        detached
+                            .entry(chunk_pos)
+                            .or_insert_with(|| stored.as_ref().clone())
+                            .set(x, y, z, request.block);
+                    }
+                }
+            }
+            block_events.send(BlockChanged {
+                position: request.position,
+                block: request.block,
+            });
+            server.send(
+                client.connection,
+                ServerMessage::EditAccepted {
+                    sequence: request.sequence,
+                },
+            );
+        }
+    }
+
+    for (position, storage) in detached {
+        if let StoreUpdate::Updated { storage, revision } =
+            chunk_store.upsert_storage(position, &storage)
+        {
+            payload_events.send(ChunkPayloadReady {
+                position,
+                revision,
+                storage,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(block: BlockType, expected: BlockType, base_revision: u32) -> BlockEditRequest {
+        BlockEditRequest {
+            sequence: 1,
+            position: IVec3::new(10, 64, -3),
+            expected,
+            block,
+            base_revision,
+        }
+    }
+
+    #[test]
+    fn edits_are_checked_for_reach_rules_and_staleness() {
+        let eye = Vec3::new(10.5, 66.0, -2.5);
+        let remove = request(BlockType::Air, BlockType::Stone, 3);
+        assert_eq!(validate_edit(&remove, eye, BlockType::Stone, 3), Ok(()));
+        // Someone else edited elsewhere in the chunk; the block is unchanged.
+        assert_eq!(validate_edit(&remove, eye, BlockType::Stone, 5), Ok(()));
+        // Someone else already replaced the block.
+        assert_eq!(
+            validate_edit(&remove, eye, BlockType::Dirt, 4),
+            Err(EditRejection::Stale)
+        );
+        assert_eq!(
+            validate_edit(&remove, eye + Vec3::X * 20.0, BlockType::Stone, 3),
+            Err(EditRejection::OutOfReach)
+        );
+
+        let bedrock = request(BlockType::Air, BlockType::Bedrock, 3);
+        assert_eq!(
+            validate_edit(&bedrock, eye, BlockType::Bedrock, 3),
+            Err(EditRejection::NotAllowed)
+        );
+
+        let place = request(BlockType::Dirt, BlockType::Stone, 3);
+        assert_eq!(
+            validate_edit(&place, eye, BlockType::Stone, 3),
+            Err(EditRejection::NotAllowed)
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use crate::chunk::manager::CandidateState;
+use crate::chunk::ChunkPos;
+use crate::world::WorldGenerator;
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+/// Chunks a viewer keeps an interest in after they drop out of its
+/// candidates (by turning away or moving), as long as they stay this close.
+const RETAIN_MARGIN: i32 = 2;
+
+/// Chunk counters for one player, kept on its entity.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct ChunkInterestMetrics {
+    /// Chunks the player currently holds an interest in.
+    pub interested: usize,
+    /// Chunks queued for baking on the player's behalf.
+    pub queued: u64,
+    /// Full chunk payloads sent to the player.
+    pub sent: u64,
+    /// Payloads the player's client confirmed it received.
+    pub acknowledged: u64,
+}
+
+struct Viewer {
+    candidates: CandidateState,
+    interest: HashSet<ChunkPos>,
+}
+
+/// Area of interest for every player on the server. Each viewer builds its
+/// candidates with the client's own `CandidateState`; the union of their
+/// interests is reference-counted per chunk, so a chunk stays loaded while
+/// any player still needs it, and is streamed once at the best priority any
+/// player gives it.
+#[derive(Resource, Default)]
+pub struct InterestManager {
+    viewers: HashMap<Entity, Viewer>,
+    refcounts: HashMap<ChunkPos, u32>,
+    queue: Vec<(ChunkPos, i32)>,
+    cursor: usize,
+    queue_stale: bool,
+}
+
+impl InterestManager {
+    /// Update a viewer's view volume from its latest position and heading.
+    pub fn update_viewer(
+        &mut self,
+        entity: Entity,
+        position: Vec3,
+        forward: Vec3,
+        view_distance: i32,
+        world_gen: &WorldGenerator,
+    ) {
+        let center = ChunkPos::from_world_pos(position);
+        let viewer = self.viewers.entry(entity).or_insert_with(|| Viewer {
+            candidates: CandidateState::default(),
+            interest: HashSet::new(),
+        });
+        if !viewer
+            .candidates
+            .ensure_candidates(center, view_distance, forward, position, world_gen)
+        {
+            return;
+        }
+
+        let retain = view_distance.saturating_add(RETAIN_MARGIN);
+        let mut interest: HashSet<ChunkPos> = viewer
+            .candidates
+            .candidates()
+            .iter()
+            .map(|(position, _)| *position)
+            .collect();
+        interest.extend(
+            viewer
+                .interest
+                .iter()
+                .filter(|position| chebyshev(position, &center) <= retain)
+                .copied(),
+        );
+        self.set_interest(entity, interest);
+    }
+
+    /// Forget a viewer that left or stopped needing chunks.
+    pub fn remove_viewer(&mut self, entity: Entity) {
+        if self.viewers.contains_key(&entity) {
+            self.set_interest(entity, HashSet::new());
+            self.viewers.remove(&entity);
+        }
+    }
+
+    pub fn viewers(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.viewers.keys().copied()
+    }
+
+    pub fn viewer_count(&self) -> usize {
+        self.viewers.len()
+    }
+
+    /// How many viewers need the chunk.
+    pub fn refcount(&self, position: &ChunkPos) -> u32 {
+        self.refcounts.get(position).copied().unwrap_or(0)
+    }
+
+    pub fn interested_chunks(&self, entity: Entity) -> usize {
+        self.viewers
+            .get(&entity)
+            .map_or(0, |viewer| viewer.interest.len())
+    }
+
+    /// Viewers that need the chunk.
+    pub fn viewers_of<'a>(&'a self, position: &'a ChunkPos) -> impl Iterator<Item = Entity> + 'a {
+        self.viewers
+            .iter()
+            .filter(|(_, viewer)| viewer.interest.contains(position))
+            .map(|(entity, _)| *entity)
+    }
+
+    /// Next chunk to stream, best priority first across all viewers.
+    pub fn next_chunk(&mut self) -> Option<(ChunkPos, i32)> {
+        if self.queue_stale {
+            self.rebuild_queue();
+        }
+        let candidate = self.queue.get(self.cursor).copied()?;
+        self.cursor += 1;
+        Some(candidate)
+    }
+
+    fn set_interest(&mut self, entity: Entity, interest: HashSet<ChunkPos>) {
+        let Some(viewer) = self.viewers.get_mut(&entity) else {
+            return;
+        };
+        for position in viewer.interest.difference(&interest) {
+            if let Some(count) = self.refcounts.get_mut(position) {
+                *count -= 1;
+                if *count == 0 {
+                    self.refcounts.remove(position);
+                }
+            }
+        }
+        for position in interest.difference(&viewer.interest) {
+            *self.refcounts.entry(*position).or_insert(0) += 1;
+        }
+        viewer.interest = interest;
+        self.queue_stale = true;
+    }
+
+    fn rebuild_queue(&mut self) {
+        let mut best: HashMap<ChunkPos, i32> = HashMap::new();
+        for viewer in self.viewers.values() {
+            for (position, score) in viewer.candidates.candidates() {
+                best.entry(*position)
+                    .and_modify(|existing| *existing = (*existing).min(*score))
+                    .or_insert(*score);
+            }
+        }
+
+        let mut queue: Vec<(ChunkPos, i32)> = best.into_iter().collect();
+        queue
+            .sort_unstable_by_key(|(position, score)| (*score, position.x, position.y, position.z));
+        self.queue = queue;
+        self.cursor = 0;
+        self.queue_stale = false;
+    }
+}
+
+fn chebyshev(a: &ChunkPos, b: &ChunkPos) -> i32 {
+    (a.x - b.x)
+        .abs()
+        .max((a.y - b.y).abs())
+        .max((a.z - b.z).abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add_viewer(manager: &mut InterestManager, entity: Entity, chunks: &[ChunkPos]) {
+        manager.viewers.insert(
+            entity,
+            Viewer {
+                candidates: CandidateState::default(),
+                interest: HashSet::new(),
+            },
+        );
+        manager.set_interest(entity, chunks.iter().copied().collect());
+    }
+
+    #[test]
+    fn chunks_stay_wanted_until_the_last_viewer_lets_go() {
+        let mut manager = InterestManager::default();
+        let shared = ChunkPos::new(0, 2, 0);
+        let only_first = ChunkPos::new(-1, 2, 0);
+        let first = Entity::from_raw(1);
+        let second = Entity::from_raw(2);
+
+        add_viewer(&mut manager, first, &[shared, only_first]);
+        add_viewer(&mut manager, second, &[shared]);
+        assert_eq!(manager.refcount(&shared), 2);
+        assert_eq!(manager.refcount(&only_first), 1);
+        assert_eq!(manager.viewers_of(&shared).count(), 2);
+
+        manager.remove_viewer(first);
+        assert_eq!(manager.refcount(&shared), 1);
+        assert_eq!(manager.refcount(&only_first), 0);
+
+        manager.set_interest(second, HashSet::new());
+        assert_eq!(manager.refcount(&shared), 0);
+        assert_eq!(manager.interested_chunks(second), 0);
+    }
+}
@@ -0,0 +1,182 @@
+pub mod console;
+pub mod edits;
+pub mod interest;
+pub mod network;
+pub mod streaming;
+
+use crate::celestial::time::TimePlugin as GameTimePlugin;
+use crate::chunk::{fluid, manager};
+use crate::chunk::{BlockChanged, ChunkGenerationQueue, ChunkManager};
+use crate::loading::{GameState, LoadingProgress};
+use crate::net::protocol::DEFAULT_SERVER_ADDR;
+use crate::physics::falling;
+use crate::player::Player;
+use crate::world::generator::update_player_temperatures;
+use crate::world::CurrentTemperature;
+use bevy::core::FrameCount;
+use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
+
+pub use console::ServerConsolePlugin;
+pub use interest::{ChunkInterestMetrics, InterestManager};
+pub use network::NetworkServer;
+
+/// Chunk radius streamed around a player that did not ask for one.
+pub const DEFAULT_VIEW_DISTANCE: i32 = 10;
+/// Largest chunk radius a client may ask for by default; the client's own
+/// render distance tops out at 12.
+pub const DEFAULT_MAX_VIEW_DISTANCE: i32 = 16;
+
+/// A player attached to the server, next to its `Player` identity. Chunk
+/// streaming follows the entity's `Transform` the same way the client
+/// follows its `PlayerCamera`.
+#[derive(Component, Clone, Debug)]
+pub struct ConnectedPlayer {
+    pub view_distance: i32,
+}
+
+impl Default for ConnectedPlayer {
+    fn default() -> Self {
+        Self {
+            view_distance: DEFAULT_VIEW_DISTANCE,
+        }
+    }
+}
+
+/// Components every server-side player starts with; a `Transform` is added
+/// once its position is known.
+pub fn player_components(
+    id: u64,
+    name: impl Into<String>,
+) -> (
+    Player,
+    ConnectedPlayer,
+    CurrentTemperature,
+    ChunkInterestMetrics,
+) {
+    (
+        Player::new(id, name),
+        ConnectedPlayer::default(),
+        CurrentTemperature::new(),
+        ChunkInterestMetrics::default(),
+    )
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct ServerSettings {
+    /// Address to accept client connections on; `None` runs offline.
+    pub listen: Option<String>,
+    /// Stop after this many ticks; used by CI smoke runs.
+    pub max_ticks: Option<u64>,
+    /// Read commands from stdin.
+    pub console: bool,
+    /// View distances clients report are clamped to this many chunks.
+    pub max_view_distance: i32,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            listen: Some(DEFAULT_SERVER_ADDR.to_string()),
+            max_ticks: None,
+            console: true,
+            max_view_distance: DEFAULT_MAX_VIEW_DISTANCE,
+        }
+    }
+}
+
+/// Headless counterpart of `ChunkPlugin`: runs chunk streaming, baking and
+/// payload collection for every `ConnectedPlayer`, without meshing, lighting
+/// or any render resources. Expects `WorldPlugin` and `PlanetPlugin`.
+#[derive(Default)]
+pub struct ServerPlugin {
+    pub settings: ServerSettings,
+}
+
+impl Plugin for ServerPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<StatesPlugin>() {
+            app.add_plugins(StatesPlugin);
+        }
+
+        if let Some(addr) = self.settings.listen.as_deref() {
+            let server = NetworkServer::bind(addr)
+                .unwrap_or_else(|error| panic!("Failed to listen on {}: {}", addr, error));
+            info!("Listening for clients on {}", server.local_addr());
+            app.insert_resource(server);
+        }
+
+        // There is no loading screen on the server; chunks are baked on
+        // demand as players arrive.
+        app.insert_state(GameState::Playing)
+            .insert_resource(self.settings.clone())
+            .init_resource::<ChunkManager>()
+            .init_resource::<ChunkGenerationQueue>()
+            .init_resource::<LoadingProgress>()
+            .init_resource::<InterestManager>()
+            .init_resource::<fluid::FluidSimulation>()
+            .init_resource::<falling::SupportChecks>()
+            .add_event::<BlockChanged>()
+            .add_event::<falling::FallingBlockDropped>()
+            .add_plugins((GameTimePlugin, ServerConsolePlugin))
+            // Water flows and blocks fall for everyone here. Each gets its
+            // own revision before `Update` applies the clients' edits, so a
+            // `BlockDelta` never hides a change that landed in the same frame.
+            .add_systems(
+                FixedUpdate,
+                (
+                    fluid::update_fluids,
+                    manager::sync_dirty_chunks_to_store,
+                    falling::queue_support_checks,
+                    falling::collapse_unsupported_blocks,
+                    falling::update_falling_blocks,
+                    manager::sync_dirty_chunks_to_store,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                (
+                    (network::handle_client_messages, edits::apply_block_edits)
+                        .chain()
+                        .run_if(resource_exists::<NetworkServer>),
+                    streaming::stream_chunks_for_players,
+                    manager::spawn_chunk_tasks,
+                    manager::poll_chunk_tasks,
+                    manager::sync_dirty_chunks_to_store,
+                    streaming::clear_dirty_flags,
+                    streaming::unload_unwatched_chunks,
+                    manager::collect_chunk_payloads,
+                    update_player_temperatures,
+                    (
+                        network::serve_chunk_requests,
+                        network::broadcast_chunk_updates,
+                        network::replicate_players,
+                    )
+                        .chain()
+                        .run_if(resource_exists::<NetworkServer>),
+                    streaming::log_server_status,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(Update, stop_after_max_ticks);
+    }
+}
+
+fn stop_after_max_ticks(
+    settings: Res<ServerSettings>,
+    frames: Res<FrameCount>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let Some(max_ticks) = settings.max_ticks else {
+        return;
+    };
+
+    // FrameCount is bumped in `Last`, so the current tick is one ahead.
+    if u64::from(frames.0) + 1 >= max_ticks {
+        info!("Reached {} ticks, shutting down", max_ticks);
+        exit.send(AppExit::Success);
+    }
+}
@@ -0,0 +1,1091 @@
+use super::interest::ChunkInterestMetrics;
+use super::{player_components, ConnectedPlayer, ServerSettings, DEFAULT_MAX_VIEW_DISTANCE};
+use crate::block::BlockRegistry;
+use crate::chunk::manager::spawn_known_chunk;
+use crate::chunk::{
+    BlockChanged, ChunkGenerationQueue, ChunkManager, ChunkPayloadHeader, ChunkPos, CHUNK_SIZE,
+};
+use crate::net::client::MAX_CHUNK_REQUESTS_IN_FLIGHT;
+use crate::net::protocol::{
+    BlockDelta, BlockEdit, BlockEditRequest, ClientMessage, PlayerState, ServerMessage,
+    PROTOCOL_VERSION,
+};
+use crate::net::transport::{spawn_reader, spawn_writer};
+use crate::player::Player;
+use crate::world::persistence::PersistenceHandler;
+use crate::world::region::RegionChunkPersistence;
+use crate::world::{ChunkPayloadReady, PlanetChunkStore};
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::Mutex;
+
+/// Chunks sent to one client per tick, so a fresh join doesn't starve
+/// everyone else's updates.
+const MAX_CHUNKS_SENT_PER_TICK: usize = 64;
+/// How far past a player's view distance requests are honored and sent
+/// chunks kept; matches the client's prefetch plus despawn margins.
+const SUBSCRIPTION_MARGIN: i32 = 4;
+/// Messages queued for one client before it counts as stalled and is
+/// disconnected: several ticks' worth of chunks at the per-tick cap.
+const OUTGOING_QUEUE_CAPACITY: usize = 1024;
+/// Messages and connection changes waiting for the next tick. Socket
+/// threads wait while it is full, which holds back the clients sending.
+const EVENT_QUEUE_CAPACITY: usize = 4096;
+
+pub type ConnectionId = u64;
+
+enum ConnectionEvent {
+    Opened(ConnectionId, SyncSender<ServerMessage>, SocketAddr),
+    Message(ConnectionId, ClientMessage),
+    Closed(ConnectionId),
+}
+
+struct Connection {
+    outgoing: SyncSender<ServerMessage>,
+    player: Option<Entity>,
+    /// Set when `outgoing` filled up; the connection is dropped on the next
+    /// `handle_client_messages`.
+    stalled: AtomicBool,
+}
+
+/// TCP listener for client connections. Sockets are serviced on their own
+/// threads; the main schedule only sees decoded messages and queues
+/// replies, so a slow client can't stall a tick.
+#[derive(Resource)]
+pub struct NetworkServer {
+    local_addr: SocketAddr,
+    events: Mutex<Receiver<ConnectionEvent>>,
+    connections: HashMap<ConnectionId, Connection>,
+}
+
+impl NetworkServer {
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let (events, receiver) = mpsc::sync_channel(EVENT_QUEUE_CAPACITY);
+
+        std::thread::Builder::new()
+            .name("server-accept".into())
+            .spawn(move || accept_connections(listener, events))?;
+
+        Ok(Self {
+            local_addr,
+            events: Mutex::new(receiver),
+            connections: HashMap::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn connection_count(&self) -> usize {
+        self.connections.len()
+    }
+
+    /// Queue `message` for a client. A client too slow to take its messages
+    /// is marked stalled rather than left to queue without limit.
+    pub fn send(&self, connection: ConnectionId, message: ServerMessage) {
+        if let Some(connection) = self.connections.get(&connection) {
+            if let Err(TrySendError::Full(_)) = connection.outgoing.try_send(message) {
+                connection.stalled.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Drop a connection and despawn its player, if it had one. Dropping
+    /// the sender closes the socket once what is queued has been written.
+    fn close(&mut self, world: &mut World, id: ConnectionId) -> bool {
+        let Some(connection) = self.connections.remove(&id) else {
+            return false;
+        };
+        if let Some(entity) = connection.player {
+            world.despawn(entity);
+        }
+        true
+    }
+
+    fn take_events(&self) -> Vec<ConnectionEvent> {
+        self.events
+            .lock()
+            .map(|events| events.try_iter().collect())
+            .unwrap_or_default()
+    }
+}
+
+fn accept_connections(listener: TcpListener, events: SyncSender<ConnectionEvent>) {
+    let mut next_id: ConnectionId = 1;
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                warn!("Failed to accept connection: {}", error);
+                continue;
+            }
+        };
+
+        let id = next_id;
+        next_id += 1;
+        let opened = (|| {
+            let peer = stream.peer_addr()?;
+            stream.set_nodelay(true)?;
+            let reader_events = events.clone();
+            spawn_reader::<ClientMessage>(
+                stream.try_clone()?,
+                format!("server-read-{}", id),
+                move |message| {
+                    let event = match message {
+                        Some(message) => ConnectionEvent::Message(id, message),
+                        None => ConnectionEvent::Closed(id),
+                    };
+                    reader_events.send(event).is_ok()
+                },
+            )?;
+            let outgoing = spawn_writer(
+                stream,
+                format!("server-write-{}", id),
+                OUTGOING_QUEUE_CAPACITY,
+            )?;
+            Ok::<_, io::Error>((outgoing, peer))
+        })();
+
+        match opened {
+            Ok((outgoing, peer)) => {
+                if events
+                    .send(ConnectionEvent::Opened(id, outgoing, peer))
+                    .is_err()
+                {
+                    return;
+                }
+            }
+            Err(error) => warn!("Failed to set up connection {}: {}", id, error),
+        }
+    }
+}
+
+/// Per-connection chunk bookkeeping, kept on the player's entity.
+#[derive(Component, Debug)]
+pub struct RemoteClient {
+    pub connection: ConnectionId,
+    /// Chunks asked for and not yet answered, in request order.
+    requested: Vec<ChunkPos>,
+    /// The same chunks as `requested`, to drop repeated requests.
+    requested_set: HashSet<ChunkPos>,
+    /// Requests turned away, answered with an unload so the client asks
+    /// again later.
+    refused: Vec<ChunkPos>,
+    /// Revision of every chunk the client holds, as last sent.
+    sent: HashMap<ChunkPos, u32>,
+    /// Block edits waiting for `apply_block_edits`, in the order received.
+    edits: Vec<BlockEditRequest>,
+}
+
+impl RemoteClient {
+    pub fn new(connection: ConnectionId) -> Self {
+        Self {
+            connection,
+            requested: Vec::new(),
+            requested_set: HashSet::new(),
+            refused: Vec::new(),
+            sent: HashMap::new(),
+            edits: Vec::new(),
+        }
+    }
+
+    pub fn sent_revision(&self, position: &ChunkPos) -> Option<u32> {
+        self.sent.get(position).copied()
+    }
+
+    pub fn pending_requests(&self) -> usize {
+        self.requested.len()
+    }
+
+    pub(super) fn take_edits(&mut self) -> Vec<BlockEditRequest> {
+        std::mem::take(&mut self.edits)
+    }
+}
+
+/// Apply everything the socket threads received since the last tick:
+/// handshakes spawn a player entity, movement drives its `Transform`, and
+/// chunk requests and unloads update its `RemoteClient`. Clients whose
+/// outgoing queue filled up are disconnected first. Exclusive so a
+/// player spawned by a handshake can take the rest of its batch right away.
+pub fn handle_client_messages(world: &mut World) {
+    let max_view_distance = world
+        .get_resource::<ServerSettings>()
+        .map_or(DEFAULT_MAX_VIEW_DISTANCE, |settings| {
+            settings.max_view_distance
+        });
+    world.resource_scope(|world, mut server: Mut<NetworkServer>| {
+        let stalled: Vec<ConnectionId> = server
+            .connections
+            .iter()
+            .filter(|(_, connection)| connection.stalled.load(Ordering::Relaxed))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in stalled {
+            warn!("Connection {} fell too far behind, disconnecting", id);
+            server.close(world, id);
+        }
+
+        for event in server.take_events() {
+            match event {
+                ConnectionEvent::Opened(id, outgoing, peer) => {
+                    info!("Connection {} opened from {}", id, peer);
+                    server.connections.insert(
+                        id,
+                        Connection {
+                            outgoing,
+                            player: None,
+                            stalled: AtomicBool::new(false),
+                        },
+                    );
+                }
+                ConnectionEvent::Closed(id) => {
+                    if server.close(world, id) {
+                        info!("Connection {} closed", id);
+                    }
+                }
+                ConnectionEvent::Message(
+                    id,
+                    ClientMessage::Hello {
+                        version,
+                        name,
+                        blocks,
+                    },
+                ) => {
+                    let Some(connection) = server.connections.get_mut(&id) else {
+                        continue;
+                    };
+                    // Joined already: a second handshake changes nothing.
+                    if connection.player.is_some() {
+                        continue;
+                    }
+                    let refusal = if version != PROTOCOL_VERSION {
+                        Some(format!(
+                            "protocol version {} is not supported (server speaks {})",
+                            version, PROTOCOL_VERSION
+                        ))
+                    } else if blocks != BlockRegistry::global().fingerprint() {
+                        // Its chunks and edits would name the wrong blocks.
+                        Some("block definitions differ from the server's".to_string())
+                    } else {
+                        None
+                    };
+                    if let Some(reason) = refusal {
+                        warn!("Connection {}: {}, closing", id, reason);
+                        let _ = connection
+                            .outgoing
+                            .try_send(ServerMessage::Rejected { reason });
+                        // Dropping the sender closes the socket once the
+                        // rejection is written.
+                        server.connections.remove(&id);
+                        continue;
+                    }
+
+                    // The player gets a `Transform`, and with it chunk
+                    // streaming, once it reports a position.
+                    let entity = world
+                        .spawn((player_components(id, name.clone()), RemoteClient::new(id)))
+                        .id();
+                    connection.player = Some(entity);
+                    let _ = connection.outgoing.try_send(ServerMessage::Welcome {
+                        version: PROTOCOL_VERSION,
+                        player_id: id,
+                        blocks: BlockRegistry::global().fingerprint(),
+                    });
+                    info!("Player '{}' joined on connection {}", name, id);
+                }
+                ConnectionEvent::Message(id, message) => {
+                    let Some(connection) = server.connections.get(&id) else {
+                        continue;
+                    };
+                    let Some(mut player) = connection
+                        .player
+                        .and_then(|entity| world.get_entity_mut(entity))
+                    else {
+                        warn!("Connection {} sent {:?} before its handshake", id, message);
+                        continue;
+                    };
+                    apply_player_message(&mut player, message, max_view_distance);
+                }
+            }
+        }
+    });
+}
+
+fn apply_player_message(
+    player: &mut EntityWorldMut,
+    message: ClientMessage,
+    max_view_distance: i32,
+) {
+    match message {
+        ClientMessage::Hello { .. } => {}
+        ClientMessage::RequestChunks { positions } => {
+            // Without a position there is no range to hold requests to.
+            let located = player.contains::<Transform>();
+            if let Some(mut client) = player.get_mut::<RemoteClient>() {
+                for position in positions {
+                    if client.requested_set.contains(&position) {
+                        continue;
+                    }
+                    if !located || client.requested.len() >= MAX_CHUNK_REQUESTS_IN_FLIGHT {
+                        client.refused.push(position);
+                        continue;
+                    }
+                    client.requested_set.insert(position);
+                    client.requested.push(position);
+                }
+            }
+        }
+        ClientMessage::UnloadChunks { positions } => {
+            if let Some(mut client) = player.get_mut::<RemoteClient>() {
+                let positions: HashSet<ChunkPos> = positions.into_iter().collect();
+                client
+                    .requested
+                    .retain(|position| !positions.contains(position));
+                client
+                    .requested_set
+                    .retain(|position| !positions.contains(position));
+                client
+                    .sent
+                    .retain(|position, _| !positions.contains(position));
+            }
+        }
+        ClientMessage::PlayerMoved {
+            position,
+            yaw,
+            view_distance,
+        } => {
+            if let Some(mut connected) = player.get_mut::<ConnectedPlayer>() {
+                connected.view_distance = view_distance.clamp(1, max_view_distance.max(1));
+            }
+            let rotation = Quat::from_rotation_y(yaw);
+            match player.get_mut::<Transform>() {
+                Some(mut transform) => {
+                    transform.translation = position;
+                    transform.rotation = rotation;
+                }
+                None => {
+                    player.insert(TransformBundle::from_transform(
+                        Transform::from_translation(position).with_rotation(rotation),
+                    ));
+                }
+            }
+        }
+        ClientMessage::AckChunks { positions } => {
+            if let Some(mut metrics) = player.get_mut::<ChunkInterestMetrics>() {
+                metrics.acknowledged += positions.len() as u64;
+            }
+        }
+        ClientMessage::EditBlock(request) => {
+            if let Some(mut client) = player.get_mut::<RemoteClient>() {
+                client.edits.push(request);
+            }
+        }
+    }
+}
+
+/// Answer chunk requests from the store, loading from persistence or queueing
+/// a bake for chunks the server doesn't have yet, and tell clients to drop
+/// chunks they have moved away from.
+#[allow(clippy::too_many_arguments)]
+pub fn serve_chunk_requests(
+    mut commands: Commands,
+    server: Res<NetworkServer>,
+    mut clients: Query<(
+        &mut RemoteClient,
+        &ConnectedPlayer,
+        Option<&Transform>,
+        Option<&mut ChunkInterestMetrics>,
+    )>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut chunk_queue: ResMut<ChunkGenerationQueue>,
+    mut chunk_store: ResMut<PlanetChunkStore>,
+    mut chunk_events: EventWriter<ChunkPayloadReady>,
+    persistence: Option<Res<PersistenceHandler<RegionChunkPersistence>>>,
+) {
+    for (mut client, player, transform, mut metrics) in clients.iter_mut() {
+        let center = transform.map(|transform| ChunkPos::from_world_pos(transform.translation));
+        let range = player.view_distance.saturating_add(SUBSCRIPTION_MARGIN);
+        let in_range = |position: &ChunkPos| match center {
+            Some(center) => {
+                (position.x - center.x)
+                    .abs()
+                    .max((position.y - center.y).abs())
+                    .max((position.z - center.z).abs())
+                    <= range
+            }
+            None => false,
+        };
+
+        let mut unload = std::mem::take(&mut client.refused);
+        client.sent.retain(|position, _| {
+            let keep = in_range(position);
+            if !keep {
+                unload.push(*position);
+            }
+            keep
+        });
+
+        let mut sent = 0;
+        let requested = std::mem::take(&mut client.requested);
+        let mut waiting = Vec::new();
+        for position in requested {
+            if !in_range(&position) {
+                unload.push(position);
+                continue;
+            }
+            if sent >= MAX_CHUNKS_SENT_PER_TICK {
+                waiting.push(position);
+                continue;
+            }
+
+            if !chunk_store.contains(&position)
+                && !chunk_manager.loaded_chunks.contains(&position)
+                && !chunk_queue.contains(&position)
+                && !spawn_known_chunk(
+                    &mut commands,
+                    &mut chunk_manager,
+                    &mut chunk_queue,
+                    &mut chunk_store,
+                    &mut chunk_events,
+                    persistence.as_deref(),
+                    position,
+                )
+            {
+                let priority = center.map_or(0, |center| {
+                    let dx = position.x - center.x;
+                    let dz = position.z - center.z;
+                    dx * dx + dz * dz
+                });
+                chunk_queue.enqueue_with_priority(position, priority);
+            }
+
+            match encode_chunk(&chunk_store, &position) {
+                Some((revision, bytes)) => {
+                    server.send(
+                        client.connection,
+                        ServerMessage::ChunkPayload {
+                            position,
+                            revision,
+                            bytes,
+                        },
+                    );
+                    client.sent.insert(position, revision);
+                    sent += 1;
+                    if let Some(metrics) = metrics.as_mut() {
+                        metrics.sent += 1;
+                    }
+                }
+                None => waiting.push(position),
+            }
+        }
+        client.requested_set = waiting.iter().copied().collect();
+        client.requested = waiting;
+
+        if !unload.is_empty() {
+            server.send(
+                client.connection,
+                ServerMessage::UnloadChunks { positions: unload },
+            );
+        }
+    }
+}
+
+/// Push chunk changes to every client holding an older revision: the edits
+/// as a `BlockDelta` when the client is exactly one revision behind, the full
+/// chunk otherwise.
+pub fn broadcast_chunk_updates(
+    server: Res<NetworkServer>,
+    mut payload_events: EventReader<ChunkPayloadReady>,
+    mut block_events: EventReader<BlockChanged>,
+    chunk_store: Res<PlanetChunkStore>,
+    mut clients: Query<(&mut RemoteClient, Option<&mut ChunkInterestMetrics>)>,
+) {
+    let mut edits: HashMap<ChunkPos, Vec<BlockEdit>> = HashMap::new();
+    for event in block_events.read() {
+        let chunk = ChunkPos::new(
+            event.position.x.div_euclid(CHUNK_SIZE as i32),
+            event.position.y.div_euclid(CHUNK_SIZE as i32),
+            event.position.z.div_euclid(CHUNK_SIZE as i32),
+        );
+        let local = event.position.rem_euclid(IVec3::splat(CHUNK_SIZE as i32));
+        edits.entry(chunk).or_default().push(BlockEdit {
+            local: [local.x as u8, local.y as u8, local.z as u8],
+            block: event.block,
+        });
+    }
+
+    let mut changed: Vec<ChunkPos> = edits.keys().copied().collect();
+    changed.extend(payload_events.read().map(|event| event.position));
+    if changed.is_empty() {
+        return;
+    }
+
+    for (mut client, mut metrics) in clients.iter_mut() {
+        for position in &changed {
+            let Some(sent_revision) = client.sent.get(position).copied() else {
+                continue;
+            };
+            let Some((_, revision)) = chunk_store.get_with_revision(position) else {
+                continue;
+            };
+            if sent_revision >= revision {
+                continue;
+            }
+
+            let message = match edits.get(position) {
+                Some(changes) if sent_revision.wrapping_add(1) == revision => {
+                    ServerMessage::BlockDelta(BlockDelta {
+                        position: *position,
+                        revision,
+                        changes: changes.clone(),
+                    })
+                }
+                _ => {
+                    let Some((revision, bytes)) = encode_chunk(&chunk_store, position) else {
+                        continue;
+                    };
+                    if let Some(metrics) = metrics.as_mut() {
+                        metrics.sent += 1;
+                    }
+                    ServerMessage::ChunkPayload {
+                        position: *position,
+                        revision,
+                        bytes,
+                    }
+                }
+            };
+            server.send(client.connection, message);
+            client.sent.insert(*position, revision);
+        }
+    }
+}
+
+/// Tell clients about players joining, moving and leaving. A client that
+/// just connected is introduced to everyone already here; after that each
+/// tick carries only the players that moved. Nobody is told about
+/// themselves.
+#[allow(clippy::too_many_arguments)]
+pub fn replicate_players(
+    server: Res<NetworkServer>,
+    clients: Query<(Entity, &RemoteClient)>,
+    new_clients: Query<(), Added<RemoteClient>>,
+    players: Query<(Entity, &Player, Option<&Transform>)>,
+    joined: Query<Entity, Added<Player>>,
+    moved: Query<Entity, (With<Player>, Changed<Transform>)>,
+    mut departed: RemovedComponents<Player>,
+    mut known: Local<HashMap<Entity, u64>>,
+) {
+    let left: Vec<u64> = departed
+        .read()
+        .filter_map(|entity| known.remove(&entity))
+        .collect();
+    for entity in joined.iter() {
+        if let Ok((_, player, _)) = players.get(entity) {
+            known.insert(entity, player.id);
+        }
+    }
+
+    let state = |entity: Entity| {
+        let (_, player, transform) = players.get(entity).ok()?;
+        let transform = transform?;
+        let (yaw, _, _) = transform.rotation.to_euler(EulerRot::YXZ);
+        Some(PlayerState {
+            player_id: player.id,
+            position: transform.translation,
+            yaw,
+        })
+    };
+
+    for (client_entity, client) in clients.iter() {
+        let is_new = new_clients.contains(client_entity);
+        let introduce: Vec<Entity> = if is_new {
+            players.iter().map(|(entity, _, _)| entity).collect()
+        } else {
+            joined.iter().collect()
+        };
+        for entity in introduce {
+            if entity == client_entity {
+                continue;
+            }
+            if let Ok((_, player, _)) = players.get(entity) {
+                server.send(
+                    client.connection,
+                    ServerMessage::PlayerJoined {
+                        player_id: player.id,
+                        name: player.name.clone(),
+                    },
+                );
+            }
+        }
+
+        for player_id in &left {
+            server.send(
+                client.connection,
+                ServerMessage::PlayerLeft {
+                    player_id: *player_id,
+                },
+            );
+        }
+
+        let states: Vec<PlayerState> = if is_new {
+            players
+                .iter()
+                .map(|(entity, _, _)| entity)
+                .filter(|entity| *entity != client_entity)
+                .filter_map(state)
+                .collect()
+        } else {
+            moved
+                .iter()
+                .filter(|entity| *entity != client_entity)
+                .filter_map(state)
+                .collect()
+        };
+        if !states.is_empty() {
+            server.send(client.connection, ServerMessage::PlayerStates(states));
+        }
+    }
+}
+
+fn encode_chunk(chunk_store: &PlanetChunkStore, position: &ChunkPos) -> Option<(u32, Vec<u8>)> {
+    let (storage, revision) = chunk_store.get_with_revision(position)?;
+    let bytes = storage.encode_bytes(ChunkPayloadHeader {
+        position: *position,
+        revision,
+        ..default()
+    });
+    Some((revision, bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockType;
+    use crate::chunk::ChunkStorage;
+    use crate::net::client::ChunkClient;
+    use crate::net::protocol::EditRejection;
+    use crate::net::transport::{read_frame, write_frame};
+    use crate::server::edits::apply_block_edits;
+    use crate::server::DEFAULT_VIEW_DISTANCE;
+    use std::net::TcpStream;
+    use std::time::{Duration, Instant};
+
+    fn loopback_app() -> App {
+        let mut app = App::new();
+        app.insert_resource(NetworkServer::bind("127.0.0.1:0").unwrap())
+            .init_resource::<ChunkManager>()
+            .init_resource::<ChunkGenerationQueue>()
+            .init_resource::<PlanetChunkStore>()
+            .add_event::<ChunkPayloadReady>()
+            .add_event::<BlockChanged>()
+            .add_systems(
+                Update,
+                (
+                    handle_client_messages,
+                    apply_block_edits,
+                    serve_chunk_requests,
+                    broadcast_chunk_updates,
+                    replicate_players,
+                )
+                    .chain(),
+            );
+        app
+    }
+
+    /// Tick the server until the client has received `count` messages.
+    fn pump(app: &mut App, client: &mut ChunkClient, count: usize) -> Vec<ServerMessage> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut received = Vec::new();
+        while received.len() < count {
+            assert!(Instant::now() < deadline, "timed out; got {:?}", received);
+            app.update();
+            std::thread::sleep(Duration::from_millis(5));
+            received.extend(client.poll());
+        }
+        received
+    }
+
+    /// Tick the server until the client receives a message matching `find`,
+    /// returning everything received up to then.
+    fn pump_until(
+        app: &mut App,
+        client: &mut ChunkClient,
+        find: impl Fn(&ServerMessage) -> bool,
+    ) -> Vec<ServerMessage> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut received = Vec::new();
+        while !received.iter().any(&find) {
+            assert!(Instant::now() < deadline, "timed out; got {:?}", received);
+            app.update();
+            std::thread::sleep(Duration::from_millis(5));
+            received.extend(client.poll());
+        }
+        received
+    }
+
+    #[test]
+    fn loopback_client_receives_chunks_and_deltas() {
+        let mut app = loopback_app();
+        let position = ChunkPos::new(3, 2, -5);
+        let mut storage = ChunkStorage::filled(BlockType::Stone);
+        app.world_mut()
+            .resource_mut::<PlanetChunkStore>()
+            .insert_with_revision(position, storage.clone(), 4);
+
+        let addr = app.world().resource::<NetworkServer>().local_addr();
+        let mut client = ChunkClient::connect(addr, "tester").unwrap();
+        client.send(ClientMessage::PlayerMoved {
+            position: Vec3::new(3.5 * 32.0, 2.5 * 32.0, -4.5 * 32.0),
+            yaw: 0.0,
+            view_distance: 4,
+        });
+        client.request_chunks(vec![(position, 0)]);
+
+        let received = pump(&mut app, &mut client, 2);
+        assert!(
+            matches!(received[0], ServerMessage::Welcome { version, .. } if version == PROTOCOL_VERSION)
+        );
+        let ServerMessage::ChunkPayload {
+            position: payload_position,
+            revision,
+            bytes,
+        } = &received[1]
+        else {
+            panic!("expected a chunk payload, got {:?}", received[1]);
+        };
+        assert_eq!(*payload_position, position);
+        assert_eq!(*revision, 4);
+        let decoded = ChunkStorage::from_bytes(bytes).unwrap();
+        assert_eq!(decoded.get(1, 2, 3), BlockType::Stone);
+        assert!(client.player_id().is_some());
+
+        // An edit on the server reaches the client as a one-block delta.
+        storage.set(1, 2, 3, BlockType::Air);
+        let update = app
+            .world_mut()
+            .resource_mut::<PlanetChunkStore>()
+            .upsert_storage(position, &storage);
+        assert!(matches!(
+            update,
+            crate::world::StoreUpdate::Updated { revision: 5, .. }
+        ));
+        app.world_mut().send_event(BlockChanged {
+            position: IVec3::new(3 * 32 + 1, 2 * 32 + 2, -5 * 32 + 3),
+            block: BlockType::Air,
+        });
+
+        let received = pump(&mut app, &mut client, 1);
+        assert_eq!(
+            received[0],
+            ServerMessage::BlockDelta(BlockDelta {
+                position,
+                revision: 5,
+                changes: vec![BlockEdit {
+                    local: [1, 2, 3],
+                    block: BlockType::Air,
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn edits_are_applied_broadcast_and_stale_ones_rejected() {
+        let mut app = loopback_app();
+        let position = ChunkPos::new(0, 2, 0);
+        app.world_mut()
+            .resource_mut::<PlanetChunkStore>()
+            .insert_with_revision(position, ChunkStorage::filled(BlockType::Stone), 1);
+
+        let addr = app.world().resource::<NetworkServer>().local_addr();
+        let mut client = ChunkClient::connect(addr, "tester").unwrap();
+        client.send(ClientMessage::PlayerMoved {
+            position: Vec3::new(5.5, 70.0, 5.5),
+            yaw: 0.0,
+            view_distance: 4,
+        });
+        client.request_chunks(vec![(position, 0)]);
+        pump(&mut app, &mut client, 2);
+        client.set_server_revision(position, 1);
+
+        let block = IVec3::new(5, 66, 5);
+        client.submit_edit(block, BlockType::Stone, BlockType::Air);
+        let received = pump(&mut app, &mut client, 2);
+        assert_eq!(received[0], ServerMessage::EditAccepted { sequence: 1 });
+        assert_eq!(
+            received[1],
+            ServerMessage::BlockDelta(BlockDelta {
+                position,
+                revision: 2,
+                changes: vec![BlockEdit {
+                    local: [5, 2, 5],
+                    block: BlockType::Air,
+                }],
+            })
+        );
+
+        // Still editing against revision 1, where the block was stone.
+        client.submit_edit(block, BlockType::Stone, BlockType::Air);
+        let received = pump(&mut app, &mut client, 1);
+        assert_eq!(
+            received[0],
+            ServerMessage::EditRejected {
+                sequence: 2,
+                position: block,
+                block: BlockType::Air,
+                reason: EditRejection::Stale,
+            }
+        );
+    }
+
+    #[test]
+    fn chunk_requests_need_a_position_and_are_capped() {
+        let mut app = loopback_app();
+        let addr = app.world().resource::<NetworkServer>().local_addr();
+        let mut client = ChunkClient::connect(addr, "tester").unwrap();
+
+        // Nothing is loaded for a client the server can't place yet.
+        let early = ChunkPos::new(0, 0, 0);
+        client.request_chunks(vec![(early, 0)]);
+        let received = pump_until(&mut app, &mut client, |message| {
+            matches!(message, ServerMessage::UnloadChunks { .. })
+        });
+        assert_eq!(
+            received.last(),
+            Some(&ServerMessage::UnloadChunks {
+                positions: vec![early]
+            })
+        );
+        assert!(!app
+            .world()
+            .resource::<ChunkGenerationQueue>()
+            .contains(&early));
+
+        client.send(ClientMessage::PlayerMoved {
+            position: Vec3::new(16.0, 16.0, 16.0),
+            yaw: 0.0,
+            view_distance: 4,
+        });
+        let positions: Vec<ChunkPos> = (-4..4)
+            .flat_map(|x| (-4..4).flat_map(move |y| (-2..2).map(move |z| ChunkPos::new(x, y, z))))
+            .collect();
+        assert!(positions.len() > MAX_CHUNK_REQUESTS_IN_FLIGHT);
+        let mut request = positions.clone();
+        // Repeats don't count twice.
+        request.extend_from_slice(&positions[..8]);
+        client.send(ClientMessage::RequestChunks { positions: request });
+
+        let received = pump_until(&mut app, &mut client, |message| {
+            matches!(message, ServerMessage::UnloadChunks { .. })
+        });
+        let Some(ServerMessage::UnloadChunks { positions: refused }) = received.last() else {
+            unreachable!();
+        };
+        assert_eq!(refused, &positions[MAX_CHUNK_REQUESTS_IN_FLIGHT..]);
+    }
+
+    #[test]
+    fn reported_view_distance_is_clamped() {
+        let mut app = loopback_app();
+        let addr = app.world().resource::<NetworkServer>().local_addr();
+        let mut client = ChunkClient::connect(addr, "tester").unwrap();
+        client.send(ClientMessage::PlayerMoved {
+            position: Vec3::ZERO,
+            yaw: 0.0,
+            view_distance: i32::MAX,
+        });
+        pump(&mut app, &mut client, 1);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut players = app.world_mut().query::<&ConnectedPlayer>();
+        while players.single(app.world()).view_distance == DEFAULT_VIEW_DISTANCE {
+            assert!(Instant::now() < deadline, "the move never arrived");
+            app.update();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(
+            players.single(app.world()).view_distance,
+            DEFAULT_MAX_VIEW_DISTANCE
+        );
+    }
+
+    #[test]
+    fn players_see_each_other_join_move_and_leave() {
+        let mut app = loopback_app();
+        let addr = app.world().resource::<NetworkServer>().local_addr();
+
+        let mut first = ChunkClient::connect(addr, "first").unwrap();
+        pump(&mut app, &mut first, 1);
+        let first_id = first.player_id().unwrap();
+        first.send(ClientMessage::PlayerMoved {
+            position: Vec3::new(1.0, 2.0, 3.0),
+            yaw: 0.5,
+            view_distance: 4,
+        });
+
+        let mut second = ChunkClient::connect(addr, "second").unwrap();
+        let received = pump_until(&mut app, &mut second, |message| {
+            matches!(message, ServerMessage::PlayerStates(_))
+        });
+        assert!(received.contains(&ServerMessage::PlayerJoined {
+            player_id: first_id,
+            name: "first".into(),
+        }));
+        let Some(ServerMessage::PlayerStates(states)) = received.last() else {
+            unreachable!();
+        };
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].player_id, first_id);
+        assert_eq!(states[0].position, Vec3::new(1.0, 2.0, 3.0));
+        assert!((states[0].yaw - 0.5).abs() < 1e-5);
+
+        // The first player hears about the second too, but never about itself.
+        let received = pump_until(&mut app, &mut first, |message| {
+            matches!(message, ServerMessage::PlayerJoined { .. })
+        });
+        assert!(received.iter().all(|message| !matches!(
+            message,
+            ServerMessage::PlayerJoined { player_id, .. } if *player_id == first_id
+        )));
+        assert!(received.iter().any(|message| matches!(
+            message,
+            ServerMessage::PlayerJoined { name, .. } if name == "second"
+        )));
+
+        drop(first);
+        let received = pump_until(&mut app, &mut second, |message| {
+            matches!(message, ServerMessage::PlayerLeft { .. })
+        });
+        assert_eq!(
+            received.last(),
+            Some(&ServerMessage::PlayerLeft {
+                player_id: first_id
+            })
+        );
+    }
+
+    #[test]
+    fn mismatched_handshakes_are_rejected() {
+        let mut app = loopback_app();
+        let addr = app.world().resource::<NetworkServer>().local_addr();
+        let blocks = BlockRegistry::global().fingerprint();
+
+        for hello in [
+            ClientMessage::Hello {
+                version: PROTOCOL_VERSION + 1,
+                name: "from the future".into(),
+                blocks,
+            },
+            ClientMessage::Hello {
+                version: PROTOCOL_VERSION,
+                name: "with a block pack".into(),
+                blocks: blocks ^ 1,
+            },
+        ] {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_millis(20)))
+                .unwrap();
+            write_frame(&mut stream, &hello).unwrap();
+
+            let deadline = Instant::now() + Duration::from_secs(5);
+            let reply = loop {
+                assert!(Instant::now() < deadline, "no reply to the handshake");
+                app.update();
+                if let Ok(reply) = read_frame::<ServerMessage>(&mut stream) {
+                    break reply;
+                }
+            };
+            assert!(matches!(reply, ServerMessage::Rejected { .. }));
+            assert_eq!(
+                app.world().resource::<NetworkServer>().connection_count(),
+                0
+            );
+        }
+    }
+
+    #[test]
+    fn clients_that_stop_reading_are_disconnected() {
+        let mut app = loopback_app();
+        let addr = app.world().resource::<NetworkServer>().local_addr();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write_frame(
+            &mut stream,
+            &ClientMessage::Hello {
+                version: PROTOCOL_VERSION,
+                name: "asleep".into(),
+                blocks: BlockRegistry::global().fingerprint(),
+            },
+        )
+        .unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_millis(20)))
+            .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let player_id = loop {
+            assert!(Instant::now() < deadline, "no reply to the handshake");
+            app.update();
+            if let Ok(ServerMessage::Welcome { player_id, .. }) = read_frame(&mut stream) {
+                break player_id;
+            }
+        };
+
+        // Nothing is read from here on, so the socket and then the queue
+        // fill up.
+        let payload = ServerMessage::ChunkPayload {
+            position: ChunkPos::new(0, 0, 0),
+            revision: 1,
+            bytes: vec![0; 64 * 1024],
+        };
+        let server = app.world().resource::<NetworkServer>();
+        for _ in 0..4 * OUTGOING_QUEUE_CAPACITY {
+            server.send(player_id, payload.clone());
+        }
+        app.update();
+
+        assert_eq!(
+            app.world().resource::<NetworkServer>().connection_count(),
+            0
+        );
+        let mut players = app.world_mut().query::<&Player>();
+        assert_eq!(players.iter(app.world()).count(), 0);
+    }
+
+    #[test]
+    fn handshakes_after_joining_are_ignored() {
+        let mut app = loopback_app();
+        let addr = app.world().resource::<NetworkServer>().local_addr();
+        let mut client = ChunkClient::connect(addr, "twice").unwrap();
+        pump(&mut app, &mut client, 1);
+
+        client.send(ClientMessage::Hello {
+            version: PROTOCOL_VERSION + 1,
+            name: "twice again".into(),
+            blocks: 0,
+        });
+        for _ in 0..20 {
+            app.update();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        // Still connected, as the one player it joined as.
+        assert!(client.poll().is_empty());
+        assert!(client.is_connected());
+        assert_eq!(
+            app.world().resource::<NetworkServer>().connection_count(),
+            1
+        );
+        let mut players = app.world_mut().query::<&Player>();
+        let names: Vec<String> = players
+            .iter(app.world())
+            .map(|player| player.name.clone())
+            .collect();
+        assert_eq!(names, ["twice"]);
+    }
+}
@@ -0,0 +1,179 @@
+use super::interest::{ChunkInterestMetrics, InterestManager};
+use super::ConnectedPlayer;
+use crate::chunk::manager::spawn_known_chunk;
+use crate::chunk::{Chunk, ChunkGenerationQueue, ChunkManager, ChunkPos};
+use crate::planet::altitude_system::should_render_chunks;
+use crate::world::persistence::PersistenceHandler;
+use crate::world::persistence_worker::PersistenceMetrics;
+use crate::world::region::RegionChunkPersistence;
+use crate::world::{ChunkPayloadReady, PlanetChunkStore, WorldGenerator};
+use bevy::prelude::*;
+use std::collections::HashSet;
+
+const MAX_KNOWN_CHUNKS_PER_TICK: usize = 32;
+const MAX_QUEUED_CHUNKS_PER_TICK: usize = 96;
+const STATUS_INTERVAL_SECONDS: f32 = 10.0;
+
+/// Server version of `spawn_chunks_around_player`: every connected player
+/// feeds its view volume into the `InterestManager`, stored chunks are
+/// spawned straight from memory or persistence, and the rest are queued for
+/// baking.
+#[allow(clippy::too_many_arguments)]
+pub fn stream_chunks_for_players(
+    mut commands: Commands,
+    mut interest: ResMut<InterestManager>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut chunk_queue: ResMut<ChunkGenerationQueue>,
+    mut players: Query<(
+        Entity,
+        &Transform,
+        &ConnectedPlayer,
+        Option<&mut ChunkInterestMetrics>,
+    )>,
+    world_gen: Res<WorldGenerator>,
+    mut chunk_store: ResMut<PlanetChunkStore>,
+    mut chunk_events: EventWriter<ChunkPayloadReady>,
+    persistence: Option<Res<PersistenceHandler<RegionChunkPersistence>>>,
+) {
+    let mut viewing = HashSet::new();
+    for (entity, transform, player, _) in players.iter() {
+        // Players in space don't need terrain, same as on the client.
+        if !should_render_chunks(transform.translation.y) {
+            continue;
+        }
+        interest.update_viewer(
+            entity,
+            transform.translation,
+            transform.forward().into(),
+            player.view_distance.max(1),
+            &world_gen,
+        );
+        viewing.insert(entity);
+    }
+    let gone: Vec<Entity> = interest
+        .viewers()
+        .filter(|entity| !viewing.contains(entity))
+        .collect();
+    for entity in gone {
+        interest.remove_viewer(entity);
+    }
+
+    let mut spawned = 0;
+    let mut queued = 0;
+    while spawned < MAX_KNOWN_CHUNKS_PER_TICK && queued < MAX_QUEUED_CHUNKS_PER_TICK {
+        let Some((chunk_pos, score)) = interest.next_chunk() else {
+            break;
+        };
+
+        if chunk_manager.loaded_chunks.contains(&chunk_pos) || chunk_queue.contains(&chunk_pos) {
+            continue;
+        }
+
+        if spawn_known_chunk(
+            &mut commands,
+            &mut chunk_manager,
+            &mut chunk_queue,
+            &mut chunk_store,
+            &mut chunk_events,
+            persistence.as_deref(),
+            chunk_pos,
+        ) {
+            spawned += 1;
+        } else if chunk_queue.enqueue_with_priority(chunk_pos, score) {
+            queued += 1;
+            for entity in interest.viewers_of(&chunk_pos) {
+                if let Ok((_, _, _, Some(mut metrics))) = players.get_mut(entity) {
+                    metrics.queued += 1;
+                }
+            }
+        }
+    }
+
+    for (entity, _, _, metrics) in players.iter_mut() {
+        if let Some(mut metrics) = metrics {
+            let interested = interest.interested_chunks(entity);
+            if metrics.interested != interested {
+                metrics.interested = interested;
+            }
+        }
+    }
+
+    if spawned > 0 || queued > 0 {
+        debug!(
+            "server-stream: viewers={} spawned={} queued={} pending={} tasks={}",
+            interest.viewer_count(),
+            spawned,
+            queued,
+            chunk_queue.pending_len(),
+            chunk_queue.tasks.len()
+        );
+    }
+}
+
+/// Nothing meshes on the server, so clear the flag once the store has the
+/// edit instead of re-hashing the chunk every tick.
+pub fn clear_dirty_flags(mut chunks: Query<&mut Chunk>) {
+    for mut chunk in chunks.iter_mut() {
+        if chunk.dirty {
+            chunk.dirty = false;
+        }
+    }
+}
+
+/// Despawn chunk entities no player holds an interest in. Their storage
+/// stays in `PlanetChunkStore`, so a returning player gets them back without
+/// a bake.
+pub fn unload_unwatched_chunks(
+    mut commands: Commands,
+    mut chunk_manager: ResMut<ChunkManager>,
+    interest: Res<InterestManager>,
+    chunk_query: Query<(Entity, &ChunkPos)>,
+) {
+    for (entity, chunk_pos) in chunk_query.iter() {
+        if interest.refcount(chunk_pos) == 0 {
+            commands.entity(entity).despawn_recursive();
+            chunk_manager.loaded_chunks.remove(chunk_pos);
+        }
+    }
+}
+
+pub fn log_server_status(
+    time: Res<Time>,
+    players: Query<Option<&ChunkInterestMetrics>, With<ConnectedPlayer>>,
+    chunk_manager: Res<ChunkManager>,
+    chunk_queue: Res<ChunkGenerationQueue>,
+    persistence: Option<Res<PersistenceMetrics>>,
+    mut accumulator: Local<f32>,
+) {
+    *accumulator += time.delta_seconds();
+    if *accumulator < STATUS_INTERVAL_SECONDS {
+        return;
+    }
+    *accumulator = 0.0;
+
+    let (persisted, failures) = persistence
+        .map(|metrics| (metrics.persisted, metrics.failures))
+        .unwrap_or_default();
+    let mut total = ChunkInterestMetrics::default();
+    let mut player_count = 0;
+    for metrics in players.iter() {
+        player_count += 1;
+        if let Some(metrics) = metrics {
+            total.queued += metrics.queued;
+            total.sent += metrics.sent;
+            total.acknowledged += metrics.acknowledged;
+        }
+    }
+    info!(
+        "server: players={} loaded_chunks={} pending={} tasks={} queued={} sent={} acked={} persisted={} persist_failures={}",
+        player_count,
+        chunk_manager.loaded_chunks.len(),
+        chunk_queue.pending_len(),
+        chunk_queue.tasks.len(),
+        total.queued,
+        total.sent,
+        total.acknowledged,
+        persisted,
+        failures
+    );
+}
@@ -0,0 +1,250 @@
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use std::collections::HashSet;
+use std::ops::Deref;
+
+/// Simulation ticks per second, overriding `DEFAULT_TICK_RATE`.
+pub const TICK_RATE_ENV: &str = "FORGE_TICK_RATE";
+pub const DEFAULT_TICK_RATE: f64 = 60.0;
+/// Tick rates `/tickrate` and `FORGE_TICK_RATE` accept.
+pub const MIN_TICK_RATE: f64 = 10.0;
+pub const MAX_TICK_RATE: f64 = 240.0;
+
+pub fn tick_rate_from_env() -> f64 {
+    std::env::var(TICK_RATE_ENV)
+        .ok()
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .filter(|rate| (MIN_TICK_RATE..=MAX_TICK_RATE).contains(rate))
+        .unwrap_or(DEFAULT_TICK_RATE)
+}
+
+/// Runs the game simulation (player physics, dropped items) in `FixedUpdate`
+/// at a fixed tick rate, whatever the frame rate, and interpolates the
+/// simulated transforms between the last two ticks for rendering.
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        let tick_rate = tick_rate_from_env();
+        info!("Simulating at {} ticks per second", tick_rate);
+        app.insert_resource(Time::<Fixed>::from_hz(tick_rate))
+            .add_plugins(TickInputPlugin)
+            .add_systems(First, restore_simulated_transforms)
+            .add_systems(FixedPostUpdate, store_simulated_transforms)
+            .add_systems(
+                PostUpdate,
+                interpolate_simulated_transforms.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Latches keyboard input for the fixed ticks.
+pub struct TickInputPlugin;
+
+impl Plugin for TickInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TickInput>()
+            .add_systems(PreUpdate, latch_tick_input.after(InputSystem))
+            .add_systems(FixedFirst, begin_tick_input);
+    }
+}
+
+/// The keyboard as the simulation sees it. A key pressed in a frame that runs
+/// no tick is still just pressed on the next tick, and a frame that runs
+/// several ticks reports it just pressed on the first of them only.
+#[derive(Resource, Default)]
+pub struct TickInput {
+    keys: ButtonInput<KeyCode>,
+    pending: HashSet<KeyCode>,
+}
+
+impl TickInput {
+    /// The keys the current tick sees, for replays to put recorded input in.
+    #[allow(dead_code)]
+    pub fn keys_mut(&mut self) -> &mut ButtonInput<KeyCode> {
+        &mut self.keys
+    }
+}
+
+impl Deref for TickInput {
+    type Target = ButtonInput<KeyCode>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.keys
+    }
+}
+
+fn latch_tick_input(keyboard: Res<ButtonInput<KeyCode>>, mut tick_input: ResMut<TickInput>) {
+    tick_input
+        .pending
+        .extend(keyboard.get_just_pressed().copied());
+}
+
+fn begin_tick_input(keyboard: Res<ButtonInput<KeyCode>>, mut tick_input: ResMut<TickInput>) {
+    let TickInput { keys, pending } = &mut *tick_input;
+    keys.clear();
+    for key in pending.drain() {
+        // Released and pressed again since the last tick counts as a press.
+        keys.release(key);
+        keys.press(key);
+    }
+    let released: Vec<KeyCode> = keys
+        .get_pressed()
+        .filter(|key| !keyboard.pressed(**key))
+        .copied()
+        .collect();
+    for key in released {
+        keys.release(key);
+    }
+    for key in keyboard.get_pressed() {
+        keys.press(*key);
+    }
+}
+
+/// Translation of an entity moved by the simulation, at the last two ticks.
+/// Between `PostUpdate` and the next `First` its `Transform` holds the
+/// interpolated position; the rest of the frame sees the simulated one.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct InterpolatedTransform {
+    previous: Vec3,
+    current: Vec3,
+    rendered: Vec3,
+}
+
+impl InterpolatedTransform {
+    pub fn new(translation: Vec3) -> Self {
+        Self {
+            previous: translation,
+            current: translation,
+            rendered: translation,
+        }
+    }
+
+    /// Translation as of the last tick.
+    pub fn current(&self) -> Vec3 {
+        self.current
+    }
+
+    /// Start over from `translation`, without interpolating towards it.
+    fn snap(&mut self, translation: Vec3) {
+        self.previous = translation;
+        self.current = translation;
+    }
+}
+
+fn restore_simulated_transforms(mut query: Query<(&mut Transform, &mut InterpolatedTransform)>) {
+    for (mut transform, mut interpolated) in query.iter_mut() {
+        if transform.translation == interpolated.rendered {
+            transform.translation = interpolated.current;
+        } else {
+            // Moved since it was drawn, by something other than the simulation.
+            interpolated.snap(transform.translation);
+        }
+    }
+}
+
+fn store_simulated_transforms(mut query: Query<(&Transform, &mut InterpolatedTransform)>) {
+    for (transform, mut interpolated) in query.iter_mut() {
+        interpolated.previous = interpolated.current;
+        interpolated.current = transform.translation;
+    }
+}
+
+fn interpolate_simulated_transforms(
+    time: Res<Time<Fixed>>,
+    mut query: Query<(&mut Transform, &mut InterpolatedTransform)>,
+) {
+    let alpha = time.overstep_fraction();
+    for (mut transform, mut interpolated) in query.iter_mut() {
+        if transform.translation != interpolated.current {
+            // Teleported outside the simulation this frame.
+            interpolated.snap(transform.translation);
+        }
+        transform.translation = interpolated.previous.lerp(interpolated.current, alpha);
+        interpolated.rendered = transform.translation;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::time::TimeUpdateStrategy;
+    use std::time::Duration;
+
+    #[derive(Component)]
+    struct Mover;
+
+    fn step(mut query: Query<&mut Transform, With<Mover>>) {
+        for mut transform in query.iter_mut() {
+            transform.translation.x += 1.0;
+        }
+    }
+
+    #[derive(Resource, Default)]
+    struct Jumps(u32);
+
+    fn count_jumps(tick_input: Res<TickInput>, mut jumps: ResMut<Jumps>) {
+        if tick_input.just_pressed(KeyCode::Space) {
+            jumps.0 += 1;
+        }
+    }
+
+    #[test]
+    fn ticks_are_fixed_and_rendered_in_between() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<Jumps>()
+            .insert_resource(Time::<Fixed>::from_hz(10.0))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                25,
+            )))
+            .add_plugins(TickInputPlugin)
+            .add_systems(First, restore_simulated_transforms)
+            .add_systems(FixedUpdate, (step, count_jumps))
+            .add_systems(FixedPostUpdate, store_simulated_transforms)
+            .add_systems(PostUpdate, interpolate_simulated_transforms);
+        let entity = app
+            .world_mut()
+            .spawn((
+                Mover,
+                Transform::default(),
+                InterpolatedTransform::new(Vec3::ZERO),
+            ))
+            .id();
+
+        // The first update starts the clock; then four 25 ms frames make
+        // one 100 ms tick.
+        app.update();
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::Space);
+        for _ in 0..4 {
+            app.update();
+            app.world_mut()
+                .resource_mut::<ButtonInput<KeyCode>>()
+                .clear();
+        }
+        let interpolated = *app.world().get::<InterpolatedTransform>(entity).unwrap();
+        assert_eq!(interpolated.current().x, 1.0);
+        assert_eq!(app.world().resource::<Jumps>().0, 1);
+
+        // Halfway to the next tick, it is drawn halfway between the last two.
+        app.update();
+        app.update();
+        let transform = app.world().get::<Transform>(entity).unwrap();
+        assert!((transform.translation.x - 0.5).abs() < 1e-4);
+        app.update();
+        assert_eq!(app.world().resource::<Jumps>().0, 1);
+
+        // Moving it outside the simulation isn't undone.
+        app.world_mut()
+            .get_mut::<Transform>(entity)
+            .unwrap()
+            .translation = Vec3::new(0.0, 10.0, 0.0);
+        app.update();
+        let transform = app.world().get::<Transform>(entity).unwrap();
+        assert_eq!(transform.translation.y, 10.0);
+    }
+}
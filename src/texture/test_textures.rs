@@ -23,6 +23,11 @@ pub fn generate_test_textures() {
     generate_leaves_texture(&texture_dir.join("leaves"));
     generate_cobblestone_texture(&texture_dir.join("cobblestone"));
     generate_planks_texture(&texture_dir.join("planks"));
+    generate_ore_texture(&texture_dir.join("coal_ore"), [40, 40, 40]);
+    generate_ore_texture(&texture_dir.join("iron_ore"), [200, 160, 130]);
+    generate_ore_texture(&texture_dir.join("copper_ore"), [200, 110, 60]);
+    generate_ore_texture(&texture_dir.join("gold_ore"), [240, 200, 60]);
+    generate_tall_grass_texture(&texture_dir.join("tall_grass"));
 
     println!("Test textures generated successfully!");
 }
@@ -86,6 +91,52 @@ fn generate_stone_texture(dir: &Path) {
     stone.save(dir.join("all.png")).unwrap();
 }
 
+/// Stone with flecks of the ore's colour.
+fn generate_ore_texture(dir: &Path, color: [u8; 3]) {
+    std::fs::create_dir_all(dir).ok();
+
+    let mut ore = RgbaImage::new(32, 32);
+    for y in 0..32 {
+        for x in 0..32 {
+            let noise1 = ((x * 3 + y * 5) % 11) as f32 / 11.0;
+            let noise2 = ((x * 7 + y * 2) % 13) as f32 / 13.0;
+            let fleck = (x * 13 + y * 29) % 23 < 4 && (x / 4 + y / 4) % 3 != 0;
+            if fleck {
+                let shade = 0.8 + noise1 * 0.2;
+                let [r, g, b] = color.map(|channel| (channel as f32 * shade) as u8);
+                ore.put_pixel(x, y, Rgba([r, g, b, 255]));
+            } else {
+                let gray = 100 + (noise1 * 50.0 + noise2 * 30.0) as u8;
+                ore.put_pixel(x, y, Rgba([gray, gray, gray, 255]));
+            }
+        }
+    }
+    ore.save(dir.join("all.png")).unwrap();
+}
+
+/// Upright blades, lighter towards the tips.
+fn generate_tall_grass_texture(dir: &Path) {
+    std::fs::create_dir_all(dir).ok();
+
+    // Blades of varying height; the gaps between them are see-through,
+    // since plants are drawn as crossed quads cut out by alpha.
+    let mut grass = RgbaImage::new(32, 32);
+    for y in 0..32 {
+        for x in 0..32 {
+            let blade = (x * 7 % 5) < 2;
+            let top = 4 + (x * 13 % 11) * 2;
+            if !blade || y < top {
+                grass.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+                continue;
+            }
+            let tip = (32 - y) as f32 / 32.0;
+            let g = 110 + (tip * 90.0) as u8;
+            grass.put_pixel(x, y, Rgba([g / 3, g, g / 5, 255]));
+        }
+    }
+    grass.save(dir.join("all.png")).unwrap();
+}
+
 fn generate_dirt_texture(dir: &Path) {
     std::fs::create_dir_all(dir).ok();
 
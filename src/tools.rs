@@ -1,7 +1,9 @@
 use crate::block::BlockType;
+use serde::Deserialize;
 
 #[allow(dead_code)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Tool {
     #[default]
     Hand,
@@ -13,24 +15,12 @@ pub enum Tool {
 impl Tool {
     /// Get the efficiency multiplier for extracting a specific block type
     pub fn efficiency_for(&self, block: BlockType) -> f32 {
-        match (self, block) {
-            // Pickaxe is best for stone-like blocks
-            (
-                Tool::Pickaxe,
-                BlockType::Stone | BlockType::Cobblestone | BlockType::Ice | BlockType::PackedIce,
-            ) => 3.0,
-
-            // Shovel is best for soft blocks
-            (
-                Tool::Shovel,
-                BlockType::Dirt | BlockType::Sand | BlockType::Grass | BlockType::Snow,
-            ) => 3.0,
-
-            // Axe is best for wood (when we have more wood types)
-            (Tool::Axe, BlockType::Wood | BlockType::Planks) => 3.0,
-
+        match self {
             // Hand is baseline for everything
-            (Tool::Hand, _) => 1.0,
+            Tool::Hand => 1.0,
+
+            // The block's own tool (block definitions' `tool`) is best
+            tool if block.preferred_tool() == Some(*tool) => 3.0,
 
             // Other tools on wrong materials are slightly better than hand
             _ => 1.2,
@@ -395,6 +395,66 @@ impl CommandRegistry {
                 }
             },
         );
+
+        self.register_command(
+            "ao",
+            "Toggle ambient occlusion on chunk meshes",
+            "/ao [on|off]",
+            PermissionLevel::Player,
+            |args, world| {
+                let Some(mut settings) =
+                    world.get_resource_mut::<crate::chunk::material::ChunkRenderSettings>()
+                else {
+                    return Err("Chunk rendering not available".to_string());
+                };
+
+                let enabled = match args.get(1).copied() {
+                    None => !settings.ambient_occlusion,
+                    Some("on") => true,
+                    Some("off") => false,
+                    Some(_) => return Err("Usage: /ao [on|off]".to_string()),
+                };
+                settings.ambient_occlusion = enabled;
+
+                Ok(format!(
+                    "Ambient occlusion {}",
+                    if enabled { "enabled" } else { "disabled" }
+                ))
+            },
+        );
+
+        self.register_command(
+            "tickrate",
+            "Set or display the simulation tick rate",
+            "/tickrate [ticks per second]",
+            PermissionLevel::Admin,
+            |args, world| {
+                use crate::simulation::{MAX_TICK_RATE, MIN_TICK_RATE};
+
+                let Some(rate) = args.get(1) else {
+                    let timestep = world.resource::<Time<Fixed>>().timestep();
+                    return Ok(format!(
+                        "Simulating at {:.1} ticks per second",
+                        1.0 / timestep.as_secs_f64()
+                    ));
+                };
+                let rate = rate
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|rate| (MIN_TICK_RATE..=MAX_TICK_RATE).contains(rate))
+                    .ok_or_else(|| {
+                        format!(
+                            "Tick rate must be between {} and {}",
+                            MIN_TICK_RATE, MAX_TICK_RATE
+                        )
+                    })?;
+                if world.contains_resource::<crate::replay::recorder::ReplayRecorder>() {
+                    return Err("Can't change the tick rate while recording".to_string());
+                }
+                world.resource_mut::<Time<Fixed>>().set_timestep_hz(rate);
+                Ok(format!("Simulating at {:.1} ticks per second", rate))
+            },
+        );
     }
 
     pub fn execute_command(&self, input: &str, world: &mut World) -> Result<String, String> {
@@ -4,6 +4,7 @@ use crate::chunk::{Chunk, ChunkPos, CHUNK_SIZE, CHUNK_SIZE_F32};
 use crate::loading::GameState;
 use crate::physics::PlayerPhysics;
 use crate::planet::CelestialData;
+use crate::world::persistence_worker::PersistenceMetrics;
 use crate::world::CurrentTemperature;
 use bevy::prelude::*;
 
@@ -82,6 +83,7 @@ fn toggle_debug_overlay(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn update_debug_text(
     mut text_query: Query<&mut Text, With<DebugText>>,
     player_query: Query<(&Transform, &PlayerPhysics, &CameraController), With<PlayerCamera>>,
@@ -90,6 +92,7 @@ fn update_debug_text(
     debug_state: Res<DebugOverlayState>,
     game_time: Res<GameTime>,
     planet: Res<CelestialData>,
+    persistence: Option<Res<PersistenceMetrics>>,
 ) {
     let Ok((transform, physics, controller)) = player_query.get_single() else {
         return;
@@ -130,6 +133,18 @@ fn update_debug_text(
         let block_y = feet_pos.y.floor();
         let offset_in_block = feet_pos.y - block_y;
 
+        // Voxel storage footprint of the loaded chunks
+        let mut loaded_chunks = 0;
+        let mut uniform_chunks = 0;
+        let mut chunk_bytes = 0;
+        for (chunk, _) in chunk_query.iter() {
+            loaded_chunks += 1;
+            uniform_chunks += chunk.storage.uniform_block().is_some() as usize;
+            chunk_bytes += chunk.storage.memory_usage();
+        }
+        let average_chunk_bytes = chunk_bytes / loaded_chunks.max(1);
+        let persistence = persistence.map(|metrics| *metrics).unwrap_or_default();
+
         // Update debug text with more detailed info
         let aabb_bottom = physics.aabb.center.y - physics.aabb.half_extents.y;
         let aabb_top = physics.aabb.center.y + physics.aabb.half_extents.y;
@@ -153,6 +168,11 @@ fn update_debug_text(
          Day length: {} hours\n\
          Gravity: {}g\n\
          \n\
+         Chunks: {} loaded, {} uniform\n\
+         Chunk memory: {:.1} MiB total, {:.1} KiB/chunk\n\
+         Persistence: {} written ({:.1} MiB), {} failed, {} coalesced\n\
+         Persist queue: {} in channel, {} deferred, {} stalls, batch {:.1}/{:.1} ms\n\
+         \n\
          Expected feet on ground: Y = {}.00\n\
          Actual difference: {:.3}",
             transform.translation.x,
@@ -174,6 +194,19 @@ fn update_debug_text(
             planet.orbital_radius,
             planet.rotation_period,
             planet.surface_gravity,
+            loaded_chunks,
+            uniform_chunks,
+            chunk_bytes as f32 / (1024.0 * 1024.0),
+            average_chunk_bytes as f32 / 1024.0,
+            persistence.persisted,
+            persistence.bytes_written as f32 / (1024.0 * 1024.0),
+            persistence.failures,
+            persistence.coalesced,
+            persistence.in_channel,
+            persistence.deferred,
+            persistence.backpressure_events,
+            persistence.last_batch_ms,
+            persistence.max_batch_ms,
             ground_y as i32,
             feet_pos.y - ground_y,
         );
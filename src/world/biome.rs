@@ -1,6 +1,9 @@
 use crate::block::BlockType;
+use serde::Deserialize;
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+/// Names in data files (structure templates) are snake case: `temperate_forest`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Biome {
     DeepOcean,
     Ocean,
@@ -6,20 +6,50 @@ use std::sync::Arc;
 
 use bevy::prelude::*;
 
-use crate::chunk::{ChunkPos, ChunkStorage};
+use crate::chunk::{ChunkPayloadHeader, ChunkPos, ChunkStorage};
 
 const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
 const FNV_PRIME: u64 = 0x100000001b3;
 
-fn compute_storage_hash(storage: &ChunkStorage) -> u64 {
+pub fn compute_storage_hash(storage: &ChunkStorage) -> u64 {
     let mut hash = FNV_OFFSET_BASIS;
     for block in storage.iter() {
-        hash ^= block.to_u8() as u64;
+        hash ^= block.id() as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    for (index, level) in storage.fluid_levels() {
+        hash ^= ((index as u64) << 8) | level as u64;
         hash = hash.wrapping_mul(FNV_PRIME);
     }
     hash
 }
 
+/// Hash of a set of chunks: each chunk's position and storage hash, folded
+/// in position order so the result doesn't depend on iteration order.
+pub fn compute_world_hash<'a>(
+    chunks: impl IntoIterator<Item = (ChunkPos, &'a ChunkStorage)>,
+) -> u64 {
+    let mut entries: Vec<(ChunkPos, u64)> = chunks
+        .into_iter()
+        .map(|(position, storage)| (position, compute_storage_hash(storage)))
+        .collect();
+    entries.sort_unstable_by_key(|(position, _)| (position.x, position.y, position.z));
+
+    let mut hash = FNV_OFFSET_BASIS;
+    for (position, storage_hash) in entries {
+        for value in [
+            position.x as u32 as u64,
+            position.y as u32 as u64,
+            position.z as u32 as u64,
+            storage_hash,
+        ] {
+            hash ^= value;
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+    hash
+}
+
 #[derive(Event, Clone)]
 pub struct ChunkPayloadReady {
     pub position: ChunkPos,
@@ -56,6 +86,14 @@ impl PlanetChunkStore {
             .map(|record| record.storage.clone())
     }
 
+    /// Borrow a chunk's storage without bumping the `Arc`; used by hot loops
+    /// such as light propagation that touch many voxels per lookup.
+    pub fn storage(&self, position: &ChunkPos) -> Option<&ChunkStorage> {
+        self.records
+            .get(position)
+            .map(|record| record.storage.as_ref())
+    }
+
     pub fn get_with_revision(&self, position: &ChunkPos) -> Option<(Arc<ChunkStorage>, u32)> {
         self.records
             .get(position)
@@ -132,10 +170,17 @@ impl PlanetChunkStore {
         self.dirty.len()
     }
 
+    /// Encode the stored chunk with its position and revision stamped in the
+    /// header; generator fields are left zeroed since the store does not know
+    /// which generator baked it.
     pub fn encode_payload_bytes(&self, position: &ChunkPos) -> Option<Vec<u8>> {
-        self.records
-            .get(position)
-            .map(|record| record.storage.encode_bytes())
+        self.records.get(position).map(|record| {
+            record.storage.encode_bytes(ChunkPayloadHeader {
+                position: *position,
+                revision: record.revision,
+                ..Default::default()
+            })
+        })
     }
 
     pub fn contains(&self, position: &ChunkPos) -> bool {
@@ -1,12 +1,13 @@
-use bevy::prelude::Resource;
+use bevy::prelude::{Component, Resource};
 use serde::{Deserialize, Serialize};
 
 use crate::planet::PlanetConfig;
 
 use super::defaults;
 
-/// Resource tracking the current air temperature at player position.
-#[derive(Resource, Default)]
+/// Air temperature at a player's position: a resource for the local player,
+/// a component on each player on the server.
+#[derive(Resource, Component, Default)]
 pub struct CurrentTemperature {
     pub fahrenheit: f32,
     pub celsius: f32,
@@ -180,6 +181,18 @@ impl Default for WorldGenConfig {
 }
 
 impl WorldGenConfig {
+    /// Stable FNV-1a hash of the serialised config, stamped into chunk
+    /// payloads so stale bakes can be told apart from current ones.
+    pub fn fingerprint(&self) -> u64 {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+        const FNV_PRIME: u64 = 0x100000001b3;
+
+        let bytes = bincode::serialize(self).expect("world config serialises");
+        bytes.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+        })
+    }
+
     pub fn from_planet_config(config: &PlanetConfig) -> Self {
         let planet_size = config.size_chunks as u32 * 32;
 
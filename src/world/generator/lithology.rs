@@ -22,6 +22,192 @@ pub struct LithologyProfile {
     pub ore_bias: f32,
 }
 
+/// Blocks below the strata that ores are still placed in.
+const BASEMENT_ORE_DEPTH: u32 = 64;
+/// Veins are counted per cube of this many blocks a side.
+pub const ORE_CELL: i32 = 16;
+
+/// Where in a profile an ore forms.
+#[derive(Clone, Copy, Debug)]
+enum OreHost {
+    /// Strata of this block.
+    Stratum(BlockType),
+    /// The basement below the strata.
+    Basement,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Crust {
+    Continental,
+    Oceanic,
+    Any,
+}
+
+struct OreRule {
+    ore: BlockType,
+    host: OreHost,
+    crust: Crust,
+    /// Veins per `ORE_CELL` cube at full `ore_bias`.
+    veins: f32,
+    vein_size: u32,
+}
+
+/// Coal in continental sediments, iron in most rock, copper in oceanic
+/// basalt and continental metamorphics, gold deep in continental crust.
+const ORE_RULES: [OreRule; 8] = [
+    OreRule {
+        ore: BlockType::CoalOre,
+        host: OreHost::Stratum(BlockType::Stone),
+        crust: Crust::Continental,
+        veins: 1.2,
+        vein_size: 12,
+    },
+    OreRule {
+        ore: BlockType::IronOre,
+        host: OreHost::Stratum(BlockType::Stone),
+        crust: Crust::Any,
+        veins: 0.5,
+        vein_size: 8,
+    },
+    OreRule {
+        ore: BlockType::IronOre,
+        host: OreHost::Stratum(BlockType::Cobblestone),
+        crust: Crust::Continental,
+        veins: 0.8,
+        vein_size: 8,
+    },
+    OreRule {
+        ore: BlockType::IronOre,
+        host: OreHost::Basement,
+        crust: Crust::Continental,
+        veins: 0.4,
+        vein_size: 8,
+    },
+    OreRule {
+        ore: BlockType::CopperOre,
+        host: OreHost::Stratum(BlockType::Stone),
+        crust: Crust::Oceanic,
+        veins: 0.9,
+        vein_size: 10,
+    },
+    OreRule {
+        ore: BlockType::CopperOre,
+        host: OreHost::Stratum(BlockType::Cobblestone),
+        crust: Crust::Continental,
+        veins: 0.3,
+        vein_size: 6,
+    },
+    OreRule {
+        ore: BlockType::GoldOre,
+        host: OreHost::Stratum(BlockType::Cobblestone),
+        crust: Crust::Continental,
+        veins: 0.1,
+        vein_size: 5,
+    },
+    OreRule {
+        ore: BlockType::GoldOre,
+        host: OreHost::Basement,
+        crust: Crust::Continental,
+        veins: 0.2,
+        vein_size: 5,
+    },
+];
+
+/// A band of rock in one column where an ore forms veins.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OreDeposit {
+    pub ore: BlockType,
+    /// The rock veins replace.
+    pub host: BlockType,
+    /// Depth below the surface, in blocks: `min_depth..max_depth`.
+    pub min_depth: u32,
+    pub max_depth: u32,
+    /// Veins started per `ORE_CELL` cube, `ore_bias` included.
+    pub veins_per_cell: f32,
+    pub vein_size: u32,
+}
+
+impl LithologyProfile {
+    /// Oceanic plates sit on bedrock; continental ones on a stone basement.
+    pub fn is_oceanic(&self) -> bool {
+        self.basement_block == BlockType::Bedrock
+    }
+
+    /// The block at `depth` blocks below the surface.
+    #[allow(dead_code)]
+    pub fn block_at_depth(&self, depth: u32) -> BlockType {
+        let mut bottom = self.surface_depth.max(1) as u32;
+        if depth < bottom {
+            return self.surface_block;
+        }
+        for layer in &self.strata {
+            bottom += layer.thickness as u32;
+            if depth < bottom {
+                return layer.block;
+            }
+        }
+        self.basement_block
+    }
+
+    /// Where each ore forms in this profile's column.
+    pub fn ore_deposits(&self) -> Vec<OreDeposit> {
+        let crust = if self.is_oceanic() {
+            Crust::Oceanic
+        } else {
+            Crust::Continental
+        };
+        let mut bands = Vec::new();
+        let mut top = self.surface_depth.max(1) as u32;
+        for layer in &self.strata {
+            let bottom = top + layer.thickness as u32;
+            bands.push((Some(layer.block), layer.block, top, bottom));
+            top = bottom;
+        }
+        bands.push((None, self.basement_block, top, top + BASEMENT_ORE_DEPTH));
+
+        let mut deposits = Vec::new();
+        for rule in &ORE_RULES {
+            if rule.crust != Crust::Any && rule.crust != crust {
+                continue;
+            }
+            for &(stratum, host, min_depth, max_depth) in &bands {
+                let matches = match rule.host {
+                    OreHost::Stratum(block) => stratum == Some(block),
+                    OreHost::Basement => stratum.is_none(),
+                };
+                if matches && host.is_solid() && host.is_breakable() {
+                    deposits.push(OreDeposit {
+                        ore: rule.ore,
+                        host,
+                        min_depth,
+                        max_depth,
+                        veins_per_cell: rule.veins * self.ore_bias,
+                        vein_size: rule.vein_size,
+                    });
+                }
+            }
+        }
+        deposits
+    }
+
+    /// Ore blocks one column is expected to hold, per ore.
+    #[allow(dead_code)]
+    pub fn expected_ore_yield(&self) -> Vec<(BlockType, f32)> {
+        let cell_volume = (ORE_CELL * ORE_CELL * ORE_CELL) as f32;
+        let mut yields: Vec<(BlockType, f32)> = Vec::new();
+        for deposit in self.ore_deposits() {
+            let thickness = (deposit.max_depth - deposit.min_depth) as f32;
+            let blocks =
+                deposit.veins_per_cell * deposit.vein_size as f32 * thickness / cell_volume;
+            match yields.iter_mut().find(|(ore, _)| *ore == deposit.ore) {
+                Some((_, total)) => *total += blocks,
+                None => yields.push((deposit.ore, blocks)),
+            }
+        }
+        yields
+    }
+}
+
 pub fn generate_plate_lithology<F>(
     config: &WorldGenConfig,
     plate_map: &PlateMap,
@@ -115,3 +301,55 @@ fn oceanic_profile(rng: &mut StdRng) -> LithologyProfile {
         ore_bias: rng.gen_range(0.4..0.7),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn continental_and_oceanic_plates_yield_different_ores() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let continental = continental_profile(&mut rng);
+        let oceanic = oceanic_profile(&mut rng);
+        let yield_of = |profile: &LithologyProfile, ore: BlockType| {
+            profile
+                .expected_ore_yield()
+                .into_iter()
+                .find(|(block, _)| *block == ore)
+                .map_or(0.0, |(_, blocks)| blocks)
+        };
+
+        for ore in [BlockType::CoalOre, BlockType::GoldOre] {
+            assert!(yield_of(&continental, ore) > 0.0);
+            assert_eq!(yield_of(&oceanic, ore), 0.0);
+        }
+        assert!(
+            yield_of(&oceanic, BlockType::CopperOre) > yield_of(&continental, BlockType::CopperOre)
+        );
+        assert!(yield_of(&oceanic, BlockType::IronOre) > 0.0);
+
+        // Deposits sit in the strata they are named for, never in bedrock.
+        for profile in [&continental, &oceanic] {
+            for deposit in profile.ore_deposits() {
+                assert_ne!(deposit.host, BlockType::Bedrock);
+                if deposit.min_depth
+                    < profile.surface_depth as u32
+                        + profile
+                            .strata
+                            .iter()
+                            .map(|layer| layer.thickness as u32)
+                            .sum::<u32>()
+                {
+                    assert_eq!(profile.block_at_depth(deposit.min_depth), deposit.host);
+                    assert_eq!(profile.block_at_depth(deposit.max_depth - 1), deposit.host);
+                }
+            }
+        }
+
+        // Ore bias scales every yield.
+        let mut poor = continental.clone();
+        poor.ore_bias *= 0.5;
+        let rich = yield_of(&continental, BlockType::IronOre);
+        assert!((yield_of(&poor, BlockType::IronOre) - rich * 0.5).abs() < 1e-4);
+    }
+}
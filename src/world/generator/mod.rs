@@ -13,12 +13,16 @@ use super::chunk_store::{
     ChunkPayloadQueue, ChunkPayloadReady, PayloadDebugPlugin, PlanetChunkStore,
 };
 use super::config::{CurrentTemperature, WorldGenConfig};
-use super::persistence::{ChunkPersistencePlugin, DiskChunkPersistence, PersistenceConfig};
+use super::persistence::{
+    persistence_dir_from_env, ChunkPersistencePlugin, PersistenceConfig, WorldLock,
+};
+use super::region::RegionChunkPersistence;
 use crate::block::BlockType;
 use crate::camera::PlayerCamera;
-use crate::chunk::{ChunkPos, CHUNK_SIZE_F32};
+use crate::chunk::{ChunkPayloadHeader, ChunkPos, CHUNK_SIZE_F32};
 use crate::loading::GameState;
 use crate::planet::PlanetConfig;
+use crate::player::Player;
 use crate::world::package::planet_package_paths;
 
 mod continents;
@@ -71,6 +75,7 @@ where
 #[derive(Resource, Clone)]
 pub struct WorldGenerator {
     config: WorldGenConfig,
+    config_hash: u64,
     continent_noise: Perlin,
     detail_noise: Perlin,
     micro_detail_noise: Perlin,
@@ -187,6 +192,7 @@ impl WorldGenerator {
         let hydrology_rain_noise = Perlin::new(seed.wrapping_add(6));
 
         let mut generator = Self {
+            config_hash: config.fingerprint(),
             config,
             continent_noise,
             detail_noise,
@@ -262,6 +268,7 @@ impl WorldGenerator {
         let hydrology_rain_noise = Perlin::new(seed.wrapping_add(6));
 
         Self {
+            config_hash: metadata.config.fingerprint(),
             config: metadata.config.clone(),
             continent_noise,
             detail_noise,
@@ -284,6 +291,16 @@ impl WorldGenerator {
         &self.config
     }
 
+    /// Header stamped into payloads for chunks baked by this generator.
+    pub fn payload_header(&self, position: ChunkPos, revision: u32) -> ChunkPayloadHeader {
+        ChunkPayloadHeader {
+            position,
+            revision,
+            generator_seed: self.config.seed,
+            config_hash: self.config_hash,
+        }
+    }
+
     #[allow(dead_code)]
     pub fn planet_size(&self) -> u32 {
         self.config.planet_size
@@ -383,6 +400,21 @@ impl WorldGenerator {
         }
     }
 
+    /// The `cave_bias` of `lithology_profile_at`, blended without building
+    /// the rest of the profile.
+    pub fn cave_bias_at(&self, world_x: f32, world_z: f32) -> f32 {
+        let planet_size = self.config.planet_size as f32;
+        let u = (world_x / planet_size).rem_euclid(1.0);
+        let v = (world_z / planet_size).rem_euclid(1.0);
+        let cave_bias: f32 = self
+            .plate_map
+            .plate_weights(u, v)
+            .iter()
+            .map(|(plate, weight)| weight * self.plate_lithology[*plate].cave_bias)
+            .sum();
+        cave_bias.clamp(0.0, 1.0)
+    }
+
     pub fn metadata(&self) -> WorldMetadata {
         WorldMetadata {
             config: self.config.clone(),
@@ -435,25 +467,27 @@ pub struct WorldPlugin;
 
 impl Plugin for WorldPlugin {
     fn build(&self, app: &mut App) {
+        // Keeps `persistence_gc --prune` out while the world is open.
+        let dir = persistence_dir_from_env();
+        match WorldLock::shared(&dir) {
+            Ok(lock) => {
+                app.insert_resource(lock);
+            }
+            Err(error) => warn!("Could not lock world {:?}: {}", dir, error),
+        }
+
         app.init_resource::<CurrentTemperature>()
             .init_resource::<PlanetChunkStore>()
             .init_resource::<ChunkPayloadQueue>()
             .add_event::<ChunkPayloadReady>()
             .add_plugins(PayloadDebugPlugin)
-            .add_plugins(ChunkPersistencePlugin::new(
-                DiskChunkPersistence::new(
-                    std::env::var("FORGE_PERSISTENCE_DIR")
-                        .ok()
-                        .filter(|dir| !dir.trim().is_empty())
-                        .map(PathBuf::from)
-                        .unwrap_or_else(|| PathBuf::from("target/chunk_payload_persistence")),
-                ),
-                PersistenceConfig {
-                    enabled: std::env::var("FORGE_PERSISTENCE_ENABLED")
-                        .map(|value| !matches!(value.trim(), "0" | "false" | "False" | "FALSE"))
-                        .unwrap_or(true),
-                },
-            ))
+            .add_plugins({
+                let config = PersistenceConfig::from_env();
+                ChunkPersistencePlugin::new(
+                    RegionChunkPersistence::with_fsync(persistence_dir_from_env(), config.fsync),
+                    config,
+                )
+            })
             .add_systems(Startup, setup_world_generator)
             .add_systems(
                 Update,
@@ -462,7 +496,16 @@ impl Plugin for WorldPlugin {
     }
 }
 
-fn setup_world_generator(mut commands: Commands, planet_config: Res<PlanetConfig>) {
+fn setup_world_generator(
+    mut commands: Commands,
+    planet_config: Res<PlanetConfig>,
+    existing: Option<Res<WorldGenerator>>,
+) {
+    // Tools and the headless server may supply a generator up front.
+    if existing.is_some() {
+        return;
+    }
+
     let world_name = planet_config.name.clone();
     let (config_path, metadata_path) = planet_package_paths(&world_name);
 
@@ -555,7 +598,21 @@ fn update_temperature(
         return;
     };
 
-    let pos = transform.translation;
+    sample_temperature(&mut temperature, &world_gen, transform.translation);
+}
+
+/// Server-side counterpart of `update_temperature`, for every player.
+#[allow(dead_code)]
+pub fn update_player_temperatures(
+    world_gen: Res<WorldGenerator>,
+    mut players: Query<(&Transform, &mut CurrentTemperature), With<Player>>,
+) {
+    for (transform, mut temperature) in players.iter_mut() {
+        sample_temperature(&mut temperature, &world_gen, transform.translation);
+    }
+}
+
+fn sample_temperature(temperature: &mut CurrentTemperature, world_gen: &WorldGenerator, pos: Vec3) {
     let chunk_x = (pos.x / 32.0).floor() as i32;
     let chunk_z = (pos.z / 32.0).floor() as i32;
 
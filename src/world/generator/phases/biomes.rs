@@ -7,6 +7,7 @@ use super::super::{
     util::{lerp_color, lerp_f32},
     WorldGenerator,
 };
+use super::blending::ClimateLattice;
 use crate::block::BlockType;
 use crate::chunk::{ChunkPos, ChunkStorage, CHUNK_SIZE};
 use crate::world::biome::Biome;
@@ -63,6 +64,19 @@ impl ChunkBakeProfiler {
     }
 }
 
+/// One column of a chunk as `bake_chunk` sees it.
+#[derive(Clone, Copy)]
+pub(super) struct ColumnInfo {
+    pub(super) height: f32,
+    pub(super) water_level: f32,
+    pub(super) biome: Biome,
+    pub(super) temperature_c: f32,
+    pub(super) moisture: f32,
+    pub(super) surface_block: BlockType,
+    subsurface_block: BlockType,
+    water_block: BlockType,
+}
+
 impl WorldGenerator {
     pub fn get_biome(&self, world_x: f32, world_z: f32) -> Biome {
         let height = self.get_height(world_x, world_z);
@@ -102,109 +116,123 @@ impl WorldGenerator {
         BlockType::Stone
     }
 
+    /// Terrain height, water and biome of one column, exactly as
+    /// `bake_chunk` fills it.
+    pub(super) fn column_info(&self, world_x: f32, world_z: f32) -> ColumnInfo {
+        let lattice = self.climate_lattice(world_x, world_z, world_x, world_z);
+        self.column_info_in(&lattice, world_x, world_z)
+    }
+
+    /// `column_info` for a column `lattice` covers.
+    fn column_info_in(&self, lattice: &ClimateLattice, world_x: f32, world_z: f32) -> ColumnInfo {
+        let components = self.terrain_components(world_x, world_z);
+        let hydro = self.sample_hydrology(world_x, world_z, components.base_height);
+
+        let floodplain = self.config.hydrology_floodplain_radius.max(0.0);
+        let mut height = components.base_height - hydro.channel_depth;
+        if hydro.pond_intensity > 0.05 {
+            let soften = (floodplain * 0.1).clamp(0.0, 6.0);
+            let shore_level = (hydro.water_level - soften).min(height);
+            height = height.min(shore_level);
+        } else if hydro.river_intensity > 0.05 {
+            let soften = (floodplain * 0.2).clamp(0.5, 12.0);
+            let blend = soften * (1.0 - hydro.river_intensity).clamp(0.0, 1.0);
+            height = height.min(hydro.water_level - blend);
+        }
+
+        if hydro.coastal_factor > 0.01 {
+            let blend_strength = hydro.coastal_factor.clamp(0.0, 1.0);
+            let max_elevation = (self.config.hydrology_estuary_length * 0.05).clamp(4.0, 18.0);
+            let relative = height - self.config.sea_level;
+            let clamped = relative.clamp(-max_elevation, max_elevation);
+            let target = self.config.sea_level + clamped;
+            height = lerp_f32(height, target, (blend_strength * 0.5).clamp(0.0, 1.0));
+            height = height.max(self.config.sea_level + 0.05);
+        }
+
+        height = height.max(4.0);
+
+        let mut water_level = if hydro.water_level > self.config.sea_level {
+            hydro.water_level
+        } else {
+            self.config.sea_level
+        };
+
+        if hydro.pond_intensity > 0.05 {
+            let bed_height = height;
+            let lake_depth = (water_level - bed_height).max(0.0);
+            let max_depth = (self.config.hydrology_pond_max_radius * 0.18).clamp(2.0, 8.0);
+            let min_depth = (self.config.hydrology_pond_min_radius * 0.05).max(0.6);
+            let desired_depth = lake_depth.clamp(min_depth, max_depth);
+            water_level = (bed_height + desired_depth).min(water_level);
+        } else if hydro.river_intensity > 0.05 {
+            let bed_height = height;
+            let depth_scale = self.config.hydrology_river_depth_scale.max(1.0);
+            let max_depth = (depth_scale * 0.35).clamp(1.2, depth_scale);
+            let min_depth = (0.3 + hydro.river_intensity * 0.7).clamp(0.35, max_depth);
+            let desired_depth = hydro.channel_depth.clamp(min_depth, max_depth);
+            water_level = (bed_height + desired_depth).min(water_level);
+        }
+
+        water_level = water_level.max(self.config.sea_level).max(height);
+
+        let temperature_c = self.temperature_at_height(world_x, world_z, height);
+        let moisture = self.get_moisture(world_x, world_z);
+        let classified =
+            self.classify_biome_at_position(world_x, world_z, height, temperature_c, moisture);
+        let biome = self.blend_biome(
+            lattice,
+            world_x,
+            world_z,
+            height,
+            classified,
+            (temperature_c, moisture),
+        );
+
+        let surface_block = biome.surface_block();
+        let subsurface_block = biome.subsurface_block();
+        let water_block = match classified {
+            Biome::FrozenOcean | Biome::IceCap => BlockType::Ice,
+            _ => BlockType::Water,
+        };
+
+        ColumnInfo {
+            height,
+            water_level,
+            biome,
+            temperature_c,
+            moisture,
+            surface_block,
+            subsurface_block,
+            water_block,
+        }
+    }
+
     pub fn bake_chunk(&self, chunk_pos: ChunkPos) -> ChunkStorage {
         let mut profiler = ChunkBakeProfiler::new();
         let world_origin = chunk_pos.to_world_pos();
-        #[derive(Clone, Copy)]
-        struct ColumnInfo {
-            height: f32,
-            water_level: f32,
-            surface_block: BlockType,
-            subsurface_block: BlockType,
-            water_block: BlockType,
-        }
 
         let columns = profiler.measure("column_precompute", || {
+            let far = CHUNK_SIZE as f32 - 1.0;
+            let lattice = self.climate_lattice(
+                world_origin.x,
+                world_origin.z,
+                world_origin.x + far,
+                world_origin.z + far,
+            );
             let mut columns = Vec::with_capacity(CHUNK_SIZE * CHUNK_SIZE);
             for z in 0..CHUNK_SIZE {
                 for x in 0..CHUNK_SIZE {
                     let world_x = world_origin.x + x as f32;
                     let world_z = world_origin.z + z as f32;
-
-                    let components = self.terrain_components(world_x, world_z);
-                    let hydro = self.sample_hydrology(world_x, world_z, components.base_height);
-
-                    let floodplain = self.config.hydrology_floodplain_radius.max(0.0);
-                    let mut height = components.base_height - hydro.channel_depth;
-                    if hydro.pond_intensity > 0.05 {
-                        let soften = (floodplain * 0.1).clamp(0.0, 6.0);
-                        let shore_level = (hydro.water_level - soften).min(height);
-                        height = height.min(shore_level);
-                    } else if hydro.river_intensity > 0.05 {
-                        let soften = (floodplain * 0.2).clamp(0.5, 12.0);
-                        let blend = soften * (1.0 - hydro.river_intensity).clamp(0.0, 1.0);
-                        height = height.min(hydro.water_level - blend);
-                    }
-
-                    if hydro.coastal_factor > 0.01 {
-                        let blend_strength = hydro.coastal_factor.clamp(0.0, 1.0);
-                        let max_elevation =
-                            (self.config.hydrology_estuary_length * 0.05).clamp(4.0, 18.0);
-                        let relative = height - self.config.sea_level;
-                        let clamped = relative.clamp(-max_elevation, max_elevation);
-                        let target = self.config.sea_level + clamped;
-                        height = lerp_f32(height, target, (blend_strength * 0.5).clamp(0.0, 1.0));
-                        height = height.max(self.config.sea_level + 0.05);
-                    }
-
-                    height = height.max(4.0);
-
-                    let mut water_level = if hydro.water_level > self.config.sea_level {
-                        hydro.water_level
-                    } else {
-                        self.config.sea_level
-                    };
-
-                    if hydro.pond_intensity > 0.05 {
-                        let bed_height = height;
-                        let lake_depth = (water_level - bed_height).max(0.0);
-                        let max_depth =
-                            (self.config.hydrology_pond_max_radius * 0.18).clamp(2.0, 8.0);
-                        let min_depth = (self.config.hydrology_pond_min_radius * 0.05).max(0.6);
-                        let desired_depth = lake_depth.clamp(min_depth, max_depth);
-                        water_level = (bed_height + desired_depth).min(water_level);
-                    } else if hydro.river_intensity > 0.05 {
-                        let bed_height = height;
-                        let depth_scale = self.config.hydrology_river_depth_scale.max(1.0);
-                        let max_depth = (depth_scale * 0.35).clamp(1.2, depth_scale);
-                        let min_depth = (0.3 + hydro.river_intensity * 0.7).clamp(0.35, max_depth);
-                        let desired_depth = hydro.channel_depth.clamp(min_depth, max_depth);
-                        water_level = (bed_height + desired_depth).min(water_level);
-                    }
-
-                    water_level = water_level.max(self.config.sea_level).max(height);
-
-                    let temperature_c = self.temperature_at_height(world_x, world_z, height);
-                    let moisture = self.get_moisture(world_x, world_z);
-                    let biome = self.classify_biome_at_position(
-                        world_x,
-                        world_z,
-                        height,
-                        temperature_c,
-                        moisture,
-                    );
-
-                    let surface_block = biome.surface_block();
-                    let subsurface_block = biome.subsurface_block();
-                    let water_block = match biome {
-                        Biome::FrozenOcean | Biome::IceCap => BlockType::Ice,
-                        _ => BlockType::Water,
-                    };
-
-                    columns.push(ColumnInfo {
-                        height,
-                        water_level,
-                        surface_block,
-                        subsurface_block,
-                        water_block,
-                    });
+                    columns.push(self.column_info_in(&lattice, world_x, world_z));
                 }
             }
             columns
         });
 
-        let storage = profiler.measure("fill_storage", || {
-            ChunkStorage::from_fn(move |x, y, z| {
+        let mut storage = profiler.measure("fill_storage", || {
+            ChunkStorage::from_fn(|x, y, z| {
                 let column = &columns[z * CHUNK_SIZE + x];
                 let world_y = world_origin.y + y as f32;
 
@@ -230,11 +258,58 @@ impl WorldGenerator {
                 BlockType::Stone
             })
         });
+        profiler.measure("lithology", || {
+            self.apply_lithology(chunk_pos, &columns, &mut storage)
+        });
+        profiler.measure("caves", || {
+            self.carve_caves(chunk_pos, &columns, &mut storage)
+        });
+        // After the caves, so veins only grow into rock that is left.
+        profiler.measure("ores", || self.place_ores(chunk_pos, &mut storage));
+
+        profiler.measure("decorate", || {
+            self.decorate_chunk(chunk_pos, &columns, &mut storage)
+        });
+        profiler.measure("structures", || {
+            self.place_structures(chunk_pos, &mut storage)
+        });
 
         profiler.finish(chunk_pos);
         storage
     }
 
+    /// Replace the stone under each column with its plate's strata. The
+    /// biome's surface and soil stay, as does the bedrock floor.
+    fn apply_lithology(
+        &self,
+        chunk_pos: ChunkPos,
+        columns: &[ColumnInfo],
+        storage: &mut ChunkStorage,
+    ) {
+        let world_origin = chunk_pos.to_world_pos();
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let column = &columns[z * CHUNK_SIZE + x];
+                if world_origin.y > column.height {
+                    continue;
+                }
+                let profile =
+                    self.lithology_profile_at(world_origin.x + x as f32, world_origin.z + z as f32);
+
+                for y in 0..CHUNK_SIZE {
+                    if storage.get(x, y, z) != BlockType::Stone {
+                        continue;
+                    }
+                    let depth = (column.height - (world_origin.y + y as f32)) as u32;
+                    let block = profile.block_at_depth(depth);
+                    if block != profile.surface_block {
+                        storage.set(x, y, z, block);
+                    }
+                }
+            }
+        }
+    }
+
     pub fn preview_color(&self, world_x: f32, world_z: f32, biome: Biome, height: f32) -> [u8; 4] {
         let sea_level = self.config.sea_level;
         let water_depth = (sea_level - height).max(0.0);
@@ -409,51 +484,7 @@ impl WorldGenerator {
             };
         }
 
-        if temp_c < -15.0 {
-            return Biome::IceCap;
-        }
-        if temp_c < -5.0 {
-            return Biome::Snow;
-        }
-        if temp_c < 0.0 {
-            return Biome::Tundra;
-        }
-
-        if temp_c < 8.0 {
-            return if moisture < 0.35 {
-                Biome::BorealForest
-            } else {
-                Biome::TemperateForest
-            };
-        }
-
-        if temp_c < 18.0 {
-            if moisture < 0.25 {
-                return Biome::TemperateGrassland;
-            } else if moisture < 0.6 {
-                return Biome::TemperateForest;
-            } else {
-                return Biome::TropicalRainforest;
-            }
-        }
-
-        if temp_c < 26.0 {
-            if moisture < 0.2 {
-                return Biome::Desert;
-            } else if moisture < 0.45 {
-                return Biome::Savanna;
-            } else {
-                return Biome::TropicalRainforest;
-            }
-        }
-
-        if moisture < 0.15 {
-            Biome::Desert
-        } else if moisture < 0.45 {
-            Biome::Savanna
-        } else {
-            Biome::TropicalRainforest
-        }
+        climate_biome(temp_c, moisture)
     }
 
     fn calculate_coastal_properties(&self, world_x: f32, world_z: f32, height: f32) -> (f32, f32) {
@@ -518,3 +549,131 @@ impl WorldGenerator {
         (base_probability + noise_influence).clamp(0.0, 1.0)
     }
 }
+
+/// The land biome a climate supports, away from coasts and mountains.
+pub(super) fn climate_biome(temp_c: f32, moisture: f32) -> Biome {
+    if temp_c < -15.0 {
+        return Biome::IceCap;
+    }
+    if temp_c < -5.0 {
+        return Biome::Snow;
+    }
+    if temp_c < 0.0 {
+        return Biome::Tundra;
+    }
+
+    if temp_c < 8.0 {
+        return if moisture < 0.35 {
+            Biome::BorealForest
+        } else {
+            Biome::TemperateForest
+        };
+    }
+
+    if temp_c < 18.0 {
+        if moisture < 0.25 {
+            return Biome::TemperateGrassland;
+        } else if moisture < 0.6 {
+            return Biome::TemperateForest;
+        } else {
+            return Biome::TropicalRainforest;
+        }
+    }
+
+    if temp_c < 26.0 {
+        if moisture < 0.2 {
+            return Biome::Desert;
+        } else if moisture < 0.45 {
+            return Biome::Savanna;
+        } else {
+            return Biome::TropicalRainforest;
+        }
+    }
+
+    if moisture < 0.15 {
+        Biome::Desert
+    } else if moisture < 0.45 {
+        Biome::Savanna
+    } else {
+        Biome::TropicalRainforest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planet::PlanetConfig;
+    use crate::world::WorldGenConfig;
+    use std::sync::OnceLock;
+
+    /// A small planet on a coarse hydrology grid, quick to build.
+    fn small_world() -> &'static WorldGenerator {
+        static WORLD: OnceLock<WorldGenerator> = OnceLock::new();
+        WORLD.get_or_init(|| {
+            let mut config = WorldGenConfig::from_planet_config(&PlanetConfig {
+                size_chunks: 128,
+                ..Default::default()
+            });
+            config.hydrology_resolution = 64;
+            WorldGenerator::new(config)
+        })
+    }
+
+    /// Every voxel at least three blocks under the ground in the first
+    /// `columns` chunk columns that are dry land, as baked.
+    fn underground_blocks(generator: &WorldGenerator, columns: usize) -> Vec<BlockType> {
+        let land = (0..128 * 128)
+            .map(|index| (index % 128, index / 128))
+            .filter(|&(x, z)| {
+                let middle = |chunk: i32| (chunk * CHUNK_SIZE as i32 + 16) as f32;
+                generator.get_height(middle(x), middle(z)) > generator.config.sea_level + 16.0
+            })
+            .take(columns);
+
+        let mut blocks = Vec::new();
+        for (chunk_x, chunk_z) in land {
+            for chunk_y in 0..4 {
+                let chunk_pos = ChunkPos::new(chunk_x, chunk_y, chunk_z);
+                let storage = generator.bake_chunk(chunk_pos);
+                let origin = chunk_pos.to_world_pos();
+                for z in 0..CHUNK_SIZE {
+                    for x in 0..CHUNK_SIZE {
+                        let height = generator.get_height(origin.x + x as f32, origin.z + z as f32);
+                        for y in 0..CHUNK_SIZE {
+                            if origin.y + (y as f32) < height - 2.0 {
+                                blocks.push(storage.get(x, y, z));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        blocks
+    }
+
+    #[test]
+    fn baked_chunks_are_hollowed_by_caves() {
+        // Everything fill_storage writes under the ground is solid, so air
+        // and water down there were carved.
+        let blocks = underground_blocks(small_world(), 4);
+        assert!(blocks
+            .iter()
+            .any(|block| matches!(*block, BlockType::Air | BlockType::Water)));
+        // The plates' strata replace the plain stone.
+        assert!(blocks
+            .iter()
+            .any(|block| *block != BlockType::Stone && block.is_solid()));
+    }
+
+    #[test]
+    fn baked_chunks_hold_ore_veins() {
+        let blocks = underground_blocks(small_world(), 4);
+        let ores = [
+            BlockType::CoalOre,
+            BlockType::IronOre,
+            BlockType::CopperOre,
+            BlockType::GoldOre,
+        ];
+        assert!(blocks.iter().any(|block| ores.contains(block)));
+    }
+}
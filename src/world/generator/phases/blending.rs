@@ -0,0 +1,211 @@
+use super::super::{util::hash_position, WorldGenerator};
+use super::biomes::climate_biome;
+use crate::world::biome::Biome;
+
+/// Climate is sampled on a world-aligned lattice this many blocks apart, so
+/// every chunk reads the same samples for a column.
+const LATTICE_STEP: i32 = 4;
+/// Biomes reach this far into their neighbours' surfaces.
+const BLEND_RADIUS: f32 = 12.0;
+const DITHER_SALT: u64 = 0x51D3_8E07_A2C9_4B6F;
+
+/// Sea-level temperature and moisture on the lattice points covering an
+/// area and `BLEND_RADIUS` around it.
+pub(super) struct ClimateLattice {
+    min_x: i32,
+    min_z: i32,
+    width: i32,
+    depth: i32,
+    /// `(temperature_c, moisture)`, `x + z * width` from the minimum corner.
+    samples: Vec<(f32, f32)>,
+}
+
+impl ClimateLattice {
+    /// Sample `climate(x, z)` on the lattice points within blending reach
+    /// of the columns from `(min_x, min_z)` to `(max_x, max_z)`.
+    fn sample(
+        min_x: f32,
+        min_z: f32,
+        max_x: f32,
+        max_z: f32,
+        climate: impl Fn(f32, f32) -> (f32, f32),
+    ) -> Self {
+        let low = |value: f32| ((value - BLEND_RADIUS) / LATTICE_STEP as f32).ceil() as i32;
+        let high = |value: f32| ((value + BLEND_RADIUS) / LATTICE_STEP as f32).floor() as i32;
+        let (min_x, min_z) = (low(min_x), low(min_z));
+        let width = high(max_x) - min_x + 1;
+        let depth = high(max_z) - min_z + 1;
+
+        let mut samples = Vec::with_capacity((width * depth) as usize);
+        for z in 0..depth {
+            for x in 0..width {
+                samples.push(climate(
+                    ((min_x + x) * LATTICE_STEP) as f32,
+                    ((min_z + z) * LATTICE_STEP) as f32,
+                ));
+            }
+        }
+        Self {
+            min_x,
+            min_z,
+            width,
+            depth,
+            samples,
+        }
+    }
+
+    /// How much each climate biome weighs on the column at `(world_x,
+    /// world_z)`, nearer lattice points counting more. `cooling` is the
+    /// column's lapse below sea-level temperature, so highlands blend
+    /// between the biomes their own elevation supports.
+    fn biome_weights(&self, world_x: f32, world_z: f32, cooling: f32) -> Vec<(Biome, f32)> {
+        let mut weights: Vec<(Biome, f32)> = Vec::new();
+        let step = LATTICE_STEP as f32;
+        let first = |value: f32, min: i32| ((value - BLEND_RADIUS) / step).ceil() as i32 - min;
+        let last = |value: f32, min: i32| ((value + BLEND_RADIUS) / step).floor() as i32 - min;
+
+        for z in first(world_z, self.min_z).max(0)..=last(world_z, self.min_z).min(self.depth - 1) {
+            for x in
+                first(world_x, self.min_x).max(0)..=last(world_x, self.min_x).min(self.width - 1)
+            {
+                let dx = ((self.min_x + x) * LATTICE_STEP) as f32 - world_x;
+                let dz = ((self.min_z + z) * LATTICE_STEP) as f32 - world_z;
+                let distance = (dx * dx + dz * dz).sqrt();
+                if distance >= BLEND_RADIUS {
+                    continue;
+                }
+                let weight = (1.0 - distance / BLEND_RADIUS).powi(2);
+                let (temperature_c, moisture) = self.samples[(x + z * self.width) as usize];
+                let biome = climate_biome(temperature_c - cooling, moisture);
+                match weights.iter_mut().find(|(other, _)| *other == biome) {
+                    Some((_, total)) => *total += weight,
+                    None => weights.push((biome, weight)),
+                }
+            }
+        }
+        weights
+    }
+}
+
+/// Pick one of `weights` with probability in proportion to its weight,
+/// `roll` being uniform in 0-1.
+fn dither(weights: &[(Biome, f32)], roll: f32) -> Option<Biome> {
+    let total: f32 = weights.iter().map(|(_, weight)| weight).sum();
+    let mut threshold = roll * total;
+    for (biome, weight) in weights {
+        if threshold < *weight {
+            return Some(*biome);
+        }
+        threshold -= weight;
+    }
+    weights.last().map(|(biome, _)| *biome)
+}
+
+impl WorldGenerator {
+    /// The climate lattice `blend_biome` needs for every column from
+    /// `(min_x, min_z)` to `(max_x, max_z)`.
+    pub(super) fn climate_lattice(
+        &self,
+        min_x: f32,
+        min_z: f32,
+        max_x: f32,
+        max_z: f32,
+    ) -> ClimateLattice {
+        let sea_level = self.config.sea_level;
+        ClimateLattice::sample(min_x, min_z, max_x, max_z, |x, z| {
+            (
+                self.temperature_at_height(x, z, sea_level),
+                self.get_moisture(x, z),
+            )
+        })
+    }
+
+    /// The biome whose surface a column shows. Where the climate biomes
+    /// within `BLEND_RADIUS` differ, each column picks one of them with a
+    /// chance weighted by their influence, dithering the boundary instead
+    /// of switching abruptly. Oceans, beaches and mountains keep their
+    /// classified biome. `climate` is the column's `(temperature_c,
+    /// moisture)`.
+    pub(super) fn blend_biome(
+        &self,
+        lattice: &ClimateLattice,
+        world_x: f32,
+        world_z: f32,
+        height: f32,
+        classified: Biome,
+        climate: (f32, f32),
+    ) -> Biome {
+        if climate_biome(climate.0, climate.1) != classified {
+            return classified;
+        }
+        let cooling =
+            (height - self.config.sea_level).max(0.0) * self.config.lapse_rate_c_per_block;
+        let weights = lattice.biome_weights(world_x, world_z, cooling);
+        if weights.len() < 2 {
+            return classified;
+        }
+
+        let planet_size = self.config.planet_size.max(1) as i32;
+        let seed = hash_position(
+            self.config.seed ^ DITHER_SALT,
+            (world_x.floor() as i32).rem_euclid(planet_size),
+            0,
+            (world_z.floor() as i32).rem_euclid(planet_size),
+        );
+        let roll = (seed >> 40) as f32 / (1u64 << 24) as f32;
+        dither(&weights, roll).unwrap_or(classified)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boundaries_dither_the_same_from_any_chunk() {
+        // Temperate grassland gives way to desert as it warms eastwards.
+        let climate = |x: f32, _z: f32| (10.0 + x * 0.1, 0.1);
+        let boundary = 80.0;
+        assert_eq!(
+            climate_biome(climate(boundary - 1.0, 0.0).0, 0.1),
+            Biome::TemperateGrassland
+        );
+        assert_eq!(
+            climate_biome(climate(boundary + 1.0, 0.0).0, 0.1),
+            Biome::Desert
+        );
+
+        let chunk = ClimateLattice::sample(64.0, 0.0, 95.0, 31.0, climate);
+        let desert_share = |x: f32| {
+            let desert: f32 = (0..32)
+                .map(|z| {
+                    let z = z as f32;
+                    let single = ClimateLattice::sample(x, z, x, z, climate);
+                    let weights = chunk.biome_weights(x, z, 0.0);
+                    let roll = ((x * 7.0 + z * 13.0) % 32.0) / 32.0;
+                    // A column's weights don't depend on which area was sampled.
+                    assert_eq!(weights, single.biome_weights(x, z, 0.0));
+                    (dither(&weights, roll) == Some(Biome::Desert)) as u32 as f32
+                })
+                .sum();
+            desert / 32.0
+        };
+
+        // Pure far from the boundary, mixed near it, more desert eastwards.
+        let shares: Vec<f32> = [64.0, 74.0, 80.0, 83.0, 95.0].map(desert_share).to_vec();
+        assert_eq!(shares[0], 0.0);
+        assert_eq!(shares[4], 1.0);
+        assert!(shares[1] > 0.0 && shares[3] < 1.0, "{:?}", shares);
+        assert!(
+            shares.windows(2).all(|pair| pair[0] <= pair[1]),
+            "{:?}",
+            shares
+        );
+
+        // Cooling with height shifts the boundary to warmer lowland climate.
+        let cooled = chunk.biome_weights(boundary + 1.0, 0.0, 4.0);
+        assert!(cooled
+            .iter()
+            .all(|(biome, _)| *biome == Biome::TemperateGrassland));
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+
+use super::super::WorldGenerator;
+use super::biomes::ColumnInfo;
+use crate::block::BlockType;
+use crate::chunk::{ChunkPos, ChunkStorage, CHUNK_SIZE};
+
+/// Horizontal and vertical size of cheese caverns, in blocks.
+const CAVERN_SCALE: f32 = 56.0;
+const CAVERN_VERTICAL_SCALE: f32 = 24.0;
+/// Cavern noise above which rock is hollowed out, before `cave_bias`.
+const CAVERN_THRESHOLD: f32 = 0.55;
+/// Size of the noise fields whose shared zero crossings trace tunnels.
+const TUNNEL_SCALE: f32 = 72.0;
+const TUNNEL_VERTICAL_SCALE: f32 = 36.0;
+/// How far from zero both tunnel fields may be, before `cave_bias`.
+const TUNNEL_RADIUS: f32 = 0.06;
+/// Rock kept between caves and the surface: caverns stay buried, tunnels
+/// now and then come close enough to make an entrance.
+const CAVERN_ROOF: f32 = 10.0;
+const TUNNEL_ROOF: f32 = 4.0;
+/// Caves never reach into the bottom of the world.
+const CAVE_FLOOR: f32 = 4.0;
+/// Aquifers are decided per square cell of this many blocks, so a cave
+/// floods or stays dry as a whole rather than column by column.
+const AQUIFER_CELL: i32 = 64;
+/// Cells whose middle lies less than this above sea level (coasts, and
+/// everything under the sea) flood their caves up to sea level.
+const AQUIFER_COAST_HEIGHT: f32 = 12.0;
+
+const GRADIENTS: [[f32; 3]; 12] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+];
+
+/// 3D gradient noise whose lattice repeats exactly once around the planet in
+/// x and z, so it is seamless where the world wraps.
+struct WrappingNoise {
+    seed: u64,
+    /// Lattice cells around the planet.
+    period: i64,
+    cell: f32,
+    vertical_cell: f32,
+}
+
+impl WrappingNoise {
+    fn new(seed: u64, planet_size: u32, scale: f32, vertical_scale: f32) -> Self {
+        let planet_size = planet_size.max(1) as f32;
+        let period = (planet_size / scale).round().max(1.0);
+        Self {
+            seed,
+            period: period as i64,
+            cell: planet_size / period,
+            vertical_cell: vertical_scale,
+        }
+    }
+
+    fn get(&self, x: f32, y: f32, z: f32) -> f32 {
+        let (x, y, z) = (x / self.cell, y / self.vertical_cell, z / self.cell);
+        let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+        let (fx, fy, fz) = (x - x0, y - y0, z - z0);
+        let (x0, y0, z0) = (x0 as i64, y0 as i64, z0 as i64);
+
+        let corner = |dx: i64, dy: i64, dz: i64| {
+            let gradient = self.gradient(x0 + dx, y0 + dy, z0 + dz);
+            gradient[0] * (fx - dx as f32)
+                + gradient[1] * (fy - dy as f32)
+                + gradient[2] * (fz - dz as f32)
+        };
+        let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+        let x00 = lerp(corner(0, 0, 0), corner(1, 0, 0), u);
+        let x10 = lerp(corner(0, 1, 0), corner(1, 1, 0), u);
+        let x01 = lerp(corner(0, 0, 1), corner(1, 0, 1), u);
+        let x11 = lerp(corner(0, 1, 1), corner(1, 1, 1), u);
+        lerp(lerp(x00, x10, v), lerp(x01, x11, v), w)
+    }
+
+    fn gradient(&self, x: i64, y: i64, z: i64) -> [f32; 3] {
+        let x = x.rem_euclid(self.period) as u64;
+        let z = z.rem_euclid(self.period) as u64;
+        let mut hash = self.seed
+            ^ x.wrapping_mul(0x9E3779B97F4A7C15)
+            ^ (y as u64).wrapping_mul(0xC2B2AE3D27D4EB4F)
+            ^ z.wrapping_mul(0x165667B19E3779F9);
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(0xFF51AFD7ED558CCD);
+        hash ^= hash >> 33;
+        GRADIENTS[(hash % GRADIENTS.len() as u64) as usize]
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+/// The noise fields caves are carved from. Whether a voxel is cave depends
+/// only on its world position, its depth and its column's `cave_bias`, so
+/// chunks agree on every cave that crosses between them.
+pub(crate) struct CaveNoise {
+    caverns: [WrappingNoise; 2],
+    tunnels: [WrappingNoise; 2],
+}
+
+impl CaveNoise {
+    pub(crate) fn new(seed: u64, planet_size: u32) -> Self {
+        let field = |salt: u64, scale: f32, vertical_scale: f32| {
+            WrappingNoise::new(
+                seed ^ salt.wrapping_mul(0xD6E8FEB86659FD93),
+                planet_size,
+                scale,
+                vertical_scale,
+            )
+        };
+        Self {
+            caverns: [
+                field(1, CAVERN_SCALE, CAVERN_VERTICAL_SCALE),
+                field(2, CAVERN_SCALE * 0.4, CAVERN_VERTICAL_SCALE * 0.4),
+            ],
+            tunnels: [
+                field(3, TUNNEL_SCALE, TUNNEL_VERTICAL_SCALE),
+                field(4, TUNNEL_SCALE, TUNNEL_VERTICAL_SCALE),
+            ],
+        }
+    }
+
+    /// Whether the voxel at world `(x, y, z)`, `depth` blocks below the
+    /// surface, is open cave. `cave_bias` (0-1) widens tunnels and opens
+    /// more caverns.
+    pub(crate) fn is_cave(&self, x: f32, y: f32, z: f32, depth: f32, cave_bias: f32) -> bool {
+        if y < CAVE_FLOOR {
+            return false;
+        }
+        let bias = cave_bias.clamp(0.0, 1.0);
+
+        // Worm tunnels run where both fields cross zero at once.
+        if depth >= TUNNEL_ROOF {
+            let radius = TUNNEL_RADIUS * (0.6 + bias);
+            let a = self.tunnels[0].get(x, y, z);
+            let b = self.tunnels[1].get(x, y, z);
+            if a * a + b * b < radius * radius {
+                return true;
+            }
+        }
+
+        // Cheese caverns where the cavern field peaks, more of them deeper.
+        if depth >= CAVERN_ROOF {
+            let threshold = CAVERN_THRESHOLD - bias * 0.2 - (depth / 512.0).min(0.08);
+            let value = self.caverns[0].get(x, y, z) * 0.75 + self.caverns[1].get(x, y, z) * 0.25;
+            if value > threshold {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+impl WorldGenerator {
+    /// Hollow caves out of the solid voxels of a baked chunk. Cave voxels
+    /// below sea level in a flooded aquifer cell fill with water; the rest
+    /// become air.
+    pub(super) fn carve_caves(
+        &self,
+        chunk_pos: ChunkPos,
+        columns: &[ColumnInfo],
+        storage: &mut ChunkStorage,
+    ) {
+        if storage
+            .uniform_block()
+            .is_some_and(|block| !block.is_solid())
+        {
+            return;
+        }
+
+        let noise = CaveNoise::new(self.config.seed, self.config.planet_size);
+        let world_origin = chunk_pos.to_world_pos();
+        let sea_level = self.config.sea_level;
+        let mut aquifers: HashMap<(i32, i32), bool> = HashMap::new();
+
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let world_x = world_origin.x + x as f32;
+                let world_z = world_origin.z + z as f32;
+                let surface = columns[z * CHUNK_SIZE + x].height;
+                if world_origin.y > surface - TUNNEL_ROOF {
+                    continue;
+                }
+                let cave_bias = self.cave_bias_at(world_x, world_z);
+
+                for y in 0..CHUNK_SIZE {
+                    let world_y = world_origin.y + y as f32;
+                    let block = storage.get(x, y, z);
+                    if !block.is_solid() || block == BlockType::Bedrock {
+                        continue;
+                    }
+                    let depth = surface - world_y;
+                    if !noise.is_cave(world_x, world_y, world_z, depth, cave_bias) {
+                        continue;
+                    }
+
+                    let flooded = world_y <= sea_level
+                        && self.aquifer_floods(&mut aquifers, world_x, world_z);
+                    let carved = if flooded {
+                        BlockType::Water
+                    } else {
+                        BlockType::Air
+                    };
+                    storage.set(x, y, z, carved);
+                }
+            }
+        }
+    }
+
+    /// Whether caves in the aquifer cell around `(world_x, world_z)` fill
+    /// with water up to sea level. Cells are taken around the wrapped
+    /// position, so both sides of the wrap agree.
+    fn aquifer_floods(
+        &self,
+        cache: &mut HashMap<(i32, i32), bool>,
+        world_x: f32,
+        world_z: f32,
+    ) -> bool {
+        let planet_size = self.config.planet_size.max(1) as i32;
+        let cell = |value: f32| (value.floor() as i32).rem_euclid(planet_size) / AQUIFER_CELL;
+        let key = (cell(world_x), cell(world_z));
+        *cache.entry(key).or_insert_with(|| {
+            let middle = |cell: i32| (cell * AQUIFER_CELL + AQUIFER_CELL / 2) as f32;
+            let height = self.get_height(middle(key.0), middle(key.1));
+            height < self.config.sea_level + AQUIFER_COAST_HEIGHT
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caves_are_seamless_around_the_planet() {
+        let planet_size = 2048;
+        let noise = CaveNoise::new(42, planet_size);
+        let wrap = planet_size as f32;
+
+        let mut caves = 0;
+        let mut samples = 0;
+        for x in (0..256).step_by(3) {
+            for y in (8..64).step_by(3) {
+                let (x, y) = (x as f32, y as f32);
+                for z in [5.0, 700.0] {
+                    let cave = noise.is_cave(x, y, z, 40.0, 0.5);
+                    // The same voxel one planet over, either way.
+                    assert_eq!(cave, noise.is_cave(x + wrap, y, z, 40.0, 0.5));
+                    assert_eq!(cave, noise.is_cave(x, y, z - wrap, 40.0, 0.5));
+                    caves += cave as usize;
+                    samples += 1;
+                }
+            }
+        }
+        // Coherent caves, not solid rock or Swiss cheese.
+        let fraction = caves as f32 / samples as f32;
+        assert!(
+            (0.005..0.25).contains(&fraction),
+            "cave fraction {}",
+            fraction
+        );
+
+        // No caves right under the surface, and more with more bias.
+        assert!((0..64).all(|x| !noise.is_cave(x as f32, 30.0, 9.0, 2.0, 1.0)));
+        let count = |bias: f32| {
+            (0..4096)
+                .filter(|i| noise.is_cave((i % 64) as f32, 20.0 + (i / 64) as f32, 0.0, 40.0, bias))
+                .count()
+        };
+        assert!(count(1.0) > count(0.0));
+
+        // Neighbouring voxels mostly agree: tunnels and caverns, not speckle.
+        let field = &noise.tunnels[0];
+        for x in 0..200 {
+            let (a, b) = (
+                field.get(x as f32, 20.0, 3.0),
+                field.get(x as f32 + 1.0, 20.0, 3.0),
+            );
+            assert!((a - b).abs() < 0.2);
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use bevy::math::IVec3;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::super::{util::hash_position, WorldGenerator};
+use super::biomes::ColumnInfo;
+use crate::block::BlockType;
+use crate::chunk::{ChunkPos, ChunkStorage, CHUNK_SIZE};
+use crate::world::biome::Biome;
+
+/// Trees are rolled once per square cell of this many blocks. The cell a
+/// trunk stands in is the tree's feature origin: every chunk the tree
+/// reaches rolls the same cell and grows the same tree.
+const TREE_CELL: i32 = 8;
+/// Furthest a crown reaches sideways from its trunk.
+const TREE_REACH: i32 = 3;
+/// Tallest tree, trunk and crown, above the ground it stands on.
+const TREE_MAX_HEIGHT: i32 = 15;
+const TREE_SALT: u64 = 0x7A3B_51C2_D94E_0F68;
+const COVER_SALT: u64 = 0x2C81_F6A9_3B57_E40D;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TreeKind {
+    /// Short trunk, round crown.
+    Oak,
+    /// Tall trunk under a narrowing cone.
+    Spruce,
+    /// Very tall trunk with a wide crown at the top.
+    Jungle,
+    /// Short trunk with a flat, wide crown.
+    Acacia,
+}
+
+/// The tree a biome grows and the chance a cell has one, before climate.
+fn biome_tree(biome: Biome) -> Option<(TreeKind, f32)> {
+    match biome {
+        Biome::TemperateForest => Some((TreeKind::Oak, 0.85)),
+        Biome::BorealForest => Some((TreeKind::Spruce, 0.8)),
+        Biome::TropicalRainforest => Some((TreeKind::Jungle, 0.95)),
+        Biome::Savanna => Some((TreeKind::Acacia, 0.15)),
+        Biome::TemperateGrassland => Some((TreeKind::Oak, 0.06)),
+        Biome::Tundra => Some((TreeKind::Spruce, 0.04)),
+        _ => None,
+    }
+}
+
+/// Chance a column grows tall grass and a shrub, before climate.
+fn biome_cover(biome: Biome) -> (f32, f32) {
+    match biome {
+        Biome::TemperateGrassland => (0.35, 0.01),
+        Biome::Savanna => (0.25, 0.02),
+        Biome::TropicalRainforest => (0.3, 0.06),
+        Biome::TemperateForest => (0.2, 0.03),
+        Biome::BorealForest => (0.08, 0.02),
+        Biome::Tundra => (0.05, 0.0),
+        _ => (0.0, 0.0),
+    }
+}
+
+/// Wetter and warmer ground grows more; cold or dry ground thins out.
+fn climate_density(temperature_c: f32, moisture: f32) -> f32 {
+    let wet = 0.3 + 0.7 * moisture.clamp(0.0, 1.0);
+    let warm = ((temperature_c + 10.0) / 20.0).clamp(0.2, 1.0);
+    wet * warm
+}
+
+/// Ground trees and plants can root in.
+fn fertile(block: BlockType) -> bool {
+    matches!(block, BlockType::Grass | BlockType::Dirt | BlockType::Snow)
+}
+
+/// Blocks of one tree, relative to the bottom of its trunk.
+fn tree_blocks(kind: TreeKind, rng: &mut StdRng) -> Vec<(IVec3, BlockType)> {
+    let (trunk, layers): (i32, Vec<(i32, i32)>) = match kind {
+        TreeKind::Oak => {
+            let trunk = rng.gen_range(4..=6);
+            (
+                trunk,
+                vec![(trunk - 2, 2), (trunk - 1, 2), (trunk, 1), (trunk + 1, 1)],
+            )
+        }
+        TreeKind::Spruce => {
+            let trunk = rng.gen_range(6..=9);
+            // Narrowing towards the top, alternately wider and narrower.
+            let layers = (2..=trunk + 1)
+                .map(|y| {
+                    let radius = if y > trunk {
+                        0
+                    } else {
+                        ((trunk + 1 - y) * 3 / trunk).min(2) + (y % 2 == 0) as i32
+                    };
+                    (y, radius)
+                })
+                .collect();
+            (trunk, layers)
+        }
+        TreeKind::Jungle => {
+            let trunk = rng.gen_range(9..=12);
+            (
+                trunk,
+                vec![(trunk - 2, 3), (trunk - 1, 3), (trunk, 2), (trunk + 1, 1)],
+            )
+        }
+        TreeKind::Acacia => {
+            let trunk = rng.gen_range(4..=5);
+            (trunk, vec![(trunk, 3), (trunk + 1, 1)])
+        }
+    };
+
+    let mut blocks: Vec<(IVec3, BlockType)> = (0..trunk)
+        .map(|y| (IVec3::new(0, y, 0), BlockType::Wood))
+        .collect();
+    for (y, radius) in layers {
+        for dx in -radius..=radius {
+            for dz in -radius..=radius {
+                // Round the crown off, raggedly at the widest layers.
+                let distance = dx * dx + dz * dz;
+                if distance > radius * radius || (distance == radius * radius && rng.gen()) {
+                    continue;
+                }
+                blocks.push((IVec3::new(dx, y, dz), BlockType::Leaves));
+            }
+        }
+    }
+    blocks
+}
+
+/// Write `block` at world `position` if it is inside the chunk at `origin`.
+/// Trunks replace leaves and plants, leaves and plants only fill open air,
+/// so overlapping features come out the same whichever is written first.
+fn stamp(storage: &mut ChunkStorage, origin: IVec3, position: IVec3, block: BlockType) {
+    let local = position - origin;
+    if local.min_element() < 0 || local.max_element() >= CHUNK_SIZE as i32 {
+        return;
+    }
+    let (x, y, z) = (local.x as usize, local.y as usize, local.z as usize);
+    let replaceable = match storage.get(x, y, z) {
+        BlockType::Air => true,
+        BlockType::Leaves | BlockType::TallGrass => block == BlockType::Wood,
+        _ => false,
+    };
+    if replaceable {
+        storage.set(x, y, z, block);
+    }
+}
+
+impl WorldGenerator {
+    /// Grow trees, shrubs and grass on a freshly filled chunk. `columns` are
+    /// the chunk's own columns, `z * CHUNK_SIZE + x`.
+    pub(super) fn decorate_chunk(
+        &self,
+        chunk_pos: ChunkPos,
+        columns: &[ColumnInfo],
+        storage: &mut ChunkStorage,
+    ) {
+        let origin = chunk_pos.to_world_pos().as_ivec3();
+        self.place_trees(origin, storage);
+        self.place_ground_cover(origin, columns, storage);
+    }
+
+    /// Grow every tree whose cell lies within reach of the chunk, keeping
+    /// the blocks that land inside it.
+    fn place_trees(&self, origin: IVec3, storage: &mut ChunkStorage) {
+        let size = CHUNK_SIZE as i32;
+        let cells = |start: i32| {
+            (start - TREE_REACH).div_euclid(TREE_CELL)
+                ..=(start + size - 1 + TREE_REACH).div_euclid(TREE_CELL)
+        };
+        let planet_cells = (self.config.planet_size as i32 / TREE_CELL).max(1);
+
+        for cell_x in cells(origin.x) {
+            for cell_z in cells(origin.z) {
+                let (wrapped_x, wrapped_z) = (
+                    cell_x.rem_euclid(planet_cells),
+                    cell_z.rem_euclid(planet_cells),
+                );
+                let seed = hash_position(self.config.seed ^ TREE_SALT, wrapped_x, 0, wrapped_z);
+                let mut rng = StdRng::seed_from_u64(seed);
+                // Keep trunks off cell edges so neighbouring trunks never touch.
+                let offset_x = rng.gen_range(1..TREE_CELL);
+                let offset_z = rng.gen_range(1..TREE_CELL);
+                let roll: f32 = rng.gen();
+
+                let trunk_x = (wrapped_x * TREE_CELL + offset_x) as f32;
+                let trunk_z = (wrapped_z * TREE_CELL + offset_z) as f32;
+                // The ground height alone rules out trees above or below the
+                // chunk before the rest of the column is worked out.
+                let base = self.get_height(trunk_x, trunk_z).floor() as i32 + 1;
+                if base + TREE_MAX_HEIGHT < origin.y || base >= origin.y + size {
+                    continue;
+                }
+                let column = self.column_info(trunk_x, trunk_z);
+                if column.water_level >= base as f32 || !fertile(column.surface_block) {
+                    continue;
+                }
+                let Some((kind, chance)) = biome_tree(column.biome) else {
+                    continue;
+                };
+                if roll >= chance * climate_density(column.temperature_c, column.moisture) {
+                    continue;
+                }
+
+                let trunk = IVec3::new(
+                    cell_x * TREE_CELL + offset_x,
+                    base,
+                    cell_z * TREE_CELL + offset_z,
+                );
+                for (offset, block) in tree_blocks(kind, &mut rng) {
+                    stamp(storage, origin, trunk + offset, block);
+                }
+            }
+        }
+    }
+
+    /// Tall grass and single-leaf shrubs, rolled per column.
+    fn place_ground_cover(
+        &self,
+        origin: IVec3,
+        columns: &[ColumnInfo],
+        storage: &mut ChunkStorage,
+    ) {
+        let planet_size = self.config.planet_size.max(1) as i32;
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let column = &columns[z * CHUNK_SIZE + x];
+                let base = column.height.floor() as i32 + 1;
+                if !(origin.y..origin.y + CHUNK_SIZE as i32).contains(&base) {
+                    continue;
+                }
+                if column.water_level >= base as f32 || column.surface_block != BlockType::Grass {
+                    continue;
+                }
+
+                let (grass, shrub) = biome_cover(column.biome);
+                let density = climate_density(column.temperature_c, column.moisture);
+                let world_x = (origin.x + x as i32).rem_euclid(planet_size);
+                let world_z = (origin.z + z as i32).rem_euclid(planet_size);
+                let seed = hash_position(self.config.seed ^ COVER_SALT, world_x, 0, world_z);
+                let roll = StdRng::seed_from_u64(seed).gen::<f32>();
+
+                let block = if roll < shrub * density {
+                    BlockType::Leaves
+                } else if roll < (shrub + grass) * density {
+                    BlockType::TallGrass
+                } else {
+                    continue;
+                };
+                let position = IVec3::new(origin.x + x as i32, base, origin.z + z as i32);
+                stamp(storage, origin, position, block);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trees_fit_their_reach_and_trunks_win_over_leaves() {
+        for kind in [
+            TreeKind::Oak,
+            TreeKind::Spruce,
+            TreeKind::Jungle,
+            TreeKind::Acacia,
+        ] {
+            for seed in 0..32 {
+                let blocks = tree_blocks(kind, &mut StdRng::seed_from_u64(seed));
+                assert_eq!(blocks, tree_blocks(kind, &mut StdRng::seed_from_u64(seed)));
+                assert!(blocks.iter().any(|(_, block)| *block == BlockType::Leaves));
+                for (offset, _) in &blocks {
+                    assert!(offset.x.abs() <= TREE_REACH && offset.z.abs() <= TREE_REACH);
+                    assert!((0..=TREE_MAX_HEIGHT).contains(&offset.y), "{:?}", kind);
+                }
+            }
+        }
+
+        // Two trees sharing a voxel, written in either order across a chunk
+        // border, leave the same block behind.
+        let origin = IVec3::new(32, 0, 0);
+        let shared = IVec3::new(33, 5, 4);
+        let outside = IVec3::new(31, 5, 4);
+        let mut storages = [ChunkStorage::new(), ChunkStorage::new()];
+        let writes = [
+            (shared, BlockType::Leaves),
+            (shared, BlockType::Wood),
+            (outside, BlockType::Wood),
+        ];
+        for (storage, order) in storages.iter_mut().zip([[0, 1, 2], [2, 1, 0]]) {
+            for index in order {
+                let (position, block) = writes[index];
+                stamp(storage, origin, position, block);
+            }
+        }
+        for storage in &storages {
+            assert_eq!(storage.get(1, 5, 4), BlockType::Wood);
+            assert_eq!(
+                storage
+                    .iter()
+                    .filter(|block| *block != BlockType::Air)
+                    .count(),
+                1
+            );
+        }
+
+        // Nothing grows into the ground.
+        let mut storage = ChunkStorage::filled(BlockType::Stone);
+        stamp(&mut storage, origin, shared, BlockType::Wood);
+        assert_eq!(storage.get(1, 5, 4), BlockType::Stone);
+    }
+}
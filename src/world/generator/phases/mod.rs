@@ -1,3 +1,8 @@
 pub mod biomes;
+pub mod blending;
+pub mod caves;
 pub mod climate;
+pub mod decoration;
+pub mod ores;
+pub mod structures;
 pub mod terrain;
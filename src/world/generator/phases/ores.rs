@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use bevy::math::IVec3;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::super::lithology::{OreDeposit, ORE_CELL};
+use super::super::{util::hash_position, WorldGenerator};
+use crate::chunk::{ChunkPos, ChunkStorage, CHUNK_SIZE};
+
+/// Furthest a vein wanders from where it starts, per axis.
+const VEIN_REACH: i32 = 4;
+
+impl WorldGenerator {
+    /// Grow ore veins into the host rock of a baked chunk. Veins start in
+    /// `ORE_CELL` cubes on a world grid, each cube seeded from its wrapped
+    /// position and reading the lithology at its own middle, so a vein
+    /// crossing a chunk border is grown the same way by both chunks.
+    pub fn place_ores(&self, chunk_pos: ChunkPos, storage: &mut ChunkStorage) {
+        if storage
+            .uniform_block()
+            .is_some_and(|block| !block.is_solid())
+        {
+            return;
+        }
+
+        let origin = chunk_pos.to_world_pos().as_ivec3();
+        let size = CHUNK_SIZE as i32;
+        let cells = |start: i32| {
+            (start - VEIN_REACH).div_euclid(ORE_CELL)
+                ..=(start + size + VEIN_REACH).div_euclid(ORE_CELL)
+        };
+        let planet_cells = (self.config.planet_size as i32 / ORE_CELL).max(1);
+        let mut columns: HashMap<(i32, i32), (f32, Vec<OreDeposit>)> = HashMap::new();
+
+        for cell_x in cells(origin.x) {
+            for cell_z in cells(origin.z) {
+                let wrapped = (
+                    cell_x.rem_euclid(planet_cells),
+                    cell_z.rem_euclid(planet_cells),
+                );
+                let (surface, deposits) = columns.entry(wrapped).or_insert_with(|| {
+                    let middle = |cell: i32| (cell * ORE_CELL + ORE_CELL / 2) as f32;
+                    let (x, z) = (middle(wrapped.0), middle(wrapped.1));
+                    let profile = self.lithology_profile_at(x, z);
+                    (self.get_height(x, z), profile.ore_deposits())
+                });
+
+                for cell_y in cells(origin.y) {
+                    for (index, deposit) in deposits.iter().enumerate() {
+                        let salt = (index as u64 + 1).wrapping_mul(0xD6E8FEB86659FD93);
+                        let seed =
+                            hash_position(self.config.seed ^ salt, wrapped.0, cell_y, wrapped.1);
+                        let mut rng = StdRng::seed_from_u64(seed);
+                        let cell_origin = IVec3::new(cell_x, cell_y, cell_z) * ORE_CELL;
+                        grow_veins(&mut rng, cell_origin, *surface, deposit, origin, storage);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Start this cell's veins of one deposit and walk each one through the host
+/// rock, writing the voxels that fall inside the chunk at `chunk_origin`.
+fn grow_veins(
+    rng: &mut StdRng,
+    cell_origin: IVec3,
+    surface: f32,
+    deposit: &OreDeposit,
+    chunk_origin: IVec3,
+    storage: &mut ChunkStorage,
+) {
+    let whole = deposit.veins_per_cell.floor();
+    let count = whole as u32 + (rng.gen::<f32>() < deposit.veins_per_cell - whole) as u32;
+
+    for _ in 0..count {
+        let start = [
+            cell_origin.x + rng.gen_range(0..ORE_CELL),
+            cell_origin.y + rng.gen_range(0..ORE_CELL),
+            cell_origin.z + rng.gen_range(0..ORE_CELL),
+        ];
+        let depth = surface - start[1] as f32;
+        if depth < deposit.min_depth as f32 || depth >= deposit.max_depth as f32 {
+            continue;
+        }
+
+        let mut position = start;
+        for _ in 0..deposit.vein_size {
+            place(position, chunk_origin, deposit, storage);
+            let axis = rng.gen_range(0..3);
+            let step = if rng.gen::<bool>() { 1 } else { -1 };
+            let next = position[axis] + step;
+            if (next - start[axis]).abs() <= VEIN_REACH {
+                position[axis] = next;
+            }
+        }
+    }
+}
+
+fn place(
+    position: [i32; 3],
+    chunk_origin: IVec3,
+    deposit: &OreDeposit,
+    storage: &mut ChunkStorage,
+) {
+    let local = [
+        position[0] - chunk_origin.x,
+        position[1] - chunk_origin.y,
+        position[2] - chunk_origin.z,
+    ];
+    if local
+        .iter()
+        .any(|&value| value < 0 || value >= CHUNK_SIZE as i32)
+    {
+        return;
+    }
+    let [x, y, z] = local.map(|value| value as usize);
+    if storage.get(x, y, z) == deposit.host {
+        storage.set(x, y, z, deposit.ore);
+    }
+}
@@ -0,0 +1,260 @@
+use bevy::math::IVec3;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::ops::Range;
+
+use super::super::{util::hash_position, WorldGenerator};
+use crate::chunk::{ChunkPos, ChunkStorage, CHUNK_SIZE};
+use crate::world::structures::{StructureLibrary, StructureTemplate};
+
+/// Hydrology above this marks a river or pond no structure may stand in.
+const WET_INTENSITY: f32 = 0.05;
+
+/// A structure the generator has decided to place.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct StructureSite {
+    /// Index into `StructureLibrary::templates`.
+    pub template: usize,
+    /// World position of the template's low corner.
+    pub origin: IVec3,
+}
+
+impl WorldGenerator {
+    /// The structure, if any, of one template's grid cell. Cells are seeded
+    /// from their wrapped position: the cell is the feature origin every
+    /// chunk the structure touches agrees on. With `levels`, a structure
+    /// that would stand wholly above or below those world heights is
+    /// skipped before the costly checks of its site.
+    pub fn structure_site(
+        &self,
+        index: usize,
+        template: &StructureTemplate,
+        cell_x: i32,
+        cell_z: i32,
+        levels: Option<Range<i32>>,
+    ) -> Option<StructureSite> {
+        let spacing = template.spacing;
+        let planet_cells = (self.config.planet_size as i32 / spacing).max(1);
+        let (wrapped_x, wrapped_z) = (
+            cell_x.rem_euclid(planet_cells),
+            cell_z.rem_euclid(planet_cells),
+        );
+        let seed = hash_position(self.config.seed ^ template.salt(), wrapped_x, 0, wrapped_z);
+        let mut rng = StdRng::seed_from_u64(seed);
+        if rng.gen::<f32>() >= template.chance {
+            return None;
+        }
+        // The footprint stays inside its cell, so sites never overlap.
+        let offset_x = rng.gen_range(0..spacing - template.size.x);
+        let offset_z = rng.gen_range(0..spacing - template.size.z);
+
+        let corner_x = (wrapped_x * spacing + offset_x) as f32;
+        let corner_z = (wrapped_z * spacing + offset_z) as f32;
+        let (far_x, far_z) = (
+            corner_x + (template.size.x - 1) as f32,
+            corner_z + (template.size.z - 1) as f32,
+        );
+        let middle_x = corner_x + (template.size.x / 2) as f32;
+        let middle_z = corner_z + (template.size.z / 2) as f32;
+        let origin_y = |height: f32| height.floor() as i32 + 1 - template.sink;
+
+        if let Some(levels) = levels {
+            // The same ground the site is set on below.
+            let low = origin_y(self.get_height(middle_x, middle_z));
+            if low + template.size.y <= levels.start
+                || low - foundation_depth(template) >= levels.end
+            {
+                return None;
+            }
+        }
+
+        let middle = self.column_info(middle_x, middle_z);
+        if !template.biomes.contains(&middle.biome) || middle.water_level > middle.height {
+            return None;
+        }
+        let mut lowest = middle.height;
+        let mut highest = middle.height;
+        for (x, z) in [
+            (corner_x, corner_z),
+            (far_x, corner_z),
+            (corner_x, far_z),
+            (far_x, far_z),
+            (middle_x, middle_z),
+        ] {
+            // No structures in rivers, lakes or the sea.
+            let components = self.terrain_components(x, z);
+            let hydro = self.sample_hydrology(x, z, components.base_height);
+            if hydro.river_intensity > WET_INTENSITY || hydro.pond_intensity > WET_INTENSITY {
+                return None;
+            }
+            let height = self.get_height(x, z);
+            if height <= self.config.sea_level {
+                return None;
+            }
+            lowest = lowest.min(height);
+            highest = highest.max(height);
+        }
+        if highest - lowest > template.max_slope {
+            return None;
+        }
+
+        Some(StructureSite {
+            template: index,
+            origin: IVec3::new(
+                cell_x * spacing + offset_x,
+                origin_y(middle.height),
+                cell_z * spacing + offset_z,
+            ),
+        })
+    }
+
+    /// Stamp every structure reaching into a baked chunk, keeping the
+    /// blocks that fall inside it. Templates are stamped in library order,
+    /// so where two overlap every chunk resolves them the same way.
+    pub(super) fn place_structures(&self, chunk_pos: ChunkPos, storage: &mut ChunkStorage) {
+        let origin = chunk_pos.to_world_pos().as_ivec3();
+        let size = CHUNK_SIZE as i32;
+        let library = StructureLibrary::global();
+
+        for (index, template) in library.templates().iter().enumerate() {
+            let spacing = template.spacing;
+            let cells =
+                |start: i32| start.div_euclid(spacing)..=(start + size - 1).div_euclid(spacing);
+            for cell_x in cells(origin.x) {
+                for cell_z in cells(origin.z) {
+                    let levels = origin.y..origin.y + size;
+                    let Some(site) =
+                        self.structure_site(index, template, cell_x, cell_z, Some(levels))
+                    else {
+                        continue;
+                    };
+                    stamp(template, site, origin, storage, |x, z| {
+                        self.get_height(x as f32, z as f32).floor() as i32
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Layers below the bottom one a structure's foundations may reach, at
+/// most the slope its site allows.
+fn foundation_depth(template: &StructureTemplate) -> i32 {
+    template.max_slope.ceil() as i32 + 1
+}
+
+/// Write the part of a structure inside the chunk at `chunk_origin`, with
+/// foundations down to `ground(x, z)`, the top solid block of a column.
+fn stamp(
+    template: &StructureTemplate,
+    site: StructureSite,
+    chunk_origin: IVec3,
+    storage: &mut ChunkStorage,
+    ground: impl Fn(i32, i32) -> i32,
+) {
+    let size = CHUNK_SIZE as i32;
+    let low = site.origin - chunk_origin;
+    let high = low + template.size;
+    let foundation_depth = foundation_depth(template);
+    if high.x <= 0 || high.z <= 0 || low.x >= size || low.z >= size {
+        return;
+    }
+    if high.y <= 0 || low.y - foundation_depth >= size {
+        return;
+    }
+
+    for x in low.x.max(0)..high.x.min(size) {
+        for z in low.z.max(0)..high.z.min(size) {
+            for y in low.y.max(0)..high.y.min(size) {
+                if let Some(block) = template.block(IVec3::new(x, y, z) - low) {
+                    storage.set(x as usize, y as usize, z as usize, block);
+                }
+            }
+
+            // Prop the bottom layer up where the ground falls away.
+            let Some(foundation) = template.foundation else {
+                continue;
+            };
+            let bottom = template.block(IVec3::new(x - low.x, 0, z - low.z));
+            if !bottom.is_some_and(|block| block.is_solid()) {
+                continue;
+            }
+            let ground = ground(chunk_origin.x + x, chunk_origin.z + z) - chunk_origin.y;
+            let start = (ground + 1).max(low.y - foundation_depth).max(0);
+            for y in start..low.y.min(size) {
+                storage.set(x as usize, y as usize, z as usize, foundation);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockType;
+
+    #[test]
+    fn structures_cross_chunk_borders_intact() {
+        let size = CHUNK_SIZE as i32;
+        for (index, template) in StructureLibrary::global().templates().iter().enumerate() {
+            // Ground sloping away along x, under a structure whose middle
+            // sits on the corner of eight chunks.
+            let ground = |x: i32, _z: i32| 40 - x.div_euclid(3);
+            let origin = IVec3::new(size * 4 - template.size.x / 2, 0, -template.size.z / 2);
+            let origin = origin.with_y(ground(origin.x, 0) + 1 - template.sink);
+            let site = StructureSite {
+                template: index,
+                origin,
+            };
+            let corner = (origin + template.size / 2).with_z(0).with_y(origin.y + 2) - size;
+
+            let mut chunks = Vec::new();
+            for offset in [0, 1].into_iter().flat_map(|x| {
+                [0, 1]
+                    .into_iter()
+                    .flat_map(move |y| [0, 1].map(|z| IVec3::new(x, y, z)))
+            }) {
+                let chunk_origin = corner + offset * size;
+                let mut storage = ChunkStorage::new();
+                stamp(template, site, chunk_origin, &mut storage, ground);
+                chunks.push((chunk_origin, storage));
+            }
+            let block_at = |world: IVec3| {
+                let (chunk_origin, storage) = chunks
+                    .iter()
+                    .find(|(chunk_origin, _)| {
+                        let local = world - *chunk_origin;
+                        local.min_element() >= 0 && local.max_element() < size
+                    })
+                    .unwrap();
+                let local = (world - *chunk_origin).as_uvec3();
+                storage.get(local.x as usize, local.y as usize, local.z as usize)
+            };
+
+            for x in 0..template.size.x {
+                for z in 0..template.size.z {
+                    for y in 0..template.size.y {
+                        let local = IVec3::new(x, y, z);
+                        let expected = template.block(local).unwrap_or(BlockType::Air);
+                        assert_eq!(
+                            block_at(origin + local),
+                            expected,
+                            "{} {:?}",
+                            template.name,
+                            local
+                        );
+                    }
+                    // Foundations fill down to the ground and no further.
+                    let (world_x, world_z) = (origin.x + x, origin.z + z);
+                    let below = block_at(IVec3::new(world_x, origin.y - 1, world_z));
+                    if ground(world_x, world_z) < origin.y - 1 {
+                        assert_eq!(Some(below), template.foundation, "{}", template.name);
+                    }
+                    let under = IVec3::new(world_x, ground(world_x, world_z), world_z);
+                    if under.y < origin.y {
+                        assert_eq!(block_at(under), BlockType::Air);
+                    }
+                }
+            }
+        }
+    }
+}
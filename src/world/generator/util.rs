@@ -45,6 +45,19 @@ pub(super) fn torus_noise(noise: &Perlin, u: f32, v: f32, cycles: f32, extra: f3
     ]) as f32
 }
 
+/// Seed for whatever is generated at an integer position, such as a feature
+/// cell. Callers pass wrapped coordinates so both sides of the wrap agree.
+pub(super) fn hash_position(seed: u64, x: i32, y: i32, z: i32) -> u64 {
+    let mut hash = seed
+        ^ (x as i64 as u64).wrapping_mul(0x9E3779B97F4A7C15)
+        ^ (y as i64 as u64).wrapping_mul(0xC2B2AE3D27D4EB4F)
+        ^ (z as i64 as u64).wrapping_mul(0x165667B19E3779F9);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xFF51AFD7ED558CCD);
+    hash ^= hash >> 33;
+    hash
+}
+
 pub(super) fn wrap_index(value: i32, size: i32) -> i32 {
     let mut result = value % size;
     if result < 0 {
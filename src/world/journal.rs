@@ -0,0 +1,191 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use super::chunk_store::QueuedChunkPayload;
+use super::persistence::FsyncPolicy;
+use crate::chunk::data::crc32;
+use crate::chunk::ChunkPos;
+
+pub const JOURNAL_FILENAME: &str = "chunks.journal";
+
+const ENTRY_MAGIC: [u8; 4] = *b"FRJL";
+/// Magic, position, revision, timestamp, payload length, payload CRC32.
+const ENTRY_HEADER_LEN: usize = 4 + 12 + 4 + 8 + 4 + 4;
+
+/// A payload recorded in the journal but not yet known to be applied.
+#[derive(Clone, Debug)]
+pub struct JournalEntry {
+    pub payload: QueuedChunkPayload,
+    /// Seconds since the Unix epoch when the payload was journaled.
+    pub timestamp: u64,
+}
+
+/// Write-ahead log shared by the persistence backends. Each payload is
+/// appended (and synced per the `FsyncPolicy`) before the backend touches its
+/// own files, and the journal is cleared once the write lands. Entries left
+/// behind by a crash are replayed when the backend is next opened; replaying
+/// an entry that already landed is harmless because backends ignore
+/// revisions they already hold.
+pub struct ChunkJournal {
+    path: PathBuf,
+    fsync: FsyncPolicy,
+    file: Option<File>,
+}
+
+impl ChunkJournal {
+    pub fn new(root: &Path, fsync: FsyncPolicy) -> Self {
+        Self {
+            path: root.join(JOURNAL_FILENAME),
+            fsync,
+            file: None,
+        }
+    }
+
+    /// Entries from a previous session, oldest first. Reading stops at the
+    /// first torn or corrupt entry, since nothing after it was acknowledged.
+    pub fn pending(&self) -> io::Result<Vec<JournalEntry>> {
+        let bytes = match fs::read(&self.path) {
+            Ok(bytes) => bytes,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error),
+        };
+
+        let mut entries = Vec::new();
+        let mut cursor = 0;
+        while let Some((entry, len)) = decode_entry(&bytes[cursor..]) {
+            entries.push(entry);
+            cursor += len;
+        }
+        Ok(entries)
+    }
+
+    pub fn append(&mut self, payload: &QueuedChunkPayload, timestamp: u64) -> io::Result<()> {
+        let length = u32::try_from(payload.bytes.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "payload too large"))?;
+
+        let mut entry = Vec::with_capacity(ENTRY_HEADER_LEN + payload.bytes.len());
+        entry.extend_from_slice(&ENTRY_MAGIC);
+        entry.extend_from_slice(&payload.position.x.to_le_bytes());
+        entry.extend_from_slice(&payload.position.y.to_le_bytes());
+        entry.extend_from_slice(&payload.position.z.to_le_bytes());
+        entry.extend_from_slice(&payload.revision.to_le_bytes());
+        entry.extend_from_slice(&timestamp.to_le_bytes());
+        entry.extend_from_slice(&length.to_le_bytes());
+        entry.extend_from_slice(&crc32(&payload.bytes).to_le_bytes());
+        entry.extend_from_slice(&payload.bytes);
+
+        let sync = self.fsync.sync_files();
+        let file = self.file()?;
+        file.write_all(&entry)?;
+        if sync {
+            file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// Drop every entry; called once the journaled writes have landed.
+    pub fn clear(&mut self) -> io::Result<()> {
+        match self.file.as_mut() {
+            Some(file) => file.set_len(0),
+            None => match fs::remove_file(&self.path) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+                _ => Ok(()),
+            },
+        }
+    }
+
+    fn file(&mut self) -> io::Result<&mut File> {
+        if self.file.is_none() {
+            if let Some(parent) = self.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            self.file = Some(file);
+        }
+        Ok(self.file.as_mut().expect("journal just opened"))
+    }
+}
+
+fn decode_entry(bytes: &[u8]) -> Option<(JournalEntry, usize)> {
+    let header = bytes.get(..ENTRY_HEADER_LEN)?;
+    if header[..4] != ENTRY_MAGIC {
+        return None;
+    }
+
+    let word = |offset: usize| u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap());
+    let position = ChunkPos::new(word(4) as i32, word(8) as i32, word(12) as i32);
+    let revision = word(16);
+    let timestamp = u64::from_le_bytes(header[20..28].try_into().unwrap());
+    let length = word(28) as usize;
+    let checksum = word(32);
+
+    let data = bytes.get(ENTRY_HEADER_LEN..ENTRY_HEADER_LEN + length)?;
+    if crc32(data) != checksum {
+        return None;
+    }
+
+    Some((
+        JournalEntry {
+            payload: QueuedChunkPayload {
+                position,
+                revision,
+                bytes: data.to_vec(),
+            },
+            timestamp,
+        },
+        ENTRY_HEADER_LEN + length,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn torn_tail_is_ignored_on_replay() {
+        let root = std::env::temp_dir().join(format!("forge_journal_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+
+        let mut journal = ChunkJournal::new(&root, FsyncPolicy::Never);
+        for revision in 1..=2 {
+            journal
+                .append(
+                    &QueuedChunkPayload {
+                        position: ChunkPos::new(-4, 2, 9),
+                        revision,
+                        bytes: vec![revision as u8; 300],
+                    },
+                    100 + revision as u64,
+                )
+                .unwrap();
+        }
+        drop(journal);
+
+        // Chop the second entry in half, as a crash mid-append would.
+        let path = root.join(JOURNAL_FILENAME);
+        let len = fs::metadata(&path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 150)
+            .unwrap();
+
+        let mut journal = ChunkJournal::new(&root, FsyncPolicy::Never);
+        let pending = journal.pending().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].payload.position, ChunkPos::new(-4, 2, 9));
+        assert_eq!(pending[0].payload.revision, 1);
+        assert_eq!(pending[0].timestamp, 101);
+        assert_eq!(pending[0].payload.bytes, vec![1; 300]);
+
+        journal.clear().unwrap();
+        assert!(journal.pending().unwrap().is_empty());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -3,9 +3,13 @@ pub mod chunk_store;
 pub mod config;
 pub mod defaults;
 pub mod generator;
+pub mod journal;
 pub mod metadata;
 pub mod package;
 pub mod persistence;
+pub mod persistence_worker;
+pub mod region;
+pub mod structures;
 
 pub use biome::Biome;
 pub use chunk_store::{
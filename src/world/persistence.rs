@@ -2,14 +2,19 @@ use std::path::{Path, PathBuf};
 
 use bevy::prelude::*;
 use std::collections::HashMap;
-use std::fs::{create_dir_all, File};
+use std::fs::{create_dir_all, File, OpenOptions, TryLockError};
 use std::io::{self, Write};
-use std::sync::{Arc, RwLock};
-use std::time::Instant;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use super::chunk_store::{ChunkPayloadQueue, QueuedChunkPayload};
-use crate::chunk::ChunkPos;
+use super::chunk_store::QueuedChunkPayload;
+use super::journal::ChunkJournal;
+use super::persistence_worker::{
+    flush_persistence_on_exit, flush_queue_to_persistence, PersistenceMetrics, PersistenceWorker,
+};
+use crate::chunk::{ChunkPos, ChunkStorage};
 
+#[allow(dead_code)]
 pub fn chunk_filename(position: &ChunkPos, revision: u32) -> String {
     format!(
         "chunk_{}_{}_{}_rev{}.bin",
@@ -19,17 +24,269 @@ pub fn chunk_filename(position: &ChunkPos, revision: u32) -> String {
 
 pub trait ChunkPersistence: Send + Sync + 'static {
     fn persist(&mut self, payload: &QueuedChunkPayload) -> io::Result<()>;
+    #[allow(dead_code)]
     fn load(&self, position: ChunkPos) -> io::Result<Option<(u32, Vec<u8>)>>;
+    /// Stored revisions of `position`, newest first.
+    fn revisions(&self, position: ChunkPos) -> io::Result<Vec<u32>>;
+    fn load_revision(&self, position: ChunkPos, revision: u32) -> io::Result<Option<Vec<u8>>>;
+    /// Disk usage, including what `policy` would prune at `now`.
+    #[allow(dead_code)]
+    fn usage(&self, policy: &RetentionPolicy, now: u64) -> io::Result<PersistenceUsage>;
+    /// Delete revisions outside `policy` and reclaim the space they used.
+    fn prune(&mut self, policy: &RetentionPolicy, now: u64) -> io::Result<PruneReport>;
 }
 
+/// Which old revisions survive pruning. A revision is kept when it is one of
+/// the newest `keep_last` of its chunk or was written within
+/// `keep_newer_than`; the newest revision is always kept.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    pub keep_last: usize,
+    pub keep_newer_than: Option<Duration>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            keep_last: 8,
+            keep_newer_than: Some(Duration::from_secs(24 * 60 * 60)),
+        }
+    }
+}
+
+impl RetentionPolicy {
+    pub fn keep_all() -> Self {
+        Self {
+            keep_last: usize::MAX,
+            keep_newer_than: None,
+        }
+    }
+
+    /// How many revisions to keep, given their timestamps (Unix seconds)
+    /// newest first. Pruning always drops a suffix so chains stay intact.
+    pub fn retained(&self, timestamps: &[u64], now: u64) -> usize {
+        let by_count = self.keep_last.max(1);
+        let by_age = self.keep_newer_than.map_or(0, |window| {
+            let cutoff = now.saturating_sub(window.as_secs());
+            timestamps
+                .iter()
+                .rposition(|timestamp| *timestamp >= cutoff)
+                .map_or(0, |index| index + 1)
+        });
+        by_count.max(by_age).min(timestamps.len())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PersistenceUsage {
+    pub chunks: usize,
+    pub revisions: usize,
+    /// Payload bytes across every stored revision.
+    pub payload_bytes: u64,
+    /// Bytes the backend occupies on disk, including headers and free space.
+    pub disk_bytes: u64,
+    pub prunable_revisions: usize,
+    pub prunable_bytes: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    pub revisions_removed: usize,
+    pub bytes_freed: u64,
+}
+
+impl std::ops::AddAssign for PruneReport {
+    fn add_assign(&mut self, other: Self) {
+        self.revisions_removed += other.revisions_removed;
+        self.bytes_freed += other.bytes_freed;
+    }
+}
+
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+/// How hard persistence writes push data to stable storage before they are
+/// considered done.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    /// Leave flushing to the OS; fastest, but a power loss can drop recent
+    /// writes.
+    Never,
+    /// Sync file contents (journal entries, payload files, region records).
+    #[default]
+    Data,
+    /// Also sync the containing directory after creating or renaming files.
+    Full,
+}
+
+impl FsyncPolicy {
+    /// Parse `FORGE_PERSISTENCE_FSYNC` style values (`never`, `data`, `full`).
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "never" | "off" | "0" => Some(Self::Never),
+            "data" => Some(Self::Data),
+            "full" => Some(Self::Full),
+            _ => None,
+        }
+    }
+
+    pub fn sync_files(self) -> bool {
+        self != Self::Never
+    }
+
+    pub fn sync_directories(self) -> bool {
+        self == Self::Full
+    }
+}
+
+/// Write `bytes` to `path` through a sibling temp file and a rename, so
+/// readers only ever observe the old or the complete new file.
+pub fn write_atomic(path: &Path, bytes: &[u8], fsync: FsyncPolicy) -> io::Result<()> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    let mut file = File::create(&temp_path)?;
+    file.write_all(bytes)?;
+    if fsync.sync_files() {
+        file.sync_data()?;
+    }
+    drop(file);
+
+    std::fs::rename(&temp_path, path)?;
+    if fsync.sync_directories() {
+        if let Some(parent) = path.parent() {
+            sync_directory(parent)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn sync_directory(path: &Path) -> io::Result<()> {
+    // Directories cannot be opened for syncing on Windows; renames there are
+    // already journaled by the filesystem.
+    if cfg!(unix) {
+        File::open(path)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Advisory lock file in a world directory. The game and server hold it
+/// shared while their persistence backend is open; pruning from outside
+/// needs it exclusively, since it rewrites region files they have cached.
+pub const WORLD_LOCK_FILE: &str = "world.lock";
+
+/// A held `WORLD_LOCK_FILE`, released when dropped.
+#[derive(Resource, Debug)]
+pub struct WorldLock {
+    _file: File,
+}
+
+impl WorldLock {
+    /// Lock `root` alongside other running games and servers.
+    pub fn shared(root: &Path) -> io::Result<Self> {
+        Self::acquire(root, false)
+    }
+
+    /// Lock `root` for exclusive use. Fails with `WouldBlock` while anything
+    /// else holds the world.
+    #[allow(dead_code)]
+    pub fn exclusive(root: &Path) -> io::Result<Self> {
+        Self::acquire(root, true)
+    }
+
+    fn acquire(root: &Path, exclusive: bool) -> io::Result<Self> {
+        create_dir_all(root)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(root.join(WORLD_LOCK_FILE))?;
+        let locked = if exclusive {
+            file.try_lock()
+        } else {
+            file.try_lock_shared()
+        };
+        match locked {
+            Ok(()) => Ok(Self { _file: file }),
+            Err(TryLockError::WouldBlock) => Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                format!("world {:?} is in use", root),
+            )),
+            Err(TryLockError::Error(error)) => Err(error),
+        }
+    }
+}
+
+/// Newest revision of `position` that decodes, walking back through older
+/// revisions when the latest one is damaged. The returned revision is the
+/// newest on record, even when an older payload was used, so later edits
+/// still supersede the damaged revision.
+pub fn load_latest_valid<T: ChunkPersistence>(
+    persistence: &T,
+    position: ChunkPos,
+) -> io::Result<Option<(u32, ChunkStorage)>> {
+    let revisions = persistence.revisions(position)?;
+    let Some(&newest) = revisions.first() else {
+        return Ok(None);
+    };
+
+    for revision in revisions {
+        let bytes = match persistence.load_revision(position, revision) {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => continue,
+            Err(error) => {
+                warn!(
+                    "Failed to read persisted chunk {:?} rev {}: {}",
+                    position, revision, error
+                );
+                continue;
+            }
+        };
+
+        match ChunkStorage::from_bytes(&bytes) {
+            Ok(storage) => {
+                if revision != newest {
+                    warn!(
+                        "Recovered persisted chunk {:?} from rev {} (newest rev {} is damaged)",
+                        position, revision, newest
+                    );
+                }
+                return Ok(Some((newest, storage)));
+            }
+            Err(error) => {
+                warn!(
+                    "Failed to decode persisted chunk {:?} rev {}: {:?}",
+                    position, revision, error
+                );
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Legacy one-file-per-revision backend, kept for tooling and migration.
+#[allow(dead_code)]
 #[derive(Clone)]
 pub struct DiskChunkPersistence {
     root: PathBuf,
     index: Arc<RwLock<HashMap<ChunkPos, (u32, PathBuf)>>>,
+    journal: Arc<Mutex<ChunkJournal>>,
+    fsync: FsyncPolicy,
 }
 
+#[allow(dead_code)]
 impl DiskChunkPersistence {
     pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self::with_fsync(root, FsyncPolicy::default())
+    }
+
+    pub fn with_fsync<P: AsRef<Path>>(root: P, fsync: FsyncPolicy) -> Self {
         let root_path = root.as_ref().to_path_buf();
         let index = Arc::new(RwLock::new(HashMap::new()));
 
@@ -41,6 +298,12 @@ impl DiskChunkPersistence {
                         continue;
                     }
 
+                    // Temp files are writes that never reached their rename.
+                    if path.extension().is_some_and(|extension| extension == "tmp") {
+                        let _ = std::fs::remove_file(&path);
+                        continue;
+                    }
+
                     if let Some((position, revision)) = parse_chunk_filename(&entry.file_name()) {
                         match map.get(&position) {
                             Some((current_rev, _)) if *current_rev >= revision => {}
@@ -53,12 +316,63 @@ impl DiskChunkPersistence {
             }
         }
 
-        Self {
+        let mut persistence = Self {
+            journal: Arc::new(Mutex::new(ChunkJournal::new(&root_path, fsync))),
             root: root_path,
             index,
+            fsync,
+        };
+        persistence.replay_journal();
+        persistence
+    }
+
+    fn replay_journal(&mut self) {
+        let mut journal = self.journal.lock().expect("chunk journal poisoned");
+        let pending = match journal.pending() {
+            Ok(pending) => pending,
+            Err(error) => {
+                warn!("Failed to read chunk journal in {:?}: {}", self.root, error);
+                return;
+            }
+        };
+        if pending.is_empty() {
+            return;
+        }
+
+        info!("Replaying {} journaled chunk write(s)", pending.len());
+        for entry in &pending {
+            if let Err(error) = self.write_file(&entry.payload) {
+                warn!(
+                    "Failed to replay journaled chunk {:?} rev {}: {}",
+                    entry.payload.position, entry.payload.revision, error
+                );
+                return;
+            }
+        }
+        if let Err(error) = journal.clear() {
+            warn!("Failed to clear chunk journal: {}", error);
         }
     }
 
+    fn write_file(&self, payload: &QueuedChunkPayload) -> io::Result<()> {
+        create_dir_all(&self.root)?;
+        let path = self
+            .root
+            .join(chunk_filename(&payload.position, payload.revision));
+        write_atomic(&path, &payload.bytes, self.fsync)?;
+
+        if let Ok(mut map) = self.index.write() {
+            match map.get(&payload.position) {
+                Some((current_rev, _)) if *current_rev >= payload.revision => {}
+                _ => {
+                    map.insert(payload.position, (payload.revision, path));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     fn refresh_chunk_entry(&self, position: ChunkPos) -> io::Result<Option<(u32, PathBuf)>> {
         if !self.root.exists() {
             return Ok(None);
@@ -95,6 +409,45 @@ impl DiskChunkPersistence {
 
         Ok(None)
     }
+
+    /// Payload files grouped by chunk, newest revision first. File
+    /// modification times stand in for write timestamps.
+    fn files_by_chunk(&self) -> io::Result<HashMap<ChunkPos, Vec<RevisionFile>>> {
+        let mut files: HashMap<ChunkPos, Vec<(u32, RevisionFile)>> = HashMap::new();
+        for (position, revision, path) in list_chunk_files(&self.root)? {
+            let metadata = std::fs::metadata(&path)?;
+            let timestamp = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |elapsed| elapsed.as_secs());
+            files.entry(position).or_default().push((
+                revision,
+                RevisionFile {
+                    path,
+                    len: metadata.len(),
+                    timestamp,
+                },
+            ));
+        }
+
+        Ok(files
+            .into_iter()
+            .map(|(position, mut revisions)| {
+                revisions.sort_unstable_by_key(|(revision, _)| std::cmp::Reverse(*revision));
+                (
+                    position,
+                    revisions.into_iter().map(|(_, file)| file).collect(),
+                )
+            })
+            .collect())
+    }
+}
+
+struct RevisionFile {
+    path: PathBuf,
+    len: u64,
+    timestamp: u64,
 }
 
 fn parse_chunk_filename(filename: &std::ffi::OsStr) -> Option<(ChunkPos, u32)> {
@@ -119,25 +472,34 @@ fn parse_chunk_filename(filename: &std::ffi::OsStr) -> Option<(ChunkPos, u32)> {
     Some((ChunkPos::new(x, y, z), revision))
 }
 
-impl ChunkPersistence for DiskChunkPersistence {
-    fn persist(&mut self, payload: &QueuedChunkPayload) -> io::Result<()> {
-        create_dir_all(&self.root)?;
-        let path = self
-            .root
-            .join(chunk_filename(&payload.position, payload.revision));
-        let mut file = File::create(&path)?;
-        file.write_all(&payload.bytes)?;
+/// Every per-chunk payload file under `root`, including superseded revisions.
+pub fn list_chunk_files(root: &Path) -> io::Result<Vec<(ChunkPos, u32, PathBuf)>> {
+    if !root.exists() {
+        return Ok(Vec::new());
+    }
 
-        if let Ok(mut map) = self.index.write() {
-            match map.get(&payload.position) {
-                Some((current_rev, _)) if *current_rev >= payload.revision => {}
-                _ => {
-                    map.insert(payload.position, (payload.revision, path));
-                }
-            }
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(root)? {
+        let entry = entry?;
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
         }
 
-        Ok(())
+        if let Some((position, revision)) = parse_chunk_filename(&entry.file_name()) {
+            files.push((position, revision, path));
+        }
+    }
+
+    Ok(files)
+}
+
+impl ChunkPersistence for DiskChunkPersistence {
+    fn persist(&mut self, payload: &QueuedChunkPayload) -> io::Result<()> {
+        let mut journal = self.journal.lock().expect("chunk journal poisoned");
+        journal.append(payload, unix_timestamp())?;
+        self.write_file(payload)?;
+        journal.clear()
     }
 
     fn load(&self, position: ChunkPos) -> io::Result<Option<(u32, Vec<u8>)>> {
@@ -161,11 +523,136 @@ impl ChunkPersistence for DiskChunkPersistence {
 
         Ok(None)
     }
+
+    fn revisions(&self, position: ChunkPos) -> io::Result<Vec<u32>> {
+        let mut revisions: Vec<u32> = list_chunk_files(&self.root)?
+            .into_iter()
+            .filter(|(pos, _, _)| *pos == position)
+            .map(|(_, revision, _)| revision)
+            .collect();
+        revisions.sort_unstable_by(|a, b| b.cmp(a));
+        Ok(revisions)
+    }
+
+    fn load_revision(&self, position: ChunkPos, revision: u32) -> io::Result<Option<Vec<u8>>> {
+        match std::fs::read(self.root.join(chunk_filename(&position, revision))) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn usage(&self, policy: &RetentionPolicy, now: u64) -> io::Result<PersistenceUsage> {
+        let mut usage = PersistenceUsage::default();
+        for files in self.files_by_chunk()?.values() {
+            let timestamps: Vec<u64> = files.iter().map(|file| file.timestamp).collect();
+            let retained = policy.retained(&timestamps, now);
+
+            usage.chunks += 1;
+            usage.revisions += files.len();
+            for file in files {
+                usage.payload_bytes += file.len;
+            }
+            for file in &files[retained..] {
+                usage.prunable_revisions += 1;
+                usage.prunable_bytes += file.len;
+            }
+        }
+        usage.disk_bytes = usage.payload_bytes;
+        Ok(usage)
+    }
+
+    fn prune(&mut self, policy: &RetentionPolicy, now: u64) -> io::Result<PruneReport> {
+        let mut report = PruneReport::default();
+        for files in self.files_by_chunk()?.values() {
+            let timestamps: Vec<u64> = files.iter().map(|file| file.timestamp).collect();
+            for file in &files[policy.retained(&timestamps, now)..] {
+                std::fs::remove_file(&file.path)?;
+                report.revisions_removed += 1;
+                report.bytes_freed += file.len;
+            }
+        }
+        if report.revisions_removed > 0 && self.fsync.sync_directories() {
+            sync_directory(&self.root)?;
+        }
+        Ok(report)
+    }
+}
+
+/// `FORGE_PERSISTENCE_DIR`, or `target/chunk_payload_persistence` when unset.
+pub fn persistence_dir_from_env() -> PathBuf {
+    std::env::var("FORGE_PERSISTENCE_DIR")
+        .ok()
+        .filter(|dir| !dir.trim().is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("target/chunk_payload_persistence"))
 }
 
 #[derive(Resource, Clone)]
 pub struct PersistenceConfig {
     pub enabled: bool,
+    pub fsync: FsyncPolicy,
+    pub retention: RetentionPolicy,
+    /// How often the background pass prunes revisions and compacts storage.
+    pub compaction_interval: Duration,
+    /// Payloads the persistence worker channel holds before backpressure.
+    pub channel_capacity: usize,
+}
+
+impl Default for PersistenceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            fsync: FsyncPolicy::default(),
+            retention: RetentionPolicy::default(),
+            compaction_interval: Duration::from_secs(10 * 60),
+            channel_capacity: 64,
+        }
+    }
+}
+
+impl PersistenceConfig {
+    /// Defaults overridden by `FORGE_PERSISTENCE_ENABLED`, `_FSYNC`,
+    /// `_KEEP_REVISIONS`, `_KEEP_HOURS`, `_COMPACT_MINUTES` and `_QUEUE`.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+        };
+
+        Self {
+            enabled: var("FORGE_PERSISTENCE_ENABLED")
+                .map(|value| !matches!(value.trim(), "0" | "false" | "False" | "FALSE"))
+                .unwrap_or(defaults.enabled),
+            fsync: var("FORGE_PERSISTENCE_FSYNC")
+                .and_then(|value| FsyncPolicy::parse(&value))
+                .unwrap_or(defaults.fsync),
+            retention: RetentionPolicy {
+                keep_last: var("FORGE_PERSISTENCE_KEEP_REVISIONS")
+                    .and_then(|value| value.trim().parse().ok())
+                    .unwrap_or(defaults.retention.keep_last),
+                keep_newer_than: match var("FORGE_PERSISTENCE_KEEP_HOURS") {
+                    Some(value) => value
+                        .trim()
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|hours| *hours > 0.0)
+                        .map(|hours| Duration::from_secs_f64(hours * 3600.0)),
+                    None => defaults.retention.keep_newer_than,
+                },
+            },
+            compaction_interval: var("FORGE_PERSISTENCE_COMPACT_MINUTES")
+                .and_then(|value| value.trim().parse::<f64>().ok())
+                .filter(|minutes| *minutes > 0.0)
+                .map(|minutes| Duration::from_secs_f64(minutes * 60.0))
+                .unwrap_or(defaults.compaction_interval),
+            channel_capacity: var("FORGE_PERSISTENCE_QUEUE")
+                .and_then(|value| value.trim().parse().ok())
+                .unwrap_or(defaults.channel_capacity),
+        }
+    }
 }
 
 #[derive(Resource)]
@@ -178,6 +665,7 @@ impl<T: ChunkPersistence> PersistenceHandler<T> {
         Self { handler }
     }
 
+    #[allow(dead_code)]
     pub fn handler_mut(&mut self) -> &mut T {
         &mut self.handler
     }
@@ -187,50 +675,32 @@ impl<T: ChunkPersistence> PersistenceHandler<T> {
     }
 }
 
-pub fn flush_queue_to_persistence<T: ChunkPersistence>(
-    mut queue: ResMut<ChunkPayloadQueue>,
-    mut handler: ResMut<PersistenceHandler<T>>,
+/// Timer for the background retention pass, which runs on the persistence
+/// worker so it serialises with pending writes.
+#[derive(Resource, Default)]
+pub struct PersistenceCompaction {
+    timer: Option<Timer>,
+}
+
+pub fn run_persistence_compaction(
+    time: Res<Time>,
+    mut compaction: ResMut<PersistenceCompaction>,
+    worker: Res<PersistenceWorker>,
     config: Option<Res<PersistenceConfig>>,
 ) {
-    if let Some(config) = config {
-        if !config.enabled {
-            queue.take_all();
-            return;
-        }
-    }
-
-    let payloads = queue.take_all();
-    if payloads.is_empty() {
+    let Some(config) = config else {
+        return;
+    };
+    if !config.enabled {
         return;
     }
 
-    let start = Instant::now();
-    let mut total_bytes = 0_usize;
-    let mut processed = 0_usize;
-    let mut failures = 0_usize;
-
-    for payload in payloads {
-        total_bytes += payload.bytes.len();
-        processed += 1;
-
-        if let Err(error) = handler.handler_mut().persist(&payload) {
-            failures += 1;
-            warn!(
-                "Failed to persist chunk payload {:?} rev {}: {}",
-                payload.position, payload.revision, error
-            );
-        }
+    let timer = compaction
+        .timer
+        .get_or_insert_with(|| Timer::new(config.compaction_interval, TimerMode::Repeating));
+    if timer.tick(time.delta()).just_finished() {
+        worker.request_prune(config.retention.clone());
     }
-
-    let duration_ms = start.elapsed().as_secs_f32() * 1000.0;
-    info!(
-        "chunk-payload persist: count={} total_ms={:.2} avg_ms={:.2} total_bytes={} failures={}",
-        processed,
-        duration_ms,
-        duration_ms / processed.max(1) as f32,
-        total_bytes,
-        failures
-    );
 }
 
 pub struct ChunkPersistencePlugin<T: ChunkPersistence + Clone> {
@@ -247,7 +717,41 @@ impl<T: ChunkPersistence + Clone> ChunkPersistencePlugin<T> {
 impl<T: ChunkPersistence + Clone> Plugin for ChunkPersistencePlugin<T> {
     fn build(&self, app: &mut App) {
         app.insert_resource(PersistenceHandler::new(self.handler.clone()))
+            .insert_resource(PersistenceWorker::spawn(
+                self.handler.clone(),
+                self.config.channel_capacity,
+            ))
             .insert_resource(self.config.clone())
-            .add_systems(Update, flush_queue_to_persistence::<T>);
+            .init_resource::<PersistenceMetrics>()
+            .init_resource::<PersistenceCompaction>()
+            .add_systems(
+                Update,
+                (flush_queue_to_persistence, run_persistence_compaction),
+            )
+            .add_systems(Last, flush_persistence_on_exit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn world_lock_keeps_pruning_out_of_open_worlds() {
+        let root = std::env::temp_dir().join(format!("forge_world_lock_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+
+        // Games and servers share the world; a prune has to wait for them.
+        let game = WorldLock::shared(&root).unwrap();
+        let server = WorldLock::shared(&root).unwrap();
+        let refused = WorldLock::exclusive(&root).unwrap_err();
+        assert_eq!(refused.kind(), io::ErrorKind::WouldBlock);
+
+        drop((game, server));
+        let prune = WorldLock::exclusive(&root).unwrap();
+        assert!(WorldLock::shared(&root).is_err());
+        drop(prune);
+
+        let _ = std::fs::remove_dir_all(&root);
     }
 }
@@ -0,0 +1,403 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+use bevy::prelude::*;
+
+use super::chunk_store::{ChunkPayloadQueue, QueuedChunkPayload};
+use super::persistence::{unix_timestamp, ChunkPersistence, PersistenceConfig, RetentionPolicy};
+use crate::chunk::ChunkPos;
+
+enum WorkerMessage {
+    Persist(QueuedChunkPayload),
+    Prune(RetentionPolicy),
+    /// Acknowledged once everything sent before it has been written.
+    Flush(mpsc::Sender<()>),
+}
+
+/// Counters shared with the worker thread.
+#[derive(Default)]
+struct WorkerStats {
+    in_channel: AtomicU64,
+    coalesced: AtomicU64,
+    persisted: AtomicU64,
+    failures: AtomicU64,
+    bytes_written: AtomicU64,
+    last_batch_us: AtomicU64,
+    max_batch_us: AtomicU64,
+}
+
+/// Snapshot of the persistence pipeline, refreshed every frame.
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct PersistenceMetrics {
+    /// Payloads sent to the worker and not yet picked up.
+    pub in_channel: u64,
+    /// Payloads held on the main thread because the channel was full.
+    pub deferred: usize,
+    /// Frames in which the channel refused a payload.
+    pub backpressure_events: u64,
+    /// Older revisions dropped because a newer one of the same chunk was
+    /// waiting, on either side of the channel.
+    pub coalesced: u64,
+    pub persisted: u64,
+    pub failures: u64,
+    pub bytes_written: u64,
+    pub last_batch_ms: f32,
+    pub max_batch_ms: f32,
+}
+
+/// Dedicated persistence thread fed through a bounded channel. Payloads that
+/// do not fit are held (and coalesced per chunk) on the main thread until the
+/// worker catches up, so a burst of edits never blocks a frame on disk I/O.
+#[derive(Resource)]
+pub struct PersistenceWorker {
+    sender: Option<SyncSender<WorkerMessage>>,
+    thread: Option<JoinHandle<()>>,
+    stats: Arc<WorkerStats>,
+    deferred: HashMap<ChunkPos, QueuedChunkPayload>,
+    coalesced: u64,
+    backpressure_events: u64,
+    backpressured: bool,
+}
+
+impl PersistenceWorker {
+    pub fn spawn<T: ChunkPersistence>(mut handler: T, capacity: usize) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(capacity.max(1));
+        let stats = Arc::new(WorkerStats::default());
+        let thread_stats = stats.clone();
+        let thread = std::thread::Builder::new()
+            .name("chunk-persistence".into())
+            .spawn(move || run_worker(&mut handler, receiver, &thread_stats))
+            .expect("failed to spawn chunk persistence thread");
+
+        Self {
+            sender: Some(sender),
+            thread: Some(thread),
+            stats,
+            deferred: HashMap::new(),
+            coalesced: 0,
+            backpressure_events: 0,
+            backpressured: false,
+        }
+    }
+
+    /// Hand payloads to the worker without blocking. Whatever the channel
+    /// cannot take stays deferred until the next call.
+    pub fn submit(&mut self, payloads: impl IntoIterator<Item = QueuedChunkPayload>) {
+        for payload in payloads {
+            self.defer(payload);
+        }
+
+        let Some(sender) = self.sender.as_ref() else {
+            return;
+        };
+        let positions: Vec<ChunkPos> = self.deferred.keys().copied().collect();
+        let mut refused = false;
+        for position in positions {
+            let payload = self.deferred.remove(&position).expect("deferred payload");
+            self.stats.in_channel.fetch_add(1, Ordering::Relaxed);
+            match sender.try_send(WorkerMessage::Persist(payload)) {
+                Ok(()) => {}
+                Err(TrySendError::Full(WorkerMessage::Persist(payload)))
+                | Err(TrySendError::Disconnected(WorkerMessage::Persist(payload))) => {
+                    self.stats.in_channel.fetch_sub(1, Ordering::Relaxed);
+                    self.deferred.insert(position, payload);
+                    refused = true;
+                    break;
+                }
+                Err(_) => unreachable!("only payloads are sent here"),
+            }
+        }
+
+        if refused {
+            self.backpressure_events += 1;
+            if !self.backpressured {
+                info!(
+                    "chunk-payload persist backpressure: deferred={} in_channel={}",
+                    self.deferred.len(),
+                    self.stats.in_channel.load(Ordering::Relaxed)
+                );
+            }
+        }
+        self.backpressured = refused;
+    }
+
+    /// Queue a retention pass behind the pending writes. Skipped when the
+    /// channel is busy; the next interval will try again.
+    pub fn request_prune(&self, policy: RetentionPolicy) {
+        if let Some(sender) = self.sender.as_ref() {
+            let _ = sender.try_send(WorkerMessage::Prune(policy));
+        }
+    }
+
+    /// Block until every submitted and deferred payload is on disk.
+    pub fn flush(&mut self) {
+        let Some(sender) = self.sender.as_ref() else {
+            return;
+        };
+
+        for (_, payload) in self.deferred.drain() {
+            self.stats.in_channel.fetch_add(1, Ordering::Relaxed);
+            if sender.send(WorkerMessage::Persist(payload)).is_err() {
+                self.stats.in_channel.fetch_sub(1, Ordering::Relaxed);
+                warn!("Chunk persistence thread exited before flushing");
+                return;
+            }
+        }
+
+        let (ack_sender, ack) = mpsc::channel();
+        if sender.send(WorkerMessage::Flush(ack_sender)).is_ok() {
+            let _ = ack.recv();
+        }
+        self.backpressured = false;
+    }
+
+    pub fn metrics(&self) -> PersistenceMetrics {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        PersistenceMetrics {
+            in_channel: load(&self.stats.in_channel),
+            deferred: self.deferred.len(),
+            backpressure_events: self.backpressure_events,
+            coalesced: self.coalesced + load(&self.stats.coalesced),
+            persisted: load(&self.stats.persisted),
+            failures: load(&self.stats.failures),
+            bytes_written: load(&self.stats.bytes_written),
+            last_batch_ms: load(&self.stats.last_batch_us) as f32 / 1000.0,
+            max_batch_ms: load(&self.stats.max_batch_us) as f32 / 1000.0,
+        }
+    }
+
+    fn defer(&mut self, payload: QueuedChunkPayload) {
+        if coalesce(&mut self.deferred, payload) {
+            self.coalesced += 1;
+        }
+    }
+}
+
+impl Drop for PersistenceWorker {
+    fn drop(&mut self) {
+        self.flush();
+        // Closing the channel ends the worker loop.
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Keep the newest revision per chunk. Returns true when a payload was
+/// dropped.
+fn coalesce(
+    pending: &mut HashMap<ChunkPos, QueuedChunkPayload>,
+    payload: QueuedChunkPayload,
+) -> bool {
+    match pending.get(&payload.position) {
+        Some(existing) if existing.revision >= payload.revision => true,
+        Some(_) => {
+            pending.insert(payload.position, payload);
+            true
+        }
+        None => {
+            pending.insert(payload.position, payload);
+            false
+        }
+    }
+}
+
+fn run_worker<T: ChunkPersistence>(
+    handler: &mut T,
+    receiver: Receiver<WorkerMessage>,
+    stats: &WorkerStats,
+) {
+    while let Ok(first) = receiver.recv() {
+        let mut batch = HashMap::new();
+        let mut control = Vec::new();
+        for message in std::iter::once(first).chain(receiver.try_iter()) {
+            match message {
+                WorkerMessage::Persist(payload) => {
+                    stats.in_channel.fetch_sub(1, Ordering::Relaxed);
+                    if coalesce(&mut batch, payload) {
+                        stats.coalesced.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                other => control.push(other),
+            }
+        }
+
+        if !batch.is_empty() {
+            persist_batch(handler, batch, stats);
+        }
+
+        for message in control {
+            match message {
+                WorkerMessage::Prune(policy) => match handler.prune(&policy, unix_timestamp()) {
+                    Ok(report) if report.revisions_removed > 0 || report.bytes_freed > 0 => info!(
+                        "chunk-payload compaction: pruned_revisions={} freed_bytes={}",
+                        report.revisions_removed, report.bytes_freed
+                    ),
+                    Ok(_) => {}
+                    Err(error) => warn!("Chunk persistence compaction failed: {}", error),
+                },
+                WorkerMessage::Flush(ack) => {
+                    let _ = ack.send(());
+                }
+                WorkerMessage::Persist(_) => unreachable!("payloads are batched"),
+            }
+        }
+    }
+}
+
+fn persist_batch<T: ChunkPersistence>(
+    handler: &mut T,
+    batch: HashMap<ChunkPos, QueuedChunkPayload>,
+    stats: &WorkerStats,
+) {
+    let start = Instant::now();
+    let count = batch.len();
+    let mut failures = 0;
+    for payload in batch.into_values() {
+        match handler.persist(&payload) {
+            Ok(()) => {
+                stats.persisted.fetch_add(1, Ordering::Relaxed);
+                stats
+                    .bytes_written
+                    .fetch_add(payload.bytes.len() as u64, Ordering::Relaxed);
+            }
+            Err(error) => {
+                failures += 1;
+                stats.failures.fetch_add(1, Ordering::Relaxed);
+                warn!(
+                    "Failed to persist chunk payload {:?} rev {}: {}",
+                    payload.position, payload.revision, error
+                );
+            }
+        }
+    }
+
+    let elapsed_us = start.elapsed().as_micros() as u64;
+    stats.last_batch_us.store(elapsed_us, Ordering::Relaxed);
+    stats.max_batch_us.fetch_max(elapsed_us, Ordering::Relaxed);
+    debug!(
+        "chunk-payload persist: count={} total_ms={:.2} failures={}",
+        count,
+        elapsed_us as f32 / 1000.0,
+        failures
+    );
+}
+
+pub fn flush_queue_to_persistence(
+    mut queue: ResMut<ChunkPayloadQueue>,
+    mut worker: ResMut<PersistenceWorker>,
+    mut metrics: ResMut<PersistenceMetrics>,
+    config: Option<Res<PersistenceConfig>>,
+) {
+    let payloads = queue.take_all();
+    if config.is_some_and(|config| !config.enabled) {
+        return;
+    }
+
+    worker.submit(payloads);
+    *metrics = worker.metrics();
+}
+
+/// Runs in `Last` so payloads queued during the exit frame are included, and
+/// blocks shutdown until the worker has written everything.
+pub fn flush_persistence_on_exit(
+    mut exit_events: EventReader<AppExit>,
+    mut queue: ResMut<ChunkPayloadQueue>,
+    mut worker: ResMut<PersistenceWorker>,
+    config: Option<Res<PersistenceConfig>>,
+) {
+    if exit_events.read().next().is_none() {
+        return;
+    }
+
+    let payloads = queue.take_all();
+    if config.is_some_and(|config| !config.enabled) {
+        return;
+    }
+
+    let start = Instant::now();
+    worker.submit(payloads);
+    worker.flush();
+    let metrics = worker.metrics();
+    info!(
+        "chunk-payload persist: flushed on exit in {:.1} ms (persisted={} failures={})",
+        start.elapsed().as_secs_f32() * 1000.0,
+        metrics.persisted,
+        metrics.failures
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::persistence::{PersistenceUsage, PruneReport};
+    use std::io;
+    use std::sync::Mutex;
+
+    #[derive(Clone, Default)]
+    struct RecordingPersistence {
+        written: Arc<Mutex<Vec<(ChunkPos, u32)>>>,
+    }
+
+    impl ChunkPersistence for RecordingPersistence {
+        fn persist(&mut self, payload: &QueuedChunkPayload) -> io::Result<()> {
+            let mut written = self.written.lock().unwrap();
+            written.push((payload.position, payload.revision));
+            Ok(())
+        }
+
+        fn load(&self, _: ChunkPos) -> io::Result<Option<(u32, Vec<u8>)>> {
+            Ok(None)
+        }
+
+        fn revisions(&self, _: ChunkPos) -> io::Result<Vec<u32>> {
+            Ok(Vec::new())
+        }
+
+        fn load_revision(&self, _: ChunkPos, _: u32) -> io::Result<Option<Vec<u8>>> {
+            Ok(None)
+        }
+
+        fn usage(&self, _: &RetentionPolicy, _: u64) -> io::Result<PersistenceUsage> {
+            Ok(PersistenceUsage::default())
+        }
+
+        fn prune(&mut self, _: &RetentionPolicy, _: u64) -> io::Result<PruneReport> {
+            Ok(PruneReport::default())
+        }
+    }
+
+    #[test]
+    fn revisions_coalesce_and_flush_before_returning() {
+        let persistence = RecordingPersistence::default();
+        let mut worker = PersistenceWorker::spawn(persistence.clone(), 1);
+
+        let a = ChunkPos::new(0, 0, 0);
+        let b = ChunkPos::new(1, 0, 0);
+        let payloads =
+            (1..=5)
+                .map(|revision| (a, revision))
+                .chain([(b, 1)])
+                .map(|(position, revision)| QueuedChunkPayload {
+                    position,
+                    revision,
+                    bytes: vec![0; 16],
+                });
+        worker.submit(payloads);
+        worker.flush();
+
+        let mut written = persistence.written.lock().unwrap().clone();
+        written.sort_by_key(|(position, _)| position.x);
+        assert_eq!(written, vec![(a, 5), (b, 1)]);
+
+        let metrics = worker.metrics();
+        assert_eq!(metrics.coalesced, 4);
+        assert_eq!(metrics.persisted, 2);
+        assert_eq!((metrics.deferred, metrics.in_channel), (0, 0));
+    }
+}
@@ -0,0 +1,922 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use bevy::prelude::*;
+
+use super::chunk_store::QueuedChunkPayload;
+use super::journal::ChunkJournal;
+use super::persistence::{
+    list_chunk_files, sync_directory, unix_timestamp, ChunkPersistence, FsyncPolicy,
+    PersistenceUsage, PruneReport, RetentionPolicy,
+};
+use crate::chunk::data::crc32;
+use crate::chunk::ChunkPos;
+
+/// Chunks per region along X and Z.
+pub const REGION_WIDTH: i32 = 16;
+/// Chunks per region along Y.
+pub const REGION_HEIGHT: i32 = 8;
+const CHUNKS_PER_REGION: usize = (REGION_WIDTH * REGION_WIDTH * REGION_HEIGHT) as usize;
+
+pub const SECTOR_SIZE: u64 = 512;
+
+const REGION_MAGIC: [u8; 4] = *b"FRGN";
+const REGION_VERSION: u16 = 1;
+/// Magic, version, reserved, generation and table CRC32.
+const SLOT_PREFIX_LEN: usize = 4 + 2 + 2 + 8 + 4;
+const TABLE_ENTRY_LEN: usize = 8;
+const SLOT_SECTORS: u64 =
+    (SLOT_PREFIX_LEN + CHUNKS_PER_REGION * TABLE_ENTRY_LEN).div_ceil(SECTOR_SIZE as usize) as u64;
+/// Two header slots precede the record area; sector 0 doubles as "no record".
+const DATA_START_SECTOR: u32 = (SLOT_SECTORS * 2) as u32;
+
+const RECORD_MAGIC: [u8; 4] = *b"FRRC";
+/// Magic, revision, payload length, payload CRC32, timestamp, previous sector.
+const RECORD_HEADER_LEN: usize = 4 + 4 + 4 + 4 + 8 + 4;
+
+/// Compact a region once at least this share of its record area is free.
+const COMPACTION_FREE_RATIO: f32 = 0.25;
+const COMPACTION_MIN_FREE_SECTORS: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RegionPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl RegionPos {
+    pub fn of_chunk(position: ChunkPos) -> Self {
+        Self {
+            x: position.x.div_euclid(REGION_WIDTH),
+            y: position.y.div_euclid(REGION_HEIGHT),
+            z: position.z.div_euclid(REGION_WIDTH),
+        }
+    }
+
+    pub fn filename(&self) -> String {
+        format!("r.{}.{}.{}.region", self.x, self.y, self.z)
+    }
+
+    pub fn parse_filename(name: &str) -> Option<Self> {
+        let trimmed = name.strip_prefix("r.")?.strip_suffix(".region")?;
+        let mut parts = trimmed.split('.');
+        let x = parts.next()?.parse().ok()?;
+        let y = parts.next()?.parse().ok()?;
+        let z = parts.next()?.parse().ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self { x, y, z })
+    }
+
+    fn local_index(position: ChunkPos) -> usize {
+        let x = position.x.rem_euclid(REGION_WIDTH);
+        let y = position.y.rem_euclid(REGION_HEIGHT);
+        let z = position.z.rem_euclid(REGION_WIDTH);
+        (x + REGION_WIDTH * (z + REGION_WIDTH * y)) as usize
+    }
+}
+
+/// Newest record of a chunk; `sector == 0` means the chunk was never stored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct TableEntry {
+    sector: u32,
+    revision: u32,
+}
+
+/// Metadata for one stored revision of a chunk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecordInfo {
+    pub revision: u32,
+    pub length: u32,
+    /// Seconds since the Unix epoch when the revision was written.
+    pub timestamp: u64,
+    sector: u32,
+    previous: u32,
+    checksum: u32,
+}
+
+impl RecordInfo {
+    fn sectors(&self) -> u32 {
+        record_sectors(self.length as usize)
+    }
+}
+
+fn record_sectors(payload_len: usize) -> u32 {
+    (RECORD_HEADER_LEN + payload_len).div_ceil(SECTOR_SIZE as usize) as u32
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// One region file: two alternating header slots holding the chunk offset
+/// table, followed by sector-aligned records. Each record links to the
+/// previous revision of its chunk, so a chunk's history is a chain through
+/// the file. Records are always written to free sectors before the table
+/// that references them is committed to the inactive header slot, so a crash
+/// leaves either the old or the new table intact.
+struct RegionFile {
+    path: PathBuf,
+    file: File,
+    table: Vec<TableEntry>,
+    generation: u64,
+    active_slot: u64,
+    used: Vec<bool>,
+    fsync: FsyncPolicy,
+}
+
+impl RegionFile {
+    fn open(path: &Path, create: bool, fsync: FsyncPolicy) -> io::Result<Option<Self>> {
+        if !create && !path.exists() {
+            return Ok(None);
+        }
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(create)
+            .truncate(false)
+            .open(path)?;
+
+        let mut region = Self {
+            path: path.to_path_buf(),
+            file,
+            table: vec![TableEntry::default(); CHUNKS_PER_REGION],
+            generation: 0,
+            active_slot: 1,
+            used: vec![true; DATA_START_SECTOR as usize],
+            fsync,
+        };
+
+        let slots = [region.read_slot(0)?, region.read_slot(1)?];
+        let newest = slots
+            .into_iter()
+            .enumerate()
+            .filter_map(|(slot, contents)| {
+                contents.map(|(generation, table)| (slot, generation, table))
+            })
+            .max_by_key(|(_, generation, _)| *generation);
+
+        match newest {
+            Some((slot, generation, table)) => {
+                region.active_slot = slot as u64;
+                region.generation = generation;
+                region.table = table;
+            }
+            None => {
+                if region.file.metadata()?.len() > 0 {
+                    return Err(invalid_data(format!(
+                        "region {:?} has no valid header slot",
+                        path
+                    )));
+                }
+                region.commit_table()?;
+            }
+        }
+
+        region.rebuild_allocation()?;
+        Ok(Some(region))
+    }
+
+    fn read_slot(&mut self, slot: u64) -> io::Result<Option<(u64, Vec<TableEntry>)>> {
+        let mut bytes = vec![0u8; SLOT_PREFIX_LEN + CHUNKS_PER_REGION * TABLE_ENTRY_LEN];
+        self.file
+            .seek(SeekFrom::Start(slot * SLOT_SECTORS * SECTOR_SIZE))?;
+        if let Err(error) = self.file.read_exact(&mut bytes) {
+            return match error.kind() {
+                io::ErrorKind::UnexpectedEof => Ok(None),
+                _ => Err(error),
+            };
+        }
+
+        if bytes[..4] != REGION_MAGIC || u16::from_le_bytes([bytes[4], bytes[5]]) != REGION_VERSION
+        {
+            return Ok(None);
+        }
+        let generation = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+        let checksum = u32::from_le_bytes(bytes[16..20].try_into().unwrap());
+        let table_bytes = &bytes[SLOT_PREFIX_LEN..];
+        if crc32(table_bytes) != checksum {
+            return Ok(None);
+        }
+
+        let table = table_bytes
+            .chunks_exact(TABLE_ENTRY_LEN)
+            .map(|entry| TableEntry {
+                sector: u32::from_le_bytes(entry[0..4].try_into().unwrap()),
+                revision: u32::from_le_bytes(entry[4..8].try_into().unwrap()),
+            })
+            .collect();
+        Ok(Some((generation, table)))
+    }
+
+    /// Write the in-memory table into the inactive slot and make it active.
+    fn commit_table(&mut self) -> io::Result<()> {
+        let mut table_bytes = Vec::with_capacity(CHUNKS_PER_REGION * TABLE_ENTRY_LEN);
+        for entry in &self.table {
+            table_bytes.extend_from_slice(&entry.sector.to_le_bytes());
+            table_bytes.extend_from_slice(&entry.revision.to_le_bytes());
+        }
+
+        let generation = self.generation + 1;
+        let slot = 1 - self.active_slot;
+        let mut bytes = Vec::with_capacity(SLOT_PREFIX_LEN + table_bytes.len());
+        bytes.extend_from_slice(&REGION_MAGIC);
+        bytes.extend_from_slice(&REGION_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes.extend_from_slice(&generation.to_le_bytes());
+        bytes.extend_from_slice(&crc32(&table_bytes).to_le_bytes());
+        bytes.extend_from_slice(&table_bytes);
+
+        self.file
+            .seek(SeekFrom::Start(slot * SLOT_SECTORS * SECTOR_SIZE))?;
+        self.file.write_all(&bytes)?;
+        self.sync()?;
+
+        self.generation = generation;
+        self.active_slot = slot;
+        Ok(())
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        if self.fsync.sync_files() {
+            self.file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// Mark every sector reachable from the table as used. Broken links end a
+    /// chain rather than failing the whole region.
+    fn rebuild_allocation(&mut self) -> io::Result<()> {
+        let total_sectors = self.file.metadata()?.len().div_ceil(SECTOR_SIZE) as usize;
+        self.used = vec![false; total_sectors.max(DATA_START_SECTOR as usize)];
+        for sector in 0..DATA_START_SECTOR as usize {
+            self.used[sector] = true;
+        }
+
+        for index in 0..CHUNKS_PER_REGION {
+            for record in self.history(index)? {
+                self.mark(record.sector, record.sectors(), true);
+            }
+        }
+        Ok(())
+    }
+
+    fn mark(&mut self, sector: u32, count: u32, used: bool) {
+        let end = (sector + count) as usize;
+        if self.used.len() < end {
+            self.used.resize(end, false);
+        }
+        for flag in &mut self.used[sector as usize..end] {
+            *flag = used;
+        }
+    }
+
+    /// First run of `count` free sectors, or the end of the file.
+    fn allocate(&self, count: u32) -> u32 {
+        let count = count as usize;
+        let mut run_start = DATA_START_SECTOR as usize;
+        let mut run_len = 0;
+        for sector in DATA_START_SECTOR as usize..self.used.len() {
+            if self.used[sector] {
+                run_len = 0;
+                run_start = sector + 1;
+            } else {
+                run_len += 1;
+                if run_len == count {
+                    return run_start as u32;
+                }
+            }
+        }
+        run_start as u32
+    }
+
+    fn free_sectors(&self) -> usize {
+        self.used.iter().filter(|used| !**used).count()
+    }
+
+    fn read_record_info(&mut self, sector: u32) -> io::Result<Option<RecordInfo>> {
+        if sector < DATA_START_SECTOR || sector as usize >= self.used.len() {
+            return Ok(None);
+        }
+
+        let mut header = [0u8; RECORD_HEADER_LEN];
+        self.file
+            .seek(SeekFrom::Start(sector as u64 * SECTOR_SIZE))?;
+        if self.file.read_exact(&mut header).is_err() || header[..4] != RECORD_MAGIC {
+            return Ok(None);
+        }
+
+        Ok(Some(RecordInfo {
+            revision: u32::from_le_bytes(header[4..8].try_into().unwrap()),
+            length: u32::from_le_bytes(header[8..12].try_into().unwrap()),
+            checksum: u32::from_le_bytes(header[12..16].try_into().unwrap()),
+            timestamp: u64::from_le_bytes(header[16..24].try_into().unwrap()),
+            previous: u32::from_le_bytes(header[24..28].try_into().unwrap()),
+            sector,
+        }))
+    }
+
+    fn read_record(&mut self, record: &RecordInfo) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![0u8; record.length as usize];
+        self.file.seek(SeekFrom::Start(
+            record.sector as u64 * SECTOR_SIZE + RECORD_HEADER_LEN as u64,
+        ))?;
+        self.file.read_exact(&mut bytes)?;
+        if crc32(&bytes) != record.checksum {
+            return Err(invalid_data(format!(
+                "record for revision {} in {:?} failed its checksum",
+                record.revision, self.path
+            )));
+        }
+        Ok(bytes)
+    }
+
+    /// Stored revisions of the chunk at `index`, newest first.
+    fn history(&mut self, index: usize) -> io::Result<Vec<RecordInfo>> {
+        let mut records = Vec::new();
+        let mut visited = HashSet::new();
+        let mut sector = self.table[index].sector;
+        while visited.insert(sector) {
+            let Some(record) = self.read_record_info(sector)? else {
+                break;
+            };
+            sector = record.previous;
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    fn write_record(
+        &mut self,
+        revision: u32,
+        bytes: &[u8],
+        timestamp: u64,
+        previous: u32,
+    ) -> io::Result<u32> {
+        let length = u32::try_from(bytes.len()).map_err(|_| invalid_data("payload too large"))?;
+        let sectors = record_sectors(bytes.len());
+        let sector = self.allocate(sectors);
+
+        let mut record = Vec::with_capacity((sectors as u64 * SECTOR_SIZE) as usize);
+        record.extend_from_slice(&RECORD_MAGIC);
+        record.extend_from_slice(&revision.to_le_bytes());
+        record.extend_from_slice(&length.to_le_bytes());
+        record.extend_from_slice(&crc32(bytes).to_le_bytes());
+        record.extend_from_slice(&timestamp.to_le_bytes());
+        record.extend_from_slice(&previous.to_le_bytes());
+        record.extend_from_slice(bytes);
+        record.resize((sectors as u64 * SECTOR_SIZE) as usize, 0);
+
+        self.file
+            .seek(SeekFrom::Start(sector as u64 * SECTOR_SIZE))?;
+        self.file.write_all(&record)?;
+        self.mark(sector, sectors, true);
+        Ok(sector)
+    }
+
+    /// Append a new revision for the chunk at `index`. Revisions at or below
+    /// the stored one are ignored.
+    fn store(
+        &mut self,
+        index: usize,
+        revision: u32,
+        bytes: &[u8],
+        timestamp: u64,
+    ) -> io::Result<bool> {
+        let head = self.table[index];
+        if head.sector != 0 && revision <= head.revision {
+            return Ok(false);
+        }
+
+        let sector = self.write_record(revision, bytes, timestamp, head.sector)?;
+        self.sync()?;
+        self.table[index] = TableEntry { sector, revision };
+        self.commit_table()?;
+        Ok(true)
+    }
+
+    fn needs_compaction(&self) -> bool {
+        let free = self.free_sectors();
+        let area = self.used.len().saturating_sub(DATA_START_SECTOR as usize);
+        free >= COMPACTION_MIN_FREE_SECTORS && free as f32 >= area as f32 * COMPACTION_FREE_RATIO
+    }
+
+    fn usage(&mut self, policy: &RetentionPolicy, now: u64) -> io::Result<PersistenceUsage> {
+        let mut usage = PersistenceUsage {
+            disk_bytes: self.file.metadata()?.len(),
+            ..Default::default()
+        };
+        for index in 0..CHUNKS_PER_REGION {
+            let history = self.history(index)?;
+            if history.is_empty() {
+                continue;
+            }
+
+            usage.chunks += 1;
+            usage.revisions += history.len();
+            usage.payload_bytes += history.iter().map(|r| r.length as u64).sum::<u64>();
+            for record in &history[retained(policy, &history, now)..] {
+                usage.prunable_revisions += 1;
+                usage.prunable_bytes += record.length as u64;
+            }
+        }
+        Ok(usage)
+    }
+
+    /// Rewrite the chains back to back into a fresh file and swap it in with
+    /// a rename, dropping free space, orphaned records and every revision
+    /// outside `policy`. Returns how many revisions were dropped.
+    fn compact(&mut self, policy: &RetentionPolicy, now: u64) -> io::Result<usize> {
+        let temp_path = self.path.with_extension("region.tmp");
+        let _ = fs::remove_file(&temp_path);
+
+        let mut compacted = RegionFile::open(&temp_path, true, self.fsync)?
+            .ok_or_else(|| invalid_data("failed to create compaction target"))?;
+        let mut dropped = 0;
+        for index in 0..CHUNKS_PER_REGION {
+            let mut history = self.history(index)?;
+            let keep = retained(policy, &history, now);
+            dropped += history.len() - keep;
+            history.truncate(keep);
+
+            let mut previous = 0;
+            for record in history.iter().rev() {
+                let bytes = match self.read_record(record) {
+                    Ok(bytes) => bytes,
+                    Err(error) if error.kind() == io::ErrorKind::InvalidData => continue,
+                    Err(error) => return Err(error),
+                };
+                previous =
+                    compacted.write_record(record.revision, &bytes, record.timestamp, previous)?;
+                compacted.table[index] = TableEntry {
+                    sector: previous,
+                    revision: record.revision,
+                };
+            }
+        }
+        compacted.sync()?;
+        compacted.commit_table()?;
+        drop(compacted);
+
+        fs::rename(&temp_path, &self.path)?;
+        if self.fsync.sync_directories() {
+            if let Some(parent) = self.path.parent() {
+                sync_directory(parent)?;
+            }
+        }
+        let reopened = RegionFile::open(&self.path, false, self.fsync)?
+            .ok_or_else(|| invalid_data("compacted region vanished"))?;
+        *self = reopened;
+        Ok(dropped)
+    }
+}
+
+fn retained(policy: &RetentionPolicy, history: &[RecordInfo], now: u64) -> usize {
+    let timestamps: Vec<u64> = history.iter().map(|record| record.timestamp).collect();
+    policy.retained(&timestamps, now)
+}
+
+/// `ChunkPersistence` backed by region files of `REGION_WIDTH` x
+/// `REGION_HEIGHT` x `REGION_WIDTH` chunks, named `r.{x}.{y}.{z}.region`.
+/// Every revision is kept as a chain inside its region, and writes go
+/// through a `ChunkJournal` that is replayed on open.
+#[derive(Clone)]
+pub struct RegionChunkPersistence {
+    root: PathBuf,
+    regions: Arc<Mutex<HashMap<RegionPos, RegionFile>>>,
+    journal: Arc<Mutex<ChunkJournal>>,
+    fsync: FsyncPolicy,
+}
+
+impl RegionChunkPersistence {
+    #[allow(dead_code)]
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self::with_fsync(root, FsyncPolicy::default())
+    }
+
+    pub fn with_fsync<P: AsRef<Path>>(root: P, fsync: FsyncPolicy) -> Self {
+        let root = root.as_ref().to_path_buf();
+
+        if let Ok(legacy) = list_chunk_files(&root) {
+            if !legacy.is_empty() {
+                warn!(
+                    "{} per-chunk payload file(s) in {:?} are not read by region persistence; run `cargo run --bin region_migrate {}` to convert them",
+                    legacy.len(),
+                    root,
+                    root.display()
+                );
+            }
+        }
+
+        let persistence = Self {
+            journal: Arc::new(Mutex::new(ChunkJournal::new(&root, fsync))),
+            root,
+            regions: Arc::new(Mutex::new(HashMap::new())),
+            fsync,
+        };
+        persistence.replay_journal();
+        persistence
+    }
+
+    fn replay_journal(&self) {
+        let mut journal = self.journal.lock().expect("chunk journal poisoned");
+        let pending = match journal.pending() {
+            Ok(pending) => pending,
+            Err(error) => {
+                warn!("Failed to read chunk journal in {:?}: {}", self.root, error);
+                return;
+            }
+        };
+        if pending.is_empty() {
+            return;
+        }
+
+        info!("Replaying {} journaled chunk write(s)", pending.len());
+        for entry in &pending {
+            if let Err(error) = self.write_payload(&entry.payload, entry.timestamp) {
+                warn!(
+                    "Failed to replay journaled chunk {:?} rev {}: {}",
+                    entry.payload.position, entry.payload.revision, error
+                );
+                return;
+            }
+        }
+        if let Err(error) = journal.clear() {
+            warn!("Failed to clear chunk journal: {}", error);
+        }
+    }
+
+    fn with_region<R>(
+        &self,
+        region: RegionPos,
+        create: bool,
+        f: impl FnOnce(&mut RegionFile) -> io::Result<R>,
+    ) -> io::Result<Option<R>> {
+        let mut regions = self.regions.lock().expect("region cache poisoned");
+        let file = match regions.entry(region) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                if create {
+                    fs::create_dir_all(&self.root)?;
+                }
+                match RegionFile::open(&self.root.join(region.filename()), create, self.fsync)? {
+                    Some(file) => entry.insert(file),
+                    None => return Ok(None),
+                }
+            }
+        };
+        f(file).map(Some)
+    }
+
+    /// Store `payload` as a new revision written at `timestamp` (Unix
+    /// seconds). Returns `false` when a newer or equal revision is stored.
+    pub fn write_payload(&self, payload: &QueuedChunkPayload, timestamp: u64) -> io::Result<bool> {
+        let region = RegionPos::of_chunk(payload.position);
+        let index = RegionPos::local_index(payload.position);
+        self.with_region(region, true, |file| {
+            let stored = file.store(index, payload.revision, &payload.bytes, timestamp)?;
+            if file.needs_compaction() {
+                file.compact(&RetentionPolicy::keep_all(), timestamp)?;
+            }
+            Ok(stored)
+        })
+        .map(|stored| stored.unwrap_or(false))
+    }
+
+    /// Stored revisions of `position`, newest first.
+    pub fn history(&self, position: ChunkPos) -> io::Result<Vec<RecordInfo>> {
+        let region = RegionPos::of_chunk(position);
+        let index = RegionPos::local_index(position);
+        self.with_region(region, false, |file| file.history(index))
+            .map(Option::unwrap_or_default)
+    }
+
+    /// Payload bytes of one revision listed by `history`.
+    pub fn read_revision(&self, position: ChunkPos, record: &RecordInfo) -> io::Result<Vec<u8>> {
+        let region = RegionPos::of_chunk(position);
+        self.with_region(region, false, |file| file.read_record(record))?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "region missing"))
+    }
+
+    /// Region files currently present under the root.
+    pub fn regions_on_disk(&self) -> io::Result<Vec<RegionPos>> {
+        if !self.root.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut regions = Vec::new();
+        for entry in fs::read_dir(&self.root)? {
+            let entry = entry?;
+            if let Some(region) = entry
+                .file_name()
+                .to_str()
+                .and_then(RegionPos::parse_filename)
+            {
+                regions.push(region);
+            }
+        }
+        Ok(regions)
+    }
+}
+
+impl ChunkPersistence for RegionChunkPersistence {
+    fn persist(&mut self, payload: &QueuedChunkPayload) -> io::Result<()> {
+        let timestamp = unix_timestamp();
+        let mut journal = self.journal.lock().expect("chunk journal poisoned");
+        journal.append(payload, timestamp)?;
+        self.write_payload(payload, timestamp)?;
+        journal.clear()
+    }
+
+    fn load(&self, position: ChunkPos) -> io::Result<Option<(u32, Vec<u8>)>> {
+        let Some(newest) = self.history(position)?.into_iter().next() else {
+            return Ok(None);
+        };
+        let bytes = self.read_revision(position, &newest)?;
+        Ok(Some((newest.revision, bytes)))
+    }
+
+    fn revisions(&self, position: ChunkPos) -> io::Result<Vec<u32>> {
+        Ok(self
+            .history(position)?
+            .into_iter()
+            .map(|record| record.revision)
+            .collect())
+    }
+
+    fn load_revision(&self, position: ChunkPos, revision: u32) -> io::Result<Option<Vec<u8>>> {
+        let history = self.history(position)?;
+        match history.iter().find(|record| record.revision == revision) {
+            Some(record) => self.read_revision(position, record).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn usage(&self, policy: &RetentionPolicy, now: u64) -> io::Result<PersistenceUsage> {
+        let mut total = PersistenceUsage::default();
+        for region in self.regions_on_disk()? {
+            let Some(usage) = self.with_region(region, false, |file| file.usage(policy, now))?
+            else {
+                continue;
+            };
+            total.chunks += usage.chunks;
+            total.revisions += usage.revisions;
+            total.payload_bytes += usage.payload_bytes;
+            total.disk_bytes += usage.disk_bytes;
+            total.prunable_revisions += usage.prunable_revisions;
+            total.prunable_bytes += usage.prunable_bytes;
+        }
+        Ok(total)
+    }
+
+    /// Compacts each region that has free sectors or prunable revisions.
+    /// Regions are locked one at a time, so writes to other regions proceed.
+    fn prune(&mut self, policy: &RetentionPolicy, now: u64) -> io::Result<PruneReport> {
+        let mut report = PruneReport::default();
+        for region in self.regions_on_disk()? {
+            let pruned = self.with_region(region, false, |file| {
+                let usage = file.usage(policy, now)?;
+                if usage.prunable_revisions == 0 && file.free_sectors() == 0 {
+                    return Ok(PruneReport::default());
+                }
+
+                let revisions_removed = file.compact(policy, now)?;
+                let after = file.file.metadata()?.len();
+                Ok(PruneReport {
+                    revisions_removed,
+                    bytes_freed: usage.disk_bytes.saturating_sub(after),
+                })
+            })?;
+            report += pruned.unwrap_or_default();
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockType;
+    use crate::chunk::{ChunkPayloadHeader, ChunkStorage};
+    use crate::world::persistence::load_latest_valid;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!(
+            "forge_region_{}_{}_{}",
+            name,
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let _ = fs::remove_dir_all(&root);
+        root
+    }
+
+    fn payload(position: ChunkPos, revision: u32, fill: u8, len: usize) -> QueuedChunkPayload {
+        QueuedChunkPayload {
+            position,
+            revision,
+            bytes: vec![fill; len],
+        }
+    }
+
+    #[test]
+    fn revisions_survive_reopen_and_keep_history() {
+        let root = temp_root("reopen");
+        let a = ChunkPos::new(3, -2, 17);
+        let b = ChunkPos::new(-1, 0, 0);
+
+        let mut persistence = RegionChunkPersistence::new(&root);
+        persistence.persist(&payload(a, 1, 1, 100)).unwrap();
+        persistence.persist(&payload(a, 2, 2, 1500)).unwrap();
+        persistence.persist(&payload(a, 2, 9, 10)).unwrap();
+        persistence.persist(&payload(b, 1, 3, 700)).unwrap();
+        drop(persistence);
+
+        let reopened = RegionChunkPersistence::new(&root);
+        assert_eq!(reopened.load(a).unwrap(), Some((2, vec![2; 1500])));
+        assert_eq!(reopened.load(b).unwrap(), Some((1, vec![3; 700])));
+        assert_eq!(reopened.load(ChunkPos::new(3, -2, 18)).unwrap(), None);
+
+        let history = reopened.history(a).unwrap();
+        let revisions: Vec<u32> = history.iter().map(|record| record.revision).collect();
+        assert_eq!(revisions, vec![2, 1]);
+        assert_eq!(
+            reopened.read_revision(a, &history[1]).unwrap(),
+            vec![1; 100]
+        );
+        assert_eq!(reopened.regions_on_disk().unwrap().len(), 2);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn torn_header_falls_back_to_previous_table() {
+        let root = temp_root("torn");
+        let position = ChunkPos::new(0, 0, 0);
+        let mut persistence = RegionChunkPersistence::new(&root);
+        persistence.persist(&payload(position, 1, 1, 64)).unwrap();
+        persistence.persist(&payload(position, 2, 2, 64)).unwrap();
+        drop(persistence);
+
+        // Corrupt whichever slot was written last.
+        let path = root.join(RegionPos::of_chunk(position).filename());
+        let active = RegionFile::open(&path, false, FsyncPolicy::Never)
+            .unwrap()
+            .unwrap()
+            .active_slot;
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(active * SLOT_SECTORS * SECTOR_SIZE + 100))
+            .unwrap();
+        file.write_all(&[0xAB; 16]).unwrap();
+        drop(file);
+
+        let reopened = RegionChunkPersistence::new(&root);
+        assert_eq!(reopened.load(position).unwrap(), Some((1, vec![1; 64])));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn compaction_preserves_chains_and_drops_orphans() {
+        let root = temp_root("compact");
+        let position = ChunkPos::new(5, 5, 5);
+        let persistence = RegionChunkPersistence::new(&root);
+        let region = RegionPos::of_chunk(position);
+
+        persistence
+            .write_payload(&payload(position, 1, 1, 40), 10)
+            .unwrap();
+        persistence
+            .write_payload(&payload(position, 2, 2, 40), 20)
+            .unwrap();
+        // An uncommitted record, as left behind by a crash mid-persist.
+        persistence
+            .with_region(region, false, |file| {
+                file.write_record(3, &[3; 4000], 30, 0).map(|_| ())
+            })
+            .unwrap();
+        let size_before = fs::metadata(root.join(region.filename())).unwrap().len();
+
+        let mut reopened = RegionChunkPersistence::new(&root);
+        let report = reopened
+            .prune(&RetentionPolicy::keep_all(), unix_timestamp())
+            .unwrap();
+        assert_eq!(report.revisions_removed, 0);
+        assert!(report.bytes_freed > 0);
+        let size_after = fs::metadata(root.join(region.filename())).unwrap().len();
+        assert!(size_after < size_before);
+
+        let history = reopened.history(position).unwrap();
+        let stamps: Vec<(u32, u64)> = history.iter().map(|r| (r.revision, r.timestamp)).collect();
+        assert_eq!(stamps, vec![(2, 20), (1, 10)]);
+        assert_eq!(reopened.load(position).unwrap(), Some((2, vec![2; 40])));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn journal_is_replayed_on_open() {
+        let root = temp_root("journal");
+        let position = ChunkPos::new(-20, 3, 40);
+        let mut journal = ChunkJournal::new(&root, FsyncPolicy::Never);
+        journal.append(&payload(position, 4, 7, 900), 55).unwrap();
+        drop(journal);
+
+        let reopened = RegionChunkPersistence::new(&root);
+        assert_eq!(reopened.load(position).unwrap(), Some((4, vec![7; 900])));
+        assert_eq!(reopened.history(position).unwrap()[0].timestamp, 55);
+        assert!(ChunkJournal::new(&root, FsyncPolicy::Never)
+            .pending()
+            .unwrap()
+            .is_empty());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn damaged_newest_revision_falls_back_to_previous() {
+        let root = temp_root("fallback");
+        let position = ChunkPos::new(1, 1, 1);
+        let mut persistence = RegionChunkPersistence::new(&root);
+        for (revision, block) in [(1, BlockType::Stone), (2, BlockType::Dirt)] {
+            let bytes = ChunkStorage::filled(block).encode_bytes(ChunkPayloadHeader {
+                position,
+                revision,
+                ..Default::default()
+            });
+            persistence
+                .persist(&QueuedChunkPayload {
+                    position,
+                    revision,
+                    bytes,
+                })
+                .unwrap();
+        }
+
+        // Flip a payload byte of the newest record behind the CRC's back.
+        let newest = persistence.history(position).unwrap()[0];
+        let path = root.join(RegionPos::of_chunk(position).filename());
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(
+            newest.sector as u64 * SECTOR_SIZE + RECORD_HEADER_LEN as u64 + 8,
+        ))
+        .unwrap();
+        file.write_all(&[0xFF]).unwrap();
+        drop(file);
+
+        let reopened = RegionChunkPersistence::new(&root);
+        let (revision, storage) = load_latest_valid(&reopened, position).unwrap().unwrap();
+        assert_eq!(revision, 2);
+        assert_eq!(storage.uniform_block(), Some(BlockType::Stone));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn prune_applies_retention_policy() {
+        let root = temp_root("retention");
+        let position = ChunkPos::new(0, 2, 0);
+        let mut persistence = RegionChunkPersistence::new(&root);
+        for revision in 1..=5 {
+            persistence
+                .write_payload(
+                    &payload(position, revision, revision as u8, 2000),
+                    revision as u64 * 100,
+                )
+                .unwrap();
+        }
+
+        // Two by count, but the window reaches back to revision 3.
+        let policy = RetentionPolicy {
+            keep_last: 2,
+            keep_newer_than: Some(std::time::Duration::from_secs(250)),
+        };
+        let usage = persistence.usage(&policy, 550).unwrap();
+        assert_eq!((usage.chunks, usage.revisions), (1, 5));
+        assert_eq!(usage.prunable_revisions, 2);
+        assert_eq!(usage.prunable_bytes, 4000);
+
+        let report = persistence.prune(&policy, 550).unwrap();
+        assert_eq!(report.revisions_removed, 2);
+        assert!(report.bytes_freed > 0);
+
+        let reopened = RegionChunkPersistence::new(&root);
+        let revisions = reopened.revisions(position).unwrap();
+        assert_eq!(revisions, vec![5, 4, 3]);
+        assert_eq!(reopened.load(position).unwrap(), Some((5, vec![5; 2000])));
+        assert_eq!(reopened.usage(&policy, 550).unwrap().prunable_revisions, 0);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
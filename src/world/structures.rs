@@ -0,0 +1,285 @@
+use bevy::log::error;
+use bevy::math::IVec3;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::block::{BlockRegistry, BlockType};
+use crate::world::biome::Biome;
+
+/// Directory of structure templates, one `*.json` file per template, read
+/// in file name order.
+pub const STRUCTURES_DIR: &str = "assets/structures";
+/// The templates shipped in `STRUCTURES_DIR`, for binaries started somewhere
+/// the directory isn't.
+const BUILTIN_TEMPLATES: [(&str, &str); 3] = [
+    ("cabin", include_str!("../../assets/structures/cabin.json")),
+    ("ruin", include_str!("../../assets/structures/ruin.json")),
+    ("well", include_str!("../../assets/structures/well.json")),
+];
+
+/// A template as written in its file. `layers` run bottom to top, each a
+/// list of rows along z whose characters run along x. Characters are looked
+/// up in `palette`; a space leaves whatever the world has there.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TemplateFile {
+    name: String,
+    color: [f32; 4],
+    biomes: Vec<Biome>,
+    spacing: i32,
+    chance: f32,
+    #[serde(default)]
+    max_slope: f32,
+    #[serde(default)]
+    sink: i32,
+    foundation: Option<String>,
+    palette: HashMap<char, String>,
+    layers: Vec<Vec<String>>,
+}
+
+/// A voxel blueprint and the rules for where it may stand.
+#[derive(Clone, Debug)]
+pub struct StructureTemplate {
+    pub name: String,
+    /// Colour on the world builder's structures map.
+    #[allow(dead_code)]
+    pub color: [f32; 4],
+    /// Blocks along x, y and z.
+    pub size: IVec3,
+    pub biomes: Vec<Biome>,
+    /// Side of the square grid cells the template is rolled in, once each.
+    /// It should divide the planet size so cells line up where it wraps.
+    pub spacing: i32,
+    /// Chance a cell has the template, before the site is checked.
+    pub chance: f32,
+    /// Largest height difference allowed across the footprint.
+    pub max_slope: f32,
+    /// Layers below the ground the bottom layer is set into.
+    pub sink: i32,
+    /// Filled under the bottom layer down to the ground on slopes.
+    pub foundation: Option<BlockType>,
+    /// `None` keeps the world's block, `x + z * size.x + y * size.x * size.z`.
+    blocks: Vec<Option<BlockType>>,
+}
+
+impl StructureTemplate {
+    fn parse(source: &str, text: &str, blocks: &BlockRegistry) -> Result<Self, String> {
+        let file: TemplateFile = serde_json::from_str(text)
+            .map_err(|err| format!("failed to parse structure {}: {}", source, err))?;
+        let name = &file.name;
+        let block = |block_name: &str| {
+            blocks
+                .by_name(block_name)
+                .ok_or_else(|| format!("structure {:?} uses unknown block {:?}", name, block_name))
+        };
+
+        let size_y = file.layers.len();
+        let size_z = file.layers.first().map_or(0, |layer| layer.len());
+        let size_x = file
+            .layers
+            .first()
+            .and_then(|layer| layer.first())
+            .map_or(0, |row| row.chars().count());
+        if size_x == 0 || size_y == 0 || size_z == 0 {
+            return Err(format!("structure {:?} has no blocks", name));
+        }
+        if size_x.max(size_z) as i32 >= file.spacing {
+            return Err(format!(
+                "structure {:?} is {}x{} blocks; it must be smaller than its spacing {}",
+                name, size_x, size_z, file.spacing
+            ));
+        }
+        if !(0.0..=1.0).contains(&file.chance) || file.sink < 0 || file.max_slope < 0.0 {
+            return Err(format!(
+                "structure {:?} needs a chance of 0-1 and no negative sink or slope",
+                name
+            ));
+        }
+
+        let mut palette = HashMap::new();
+        for (symbol, block_name) in &file.palette {
+            palette.insert(*symbol, block(block_name)?);
+        }
+        let mut voxels = Vec::with_capacity(size_x * size_y * size_z);
+        for (y, layer) in file.layers.iter().enumerate() {
+            if layer.len() != size_z {
+                return Err(format!(
+                    "structure {:?} layer {} has {} rows; expected {}",
+                    name,
+                    y,
+                    layer.len(),
+                    size_z
+                ));
+            }
+            for row in layer {
+                if row.chars().count() != size_x {
+                    return Err(format!(
+                        "structure {:?} row {:?} is not {} wide",
+                        name, row, size_x
+                    ));
+                }
+                for symbol in row.chars() {
+                    let voxel = match symbol {
+                        ' ' => None,
+                        _ => Some(*palette.get(&symbol).ok_or_else(|| {
+                            format!("structure {:?} uses {:?}, not in its palette", name, symbol)
+                        })?),
+                    };
+                    voxels.push(voxel);
+                }
+            }
+        }
+
+        Ok(Self {
+            size: IVec3::new(size_x as i32, size_y as i32, size_z as i32),
+            foundation: file.foundation.as_deref().map(block).transpose()?,
+            name: file.name,
+            color: file.color,
+            biomes: file.biomes,
+            spacing: file.spacing,
+            chance: file.chance,
+            max_slope: file.max_slope,
+            sink: file.sink,
+            blocks: voxels,
+        })
+    }
+
+    /// Block at `local` (from the template's low corner), `None` to keep
+    /// the world's.
+    pub fn block(&self, local: IVec3) -> Option<BlockType> {
+        let index = local.x + local.z * self.size.x + local.y * self.size.x * self.size.z;
+        self.blocks[index as usize]
+    }
+
+    /// Salt for the template's placement grid, from its name so adding or
+    /// reordering templates leaves the others where they were.
+    pub fn salt(&self) -> u64 {
+        self.name.bytes().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3)
+        })
+    }
+}
+
+/// Every structure template the world generator places. Like the block
+/// registry, the game loads one library from `STRUCTURES_DIR` on first use.
+#[derive(Debug)]
+pub struct StructureLibrary {
+    templates: Vec<StructureTemplate>,
+}
+
+impl StructureLibrary {
+    pub fn global() -> &'static StructureLibrary {
+        static LIBRARY: OnceLock<StructureLibrary> = OnceLock::new();
+        LIBRARY.get_or_init(|| {
+            let dir = Path::new(STRUCTURES_DIR);
+            if !dir.is_dir() {
+                return Self::builtin();
+            }
+            Self::load_dir(dir).unwrap_or_else(|err| {
+                error!("{}; using the built-in structures", err);
+                Self::builtin()
+            })
+        })
+    }
+
+    fn builtin() -> Self {
+        Self::from_sources(&BUILTIN_TEMPLATES).expect("built-in structure templates are valid")
+    }
+
+    pub fn load_dir(dir: &Path) -> Result<Self, String> {
+        let entries = fs::read_dir(dir)
+            .map_err(|err| format!("failed to read structures {:?}: {}", dir, err))?;
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+
+        let mut sources = Vec::with_capacity(paths.len());
+        for path in &paths {
+            let text = fs::read_to_string(path)
+                .map_err(|err| format!("failed to read structure {:?}: {}", path, err))?;
+            sources.push((path.display().to_string(), text));
+        }
+        let sources: Vec<(&str, &str)> = sources
+            .iter()
+            .map(|(name, text)| (name.as_str(), text.as_str()))
+            .collect();
+        Self::from_sources(&sources)
+    }
+
+    /// Build a library from `(source name, JSON)` pairs, one template each.
+    /// Template names must be unique.
+    pub fn from_sources(sources: &[(&str, &str)]) -> Result<Self, String> {
+        let blocks = BlockRegistry::global();
+        let mut templates: Vec<StructureTemplate> = Vec::with_capacity(sources.len());
+        for (source, text) in sources {
+            let template = StructureTemplate::parse(source, text, blocks)?;
+            if templates.iter().any(|other| other.name == template.name) {
+                return Err(format!("structure {:?} is declared twice", template.name));
+            }
+            templates.push(template);
+        }
+        Ok(Self { templates })
+    }
+
+    pub fn templates(&self) -> &[StructureTemplate] {
+        &self.templates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn templates_parse_into_blueprints() {
+        let library = StructureLibrary::builtin();
+        let names: Vec<_> = library
+            .templates()
+            .iter()
+            .map(|t| t.name.as_str())
+            .collect();
+        assert_eq!(names, ["cabin", "ruin", "well"]);
+
+        let cabin = &library.templates()[0];
+        assert_eq!(cabin.size, IVec3::new(7, 7, 7));
+        assert_eq!(cabin.foundation, Some(BlockType::Cobblestone));
+        assert!(cabin.biomes.contains(&Biome::TemperateForest));
+        assert_eq!(cabin.block(IVec3::ZERO), Some(BlockType::Cobblestone));
+        // The door, a corner post, the ceiling light and untouched roof edges.
+        assert_eq!(cabin.block(IVec3::new(3, 1, 0)), Some(BlockType::Air));
+        assert_eq!(cabin.block(IVec3::new(6, 2, 6)), Some(BlockType::Wood));
+        assert_eq!(cabin.block(IVec3::new(3, 4, 3)), Some(BlockType::Glowstone));
+        assert_eq!(cabin.block(IVec3::new(0, 6, 0)), None);
+        assert_ne!(cabin.salt(), library.templates()[1].salt());
+
+        let template = |palette: &str, rows: &str, spacing: i32| {
+            format!(
+                r#"{{ "name": "hut", "color": [1, 1, 1, 1], "biomes": ["desert"],
+                    "spacing": {}, "chance": 0.5, "palette": {{ {} }},
+                    "layers": [[{}]] }}"#,
+                spacing, palette, rows
+            )
+        };
+        let valid = template(r#""P": "planks""#, r#""P P", "PPP""#, 16);
+        let hut = StructureLibrary::from_sources(&[("hut", &valid)]).unwrap();
+        assert_eq!(hut.templates()[0].size, IVec3::new(3, 1, 2));
+        assert_eq!(hut.templates()[0].block(IVec3::new(1, 0, 0)), None);
+
+        // Unknown blocks and symbols, ragged rows and footprints that would
+        // spill out of their cell are all rejected.
+        for invalid in [
+            template(r#""P": "marble""#, r#""PPP""#, 16),
+            template(r#""P": "planks""#, r#""PxP""#, 16),
+            template(r#""P": "planks""#, r#""PPP", "PP""#, 16),
+            template(r#""P": "planks""#, r#""PPP""#, 3),
+        ] {
+            assert!(StructureLibrary::from_sources(&[("hut", &invalid)]).is_err());
+        }
+        assert!(StructureLibrary::from_sources(&[("a", &valid), ("b", &valid)]).is_err());
+    }
+}